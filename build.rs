@@ -105,6 +105,5 @@ fn main() {
             "cargo:warning=failed to copy ui from {:?} to {:?}: {}",
             ui_src, ui_out, e
         );
-        return;
     }
 }
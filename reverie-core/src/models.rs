@@ -62,6 +62,61 @@ pub struct User {
     pub updated_at: DateTime<Utc>,
 }
 
+/// 用户可访问的音乐文件夹
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MusicFolderAccess {
+    /// 不限制，包括以后添加的文件夹
+    #[default]
+    All,
+    /// 只能访问列出的文件夹；列表为空时用户看不到任何内容，例如这些文件夹都已被删除
+    Only(Vec<i32>),
+}
+
+impl MusicFolderAccess {
+    /// 查询使用的文件夹范围，`None` 表示不限制
+    pub fn folder_ids(&self) -> Option<&[i32]> {
+        match self {
+            Self::All => None,
+            Self::Only(ids) => Some(ids),
+        }
+    }
+
+    /// 是否可以访问指定的文件夹
+    pub fn allows(&self, folder_id: i32) -> bool {
+        self.folder_ids().is_none_or(|ids| ids.contains(&folder_id))
+    }
+}
+
+/// 已通过身份验证的请求用户
+///
+/// 由网络层的身份验证中间件生成，传递给需要按用户区分数据的存储方法
+/// （收藏、评分、播放记录、书签、播放队列）。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserContext {
+    pub username: String,
+    pub is_admin: bool,
+    /// 可访问的音乐文件夹
+    #[serde(default)]
+    pub music_folders: MusicFolderAccess,
+}
+
+impl UserContext {
+    pub fn new(username: impl Into<String>, is_admin: bool) -> Self {
+        Self {
+            username: username.into(),
+            is_admin,
+            music_folders: MusicFolderAccess::All,
+        }
+    }
+
+    /// 设置用户可访问的音乐文件夹
+    pub fn with_music_folders(mut self, music_folders: MusicFolderAccess) -> Self {
+        self.music_folders = music_folders;
+        self
+    }
+}
+
 /// 表示播放列表
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Playlist {
@@ -84,7 +139,7 @@ pub struct PlaylistTrack {
 }
 
 /// 库扫描统计信息
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ScanStats {
     pub total_tracks: u64,
     pub total_albums: u64,
@@ -235,13 +290,19 @@ pub struct SubsonicLyrics {
     pub value: String,
 }
 
+/// 用户保存的播放队列，用于在不同设备之间继续播放
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SubsonicPlayQueue {
     pub entries: Vec<MediaFile>,
+    /// 当前曲目的 ID
     pub current: Option<String>,
+    /// 当前曲目在 `entries` 中的位置，队列中有重复曲目时据此区分
+    pub current_index: Option<usize>,
+    /// 当前曲目的播放位置（毫秒）
     pub position: i64,
     pub username: String,
     pub changed: DateTime<Utc>,
+    /// 保存队列的客户端
     pub changed_by: String,
 }
 
@@ -302,6 +363,15 @@ pub struct SubsonicInternetRadioStation {
     pub homepage_url: Option<String>,
 }
 
+/// 用户配置的播放记录转发账号，不包含令牌
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubsonicScrobbleAccount {
+    /// 服务名称，例如 `listenbrainz`
+    pub service: String,
+    /// 服务地址，为 `None` 时使用官方服务
+    pub url: Option<String>,
+}
+
 /// 用于与他人分享媒体的分享
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SubsonicShare {
@@ -437,7 +507,7 @@ pub struct SubsonicUser {
     pub share_role: bool,
     pub video_conversion_role: bool,
     pub avatar_last_changed: Option<DateTime<Utc>>,
-    pub folders: Vec<i32>,
+    pub folders: MusicFolderAccess,
 }
 
 /// 收藏的内容
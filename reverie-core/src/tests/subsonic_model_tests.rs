@@ -145,6 +145,7 @@ fn test_subsonic_play_queue_creation() {
     let queue = SubsonicPlayQueue {
         entries: vec![],
         current: Some("song-1".to_string()),
+        current_index: Some(0),
         position: 5,
         username: "testuser".to_string(),
         changed: Utc::now(),
@@ -176,13 +177,17 @@ fn test_subsonic_user_creation() {
         share_role: true,
         video_conversion_role: false,
         avatar_last_changed: None,
-        folders: vec![1, 2],
+        folders: MusicFolderAccess::Only(vec![1, 2]),
     };
 
     assert_eq!(user.username, "admin");
     assert!(user.admin_role);
     assert!(user.stream_role);
-    assert_eq!(user.folders.len(), 2);
+    assert_eq!(user.folders.folder_ids(), Some(&[1, 2][..]));
+    assert!(user.folders.allows(2));
+    assert!(!user.folders.allows(3));
+    assert!(MusicFolderAccess::All.allows(3));
+    assert!(!MusicFolderAccess::Only(vec![]).allows(1));
 }
 
 #[test]
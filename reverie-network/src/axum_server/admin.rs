@@ -0,0 +1,185 @@
+//! 管理处理器
+//!
+//! 管理端点使用与 Subsonic API 相同的凭据（`u`/`p`、`t`/`s` 或 `apiKey` 查询参数），
+//! 仅管理员可访问。
+use axum::{
+    body::Body,
+    extract::{Path, Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{get, patch},
+    Router,
+};
+
+use crate::{
+    dto::{CreateMusicFolderRequest, ErrorResponse, MusicFolderResponse, UpdateMusicFolderRequest},
+    subsonic,
+};
+use reverie_storage::{error::StorageError, SubsonicStorage};
+
+fn error(status: StatusCode, error: &str, message: impl Into<String>) -> Response {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            message: message.into(),
+        }),
+    )
+        .into_response()
+}
+
+fn storage_error(e: StorageError) -> Response {
+    match e {
+        StorageError::NotFound(message) => error(StatusCode::NOT_FOUND, "not_found", message),
+        e => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "storage_error",
+            e.to_string(),
+        ),
+    }
+}
+
+/// 验证请求者身份并要求管理员权限
+pub async fn require_admin<S>(
+    State(state): State<subsonic::SubsonicState<S>>,
+    params: subsonic::SubsonicParams,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response
+where
+    S: SubsonicStorage + Clone + Send + Sync + 'static,
+{
+    match subsonic::authenticate(state.storage.as_ref(), &params).await {
+        Ok(user) if user.is_admin => {
+            if let Err(e) = subsonic::require_password_changed(state.storage.as_ref(), &user).await
+            {
+                return error(
+                    StatusCode::FORBIDDEN,
+                    "password_change_required",
+                    e.to_string(),
+                );
+            }
+            req.extensions_mut().insert(user);
+            next.run(req).await
+        }
+        Ok(user) => error(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            format!("User {} is not an administrator", user.username),
+        ),
+        Err(e) => error(StatusCode::UNAUTHORIZED, "unauthorized", e.to_string()),
+    }
+}
+
+/// 列出音乐文件夹处理程序
+pub async fn list_folders_handler<S>(State(state): State<subsonic::SubsonicState<S>>) -> Response
+where
+    S: SubsonicStorage + Clone + Send + Sync + 'static,
+{
+    match state.storage.get_music_folder_configs().await {
+        Ok(folders) => {
+            let responses: Vec<MusicFolderResponse> =
+                folders.into_iter().map(MusicFolderResponse::from).collect();
+            (StatusCode::OK, Json(responses)).into_response()
+        }
+        Err(e) => storage_error(e),
+    }
+}
+
+/// 添加音乐文件夹处理程序
+pub async fn create_folder_handler<S>(
+    State(state): State<subsonic::SubsonicState<S>>,
+    Json(request): Json<CreateMusicFolderRequest>,
+) -> Response
+where
+    S: SubsonicStorage + Clone + Send + Sync + 'static,
+{
+    match state
+        .storage
+        .create_music_folder(
+            &request.name,
+            &request.path,
+            request.vfs_config.as_ref(),
+            request.scan,
+        )
+        .await
+    {
+        Ok(folder) => {
+            (StatusCode::CREATED, Json(MusicFolderResponse::from(folder))).into_response()
+        }
+        Err(e) => storage_error(e),
+    }
+}
+
+/// 修改音乐文件夹处理程序
+pub async fn update_folder_handler<S>(
+    State(state): State<subsonic::SubsonicState<S>>,
+    Path(id): Path<i32>,
+    Json(request): Json<UpdateMusicFolderRequest>,
+) -> Response
+where
+    S: SubsonicStorage + Clone + Send + Sync + 'static,
+{
+    let mut folder = match state.storage.get_music_folder_configs().await {
+        Ok(folders) => match folders.into_iter().find(|f| f.id == id) {
+            Some(folder) => folder,
+            None => {
+                return error(
+                    StatusCode::NOT_FOUND,
+                    "not_found",
+                    format!("Music folder {} not found", id),
+                )
+            }
+        },
+        Err(e) => return storage_error(e),
+    };
+    if let Some(name) = request.name {
+        folder.name = name;
+    }
+    if let Some(path) = request.path {
+        folder.path = path;
+    }
+    if let Some(mut vfs_config) = request.vfs_config {
+        if let Some(config) = vfs_config.as_mut() {
+            config.restore_redacted(folder.vfs_config.as_ref());
+        }
+        folder.vfs_config = vfs_config;
+    }
+
+    match state.storage.update_music_folder(&folder).await {
+        Ok(()) => (StatusCode::OK, Json(MusicFolderResponse::from(folder))).into_response(),
+        Err(e) => storage_error(e),
+    }
+}
+
+/// 删除音乐文件夹处理程序
+pub async fn delete_folder_handler<S>(
+    State(state): State<subsonic::SubsonicState<S>>,
+    Path(id): Path<i32>,
+) -> Response
+where
+    S: SubsonicStorage + Clone + Send + Sync + 'static,
+{
+    match state.storage.delete_music_folder(id).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => storage_error(e),
+    }
+}
+
+/// 创建管理路由，所有路由都需要管理员权限
+pub fn create_router<S>(state: subsonic::SubsonicState<S>) -> Router<subsonic::SubsonicState<S>>
+where
+    S: SubsonicStorage + Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route(
+            "/api/admin/folders",
+            get(list_folders_handler::<S>).post(create_folder_handler::<S>),
+        )
+        .route(
+            "/api/admin/folders/:id",
+            patch(update_folder_handler::<S>).delete(delete_folder_handler::<S>),
+        )
+        .route_layer(middleware::from_fn_with_state(state, require_admin::<S>))
+}
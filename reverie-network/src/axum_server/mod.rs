@@ -4,7 +4,6 @@ use axum::{
     routing::{get, get_service},
     Router,
 };
-use serde::Deserialize;
 use std::{net::SocketAddr, path::PathBuf, sync::Arc};
 use tokio::sync::RwLock;
 use tower_http::{
@@ -15,24 +14,33 @@ use tower_http::{
 
 use crate::{
     error::{NetworkError, Result},
+    radio::RadioRelay,
     subsonic,
-    traits::{HttpServer, NetworkConfig},
+    traits::{HttpServer, MediaStreamer, NetworkConfig},
+    transcoding::TranscodeCache,
 };
 use reverie_storage::{
     AlbumStorage, ArtistStorage, FileStorage, PlaylistStorage, SubsonicStorage, TrackStorage,
 };
 
+pub mod admin;
 pub mod health;
 pub mod tracks;
 pub mod albums;
 pub mod artists;
 pub mod playlists;
+pub mod shares;
 
 /// 基于 Axum 的 HTTP 服务器。
 pub struct AxumServer<S> {
     storage: Arc<S>,
     config: NetworkConfig,
     ui_dir: Option<PathBuf>,
+    transcoder: Option<Arc<dyn MediaStreamer>>,
+    transcode_cache: Option<TranscodeCache>,
+    radio_relay: Option<RadioRelay>,
+    public_base_url: Option<String>,
+    trust_forwarded_headers: bool,
     addr: Arc<RwLock<Option<SocketAddr>>>,
     is_running: Arc<RwLock<bool>>,
 }
@@ -55,6 +63,11 @@ where
             storage,
             config,
             ui_dir: None,
+            transcoder: None,
+            transcode_cache: None,
+            radio_relay: None,
+            public_base_url: None,
+            trust_forwarded_headers: false,
             addr: Arc::new(RwLock::new(None)),
             is_running: Arc::new(RwLock::new(false)),
         }
@@ -71,6 +84,36 @@ where
         self
     }
 
+    /// 为 `/rest/stream` 启用实时转码
+    pub fn with_transcoder(mut self, transcoder: Arc<dyn MediaStreamer>) -> Self {
+        self.transcoder = Some(transcoder);
+        self
+    }
+
+    /// 将转码结果缓存到给定的缓存中
+    pub fn with_transcode_cache(mut self, cache: TranscodeCache) -> Self {
+        self.transcode_cache = Some(cache);
+        self
+    }
+
+    /// 启用网络电台转发（`/rest/streamInternetRadio`）
+    pub fn with_radio_relay(mut self, relay: RadioRelay) -> Self {
+        self.radio_relay = Some(relay);
+        self
+    }
+
+    /// 设置分享链接使用的公开地址（如 `https://music.example`）
+    pub fn with_public_base_url(mut self, url: impl Into<String>) -> Self {
+        self.public_base_url = Some(url.into());
+        self
+    }
+
+    /// 信任反向代理设置的 `X-Forwarded-*` 请求头
+    pub fn trust_forwarded_headers(mut self) -> Self {
+        self.trust_forwarded_headers = true;
+        self
+    }
+
     fn create_ui_router(&self) -> Option<Router<subsonic::SubsonicState<S>>> {
         let ui_dir = self.ui_dir.clone()?;
 
@@ -93,11 +136,27 @@ where
     }
 
     fn create_router(&self) -> Router {
+        let mut state = subsonic::SubsonicState::new(Arc::clone(&self.storage));
+        if let Some(transcoder) = self.transcoder.clone() {
+            state = state.with_transcoder(transcoder);
+        }
+        if let Some(cache) = self.transcode_cache.clone() {
+            state = state.with_transcode_cache(cache);
+        }
+        if let Some(relay) = self.radio_relay.clone() {
+            state = state.with_radio_relay(relay);
+        }
+        if let Some(url) = self.public_base_url.clone() {
+            state = state.with_public_base_url(url);
+        }
+        if self.trust_forwarded_headers {
+            state = state.trust_forwarded_headers();
+        }
         let mut router = Router::<subsonic::SubsonicState<S>>::new()
             // 健康检查
             .route("/health", get(health::health_handler))
             // Subsonic API
-            .nest("/rest", subsonic::create_router::<S>())
+            .nest("/rest", subsonic::create_router::<S>(state.clone()))
             // 曲目路由
             .route("/api/tracks", get(tracks::list_tracks_handler::<S>))
             .route("/api/tracks/:id", get(tracks::get_track_handler::<S>))
@@ -114,14 +173,18 @@ where
                 get(artists::get_artist_albums_handler::<S>),
             )
             // 播放列表路由
-            .route("/api/playlists/:id", get(playlists::get_playlist_handler::<S>));
+            .route("/api/playlists/:id", get(playlists::get_playlist_handler::<S>))
+            // 公开分享路由
+            .merge(shares::create_router::<S>())
+            // 管理路由
+            .merge(admin::create_router::<S>(state.clone()));
 
         if let Some(ui_router) = self.create_ui_router() {
             router = router.merge(ui_router);
         }
 
         router
-            .with_state(subsonic::SubsonicState::new(Arc::clone(&self.storage)))
+            .with_state(state)
             .layer(if self.config.enable_cors {
                 CorsLayer::permissive()
             } else {
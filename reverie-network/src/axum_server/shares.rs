@@ -0,0 +1,138 @@
+//! 公开分享处理器
+//!
+//! 分享链接无需 Subsonic 凭据即可访问：`/share/{id}` 返回可直接播放的页面，
+//! `/share/{id}/stream/{song_id}` 流式传输分享中的歌曲。分享不存在、已删除或已过期时返回 404。
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Router,
+};
+
+use crate::subsonic::{self, range};
+use reverie_core::SubsonicShare;
+use reverie_storage::{FileStorage, SubsonicStorage};
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, "Share not found").into_response()
+}
+
+fn internal_error(e: impl ToString) -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+}
+
+/// 转义插入到 HTML 中的文本
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn render_share_page(share: &SubsonicShare) -> String {
+    let title = escape_html(share.description.as_deref().unwrap_or("Shared music"));
+    let entries: String = share
+        .entries
+        .iter()
+        .map(|song| {
+            let artist = song
+                .artist
+                .as_deref()
+                .map(|a| format!(" &ndash; {}", escape_html(a)))
+                .unwrap_or_default();
+            format!(
+                concat!(
+                    "<li><p>{}{}</p>",
+                    "<audio controls preload=\"none\" src=\"{}/stream/{}\"></audio></li>\n"
+                ),
+                escape_html(&song.title),
+                artist,
+                escape_html(&share.url),
+                escape_html(&song.id),
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+</head>
+<body>
+<h1>{title}</h1>
+<p>Shared by {username}</p>
+<ol>
+{entries}</ol>
+</body>
+</html>
+"#,
+        title = title,
+        username = escape_html(&share.username),
+        entries = entries,
+    )
+}
+
+/// 分享页面处理程序，每次打开页面计为一次访问
+pub async fn share_page_handler<S>(
+    State(state): State<subsonic::SubsonicState<S>>,
+    Path(id): Path<String>,
+) -> Response
+where
+    S: SubsonicStorage + Clone + Send + Sync + 'static,
+{
+    let share = match state.storage.get_public_share(&id).await {
+        Ok(Some(share)) => share,
+        Ok(None) => return not_found(),
+        Err(e) => return internal_error(e),
+    };
+    if let Err(e) = state.storage.record_share_visit(&id).await {
+        tracing::warn!("Failed to record visit of share {}: {}", id, e);
+    }
+    Html(render_share_page(&share)).into_response()
+}
+
+/// 分享歌曲流式传输处理程序，只允许访问分享中包含的歌曲
+pub async fn share_stream_handler<S>(
+    State(state): State<subsonic::SubsonicState<S>>,
+    Path((id, song_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response
+where
+    S: SubsonicStorage + FileStorage + Clone + Send + Sync + 'static,
+{
+    match state.storage.get_public_share(&id).await {
+        Ok(Some(share)) if share.entries.iter().any(|song| song.id == song_id) => {}
+        Ok(_) => return not_found(),
+        Err(e) => return internal_error(e),
+    }
+
+    let path = match state.storage.get_stream_path(&song_id).await {
+        Ok(Some(path)) => path,
+        Ok(None) => return not_found(),
+        Err(e) => return internal_error(e),
+    };
+    let mime_type = subsonic::audio_content_type(&path);
+    range::serve_file(state.storage.clone(), path, &headers, mime_type, None).await
+}
+
+/// 创建公开分享路由，无需身份验证
+pub fn create_router<S>() -> Router<subsonic::SubsonicState<S>>
+where
+    S: SubsonicStorage + FileStorage + Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/share/:id", get(share_page_handler::<S>))
+        .route("/share/:id/stream/:song_id", get(share_stream_handler::<S>))
+}
@@ -1,5 +1,6 @@
 //! 用于 API 请求和响应的数据传输对象 (DTOs)
-use serde::{Deserialize, Serialize};
+use reverie_storage::{LibraryFolder, VfsConfig};
+use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
 
 /// 曲目信息响应
@@ -58,6 +59,58 @@ pub struct AddTrackToPlaylistRequest {
     pub track_id: Uuid,
 }
 
+/// 音乐文件夹信息响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MusicFolderResponse {
+    pub id: i32,
+    pub name: String,
+    pub path: String,
+    /// VFS 方案和选项，为 `None` 时使用默认 VFS；凭据以 `***` 代替
+    pub vfs_config: Option<VfsConfig>,
+}
+
+impl From<LibraryFolder> for MusicFolderResponse {
+    fn from(folder: LibraryFolder) -> Self {
+        Self {
+            id: folder.id,
+            name: folder.name,
+            path: folder.path,
+            vfs_config: folder.vfs_config.as_ref().map(VfsConfig::redacted),
+        }
+    }
+}
+
+/// 添加音乐文件夹的请求
+#[derive(Debug, Deserialize)]
+pub struct CreateMusicFolderRequest {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub vfs_config: Option<VfsConfig>,
+    /// 添加后立即扫描该文件夹
+    #[serde(default)]
+    pub scan: bool,
+}
+
+/// 修改音乐文件夹的请求，省略的字段保持不变
+#[derive(Debug, Deserialize)]
+pub struct UpdateMusicFolderRequest {
+    pub name: Option<String>,
+    pub path: Option<String>,
+    /// 为 `null` 时改用默认 VFS
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub vfs_config: Option<Option<VfsConfig>>,
+}
+
+/// 区分缺省字段和显式的 `null`
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
 /// 带分页的通用列表响应
 #[derive(Debug, Serialize)]
 pub struct ListResponse<T> {
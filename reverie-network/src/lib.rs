@@ -5,8 +5,10 @@
 //! 和外部连接系统一起工作。
 pub mod dto;
 pub mod error;
+pub mod radio;
 pub mod subsonic;
 pub mod traits;
+pub mod transcoding;
 
 #[cfg(feature = "axum-server")]
 pub mod axum_server;
@@ -16,7 +18,9 @@ mod tests;
 
 pub use dto::*;
 pub use error::*;
+pub use radio::RadioRelay;
 pub use traits::*;
+pub use transcoding::{FfmpegTranscoder, TranscodeCache, TranscodeProfile, TranscodingConfig};
 
 // 注意：subsonic 模块是 pub(crate) - 不重新导出
 // 使用 reverie_server 访问 Subsonic API 端点
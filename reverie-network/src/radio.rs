@@ -0,0 +1,493 @@
+//! 网络电台转发
+//!
+//! 服务器代替客户端连接电台，再把音频转发给客户端，浏览器因此不受 CORS 和混合内容限制。
+//! 连接时请求 ICY 元数据（`Icy-MetaData: 1`），电台返回 `icy-metaint` 时从流中剔除元数据块，
+//! 并把其中的 `StreamTitle` 记录为电台正在播放的内容。
+//!
+//! 电台地址也可以是 M3U 或 PLS 播放列表，此时连接列表中的第一个流地址。
+//!
+//! 默认只连接公网地址：电台地址、播放列表条目和重定向指向本机、链路本地或内网地址时拒绝连接，
+//! 避免借电台访问服务器所在网络中的服务。确实需要转发内网电台时使用
+//! [`RadioRelay::allow_private_targets`]。
+
+use bytes::Bytes;
+use reverie_storage::outbound;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::error::{NetworkError, Result};
+use crate::traits::MediaStream;
+
+/// 连接电台的超时时间；音频流本身没有总时长限制
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 播放列表的大小上限，避免把误判为播放列表的音频流读入内存
+const MAX_PLAYLIST_SIZE: usize = 64 * 1024;
+
+/// 播放列表嵌套的最大层数
+const MAX_PLAYLIST_DEPTH: usize = 3;
+
+/// 检查电台地址的协议和 IP 形式的主机名，见 [`outbound::check_target`]
+fn check_target(url: &reqwest::Url, allow_private: bool) -> Result<()> {
+    outbound::check_target(url, allow_private)
+        .map_err(|e| NetworkError::InvalidRequest(e.to_string()))
+}
+
+/// 创建连接电台的 HTTP 客户端，每次重定向都检查目标地址
+fn build_client(allow_private: bool) -> reqwest::Client {
+    outbound::client_builder(allow_private)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// 电台地址指向的播放列表格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlaylistKind {
+    M3u,
+    Pls,
+}
+
+/// 根据响应的 MIME 类型和地址的扩展名判断是否为播放列表
+///
+/// 返回音频类型的地址即使扩展名为 `.m3u` 也按音频流处理
+fn playlist_kind(url: &reqwest::Url, content_type: &str) -> Option<PlaylistKind> {
+    match content_type {
+        "audio/x-mpegurl" | "audio/mpegurl" | "application/x-mpegurl"
+        | "application/vnd.apple.mpegurl" => return Some(PlaylistKind::M3u),
+        "audio/x-scpls" | "application/pls+xml" => return Some(PlaylistKind::Pls),
+        t if t.starts_with("audio/") => return None,
+        _ => {}
+    }
+    let path = url.path().to_ascii_lowercase();
+    if path.ends_with(".m3u") || path.ends_with(".m3u8") {
+        Some(PlaylistKind::M3u)
+    } else if path.ends_with(".pls") {
+        Some(PlaylistKind::Pls)
+    } else {
+        None
+    }
+}
+
+/// M3U 中第一个不是注释的行
+fn parse_m3u(body: &str) -> Option<&str> {
+    body.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// PLS 中编号最小的 `FileN` 条目
+fn parse_pls(body: &str) -> Option<&str> {
+    body.lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once('=')?;
+            let index: u32 = key.trim().strip_prefix("File")?.parse().ok()?;
+            Some((index, value.trim()))
+        })
+        .min_by_key(|(index, _)| *index)
+        .map(|(_, value)| value)
+}
+
+/// 从 ICY 元数据块中解析 `StreamTitle`
+///
+/// 元数据形如 `StreamTitle='Artist - Title';StreamUrl='';`，末尾以 NUL 补齐到 16 字节的倍数
+fn parse_stream_title(metadata: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(metadata);
+    let start = text.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &text[start..];
+    let end = rest.find("';").unwrap_or_else(|| rest.trim_end_matches('\0').len());
+    let title = rest[..end].trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+/// ICY 流的解析状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IcyState {
+    /// 距离下一个元数据块还有多少字节音频
+    Audio(usize),
+    /// 下一个字节是元数据长度（以 16 字节为单位）
+    Length,
+    /// 元数据块还剩多少字节
+    Metadata(usize),
+}
+
+/// 按 `icy-metaint` 间隔从流中分离音频和元数据，数据块可以在任意位置被截断
+#[derive(Debug)]
+struct IcyParser {
+    metaint: usize,
+    state: IcyState,
+    metadata: Vec<u8>,
+}
+
+impl IcyParser {
+    fn new(metaint: usize) -> Self {
+        Self {
+            metaint,
+            state: IcyState::Audio(metaint),
+            metadata: Vec::new(),
+        }
+    }
+
+    /// 处理一块数据，音频追加到 `audio`；本块中有新的标题时返回最后一个
+    fn push(&mut self, mut data: &[u8], audio: &mut Vec<u8>) -> Option<String> {
+        let mut title = None;
+        while !data.is_empty() {
+            match self.state {
+                IcyState::Audio(remaining) => {
+                    let take = remaining.min(data.len());
+                    audio.extend_from_slice(&data[..take]);
+                    data = &data[take..];
+                    self.state = if take == remaining {
+                        IcyState::Length
+                    } else {
+                        IcyState::Audio(remaining - take)
+                    };
+                }
+                IcyState::Length => {
+                    let len = data[0] as usize * 16;
+                    data = &data[1..];
+                    self.metadata.clear();
+                    self.state = if len == 0 {
+                        IcyState::Audio(self.metaint)
+                    } else {
+                        IcyState::Metadata(len)
+                    };
+                }
+                IcyState::Metadata(remaining) => {
+                    let take = remaining.min(data.len());
+                    self.metadata.extend_from_slice(&data[..take]);
+                    data = &data[take..];
+                    if take == remaining {
+                        title = parse_stream_title(&self.metadata).or(title);
+                        self.state = IcyState::Audio(self.metaint);
+                    } else {
+                        self.state = IcyState::Metadata(remaining - take);
+                    }
+                }
+            }
+        }
+        title
+    }
+}
+
+/// 已连接的电台音频流
+pub struct RadioStream {
+    /// 电台返回的 MIME 类型
+    pub content_type: String,
+    /// 剔除了 ICY 元数据的音频
+    pub stream: MediaStream,
+}
+
+/// 网络电台转发器，记录各电台正在播放的内容
+#[derive(Clone)]
+pub struct RadioRelay {
+    client: reqwest::Client,
+    allow_private: bool,
+    now_playing: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl RadioRelay {
+    /// 创建只连接公网地址的转发器
+    pub fn new() -> Self {
+        Self {
+            client: build_client(false),
+            allow_private: false,
+            now_playing: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// 允许连接本机和内网中的电台
+    pub fn allow_private_targets(mut self) -> Self {
+        self.client = build_client(true);
+        self.allow_private = true;
+        self
+    }
+
+    /// 电台最近一次通过 ICY 元数据报告的标题
+    pub fn now_playing(&self, station_id: &str) -> Option<String> {
+        self.now_playing
+            .read()
+            .ok()
+            .and_then(|titles| titles.get(station_id).cloned())
+    }
+
+    /// 连接电台，地址为播放列表时连接其中的第一个流
+    pub async fn open(&self, station_id: &str, url: &str) -> Result<RadioStream> {
+        let mut url = reqwest::Url::parse(url)
+            .map_err(|e| NetworkError::InvalidRequest(format!("Invalid stream URL: {}", e)))?;
+
+        for _ in 0..=MAX_PLAYLIST_DEPTH {
+            check_target(&url, self.allow_private)?;
+            let response = self
+                .client
+                .get(url.clone())
+                .header("Icy-MetaData", "1")
+                .send()
+                .await
+                .map_err(|e| NetworkError::ConnectionError(format!("{}: {}", url, e)))?;
+            if !response.status().is_success() {
+                return Err(NetworkError::ConnectionError(format!(
+                    "{} returned {}",
+                    url,
+                    response.status()
+                )));
+            }
+
+            let content_type = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(';').next())
+                .map(|v| v.trim().to_ascii_lowercase())
+                .unwrap_or_default();
+            let Some(kind) = playlist_kind(&url, &content_type) else {
+                return Ok(self.relay(station_id, response, content_type));
+            };
+
+            let body = read_playlist(response).await?;
+            let entry = match kind {
+                PlaylistKind::M3u => parse_m3u(&body),
+                PlaylistKind::Pls => parse_pls(&body),
+            }
+            .ok_or_else(|| {
+                NetworkError::InvalidRequest(format!("Playlist {} contains no streams", url))
+            })?;
+            // 播放列表中的地址可以是相对地址
+            url = url
+                .join(entry)
+                .map_err(|e| NetworkError::InvalidRequest(format!("Invalid stream URL: {}", e)))?;
+        }
+
+        Err(NetworkError::InvalidRequest(format!(
+            "Too many nested playlists at {}",
+            url
+        )))
+    }
+
+    fn relay(
+        &self,
+        station_id: &str,
+        response: reqwest::Response,
+        content_type: String,
+    ) -> RadioStream {
+        let parser = response
+            .headers()
+            .get("icy-metaint")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|metaint: &usize| *metaint > 0)
+            .map(IcyParser::new);
+        let state = (response, parser, self.clone(), station_id.to_string());
+
+        let stream = futures::stream::unfold(state, |(mut response, mut parser, relay, id)| {
+            async move {
+                loop {
+                    let chunk = match response.chunk().await {
+                        Ok(Some(chunk)) => chunk,
+                        Ok(None) => return None,
+                        Err(e) => {
+                            let error = std::io::Error::other(e);
+                            return Some((Err(error), (response, parser, relay, id)));
+                        }
+                    };
+                    let Some(icy) = parser.as_mut() else {
+                        return Some((Ok(chunk), (response, parser, relay, id)));
+                    };
+
+                    let mut audio = Vec::with_capacity(chunk.len());
+                    if let Some(title) = icy.push(&chunk, &mut audio) {
+                        if let Ok(mut titles) = relay.now_playing.write() {
+                            titles.insert(id.clone(), title);
+                        }
+                    }
+                    // 整块都是元数据时继续读取下一块
+                    if !audio.is_empty() {
+                        return Some((Ok(Bytes::from(audio)), (response, parser, relay, id)));
+                    }
+                }
+            }
+        });
+
+        RadioStream {
+            content_type: if content_type.is_empty() {
+                "audio/mpeg".to_string()
+            } else {
+                content_type
+            },
+            stream: Box::pin(stream),
+        }
+    }
+}
+
+impl Default for RadioRelay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn read_playlist(mut response: reqwest::Response) -> Result<String> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| NetworkError::ConnectionError(e.to_string()))?
+    {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_PLAYLIST_SIZE {
+            return Err(NetworkError::InvalidRequest(
+                "Playlist exceeds the size limit".to_string(),
+            ));
+        }
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::header, response::IntoResponse, routing::get, Router};
+    use futures::StreamExt;
+
+    #[test]
+    fn test_parse_playlists() {
+        let m3u = "#EXTM3U\n#EXTINF:-1,Radio\n\nhttp://radio.example/live\nhttp://backup\n";
+        assert_eq!(parse_m3u(m3u), Some("http://radio.example/live"));
+        assert_eq!(parse_m3u("#EXTM3U\n"), None);
+
+        let pls = "[playlist]\r\nFile2=http://b\r\nTitle1=Radio\r\nFile1=http://a\r\n";
+        assert_eq!(parse_pls(pls), Some("http://a"));
+        assert_eq!(parse_pls("[playlist]\n"), None);
+    }
+
+    #[test]
+    fn test_playlist_kind() {
+        let url = |u: &str| reqwest::Url::parse(u).unwrap();
+        assert_eq!(playlist_kind(&url("http://a/listen.pls"), ""), Some(PlaylistKind::Pls));
+        assert_eq!(playlist_kind(&url("http://a/x"), "audio/x-mpegurl"), Some(PlaylistKind::M3u));
+        assert_eq!(playlist_kind(&url("http://a/live.m3u"), "audio/mpeg"), None);
+        assert_eq!(playlist_kind(&url("http://a/live"), "audio/aac"), None);
+    }
+
+    #[test]
+    fn test_parse_stream_title() {
+        let mut metadata = b"StreamTitle='Artist - It''s On';StreamUrl='';".to_vec();
+        metadata.resize(64, 0);
+        assert_eq!(parse_stream_title(&metadata).as_deref(), Some("Artist - It''s On"));
+        assert_eq!(parse_stream_title(b"StreamTitle='';\0\0"), None);
+        assert_eq!(parse_stream_title(b"StreamUrl='x';"), None);
+    }
+
+    /// 按 ICY 格式交错音频和元数据：每 4 字节音频后跟一个元数据块
+    fn icy_body(blocks: &[(&[u8], Option<&str>)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (audio, title) in blocks {
+            body.extend_from_slice(audio);
+            match title {
+                Some(title) => {
+                    let mut metadata = format!("StreamTitle='{}';", title).into_bytes();
+                    metadata.resize(metadata.len().div_ceil(16) * 16, 0);
+                    body.push((metadata.len() / 16) as u8);
+                    body.extend_from_slice(&metadata);
+                }
+                None => body.push(0),
+            }
+        }
+        body
+    }
+
+    #[test]
+    fn test_icy_parser_handles_split_chunks() {
+        let body = icy_body(&[
+            (b"abcd", Some("First")),
+            (b"efgh", None),
+            (b"ijkl", Some("Second")),
+        ]);
+        // 逐字节输入，元数据块和长度字节都会被截断
+        let mut parser = IcyParser::new(4);
+        let mut audio = Vec::new();
+        let mut titles = Vec::new();
+        for byte in body.chunks(1) {
+            titles.extend(parser.push(byte, &mut audio));
+        }
+        assert_eq!(audio, b"abcdefghijkl");
+        assert_eq!(titles, ["First", "Second"]);
+    }
+
+    /// 在本地启动一个模拟的 Icecast 电台，同时提供 M3U 和 PLS 播放列表
+    async fn fake_icecast() -> String {
+        let mut body = icy_body(&[(b"abcd", Some("Artist - Song")), (b"efgh", None)]);
+        // 流在两个元数据块之间结束
+        body.extend_from_slice(b"ij");
+        let router = Router::new()
+            .route(
+                "/live",
+                get(move || async move {
+                    let headers = [
+                        (header::CONTENT_TYPE, "audio/mpeg"),
+                        (header::HeaderName::from_static("icy-metaint"), "4"),
+                    ];
+                    (headers, body).into_response()
+                }),
+            )
+            .route("/plain", get(|| async { ([(header::CONTENT_TYPE, "audio/ogg")], "raw") }))
+            .route("/radio.m3u", get(|| async { "#EXTM3U\n/live\n" }))
+            .route(
+                "/radio.pls",
+                get(|| async {
+                    let playlist = "[playlist]\nFile1=radio.m3u\n";
+                    ([(header::CONTENT_TYPE, "audio/x-scpls")], playlist)
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    async fn collect(mut stream: MediaStream) -> Vec<u8> {
+        let mut out = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            out.extend_from_slice(&chunk.unwrap());
+        }
+        out
+    }
+
+    #[tokio::test]
+    async fn test_relay_strips_icy_metadata() {
+        let base = fake_icecast().await;
+        let relay = RadioRelay::new().allow_private_targets();
+        assert_eq!(relay.now_playing("1"), None);
+
+        // PLS 指向 M3U，M3U 再指向音频流
+        let radio = relay.open("1", &format!("{}/radio.pls", base)).await.unwrap();
+        assert_eq!(radio.content_type, "audio/mpeg");
+        assert_eq!(collect(radio.stream).await, b"abcdefghij");
+        assert_eq!(relay.now_playing("1").as_deref(), Some("Artist - Song"));
+
+        // 没有 icy-metaint 时原样转发
+        let radio = relay.open("2", &format!("{}/plain", base)).await.unwrap();
+        assert_eq!(radio.content_type, "audio/ogg");
+        assert_eq!(collect(radio.stream).await, b"raw");
+        assert_eq!(relay.now_playing("2"), None);
+
+        assert!(relay.open("3", &format!("{}/missing", base)).await.is_err());
+        assert!(relay.open("3", "not a url").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_relay_rejects_private_targets() {
+        let base = fake_icecast().await;
+        let relay = RadioRelay::new();
+        let port = base.rsplit(':').next().unwrap();
+        for url in [
+            format!("{}/live", base),
+            format!("http://localhost:{}/live", port),
+            format!("http://[::1]:{}/live", port),
+            "http://169.254.169.254/latest/meta-data/".to_string(),
+            "file:///etc/passwd".to_string(),
+        ] {
+            assert!(relay.open("1", &url).await.is_err(), "{}", url);
+        }
+    }
+}
@@ -1,11 +1,11 @@
 //! Subsonic API 身份验证中间件
 //!
 //! 支持：
-//! - 用户名/密码身份验证 (u=, p= 参数)
-//! - 基于令牌的身份验证 (u=, t=, s= 参数)
+//! - 用户名/密码身份验证 (u=, p= 参数，`p` 可为 `enc:` 前缀的十六进制编码)
+//! - 基于令牌的身份验证 (u=, t=, s= 参数，`t = md5(password + salt)`)
+//! - OpenSubsonic API 密钥身份验证 (apiKey= 参数)
 //!
-//! 注意：当前实现是简化版本，不执行真实的密码/令牌验证。
-//! 这是用于开发目的的占位符。
+//! 验证失败时返回 Subsonic 响应信封中的错误码，而不是 HTTP 错误。
 
 use axum::{
     body::Body,
@@ -13,97 +13,283 @@ use axum::{
     middleware::Next,
     response::Response,
 };
+use reverie_core::{MediaFile, SubsonicUser, UserContext};
 use reverie_storage::SubsonicStorage;
-use std::sync::Arc;
+use std::fmt;
+use subtle::ConstantTimeEq;
+
+use super::{error_response, SubsonicParams, SubsonicState};
 
 pub const SUBSONIC_API_VERSION: &str = "1.16.1";
 
-/// 从请求中提取的身份验证上下文
-#[derive(Debug, Clone)]
-pub struct AuthContext {
-    pub username: String,
-    pub is_admin: bool,
+/// 从请求中提取的身份验证上下文，即存储层按用户区分数据时使用的 [`UserContext`]
+pub type AuthContext = UserContext;
+
+/// 身份验证失败原因，对应 Subsonic 错误码
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    /// 10: 缺少必需参数
+    MissingParameter(&'static str),
+    /// 10: 参数值无效
+    InvalidParameter(&'static str),
+    /// 40: 用户名或密码错误
+    WrongCredentials,
+    /// 41: 该用户不支持令牌验证
+    TokenAuthNotSupported,
+    /// 42: 不支持所提供的身份验证机制
+    MechanismNotSupported,
+    /// 43: 同时提供了多种相互冲突的身份验证机制
+    ConflictingMechanisms,
+    /// 44: API 密钥无效
+    InvalidApiKey,
+    /// 50: 用户无权执行该操作
+    NotAuthorized,
+    /// 50: 用户必须先修改密码
+    PasswordChangeRequired,
+    /// 0: 存储层错误
+    Storage(String),
+}
+
+impl AuthError {
+    /// Subsonic 错误码
+    pub fn code(&self) -> i32 {
+        match self {
+            AuthError::MissingParameter(_) | AuthError::InvalidParameter(_) => 10,
+            AuthError::WrongCredentials => 40,
+            AuthError::TokenAuthNotSupported => 41,
+            AuthError::MechanismNotSupported => 42,
+            AuthError::ConflictingMechanisms => 43,
+            AuthError::InvalidApiKey => 44,
+            AuthError::NotAuthorized | AuthError::PasswordChangeRequired => 50,
+            AuthError::Storage(_) => 0,
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::MissingParameter(name) => {
+                write!(f, "Required parameter is missing: {}", name)
+            }
+            AuthError::InvalidParameter(name) => write!(f, "Invalid parameter: {}", name),
+            AuthError::WrongCredentials => write!(f, "Wrong username or password"),
+            AuthError::TokenAuthNotSupported => {
+                write!(f, "Token authentication not supported for this user")
+            }
+            AuthError::MechanismNotSupported => {
+                write!(f, "Provided authentication mechanism not supported")
+            }
+            AuthError::ConflictingMechanisms => {
+                write!(f, "Multiple conflicting authentication mechanisms provided")
+            }
+            AuthError::InvalidApiKey => write!(f, "Invalid API key"),
+            AuthError::NotAuthorized => write!(f, "User is not authorized for the given operation"),
+            AuthError::PasswordChangeRequired => {
+                write!(f, "Password must be changed before using the API")
+            }
+            AuthError::Storage(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl From<reverie_storage::error::StorageError> for AuthError {
+    fn from(e: reverie_storage::error::StorageError) -> Self {
+        AuthError::Storage(e.to_string())
+    }
 }
 
 /// Subsonic API 请求的身份验证中间件
-pub async fn auth_middleware<S: SubsonicStorage>(
-    State(storage): State<Arc<S>>,
+pub async fn auth_middleware<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
     mut req: Request<Body>,
     next: Next,
-) -> Result<Response, (i32, String)> {
-    let auth_context = extract_auth(&storage, &req).await?;
-    req.extensions_mut().insert(auth_context);
-    Ok(next.run(req).await)
-}
-
-/// 从请求查询参数中提取身份验证信息
-async fn extract_auth<S: SubsonicStorage>(
-    storage: &Arc<S>,
-    req: &Request<Body>,
-) -> Result<AuthContext, (i32, String)> {
-    let query = req.uri().query().unwrap_or("");
-
-    // 解析查询参数
-    let mut username = None;
-    let mut _password = None;
-    let mut _token = None;
-    let mut _salt = None;
-
-    for pair in query.split('&') {
-        let parts: Vec<&str> = pair.splitn(2, '=').collect();
-        if parts.len() == 2 {
-            let key = parts[0];
-            let value = percent_decode(parts[1]);
-
-            match key {
-                "u" => username = Some(value),
-                "p" => _password = Some(value),
-                "t" => _token = Some(value),
-                "s" => _salt = Some(value),
-                _ => {}
-            }
+) -> Response {
+    let params = SubsonicParams::from_request(req.uri(), req.extensions());
+
+    let result = match authenticate(state.storage.as_ref(), &params).await {
+        // 必须先修改密码的用户只能修改密码
+        Ok(user) if !PASSWORD_CHANGE_PATHS.contains(&req.uri().path()) => {
+            require_password_changed(state.storage.as_ref(), &user)
+                .await
+                .map(|()| user)
+        }
+        result => result,
+    };
+    match result {
+        Ok(auth_context) => {
+            req.extensions_mut().insert(auth_context);
+            next.run(req).await
         }
+        Err(e) => error_response(&params, e.code(), &e.to_string()),
     }
+}
 
-    // 检查是否提供了用户名
-    let username = username.ok_or((10, "Missing username parameter".to_string()))?;
-
-    // 在存储中查找用户
-    match storage.get_user(&username).await {
-        Ok(Some(user)) => {
-            // TODO: 实现正确的密码/令牌验证
-            // 目前只检查用户是否存在
-            Ok(AuthContext {
-                username: user.username,
-                is_admin: user.admin_role,
-            })
-        }
-        Ok(None) => Err((40, "User not found".to_string())),
-        Err(e) => Err((0, format!("Database error: {}", e))),
+/// 必须先修改密码的用户仍可访问的端点
+const PASSWORD_CHANGE_PATHS: &[&str] = &["/ping", "/changePassword"];
+
+/// 用户必须先修改密码时返回 [`AuthError::PasswordChangeRequired`]
+pub async fn require_password_changed<S: SubsonicStorage>(
+    storage: &S,
+    user: &AuthContext,
+) -> Result<(), AuthError> {
+    if storage.password_change_required(&user.username).await? {
+        return Err(AuthError::PasswordChangeRequired);
     }
+    Ok(())
 }
 
-/// 简单的 URL 参数百分号解码
-fn percent_decode(s: &str) -> String {
-    let mut result = String::with_capacity(s.len());
-    let mut chars = s.chars().peekable();
+/// 根据请求参数验证用户身份
+pub async fn authenticate<S: SubsonicStorage>(
+    storage: &S,
+    params: &SubsonicParams,
+) -> Result<AuthContext, AuthError> {
+    let password = params.get("p");
+    let token = params.get("t");
+    let salt = params.get("s");
+
+    let username = if let Some(api_key) = params.get("apiKey") {
+        // apiKey 不能与其他任何凭据一起使用
+        if params.contains("u") || password.is_some() || token.is_some() {
+            return Err(AuthError::ConflictingMechanisms);
+        }
+        if !storage.supports_api_key_auth() {
+            return Err(AuthError::MechanismNotSupported);
+        }
+        storage
+            .get_username_by_api_key(api_key)
+            .await?
+            .ok_or(AuthError::InvalidApiKey)?
+    } else {
+        let username = params
+            .username()
+            .ok_or(AuthError::MissingParameter("u"))?
+            .to_string();
 
-    while let Some(c) = chars.next() {
-        if c == '%' {
-            let hex: String = chars.by_ref().take(2).collect();
-            if let Ok(byte) = u8::from_str_radix(&hex, 16) {
-                result.push(byte as char);
-            } else {
-                result.push('%');
-                result.push_str(&hex);
+        match (password, token, salt) {
+            (Some(_), Some(_), _) => return Err(AuthError::ConflictingMechanisms),
+            (Some(password), None, _) => {
+                if !storage
+                    .verify_user_password(&username, &decode_password(password))
+                    .await?
+                {
+                    return Err(AuthError::WrongCredentials);
+                }
             }
-        } else if c == '+' {
-            result.push(' ');
-        } else {
-            result.push(c);
+            (None, Some(token), Some(salt)) => {
+                let Some(stored) = storage.get_user_password(&username).await? else {
+                    // 用户存在但密码不可取回时，令牌验证不可用
+                    return Err(if storage.get_user(&username).await?.is_some() {
+                        AuthError::TokenAuthNotSupported
+                    } else {
+                        AuthError::WrongCredentials
+                    });
+                };
+                let expected = token_for(&stored, salt);
+                if !bool::from(
+                    token
+                        .to_ascii_lowercase()
+                        .as_bytes()
+                        .ct_eq(expected.as_bytes()),
+                ) {
+                    return Err(AuthError::WrongCredentials);
+                }
+            }
+            (None, Some(_), None) => return Err(AuthError::MissingParameter("s")),
+            (None, None, _) => return Err(AuthError::MissingParameter("p")),
         }
+
+        username
+    };
+
+    let user = storage
+        .get_user(&username)
+        .await?
+        .ok_or(AuthError::WrongCredentials)?;
+
+    Ok(AuthContext::new(username, user.admin_role).with_music_folders(user.folders))
+}
+
+/// 根据 `musicFolderId` 参数和用户可访问的音乐文件夹确定查询范围
+///
+/// 返回 `None` 表示不限制；请求用户无权访问的文件夹时返回 [`AuthError::NotAuthorized`]，
+/// 参数值无效时返回 [`AuthError::InvalidParameter`]
+pub fn music_folder_scope(
+    user: &AuthContext,
+    params: &SubsonicParams,
+) -> Result<Option<Vec<i32>>, AuthError> {
+    let folder = params
+        .parse_opt::<i32>("musicFolderId")
+        .map_err(|_| AuthError::InvalidParameter("musicFolderId"))?;
+    match folder {
+        Some(id) if user.music_folders.allows(id) => Ok(Some(vec![id])),
+        Some(_) => Err(AuthError::NotAuthorized),
+        None => Ok(user.music_folders.folder_ids().map(<[i32]>::to_vec)),
+    }
+}
+
+/// 获取用户可以访问的歌曲，位于用户无权访问的音乐文件夹中的歌曲视为不存在
+pub(super) async fn accessible_song<S: SubsonicStorage>(
+    storage: &S,
+    user: &AuthContext,
+    id: &str,
+) -> reverie_storage::error::Result<Option<MediaFile>> {
+    Ok(storage
+        .get_song(user, id)
+        .await?
+        .filter(|song| user.music_folders.allows(song.library_id)))
+}
+
+fn not_authorized(params: &SubsonicParams) -> Response {
+    let e = AuthError::NotAuthorized;
+    error_response(params, e.code(), &e.to_string())
+}
+
+/// 非管理员请求时返回错误响应
+pub(super) fn require_admin(user: &AuthContext, params: &SubsonicParams) -> Option<Response> {
+    (!user.is_admin).then(|| not_authorized(params))
+}
+
+/// 用户没有 `has_role` 检查的角色时返回错误响应，管理员拥有所有角色
+pub(super) async fn require_role<S: SubsonicStorage>(
+    storage: &S,
+    user: &AuthContext,
+    params: &SubsonicParams,
+    has_role: fn(&SubsonicUser) -> bool,
+) -> Option<Response> {
+    if user.is_admin {
+        return None;
+    }
+    match storage.get_user(&user.username).await {
+        Ok(Some(u)) if has_role(&u) => None,
+        Ok(_) => Some(not_authorized(params)),
+        Err(e) => Some(error_response(params, 0, &e.to_string())),
+    }
+}
+
+/// 计算令牌：`md5(password + salt)` 的小写十六进制
+fn token_for(password: &str, salt: &str) -> String {
+    format!("{:x}", md5::compute(format!("{}{}", password, salt)))
+}
+
+/// 解码 `p` 参数，支持 `enc:` 前缀的十六进制编码
+pub(super) fn decode_password(password: &str) -> String {
+    match password.strip_prefix("enc:") {
+        Some(hex) => hex_decode(hex)
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .unwrap_or_else(|| password.to_string()),
+        None => password.to_string(),
+    }
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
     }
-    result
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
 }
 
 /// 从请求扩展中获取身份验证上下文的辅助函数
@@ -117,14 +303,32 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_percent_decode() {
-        assert_eq!(percent_decode("hello%20world"), "hello world");
-        assert_eq!(percent_decode("test+space"), "test space");
-        assert_eq!(percent_decode("normal"), "normal");
+    fn test_api_version() {
+        assert_eq!(SUBSONIC_API_VERSION, "1.16.1");
     }
 
     #[test]
-    fn test_api_version() {
-        assert_eq!(SUBSONIC_API_VERSION, "1.16.1");
+    fn test_decode_password() {
+        assert_eq!(decode_password("sesame"), "sesame");
+        assert_eq!(decode_password("enc:736573616d65"), "sesame");
+        assert_eq!(decode_password("enc:zz"), "enc:zz");
+    }
+
+    #[test]
+    fn test_token_for_matches_spec_example() {
+        // Subsonic API 文档示例：password=sesame, salt=c19b2d
+        assert_eq!(
+            token_for("sesame", "c19b2d"),
+            "26719a1196d2a940705a59634eb18eab"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_key_unsupported_by_storage() {
+        let storage = reverie_storage::memory::MemoryStorage::new();
+        let params = SubsonicParams::parse("apiKey=key");
+        let err = authenticate(&storage, &params).await.unwrap_err();
+        assert_eq!(err, AuthError::MechanismNotSupported);
+        assert_eq!(err.code(), 42);
     }
 }
@@ -0,0 +1,178 @@
+//! 书签和播放队列端点处理器
+//!
+//! 书签和播放队列都按用户保存，用于在不同设备之间接着播放。
+
+use axum::{extract::State, response::Response, Extension};
+use reverie_storage::{error::StorageError, SubsonicStorage};
+
+use super::response::*;
+use super::{
+    error_response, format_response, ok_response, param_error, AuthContext, SubsonicParams,
+    SubsonicState,
+};
+
+/// 保存播放队列的客户端名称
+fn client_name(params: &SubsonicParams) -> &str {
+    params.client().unwrap_or("unknown")
+}
+
+/// GET /rest/getBookmarks - 获取当前用户的书签
+pub async fn get_bookmarks_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    match state.storage.get_bookmarks(&user).await {
+        Ok(bookmarks) => {
+            let data = BookmarksData {
+                bookmarks: BookmarksList {
+                    bookmark: bookmarks.iter().map(BookmarkItem::from).collect(),
+                },
+            };
+            let response = SubsonicResponse::ok_with(ResponseData::Bookmarks(data));
+            format_response(&params, response)
+        }
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
+
+/// GET /rest/createBookmark - 创建或更新书签
+pub async fn create_bookmark_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
+    };
+    let position: i64 = match params.parse_required("position") {
+        Ok(position) => position,
+        Err(e) => return param_error(&params, e),
+    };
+    let comment = params.get("comment");
+
+    match state
+        .storage
+        .create_bookmark(&user, id, position, comment)
+        .await
+    {
+        Ok(()) => ok_response(&params),
+        Err(StorageError::NotFound(_)) => error_response(&params, 70, "Song not found"),
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
+
+/// GET /rest/deleteBookmark - 删除书签
+pub async fn delete_bookmark_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
+    };
+
+    match state.storage.delete_bookmark(&user, id).await {
+        Ok(()) => ok_response(&params),
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
+
+/// GET /rest/getPlayQueue - 获取当前用户保存的播放队列
+pub async fn get_play_queue_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    match state.storage.get_play_queue(&user).await {
+        Ok(Some(queue)) => {
+            let data = PlayQueueData {
+                play_queue: PlayQueueInner::from(&queue),
+            };
+            let response = SubsonicResponse::ok_with(ResponseData::PlayQueue(data));
+            format_response(&params, response)
+        }
+        // 没有保存过队列时返回空响应
+        Ok(None) => ok_response(&params),
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
+
+/// GET /rest/savePlayQueue - 保存播放队列
+///
+/// `current` 为当前曲目的 ID，队列中有重复曲目时取第一次出现的位置；
+/// 不带 `id` 参数时清空队列
+pub async fn save_play_queue_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    // 按出现顺序保留重复的 `id` 参数，队列中同一首歌可以出现多次
+    let ids = params.get_all("id");
+    let current_index = match params.get("current") {
+        Some(current) => match ids.iter().position(|id| *id == current) {
+            Some(index) => Some(index),
+            None => return error_response(&params, 10, "Current song is not in the queue"),
+        },
+        None => None,
+    };
+    let position = opt_param!(params, "position");
+
+    match state
+        .storage
+        .save_play_queue(&user, &ids, current_index, position, client_name(&params))
+        .await
+    {
+        Ok(()) => ok_response(&params),
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
+
+/// GET /rest/getPlayQueueByIndex - 获取播放队列，当前曲目以队列中的位置表示
+///
+/// OpenSubsonic `indexBasedQueue` 扩展
+pub async fn get_play_queue_by_index_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    match state.storage.get_play_queue(&user).await {
+        Ok(Some(queue)) => {
+            let data = PlayQueueByIndexData {
+                play_queue_by_index: PlayQueueByIndexInner::from(&queue),
+            };
+            let response = SubsonicResponse::ok_with(ResponseData::PlayQueueByIndex(data));
+            format_response(&params, response)
+        }
+        Ok(None) => ok_response(&params),
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
+
+/// GET /rest/savePlayQueueByIndex - 保存播放队列，当前曲目以队列中的位置表示
+///
+/// OpenSubsonic `indexBasedQueue` 扩展
+pub async fn save_play_queue_by_index_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    let ids = params.get_all("id");
+    let current_index = match params.get("currentIndex").map(|i| i.parse::<usize>()) {
+        Some(Ok(index)) if index < ids.len() => Some(index),
+        Some(_) => return error_response(&params, 10, "Invalid parameter: currentIndex"),
+        None => None,
+    };
+    let position = opt_param!(params, "position");
+
+    match state
+        .storage
+        .save_play_queue(&user, &ids, current_index, position, client_name(&params))
+        .await
+    {
+        Ok(()) => ok_response(&params),
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
@@ -2,25 +2,32 @@
 //!
 //! 实现 Subsonic API 的浏览功能（getIndexes, getMusicDirectory, getGenres 等）
 
-use axum::{
-    extract::{Query, State},
-    response::Response,
-};
+use axum::{extract::State, response::Response, Extension};
 use reverie_storage::SubsonicStorage;
-use std::collections::HashMap;
 
-use super::{error_response, format_response, SubsonicState};
+use super::auth::music_folder_scope;
 use super::response::*;
+use super::{
+    error_response, format_response, param_error, AuthContext, SubsonicParams, SubsonicState,
+};
 
 /// GET /rest/getIndexes - 获取艺术家索引
 pub async fn get_indexes_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let music_folder_id = params.get("musicFolderId").and_then(|s| s.parse().ok());
-    let if_modified_since = params.get("ifModifiedSince").and_then(|s| s.parse().ok());
+    let music_folders = match music_folder_scope(&user, &params) {
+        Ok(scope) => scope,
+        Err(e) => return error_response(&params, e.code(), &e.to_string()),
+    };
+    let if_modified_since = opt_param!(params, "ifModifiedSince");
 
-    match state.storage.get_indexes(music_folder_id, if_modified_since).await {
+    match state
+        .storage
+        .get_indexes(&user, music_folders.as_deref(), if_modified_since)
+        .await
+    {
         Ok(indexes) => {
             let data = build_indexes(&indexes, 0);
             let response = SubsonicResponse::ok_with(ResponseData::Indexes(data));
@@ -33,14 +40,15 @@ pub async fn get_indexes_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/getMusicDirectory - 获取目录内容
 pub async fn get_music_directory_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let id = match params.get("id") {
-        Some(id) => id,
-        None => return error_response(&params, 10, "Missing required parameter: id"),
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
     };
 
-    match state.storage.get_music_directory(id).await {
+    match state.storage.get_music_directory(&user, id).await {
         Ok(Some(dir)) => {
             let data = DirectoryData {
                 directory: DirectoryInner::from(&dir),
@@ -56,9 +64,15 @@ pub async fn get_music_directory_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/getGenres - 获取流派列表
 pub async fn get_genres_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    match state.storage.get_genres().await {
+    let music_folders = match music_folder_scope(&user, &params) {
+        Ok(scope) => scope,
+        Err(e) => return error_response(&params, e.code(), &e.to_string()),
+    };
+
+    match state.storage.get_genres(music_folders.as_deref()).await {
         Ok(genres) => {
             let items: Vec<GenreItem> = genres.iter().map(GenreItem::from).collect();
             let data = GenresData {
@@ -74,54 +88,70 @@ pub async fn get_genres_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/getAlbumList - 获取专辑列表（基于文件夹）
 pub async fn get_album_list_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let list_type = match params.get("type") {
-        Some(t) => t.as_str(),
-        None => return error_response(&params, 10, "Missing required parameter: type"),
+    let list_type = match params.required("type") {
+        Ok(t) => t,
+        Err(e) => return param_error(&params, e),
     };
 
-    let size = params.get("size").and_then(|s| s.parse().ok());
-    let offset = params.get("offset").and_then(|s| s.parse().ok());
-    let from_year = params.get("fromYear").and_then(|s| s.parse().ok());
-    let to_year = params.get("toYear").and_then(|s| s.parse().ok());
-    let genre = params.get("genre").map(|s| s.as_str());
-    let music_folder_id = params.get("musicFolderId").and_then(|s| s.parse().ok());
+    let size = opt_param!(params, "size");
+    let offset = opt_param!(params, "offset");
+    let from_year = opt_param!(params, "fromYear");
+    let to_year = opt_param!(params, "toYear");
+    let genre = params.get("genre");
+    let music_folders = match music_folder_scope(&user, &params) {
+        Ok(scope) => scope,
+        Err(e) => return error_response(&params, e.code(), &e.to_string()),
+    };
 
     match state
         .storage
-        .get_album_list(list_type, size, offset, from_year, to_year, genre, music_folder_id)
+        .get_album_list(
+            &user,
+            list_type,
+            size,
+            offset,
+            from_year,
+            to_year,
+            genre,
+            music_folders.as_deref(),
+        )
         .await
     {
         Ok(albums) => {
             // AlbumList 返回 Child 类型，与 AlbumList2 不同
-            let items: Vec<Child> = albums.iter().map(|a| Child {
-                id: a.id.clone(),
-                parent: a.artist_id.clone(),
-                is_dir: true,
-                title: a.name.clone(),
-                album: Some(a.name.clone()),
-                artist: a.artist.clone(),
-                track: None,
-                year: a.year,
-                genre: a.genre.clone(),
-                cover_art: a.cover_art.clone(),
-                size: None,
-                content_type: None,
-                suffix: None,
-                duration: Some(a.duration as i32),
-                bit_rate: None,
-                path: None,
-                play_count: a.play_count,
-                disc_number: None,
-                created: a.created.map(|d| d.to_rfc3339()),
-                album_id: Some(a.id.clone()),
-                artist_id: a.artist_id.clone(),
-                starred: a.starred.map(|d| d.to_rfc3339()),
-                user_rating: a.user_rating,
-                media_type: Some("album".to_string()),
-                is_video: false,
-            }).collect();
+            let items: Vec<Child> = albums
+                .iter()
+                .map(|a| Child {
+                    id: a.id.clone(),
+                    parent: a.artist_id.clone(),
+                    is_dir: true,
+                    title: a.name.clone(),
+                    album: Some(a.name.clone()),
+                    artist: a.artist.clone(),
+                    track: None,
+                    year: a.year,
+                    genre: a.genre.clone(),
+                    cover_art: a.cover_art.clone(),
+                    size: None,
+                    content_type: None,
+                    suffix: None,
+                    duration: Some(a.duration as i32),
+                    bit_rate: None,
+                    path: None,
+                    play_count: a.play_count,
+                    disc_number: None,
+                    created: a.created.map(|d| d.to_rfc3339()),
+                    album_id: Some(a.id.clone()),
+                    artist_id: a.artist_id.clone(),
+                    starred: a.starred.map(|d| d.to_rfc3339()),
+                    user_rating: a.user_rating,
+                    media_type: Some("album".to_string()),
+                    is_video: false,
+                })
+                .collect();
             let data = AlbumListData {
                 album_list: AlbumListInner { album: items },
             };
@@ -135,17 +165,28 @@ pub async fn get_album_list_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/getRandomSongs - 获取随机歌曲
 pub async fn get_random_songs_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let size = params.get("size").and_then(|s| s.parse().ok());
-    let genre = params.get("genre").map(|s| s.as_str());
-    let from_year = params.get("fromYear").and_then(|s| s.parse().ok());
-    let to_year = params.get("toYear").and_then(|s| s.parse().ok());
-    let music_folder_id = params.get("musicFolderId").and_then(|s| s.parse().ok());
+    let size = opt_param!(params, "size");
+    let genre = params.get("genre");
+    let from_year = opt_param!(params, "fromYear");
+    let to_year = opt_param!(params, "toYear");
+    let music_folders = match music_folder_scope(&user, &params) {
+        Ok(scope) => scope,
+        Err(e) => return error_response(&params, e.code(), &e.to_string()),
+    };
 
     match state
         .storage
-        .get_random_songs(size, genre, from_year, to_year, music_folder_id)
+        .get_random_songs(
+            &user,
+            size,
+            genre,
+            from_year,
+            to_year,
+            music_folders.as_deref(),
+        )
         .await
     {
         Ok(songs) => {
@@ -163,20 +204,24 @@ pub async fn get_random_songs_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/getSongsByGenre - 获取指定流派的歌曲
 pub async fn get_songs_by_genre_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let genre = match params.get("genre") {
-        Some(g) => g.as_str(),
-        None => return error_response(&params, 10, "Missing required parameter: genre"),
+    let genre = match params.required("genre") {
+        Ok(g) => g,
+        Err(e) => return param_error(&params, e),
     };
 
-    let count = params.get("count").and_then(|s| s.parse().ok());
-    let offset = params.get("offset").and_then(|s| s.parse().ok());
-    let music_folder_id = params.get("musicFolderId").and_then(|s| s.parse().ok());
+    let count = opt_param!(params, "count");
+    let offset = opt_param!(params, "offset");
+    let music_folders = match music_folder_scope(&user, &params) {
+        Ok(scope) => scope,
+        Err(e) => return error_response(&params, e.code(), &e.to_string()),
+    };
 
     match state
         .storage
-        .get_songs_by_genre(genre, count, offset, music_folder_id)
+        .get_songs_by_genre(&user, genre, count, offset, music_folders.as_deref())
         .await
     {
         Ok(songs) => {
@@ -194,54 +239,70 @@ pub async fn get_songs_by_genre_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/getStarred - 获取收藏内容
 pub async fn get_starred_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let music_folder_id = params.get("musicFolderId").and_then(|s| s.parse().ok());
+    let music_folders = match music_folder_scope(&user, &params) {
+        Ok(scope) => scope,
+        Err(e) => return error_response(&params, e.code(), &e.to_string()),
+    };
 
-    match state.storage.get_starred(music_folder_id).await {
+    match state
+        .storage
+        .get_starred(&user, music_folders.as_deref())
+        .await
+    {
         Ok(starred) => {
             // 转换 artists
-            let artists: Vec<ArtistItem> = starred.artists.iter().map(|a| ArtistItem {
-                id: a.id.clone(),
-                name: a.name.clone(),
-                cover_art: a.cover_art.clone(),
-                artist_image_url: None,
-                starred: a.starred.map(|d| d.to_rfc3339()),
-                user_rating: a.user_rating,
-            }).collect();
-            
+            let artists: Vec<ArtistItem> = starred
+                .artists
+                .iter()
+                .map(|a| ArtistItem {
+                    id: a.id.clone(),
+                    name: a.name.clone(),
+                    cover_art: a.cover_art.clone(),
+                    artist_image_url: None,
+                    starred: a.starred.map(|d| d.to_rfc3339()),
+                    user_rating: a.user_rating,
+                })
+                .collect();
+
             // 转换 albums 为 Child
-            let albums: Vec<Child> = starred.albums.iter().map(|a| Child {
-                id: a.id.clone(),
-                parent: a.artist_id.clone(),
-                is_dir: true,
-                title: a.name.clone(),
-                album: Some(a.name.clone()),
-                artist: a.artist.clone(),
-                track: None,
-                year: a.year,
-                genre: a.genre.clone(),
-                cover_art: a.cover_art.clone(),
-                size: None,
-                content_type: None,
-                suffix: None,
-                duration: Some(a.duration as i32),
-                bit_rate: None,
-                path: None,
-                play_count: a.play_count,
-                disc_number: None,
-                created: a.created.map(|d| d.to_rfc3339()),
-                album_id: Some(a.id.clone()),
-                artist_id: a.artist_id.clone(),
-                starred: a.starred.map(|d| d.to_rfc3339()),
-                user_rating: a.user_rating,
-                media_type: Some("album".to_string()),
-                is_video: false,
-            }).collect();
-            
+            let albums: Vec<Child> = starred
+                .albums
+                .iter()
+                .map(|a| Child {
+                    id: a.id.clone(),
+                    parent: a.artist_id.clone(),
+                    is_dir: true,
+                    title: a.name.clone(),
+                    album: Some(a.name.clone()),
+                    artist: a.artist.clone(),
+                    track: None,
+                    year: a.year,
+                    genre: a.genre.clone(),
+                    cover_art: a.cover_art.clone(),
+                    size: None,
+                    content_type: None,
+                    suffix: None,
+                    duration: Some(a.duration as i32),
+                    bit_rate: None,
+                    path: None,
+                    play_count: a.play_count,
+                    disc_number: None,
+                    created: a.created.map(|d| d.to_rfc3339()),
+                    album_id: Some(a.id.clone()),
+                    artist_id: a.artist_id.clone(),
+                    starred: a.starred.map(|d| d.to_rfc3339()),
+                    user_rating: a.user_rating,
+                    media_type: Some("album".to_string()),
+                    is_video: false,
+                })
+                .collect();
+
             // 转换 songs
             let songs: Vec<Child> = starred.songs.iter().map(Child::from).collect();
-            
+
             let data = StarredData {
                 starred: StarredInner {
                     artist: artists,
@@ -259,16 +320,25 @@ pub async fn get_starred_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/getStarred2 - 获取收藏内容（ID3）
 pub async fn get_starred2_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let music_folder_id = params.get("musicFolderId").and_then(|s| s.parse().ok());
+    let music_folders = match music_folder_scope(&user, &params) {
+        Ok(scope) => scope,
+        Err(e) => return error_response(&params, e.code(), &e.to_string()),
+    };
 
-    match state.storage.get_starred2(music_folder_id).await {
+    match state
+        .storage
+        .get_starred2(&user, music_folders.as_deref())
+        .await
+    {
         Ok(starred) => {
-            let artists: Vec<ArtistID3Item> = starred.artists.iter().map(ArtistID3Item::from).collect();
+            let artists: Vec<ArtistID3Item> =
+                starred.artists.iter().map(ArtistID3Item::from).collect();
             let albums: Vec<AlbumID3Item> = starred.albums.iter().map(AlbumID3Item::from).collect();
             let songs: Vec<Child> = starred.songs.iter().map(Child::from).collect();
-            
+
             let data = Starred2Data {
                 starred2: Starred2Inner {
                     artist: artists,
@@ -286,9 +356,10 @@ pub async fn get_starred2_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/getNowPlaying - 获取正在播放
 pub async fn get_now_playing_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    match state.storage.get_now_playing().await {
+    match state.storage.get_now_playing(&user).await {
         Ok(entries) => {
             let items: Vec<NowPlayingEntry> = entries.iter().map(NowPlayingEntry::from).collect();
             let data = NowPlayingData {
@@ -0,0 +1,142 @@
+//! 音乐文件夹管理端点处理器
+//!
+//! 这些端点不属于 Subsonic 规范，仅管理员可用。VFS 配置通过 `vfs`（方案）和
+//! `vfs.<选项名>` 参数传递，例如 `vfs=fs&vfs.root=/mnt/music`。
+
+use axum::{extract::State, response::Response, Extension};
+use reverie_storage::{error::StorageError, LibraryFolder, SubsonicStorage, VfsConfig};
+
+use super::response::*;
+use super::{
+    error_response, format_response, ok_response, param_error, require_admin, AuthContext,
+    SubsonicParams, SubsonicState,
+};
+
+/// 从 `vfs` 和 `vfs.<选项名>` 参数解析 VFS 配置
+///
+/// 未传 `vfs` 时返回 `None`，`vfs` 为空表示使用默认 VFS
+fn vfs_config_param(params: &SubsonicParams) -> Option<Option<VfsConfig>> {
+    let scheme = params.get("vfs")?;
+    if scheme.is_empty() {
+        return Some(None);
+    }
+    let options = params
+        .iter()
+        .filter_map(|(key, value)| Some((key.strip_prefix("vfs.")?.to_string(), value.to_string())))
+        .collect();
+    Some(Some(VfsConfig {
+        scheme: scheme.to_string(),
+        options,
+    }))
+}
+
+fn folder_response(params: &SubsonicParams, folder: &LibraryFolder) -> Response {
+    let data = MusicFolderData {
+        music_folder: MusicFolderConfigItem::from(folder),
+    };
+    format_response(
+        params,
+        SubsonicResponse::ok_with(ResponseData::MusicFolder(data)),
+    )
+}
+
+fn storage_error_response(params: &SubsonicParams, e: StorageError) -> Response {
+    match e {
+        StorageError::NotFound(_) => error_response(params, 70, "Music folder not found"),
+        e => error_response(params, 0, &e.to_string()),
+    }
+}
+
+/// GET /rest/createMusicFolder - 添加音乐文件夹
+///
+/// 传入 `scan=true` 时在后台扫描新文件夹
+pub async fn create_music_folder_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    if let Some(response) = require_admin(&user, &params) {
+        return response;
+    }
+    let name = match params.required("name") {
+        Ok(name) => name,
+        Err(e) => return param_error(&params, e),
+    };
+    let path = match params.required("path") {
+        Ok(path) => path,
+        Err(e) => return param_error(&params, e),
+    };
+    let vfs_config = vfs_config_param(&params).flatten();
+    let scan = opt_param!(params, "scan").unwrap_or(false);
+
+    match state
+        .storage
+        .create_music_folder(name, path, vfs_config.as_ref(), scan)
+        .await
+    {
+        Ok(folder) => folder_response(&params, &folder),
+        Err(e) => storage_error_response(&params, e),
+    }
+}
+
+/// GET /rest/updateMusicFolder - 重命名音乐文件夹或修改其路径和 VFS
+///
+/// 未传入的参数保持不变
+pub async fn update_music_folder_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    if let Some(response) = require_admin(&user, &params) {
+        return response;
+    }
+    let id: i32 = match params.parse_required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
+    };
+
+    let mut folder = match state.storage.get_music_folder_configs().await {
+        Ok(folders) => match folders.into_iter().find(|f| f.id == id) {
+            Some(folder) => folder,
+            None => return error_response(&params, 70, "Music folder not found"),
+        },
+        Err(e) => return storage_error_response(&params, e),
+    };
+    if let Some(name) = params.get("name") {
+        folder.name = name.to_string();
+    }
+    if let Some(path) = params.get("path") {
+        folder.path = path.to_string();
+    }
+    if let Some(mut vfs_config) = vfs_config_param(&params) {
+        if let Some(config) = vfs_config.as_mut() {
+            config.restore_redacted(folder.vfs_config.as_ref());
+        }
+        folder.vfs_config = vfs_config;
+    }
+
+    match state.storage.update_music_folder(&folder).await {
+        Ok(()) => folder_response(&params, &folder),
+        Err(e) => storage_error_response(&params, e),
+    }
+}
+
+/// GET /rest/deleteMusicFolder - 删除音乐文件夹及其中的曲目
+pub async fn delete_music_folder_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    if let Some(response) = require_admin(&user, &params) {
+        return response;
+    }
+    let id: i32 = match params.parse_required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
+    };
+
+    match state.storage.delete_music_folder(id).await {
+        Ok(()) => ok_response(&params),
+        Err(e) => storage_error_response(&params, e),
+    }
+}
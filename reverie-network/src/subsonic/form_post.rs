@@ -0,0 +1,93 @@
+//! 表单 POST 请求支持（OpenSubsonic `formPost` 扩展）
+//!
+//! 客户端可以用 `application/x-www-form-urlencoded` 请求体代替查询字符串传递参数，
+//! 避免密码出现在 URL 中，也不受 URL 长度限制。中间件解析表单后放入请求扩展，URI 保持不变，
+//! 记录请求 URI 的日志中因此不会出现表单中的凭据。[`SubsonicParams`] 读取参数时合并查询字符串
+//! 和表单，重复的参数都会保留。
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use super::params::{FormParams, SubsonicParams};
+
+/// 表单请求体的大小上限
+const MAX_FORM_LEN: usize = 1024 * 1024;
+
+fn is_form(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|v| {
+            v.trim()
+                .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+        })
+}
+
+/// 读取 POST 表单请求体并放入请求扩展
+pub(super) async fn form_post_middleware(req: Request, next: Next) -> Response {
+    if req.method() != Method::POST || !is_form(req.headers()) {
+        return next.run(req).await;
+    }
+
+    let (mut parts, body) = req.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, MAX_FORM_LEN).await else {
+        return (StatusCode::PAYLOAD_TOO_LARGE, "Form body is too large").into_response();
+    };
+    let Ok(form) = std::str::from_utf8(&bytes) else {
+        return (StatusCode::BAD_REQUEST, "Form body is not valid UTF-8").into_response();
+    };
+    parts
+        .extensions
+        .insert(FormParams(SubsonicParams::parse(form.trim())));
+    next.run(Request::from_parts(parts, Body::empty())).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_form_stays_out_of_uri() {
+        use axum::{http::Uri, middleware, routing::post, Router};
+        use tower::ServiceExt;
+
+        let app = Router::new()
+            .route(
+                "/star",
+                post(|uri: Uri, params: SubsonicParams| async move {
+                    format!("{} {:?}", uri, params.get_all("id"))
+                }),
+            )
+            .layer(middleware::from_fn(form_post_middleware));
+        let request = Request::builder()
+            .method("POST")
+            .uri("/star?id=1")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from("p=secret&id=2"))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], br#"/star?id=1 ["1", "2"]"#);
+    }
+
+    #[test]
+    fn test_is_form() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_form(&headers));
+        headers.insert(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded; charset=UTF-8".parse().unwrap(),
+        );
+        assert!(is_form(&headers));
+        headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        assert!(!is_form(&headers));
+    }
+}
@@ -0,0 +1,180 @@
+//! 网络电台端点处理器
+//!
+//! 所有用户都可以获取电台列表，创建、修改和删除电台仅管理员可用。
+//! 启用电台转发时，`streamInternetRadio`（非 Subsonic 标准端点）由服务器连接电台并转发音频，
+//! 电台列表同时返回从 ICY 元数据读取的当前曲目。
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::Response,
+    Extension,
+};
+use reverie_storage::{error::StorageError, SubsonicStorage};
+
+use super::response::*;
+use super::{
+    error_response, format_response, ok_response, param_error, require_admin, AuthContext,
+    ParamError, SubsonicParams, SubsonicState,
+};
+
+fn storage_error_response(params: &SubsonicParams, e: StorageError) -> Response {
+    match e {
+        StorageError::NotFound(_) => error_response(params, 70, "Internet radio station not found"),
+        e => error_response(params, 0, &e.to_string()),
+    }
+}
+
+/// 去掉首尾空白后的参数值，空值视为未传
+fn text_param<'a>(params: &'a SubsonicParams, name: &str) -> Option<&'a str> {
+    params.get(name).map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn missing_param(params: &SubsonicParams, name: &str) -> Response {
+    param_error(params, ParamError::Missing(name.to_string()))
+}
+
+/// GET /rest/getInternetRadioStations - 获取所有网络电台
+pub async fn get_internet_radio_stations_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    params: SubsonicParams,
+) -> Response {
+    match state.storage.get_internet_radio_stations().await {
+        Ok(stations) => {
+            let station = stations
+                .iter()
+                .map(|s| {
+                    let mut item = InternetRadioStationItem::from(s);
+                    item.now_playing = state
+                        .radio_relay
+                        .as_ref()
+                        .and_then(|relay| relay.now_playing(&s.id));
+                    item
+                })
+                .collect();
+            let data = InternetRadioStationsData {
+                internet_radio_stations: InternetRadioStationsList {
+                    internet_radio_station: station,
+                },
+            };
+            let response = SubsonicResponse::ok_with(ResponseData::InternetRadioStations(data));
+            format_response(&params, response)
+        }
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
+
+/// GET /rest/createInternetRadioStation - 添加网络电台
+///
+/// `streamUrl` 可以是音频流，也可以是 M3U 或 PLS 播放列表
+pub async fn create_internet_radio_station_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    if let Some(response) = require_admin(&user, &params) {
+        return response;
+    }
+    let Some(stream_url) = text_param(&params, "streamUrl") else {
+        return missing_param(&params, "streamUrl");
+    };
+    let Some(name) = text_param(&params, "name") else {
+        return missing_param(&params, "name");
+    };
+    let homepage_url = params.get("homepageUrl");
+
+    match state
+        .storage
+        .create_internet_radio_station(stream_url, name, homepage_url)
+        .await
+    {
+        Ok(()) => ok_response(&params),
+        Err(e) => storage_error_response(&params, e),
+    }
+}
+
+/// GET /rest/updateInternetRadioStation - 修改网络电台
+pub async fn update_internet_radio_station_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    if let Some(response) = require_admin(&user, &params) {
+        return response;
+    }
+    let Some(id) = text_param(&params, "id") else {
+        return missing_param(&params, "id");
+    };
+    let Some(stream_url) = text_param(&params, "streamUrl") else {
+        return missing_param(&params, "streamUrl");
+    };
+    let Some(name) = text_param(&params, "name") else {
+        return missing_param(&params, "name");
+    };
+    let homepage_url = params.get("homepageUrl");
+
+    match state
+        .storage
+        .update_internet_radio_station(id, stream_url, name, homepage_url)
+        .await
+    {
+        Ok(()) => ok_response(&params),
+        Err(e) => storage_error_response(&params, e),
+    }
+}
+
+/// GET /rest/deleteInternetRadioStation - 删除网络电台
+pub async fn delete_internet_radio_station_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    if let Some(response) = require_admin(&user, &params) {
+        return response;
+    }
+    let Some(id) = text_param(&params, "id") else {
+        return missing_param(&params, "id");
+    };
+
+    match state.storage.delete_internet_radio_station(id).await {
+        Ok(()) => ok_response(&params),
+        Err(e) => storage_error_response(&params, e),
+    }
+}
+
+/// GET /rest/streamInternetRadio - 通过服务器收听网络电台
+///
+/// 非 Subsonic 标准端点，服务器未启用电台转发时返回错误
+pub async fn stream_internet_radio_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    params: SubsonicParams,
+) -> Response {
+    let Some(relay) = state.radio_relay.as_ref() else {
+        return error_response(&params, 0, "Internet radio relay is disabled");
+    };
+    let Some(id) = text_param(&params, "id") else {
+        return missing_param(&params, "id");
+    };
+    let station = match state.storage.get_internet_radio_station(id).await {
+        Ok(Some(station)) => station,
+        Ok(None) => return error_response(&params, 70, "Internet radio station not found"),
+        Err(e) => return error_response(&params, 0, &e.to_string()),
+    };
+
+    match relay.open(&station.id, &station.stream_url).await {
+        Ok(radio) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, radio.content_type)
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(axum::body::Body::from_stream(radio.stream))
+            .unwrap(),
+        Err(e) => {
+            tracing::warn!(
+                "Failed to relay internet radio station {}: {}",
+                station.id,
+                e
+            );
+            error_response(&params, 0, &e.to_string())
+        }
+    }
+}
@@ -3,30 +3,64 @@
 //! Reverie 旨在兼容 Subsonic API 1.16.1。
 //! 该模块提供了所有 Subsonic API 端点的处理程序。
 
+/// 解析可选参数，值无效时从所在的处理器返回错误响应（错误码 10）
+macro_rules! opt_param {
+    ($params:expr, $name:expr) => {
+        match $params.parse_opt($name) {
+            Ok(value) => value,
+            Err(e) => return $crate::subsonic::param_error(&$params, e),
+        }
+    };
+}
+
 mod auth;
+mod bookmarks;
 mod browsing;
+mod folders;
+mod form_post;
+mod internet_radio;
+mod params;
 mod playlists;
-mod users;
+pub(crate) mod range;
 pub mod response;
+mod shares;
+mod users;
 
 #[cfg(test)]
 mod tests;
 
+use auth::{accessible_song, music_folder_scope, require_admin, require_role};
+pub use auth::{
+    authenticate, require_password_changed, AuthContext, AuthError, SUBSONIC_API_VERSION,
+};
+pub use params::{ParamError, SubsonicParams};
+
 use axum::{
-    extract::{Query, State},
-    http::{header, StatusCode},
+    extract::State,
+    handler::Handler,
+    http::{header, HeaderMap, StatusCode},
+    middleware,
     response::{IntoResponse, Response},
-    routing::get,
-    Router,
+    routing::{get, MethodRouter},
+    Extension, Router,
 };
+use futures::TryStreamExt;
 use reverie_storage::{FileStorage, SubsonicStorage};
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
+
+use crate::radio::RadioRelay;
+use crate::traits::{MediaStreamer, TranscodeInput, TranscodeOptions};
+use crate::transcoding::{self, CacheKey, TranscodeCache};
 
 use response::*;
 
 // 导入子模块处理器
+use bookmarks::*;
 use browsing::*;
+use folders::*;
+use internet_radio::*;
 use playlists::*;
+use shares::*;
 use users::*;
 
 // === State and Response Helpers ===
@@ -34,152 +68,334 @@ use users::*;
 #[derive(Clone)]
 pub struct SubsonicState<S: Clone> {
     pub storage: Arc<S>,
+    /// 转码器，未配置时始终返回原始文件
+    pub transcoder: Option<Arc<dyn MediaStreamer>>,
+    /// 转码结果缓存
+    pub transcode_cache: Option<TranscodeCache>,
+    /// 网络电台转发，未配置时客户端直接连接电台
+    pub radio_relay: Option<RadioRelay>,
+    /// 分享链接使用的公开地址（如 `https://music.example`），未配置时根据请求生成
+    pub public_base_url: Option<String>,
+    /// 是否信任反向代理设置的 `X-Forwarded-Host` 和 `X-Forwarded-Proto`
+    pub trust_forwarded_headers: bool,
 }
 
 impl<S: Clone> SubsonicState<S> {
     pub fn new(storage: Arc<S>) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            transcoder: None,
+            transcode_cache: None,
+            radio_relay: None,
+            public_base_url: None,
+            trust_forwarded_headers: false,
+        }
     }
-}
 
-/// 根据格式参数返回 JSON 或 XML
-fn format_response(params: &HashMap<String, String>, response: SubsonicResponse) -> Response {
-    let format = params.get("f").map(|s| s.as_str()).unwrap_or("xml");
+    /// 启用实时转码
+    pub fn with_transcoder(mut self, transcoder: Arc<dyn MediaStreamer>) -> Self {
+        self.transcoder = Some(transcoder);
+        self
+    }
 
-    if format == "json" {
-        (
-            StatusCode::OK,
-            [(header::CONTENT_TYPE, "application/json")],
-            serde_json::to_string(&response).unwrap_or_default(),
-        )
-            .into_response()
-    } else {
-        // 目前，即使对于 XML 请求也返回 JSON（完整的 XML 支持待办）
-        (
+    /// 缓存转码结果
+    pub fn with_transcode_cache(mut self, cache: TranscodeCache) -> Self {
+        self.transcode_cache = Some(cache);
+        self
+    }
+
+    /// 通过服务器转发网络电台
+    pub fn with_radio_relay(mut self, relay: RadioRelay) -> Self {
+        self.radio_relay = Some(relay);
+        self
+    }
+
+    /// 设置分享链接使用的公开地址
+    pub fn with_public_base_url(mut self, url: impl Into<String>) -> Self {
+        self.public_base_url = Some(url.into());
+        self
+    }
+
+    /// 信任反向代理设置的 `X-Forwarded-*` 请求头，仅在服务器只能通过反向代理访问时启用
+    pub fn trust_forwarded_headers(mut self) -> Self {
+        self.trust_forwarded_headers = true;
+        self
+    }
+}
+
+/// 根据格式参数返回 XML（默认）、JSON 或 JSONP
+fn format_response(params: &SubsonicParams, response: SubsonicResponse) -> Response {
+    match params.format() {
+        "json" => (
             StatusCode::OK,
             [(header::CONTENT_TYPE, "application/json")],
             serde_json::to_string(&response).unwrap_or_default(),
         )
-            .into_response()
+            .into_response(),
+        "jsonp" => match params.get("callback").filter(|c| is_valid_callback(c)) {
+            Some(callback) => (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, "application/javascript")],
+                format!(
+                    "{}({});",
+                    callback,
+                    serde_json::to_string(&response).unwrap_or_default()
+                ),
+            )
+                .into_response(),
+            None => {
+                let params: SubsonicParams = [("f", "json")].into_iter().collect();
+                error_response(&params, 10, "Required parameter is missing: callback")
+            }
+        },
+        _ => match to_xml(&response) {
+            Ok(xml) => (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, "text/xml; charset=utf-8")],
+                xml,
+            )
+                .into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        },
     }
 }
 
-fn ok_response(params: &HashMap<String, String>) -> Response {
+/// JSONP 回调名只允许 JavaScript 标识符及点号路径，防止注入任意脚本
+fn is_valid_callback(callback: &str) -> bool {
+    !callback.is_empty()
+        && callback
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '.')
+}
+
+fn ok_response(params: &SubsonicParams) -> Response {
     format_response(params, SubsonicResponse::ok())
 }
 
-fn error_response(params: &HashMap<String, String>, code: i32, message: &str) -> Response {
+fn error_response(params: &SubsonicParams, code: i32, message: &str) -> Response {
     format_response(params, SubsonicResponse::error(code, message))
 }
 
+/// 参数缺失或无效时的错误响应（错误码 10）
+fn param_error(params: &SubsonicParams, e: ParamError) -> Response {
+    error_response(params, e.code(), &e.to_string())
+}
+
 /// 创建 Subsonic 路由器。
 ///
 /// 注意：返回的路由器缺少 `SubsonicState<S>`，它旨在嵌套到提供状态的外部路由器中，
-/// 通过 `Router::with_state` 实现。
+/// 通过 `Router::with_state` 实现。`state` 仅用于身份验证中间件，除
+/// `getOpenSubsonicExtensions` 外所有端点都需要验证。所有端点同时接受 GET 和表单 POST 请求。
 #[cfg(feature = "axum-server")]
-pub(crate) fn create_router<S: SubsonicStorage + FileStorage + Clone + 'static>() -> Router<SubsonicState<S>> {
+pub(crate) fn create_router<S: SubsonicStorage + FileStorage + Clone + 'static>(
+    state: SubsonicState<S>,
+) -> Router<SubsonicState<S>> {
     Router::new()
         // System endpoints
-        .route("/ping", get(ping_handler::<S>))
-        .route("/getLicense", get(get_license_handler::<S>))
-        .route("/getMusicFolders", get(get_music_folders_handler::<S>))
+        .route("/ping", get_or_post(ping_handler))
+        .route("/getLicense", get_or_post(get_license_handler))
+        .route(
+            "/getMusicFolders",
+            get_or_post(get_music_folders_handler::<S>),
+        )
+        .route(
+            "/createMusicFolder",
+            get_or_post(create_music_folder_handler::<S>),
+        )
+        .route(
+            "/updateMusicFolder",
+            get_or_post(update_music_folder_handler::<S>),
+        )
+        .route(
+            "/deleteMusicFolder",
+            get_or_post(delete_music_folder_handler::<S>),
+        )
         // Browsing endpoints
-        .route("/getIndexes", get(get_indexes_handler::<S>))
-        .route("/getMusicDirectory", get(get_music_directory_handler::<S>))
-        .route("/getGenres", get(get_genres_handler::<S>))
-        .route("/getArtists", get(get_artists_handler::<S>))
-        .route("/getArtist", get(get_artist_handler::<S>))
-        .route("/getAlbum", get(get_album_handler::<S>))
-        .route("/getSong", get(get_song_handler::<S>))
-        .route("/getArtistInfo", get(stub_handler::<S>))
-        .route("/getArtistInfo2", get(stub_handler::<S>))
-        .route("/getAlbumInfo", get(stub_handler::<S>))
-        .route("/getAlbumInfo2", get(stub_handler::<S>))
-        .route("/getSimilarSongs", get(stub_handler::<S>))
-        .route("/getSimilarSongs2", get(stub_handler::<S>))
-        .route("/getTopSongs", get(stub_handler::<S>))
+        .route("/getIndexes", get_or_post(get_indexes_handler::<S>))
+        .route(
+            "/getMusicDirectory",
+            get_or_post(get_music_directory_handler::<S>),
+        )
+        .route("/getGenres", get_or_post(get_genres_handler::<S>))
+        .route("/getArtists", get_or_post(get_artists_handler::<S>))
+        .route("/getArtist", get_or_post(get_artist_handler::<S>))
+        .route("/getAlbum", get_or_post(get_album_handler::<S>))
+        .route("/getSong", get_or_post(get_song_handler::<S>))
+        .route("/getArtistInfo", get_or_post(stub_handler))
+        .route("/getArtistInfo2", get_or_post(stub_handler))
+        .route("/getAlbumInfo", get_or_post(stub_handler))
+        .route("/getAlbumInfo2", get_or_post(stub_handler))
+        .route("/getSimilarSongs", get_or_post(stub_handler))
+        .route("/getSimilarSongs2", get_or_post(stub_handler))
+        .route("/getTopSongs", get_or_post(stub_handler))
         // Album list endpoints
-        .route("/getAlbumList", get(get_album_list_handler::<S>))
-        .route("/getAlbumList2", get(get_album_list2_handler::<S>))
-        .route("/getRandomSongs", get(get_random_songs_handler::<S>))
-        .route("/getSongsByGenre", get(get_songs_by_genre_handler::<S>))
-        .route("/getNowPlaying", get(get_now_playing_handler::<S>))
-        .route("/getStarred", get(get_starred_handler::<S>))
-        .route("/getStarred2", get(get_starred2_handler::<S>))
+        .route("/getAlbumList", get_or_post(get_album_list_handler::<S>))
+        .route("/getAlbumList2", get_or_post(get_album_list2_handler::<S>))
+        .route(
+            "/getRandomSongs",
+            get_or_post(get_random_songs_handler::<S>),
+        )
+        .route(
+            "/getSongsByGenre",
+            get_or_post(get_songs_by_genre_handler::<S>),
+        )
+        .route("/getNowPlaying", get_or_post(get_now_playing_handler::<S>))
+        .route("/getStarred", get_or_post(get_starred_handler::<S>))
+        .route("/getStarred2", get_or_post(get_starred2_handler::<S>))
         // Search endpoints
-        .route("/search2", get(search2_handler::<S>))
-        .route("/search3", get(search3_handler::<S>))
+        .route("/search2", get_or_post(search2_handler::<S>))
+        .route("/search3", get_or_post(search3_handler::<S>))
         // Playlist endpoints
-        .route("/getPlaylists", get(get_playlists_handler::<S>))
-        .route("/getPlaylist", get(get_playlist_handler::<S>))
-        .route("/createPlaylist", get(create_playlist_handler::<S>))
-        .route("/updatePlaylist", get(update_playlist_handler::<S>))
-        .route("/deletePlaylist", get(delete_playlist_handler::<S>))
+        .route("/getPlaylists", get_or_post(get_playlists_handler::<S>))
+        .route("/getPlaylist", get_or_post(get_playlist_handler::<S>))
+        .route("/createPlaylist", get_or_post(create_playlist_handler::<S>))
+        .route("/updatePlaylist", get_or_post(update_playlist_handler::<S>))
+        .route("/deletePlaylist", get_or_post(delete_playlist_handler::<S>))
         // Media retrieval endpoints
-        .route("/stream", get(stream_handler::<S>))
-        .route("/download", get(download_handler::<S>))
-        .route("/getCoverArt", get(get_cover_art_handler::<S>))
-        .route("/getLyrics", get(stub_handler::<S>))
-        .route("/getLyricsBySongId", get(stub_handler::<S>))
-        .route("/getAvatar", get(stub_handler::<S>))
+        .route("/stream", get_or_post(stream_handler::<S>))
+        .route("/download", get_or_post(download_handler::<S>))
+        .route("/getCoverArt", get_or_post(get_cover_art_handler::<S>))
+        .route("/getLyrics", get_or_post(stub_handler))
+        .route("/getLyricsBySongId", get_or_post(stub_handler))
+        .route("/getAvatar", get_or_post(stub_handler))
         // Annotation endpoints
-        .route("/star", get(star_handler::<S>))
-        .route("/unstar", get(unstar_handler::<S>))
-        .route("/setRating", get(set_rating_handler::<S>))
-        .route("/scrobble", get(scrobble_handler::<S>))
+        .route("/star", get_or_post(star_handler::<S>))
+        .route("/unstar", get_or_post(unstar_handler::<S>))
+        .route("/setRating", get_or_post(set_rating_handler::<S>))
+        .route("/scrobble", get_or_post(scrobble_handler::<S>))
+        .route(
+            "/getScrobbleAccounts",
+            get_or_post(get_scrobble_accounts_handler::<S>),
+        )
+        .route(
+            "/updateScrobbleAccount",
+            get_or_post(update_scrobble_account_handler::<S>),
+        )
+        .route(
+            "/deleteScrobbleAccount",
+            get_or_post(delete_scrobble_account_handler::<S>),
+        )
         // Bookmark endpoints
-        .route("/getBookmarks", get(stub_handler::<S>))
-        .route("/createBookmark", get(stub_handler::<S>))
-        .route("/deleteBookmark", get(stub_handler::<S>))
-        .route("/getPlayQueue", get(stub_handler::<S>))
-        .route("/savePlayQueue", get(stub_handler::<S>))
+        .route("/getBookmarks", get_or_post(get_bookmarks_handler::<S>))
+        .route("/createBookmark", get_or_post(create_bookmark_handler::<S>))
+        .route("/deleteBookmark", get_or_post(delete_bookmark_handler::<S>))
+        .route("/getPlayQueue", get_or_post(get_play_queue_handler::<S>))
+        .route("/savePlayQueue", get_or_post(save_play_queue_handler::<S>))
+        .route(
+            "/getPlayQueueByIndex",
+            get_or_post(get_play_queue_by_index_handler::<S>),
+        )
+        .route(
+            "/savePlayQueueByIndex",
+            get_or_post(save_play_queue_by_index_handler::<S>),
+        )
         // Share endpoints
-        .route("/getShares", get(stub_handler::<S>))
-        .route("/createShare", get(stub_handler::<S>))
-        .route("/updateShare", get(stub_handler::<S>))
-        .route("/deleteShare", get(stub_handler::<S>))
+        .route("/getShares", get_or_post(get_shares_handler::<S>))
+        .route("/createShare", get_or_post(create_share_handler::<S>))
+        .route("/updateShare", get_or_post(update_share_handler::<S>))
+        .route("/deleteShare", get_or_post(delete_share_handler::<S>))
         // Internet radio endpoints
-        .route("/getInternetRadioStations", get(stub_handler::<S>))
-        .route("/createInternetRadioStation", get(stub_handler::<S>))
-        .route("/updateInternetRadioStation", get(stub_handler::<S>))
-        .route("/deleteInternetRadioStation", get(stub_handler::<S>))
+        .route(
+            "/getInternetRadioStations",
+            get_or_post(get_internet_radio_stations_handler::<S>),
+        )
+        .route(
+            "/createInternetRadioStation",
+            get_or_post(create_internet_radio_station_handler::<S>),
+        )
+        .route(
+            "/updateInternetRadioStation",
+            get_or_post(update_internet_radio_station_handler::<S>),
+        )
+        .route(
+            "/deleteInternetRadioStation",
+            get_or_post(delete_internet_radio_station_handler::<S>),
+        )
+        .route(
+            "/streamInternetRadio",
+            get_or_post(stream_internet_radio_handler::<S>),
+        )
         // User management endpoints
-        .route("/getUser", get(get_user_handler::<S>))
-        .route("/getUsers", get(get_users_handler::<S>))
+        .route("/getUser", get_or_post(get_user_handler::<S>))
+        .route("/getUsers", get_or_post(get_users_handler::<S>))
+        .route("/createUser", get_or_post(create_user_handler::<S>))
+        .route("/updateUser", get_or_post(update_user_handler::<S>))
+        .route("/deleteUser", get_or_post(delete_user_handler::<S>))
+        .route("/changePassword", get_or_post(change_password_handler::<S>))
         // Scanning endpoints
-        .route("/getScanStatus", get(get_scan_status_handler::<S>))
-        .route("/startScan", get(start_scan_handler::<S>))
-        // OpenSubsonic extensions
-        .route("/getOpenSubsonicExtensions", get(stub_handler::<S>))
+        .route("/getScanStatus", get_or_post(get_scan_status_handler::<S>))
+        .route("/startScan", get_or_post(start_scan_handler::<S>))
+        .route_layer(middleware::from_fn_with_state(
+            state,
+            auth::auth_middleware::<S>,
+        ))
+        // OpenSubsonic 规范要求此端点无需身份验证
+        .route(
+            "/getOpenSubsonicExtensions",
+            get_or_post(get_open_subsonic_extensions_handler::<S>),
+        )
+        .route_layer(middleware::from_fn(form_post::form_post_middleware))
+}
+
+/// 同时接受 GET 和表单 POST 请求的路由
+fn get_or_post<H, T, S>(handler: H) -> MethodRouter<S>
+where
+    H: Handler<T, S>,
+    T: 'static,
+    S: Clone + Send + Sync + 'static,
+{
+    get(handler.clone()).post(handler)
 }
 
 // ===== 系统处理器 =====
 
 /// GET /rest/ping - 测试连接
-async fn ping_handler<S: SubsonicStorage + Clone>(
-    Query(params): Query<HashMap<String, String>>,
-) -> Response {
+async fn ping_handler(params: SubsonicParams) -> Response {
     ok_response(&params)
 }
 
 /// GET /rest/getLicense - 获取服务器许可证信息
-async fn get_license_handler<S: SubsonicStorage + Clone>(
-    Query(params): Query<HashMap<String, String>>,
-) -> Response {
+async fn get_license_handler(params: SubsonicParams) -> Response {
     let response = SubsonicResponse::ok_with(ResponseData::License(LicenseData {
         license: License { valid: true },
     }));
     format_response(&params, response)
 }
 
+/// GET /rest/getOpenSubsonicExtensions - 获取支持的 OpenSubsonic 扩展
+async fn get_open_subsonic_extensions_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    params: SubsonicParams,
+) -> Response {
+    match state.storage.get_open_subsonic_extensions().await {
+        Ok(extensions) => {
+            let data = OpenSubsonicExtensionsData {
+                open_subsonic_extensions: extensions
+                    .iter()
+                    .map(OpenSubsonicExtensionItem::from)
+                    .collect(),
+            };
+            let response = SubsonicResponse::ok_with(ResponseData::OpenSubsonicExtensions(data));
+            format_response(&params, response)
+        }
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
+
 /// GET /rest/getMusicFolders - 获取已配置的音乐文件夹
 async fn get_music_folders_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
     match state.storage.get_music_folders().await {
         Ok(folders) => {
-            let items: Vec<MusicFolderItem> = folders.iter().map(MusicFolderItem::from).collect();
+            // 只列出用户可访问的文件夹
+            let items: Vec<MusicFolderItem> = folders
+                .iter()
+                .filter(|f| user.music_folders.allows(f.id))
+                .map(MusicFolderItem::from)
+                .collect();
             let response =
                 SubsonicResponse::ok_with(ResponseData::MusicFolders(MusicFoldersData {
                     music_folders: MusicFoldersList {
@@ -195,9 +411,7 @@ async fn get_music_folders_handler<S: SubsonicStorage + Clone>(
 // ===== 未实现端点的存根处理器 =====
 
 /// 未实现端点的存根处理器 - 返回空的 OK 响应
-async fn stub_handler<S: SubsonicStorage + Clone>(
-    Query(params): Query<HashMap<String, String>>,
-) -> Response {
+async fn stub_handler(params: SubsonicParams) -> Response {
     ok_response(&params)
 }
 
@@ -206,11 +420,19 @@ async fn stub_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/getArtists - 获取所有艺术家
 async fn get_artists_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let music_folder_id = params.get("musicFolderId").and_then(|s| s.parse().ok());
+    let music_folders = match music_folder_scope(&user, &params) {
+        Ok(scope) => scope,
+        Err(e) => return error_response(&params, e.code(), &e.to_string()),
+    };
 
-    match state.storage.get_artists(music_folder_id).await {
+    match state
+        .storage
+        .get_artists(&user, music_folders.as_deref())
+        .await
+    {
         Ok(indexes) => {
             let data = build_artists(&indexes, 0);
             let response = SubsonicResponse::ok_with(ResponseData::Artists(data));
@@ -223,14 +445,15 @@ async fn get_artists_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/getArtist - 获取艺术家详情
 async fn get_artist_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let id = match params.get("id") {
-        Some(id) => id,
-        None => return error_response(&params, 10, "Missing required parameter: id"),
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
     };
 
-    match state.storage.get_artist(id).await {
+    match state.storage.get_artist(&user, id).await {
         Ok(Some(artist)) => {
             let data = ArtistData {
                 artist: ArtistWithAlbums::from(&artist),
@@ -246,14 +469,15 @@ async fn get_artist_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/getAlbum - 获取专辑详情
 async fn get_album_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let id = match params.get("id") {
-        Some(id) => id,
-        None => return error_response(&params, 10, "Missing required parameter: id"),
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
     };
 
-    match state.storage.get_album(id).await {
+    match state.storage.get_album(&user, id).await {
         Ok(Some(album)) => {
             let data = AlbumData {
                 album: AlbumWithSongs::from(&album),
@@ -269,14 +493,15 @@ async fn get_album_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/getSong - 获取歌曲详情
 async fn get_song_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let id = match params.get("id") {
-        Some(id) => id,
-        None => return error_response(&params, 10, "Missing required parameter: id"),
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
     };
 
-    match state.storage.get_song(id).await {
+    match accessible_song(state.storage.as_ref(), &user, id).await {
         Ok(Some(song)) => {
             let data = SongData {
                 song: Child::from(&song),
@@ -294,30 +519,35 @@ async fn get_song_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/getAlbumList2 - 按类型获取专辑列表（基于 ID3 标签）
 async fn get_album_list2_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let list_type = match params.get("type") {
-        Some(t) => t.as_str(),
-        None => return error_response(&params, 10, "Missing required parameter: type"),
+    let list_type = match params.required("type") {
+        Ok(t) => t,
+        Err(e) => return param_error(&params, e),
     };
 
-    let size = params.get("size").and_then(|s| s.parse().ok());
-    let offset = params.get("offset").and_then(|s| s.parse().ok());
-    let from_year = params.get("fromYear").and_then(|s| s.parse().ok());
-    let to_year = params.get("toYear").and_then(|s| s.parse().ok());
-    let genre = params.get("genre").map(|s| s.as_str());
-    let music_folder_id = params.get("musicFolderId").and_then(|s| s.parse().ok());
+    let size = opt_param!(params, "size");
+    let offset = opt_param!(params, "offset");
+    let from_year = opt_param!(params, "fromYear");
+    let to_year = opt_param!(params, "toYear");
+    let genre = params.get("genre");
+    let music_folders = match music_folder_scope(&user, &params) {
+        Ok(scope) => scope,
+        Err(e) => return error_response(&params, e.code(), &e.to_string()),
+    };
 
     match state
         .storage
         .get_album_list2(
+            &user,
             list_type,
             size,
             offset,
             from_year,
             to_year,
             genre,
-            music_folder_id,
+            music_folders.as_deref(),
         )
         .await
     {
@@ -338,24 +568,29 @@ async fn get_album_list2_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/search3 - 使用 ID3 标签搜索
 async fn search3_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let query = match params.get("query") {
-        Some(q) => q.as_str(),
-        None => return error_response(&params, 10, "Missing required parameter: query"),
+    let query = match params.required("query") {
+        Ok(q) => q,
+        Err(e) => return param_error(&params, e),
     };
 
-    let artist_count = params.get("artistCount").and_then(|s| s.parse().ok());
-    let artist_offset = params.get("artistOffset").and_then(|s| s.parse().ok());
-    let album_count = params.get("albumCount").and_then(|s| s.parse().ok());
-    let album_offset = params.get("albumOffset").and_then(|s| s.parse().ok());
-    let song_count = params.get("songCount").and_then(|s| s.parse().ok());
-    let song_offset = params.get("songOffset").and_then(|s| s.parse().ok());
-    let _music_folder_id: Option<i32> = params.get("musicFolderId").and_then(|s| s.parse().ok());
+    let artist_count = opt_param!(params, "artistCount");
+    let artist_offset = opt_param!(params, "artistOffset");
+    let album_count = opt_param!(params, "albumCount");
+    let album_offset = opt_param!(params, "albumOffset");
+    let song_count = opt_param!(params, "songCount");
+    let song_offset = opt_param!(params, "songOffset");
+    let music_folders = match music_folder_scope(&user, &params) {
+        Ok(scope) => scope,
+        Err(e) => return error_response(&params, e.code(), &e.to_string()),
+    };
 
     match state
         .storage
         .search3(
+            &user,
             query,
             artist_count,
             artist_offset,
@@ -363,6 +598,7 @@ async fn search3_handler<S: SubsonicStorage + Clone>(
             album_offset,
             song_count,
             song_offset,
+            music_folders.as_deref(),
         )
         .await
     {
@@ -389,17 +625,20 @@ async fn search3_handler<S: SubsonicStorage + Clone>(
 // ===== 媒体检索处理器 =====
 
 /// GET /rest/getCoverArt - 获取封面图片
+///
+/// 与 `stream` 相同，无权访问的音乐文件夹中的专辑和歌曲视为没有封面
 async fn get_cover_art_handler<S: SubsonicStorage + FileStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let id = match params.get("id") {
-        Some(id) => id,
-        None => return error_response(&params, 10, "Missing required parameter: id"),
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
     };
-    let _size: Option<i32> = params.get("size").and_then(|s| s.parse().ok());
+    let _size: Option<i32> = opt_param!(params, "size");
 
-    match state.storage.get_cover_art_path(id).await {
+    match state.storage.get_cover_art_path(&user, id).await {
         Ok(Some(path)) => {
             // 读取封面图片文件
             match state.storage.read_file(&path).await {
@@ -425,7 +664,10 @@ async fn get_cover_art_handler<S: SubsonicStorage + FileStorage + Clone>(
                 }
                 Err(e) => Response::builder()
                     .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .body(axum::body::Body::from(format!("Failed to read cover art: {}", e)))
+                    .body(axum::body::Body::from(format!(
+                        "Failed to read cover art: {}",
+                        e
+                    )))
                     .unwrap(),
             }
         }
@@ -441,62 +683,157 @@ async fn get_cover_art_handler<S: SubsonicStorage + FileStorage + Clone>(
 }
 
 /// GET /rest/stream - 流式传输媒体文件
-async fn stream_handler<S: SubsonicStorage + FileStorage + Clone>(
+///
+/// 根据 `format`、`maxBitRate` 与用户的比特率上限决定是否实时转码；
+/// 转码时支持 `timeOffset`（OpenSubsonic `transcodeOffset` 扩展）。
+async fn stream_handler<S: SubsonicStorage + FileStorage + Clone + 'static>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    headers: HeaderMap,
+    params: SubsonicParams,
 ) -> Response {
-    let id = match params.get("id") {
-        Some(id) => id,
-        None => return error_response(&params, 10, "Missing required parameter: id"),
+    let storage = state.storage.as_ref();
+    if let Some(response) = require_role(storage, &user, &params, |u| u.stream_role).await {
+        return response;
+    }
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
+    };
+    let song = match accessible_song(storage, &user, id).await {
+        Ok(Some(song)) => song,
+        Ok(None) => return error_response(&params, 70, "Song not found"),
+        Err(e) => return error_response(&params, 0, &e.to_string()),
     };
 
     // 可选参数
-    let _max_bit_rate: Option<i32> = params.get("maxBitRate").and_then(|s| s.parse().ok());
-    let _format = params.get("format").map(|s| s.as_str());
-    let _time_offset: Option<i32> = params.get("timeOffset").and_then(|s| s.parse().ok());
-    let _estimated_content_length: Option<bool> = params.get("estimateContentLength").and_then(|s| s.parse().ok());
-
-    match state.storage.get_stream_path(id).await {
-        Ok(Some(path)) => {
-            // 读取媒体文件
-            match state.storage.read_file(&path).await {
-                Ok(data) => {
-                    // 根据文件扩展名确定 MIME 类型
-                    let mime_type = if path.ends_with(".flac") {
-                        "audio/flac"
-                    } else if path.ends_with(".ogg") || path.ends_with(".opus") {
-                        "audio/ogg"
-                    } else if path.ends_with(".m4a") || path.ends_with(".aac") {
-                        "audio/mp4"
-                    } else if path.ends_with(".wav") {
-                        "audio/wav"
-                    } else if path.ends_with(".wma") {
-                        "audio/x-ms-wma"
-                    } else {
-                        "audio/mpeg"
-                    };
+    let max_bit_rate: Option<u32> = opt_param!(params, "maxBitRate").filter(|b| *b > 0);
+    let format = params.get("format");
+    let time_offset: Option<u32> = opt_param!(params, "timeOffset");
+    let _estimated_content_length: Option<bool> = opt_param!(params, "estimateContentLength");
+
+    let path = match state.storage.get_stream_path(id).await {
+        Ok(Some(path)) => path,
+        Ok(None) => {
+            return Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(axum::body::Body::from("Media file not found"))
+                .unwrap()
+        }
+        Err(e) => {
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(axum::body::Body::from(e.to_string()))
+                .unwrap()
+        }
+    };
 
-                    Response::builder()
-                        .status(StatusCode::OK)
-                        .header(header::CONTENT_TYPE, mime_type)
-                        .header(header::CONTENT_LENGTH, data.len())
-                        .header(header::ACCEPT_RANGES, "bytes")
-                        .body(axum::body::Body::from(data))
-                        .unwrap()
-                }
-                Err(e) => Response::builder()
-                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .body(axum::body::Body::from(format!("Failed to read media file: {}", e)))
-                    .unwrap(),
+    if let Some(transcoder) = state.transcoder.clone() {
+        // 请求的上限与用户设置的上限取较小值
+        let user_max_bit_rate = match state.storage.get_user(&user.username).await {
+            Ok(Some(u)) => u.max_bit_rate.filter(|b| *b > 0).map(|b| b as u32),
+            _ => None,
+        };
+        let max_bit_rate = match (max_bit_rate, user_max_bit_rate) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        if let Some(options) = transcoding::select_transcode(
+            transcoder.as_ref(),
+            &song.suffix,
+            song.bit_rate,
+            format,
+            max_bit_rate,
+            time_offset,
+        ) {
+            match transcode_response(&state, transcoder.as_ref(), id, &path, &options).await {
+                Ok(response) => return response,
+                Err(e) => tracing::warn!("Transcoding {} failed, serving original: {}", path, e),
             }
         }
-        Ok(None) => Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .body(axum::body::Body::from("Media file not found"))
-            .unwrap(),
-        Err(e) => Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .body(axum::body::Body::from(e.to_string()))
-            .unwrap(),
     }
+
+    let mime_type = audio_content_type(&path);
+    range::serve_file(state.storage.clone(), path, &headers, mime_type, None).await
+}
+
+/// 根据文件扩展名确定音频文件的 MIME 类型
+pub(crate) fn audio_content_type(path: &str) -> &'static str {
+    if path.ends_with(".flac") {
+        "audio/flac"
+    } else if path.ends_with(".ogg") || path.ends_with(".opus") {
+        "audio/ogg"
+    } else if path.ends_with(".m4a") || path.ends_with(".aac") {
+        "audio/mp4"
+    } else if path.ends_with(".wav") {
+        "audio/wav"
+    } else if path.ends_with(".wma") {
+        "audio/x-ms-wma"
+    } else {
+        "audio/mpeg"
+    }
+}
+
+/// 启动转码并将编码器输出作为响应体；转码输出不支持 Range
+///
+/// 配置了缓存时，从头开始播放的转码结果会被缓存；带 `timeOffset` 的请求直接转码。
+async fn transcode_response<S: FileStorage + Clone + 'static>(
+    state: &SubsonicState<S>,
+    transcoder: &dyn MediaStreamer,
+    id: &str,
+    path: &str,
+    options: &TranscodeOptions,
+) -> crate::error::Result<Response> {
+    let meta = state
+        .storage
+        .get_file_metadata(path)
+        .await
+        .map_err(|e| crate::error::NetworkError::NotFound(e.to_string()))?;
+    let encode = || async {
+        // 优先让编码器直接读取源文件，以便随机访问
+        let input = match state.storage.direct_location(path).await {
+            Some(location) => TranscodeInput::Location(location),
+            None => TranscodeInput::Stream {
+                data: Box::pin(
+                    range::file_stream(state.storage.clone(), path.to_string(), 0, meta.size)
+                        .map_err(std::io::Error::other),
+                ),
+                suffix: path
+                    .rsplit_once('.')
+                    .map(|(_, s)| s)
+                    .unwrap_or_default()
+                    .to_string(),
+            },
+        };
+        transcoder.transcode(input, options).await
+    };
+
+    let output = match &state.transcode_cache {
+        Some(cache) if options.time_offset.is_none() => {
+            let key = CacheKey {
+                track_id: id.to_string(),
+                mtime: meta
+                    .modified
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs() as i64)
+                    .unwrap_or_default(),
+                format: options.format.clone(),
+                bit_rate: options.bit_rate.unwrap_or(0),
+            };
+            cache.get_or_encode(&key, encode).await?
+        }
+        _ => encode().await?,
+    };
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(
+            header::CONTENT_TYPE,
+            transcoder
+                .content_type(&options.format)
+                .unwrap_or("application/octet-stream"),
+        )
+        .header(header::ACCEPT_RANGES, "none")
+        .body(axum::body::Body::from_stream(output))
+        .unwrap())
 }
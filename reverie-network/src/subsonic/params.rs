@@ -0,0 +1,239 @@
+//! Subsonic 请求参数
+//!
+//! Subsonic 允许重复传递同名参数，例如 `star?id=1&id=2&albumId=3`、`createPlaylist` 的多个
+//! `songId`。[`SubsonicParams`] 按原始顺序保留全部参数，单值读取时取第一个值。
+//! 表单 POST 的请求体由 `form_post` 中间件解析为 [`FormParams`]，读取时追加在查询字符串的参数之后。
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, Extensions, Uri},
+};
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+/// 参数缺失或格式无效，对应 Subsonic 错误码 10
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamError {
+    /// 缺少必需参数
+    Missing(String),
+    /// 参数值无法解析
+    Invalid(String),
+}
+
+impl ParamError {
+    /// Subsonic 错误码
+    pub fn code(&self) -> i32 {
+        10
+    }
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamError::Missing(name) => write!(f, "Missing required parameter: {}", name),
+            ParamError::Invalid(name) => write!(f, "Invalid parameter: {}", name),
+        }
+    }
+}
+
+impl std::error::Error for ParamError {}
+
+/// 表单 POST 请求体中的参数，由 `form_post` 中间件放入请求扩展
+#[derive(Debug, Clone)]
+pub(super) struct FormParams(pub(super) SubsonicParams);
+
+/// Subsonic 请求的全部参数
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubsonicParams {
+    pairs: Vec<(String, String)>,
+}
+
+impl SubsonicParams {
+    /// 解析查询字符串，没有 `=` 的参数值为空字符串
+    pub fn parse(query: &str) -> Self {
+        let pairs = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(key), percent_decode(value))
+            })
+            .collect();
+        Self { pairs }
+    }
+
+    /// 合并查询字符串和表单请求体中的参数，查询字符串中的参数在前
+    pub(super) fn from_request(uri: &Uri, extensions: &Extensions) -> Self {
+        let mut params = Self::parse(uri.query().unwrap_or(""));
+        if let Some(FormParams(form)) = extensions.get() {
+            params.pairs.extend(form.pairs.iter().cloned());
+        }
+        params
+    }
+
+    /// 参数的第一个值
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// 参数的全部值，按请求中的顺序排列
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.pairs
+            .iter()
+            .filter(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    /// 按请求中的顺序遍历全部参数
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.pairs.iter().any(|(k, _)| k == name)
+    }
+
+    /// 必需参数的第一个值
+    pub fn required(&self, name: &str) -> Result<&str, ParamError> {
+        self.get(name)
+            .ok_or_else(|| ParamError::Missing(name.to_string()))
+    }
+
+    /// 解析可选参数，未传时返回 `None`
+    pub fn parse_opt<T: FromStr>(&self, name: &str) -> Result<Option<T>, ParamError> {
+        self.get(name)
+            .map(|v| v.parse().map_err(|_| ParamError::Invalid(name.to_string())))
+            .transpose()
+    }
+
+    /// 解析必需参数
+    pub fn parse_required<T: FromStr>(&self, name: &str) -> Result<T, ParamError> {
+        self.parse_opt(name)?
+            .ok_or_else(|| ParamError::Missing(name.to_string()))
+    }
+
+    /// 解析重复参数的全部值，任一值无效时返回错误
+    pub fn parse_all<T: FromStr>(&self, name: &str) -> Result<Vec<T>, ParamError> {
+        self.get_all(name)
+            .into_iter()
+            .map(|v| v.parse().map_err(|_| ParamError::Invalid(name.to_string())))
+            .collect()
+    }
+
+    /// `u`：用户名
+    pub fn username(&self) -> Option<&str> {
+        self.get("u")
+    }
+
+    /// `v`：客户端使用的 API 版本
+    pub fn version(&self) -> Option<&str> {
+        self.get("v")
+    }
+
+    /// `c`：客户端名称
+    pub fn client(&self) -> Option<&str> {
+        self.get("c")
+    }
+
+    /// `f`：响应格式，未传时为 `xml`
+    pub fn format(&self) -> &str {
+        self.get("f").unwrap_or("xml")
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for SubsonicParams {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self {
+            pairs: iter
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for SubsonicParams {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_request(&parts.uri, &parts.extensions))
+    }
+}
+
+/// URL 参数百分号解码，`+` 解码为空格
+fn percent_decode(s: &str) -> String {
+    let mut result = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex: Vec<u8> = bytes.by_ref().take(2).collect();
+            match std::str::from_utf8(&hex)
+                .ok()
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+            {
+                Some(byte) => result.push(byte),
+                None => {
+                    result.push(b'%');
+                    result.extend_from_slice(&hex);
+                }
+            }
+        } else if b == b'+' {
+            result.push(b' ');
+        } else {
+            result.push(b);
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("hello%20world"), "hello world");
+        assert_eq!(percent_decode("test+space"), "test space");
+        assert_eq!(percent_decode("normal"), "normal");
+        assert_eq!(percent_decode("%E9%9F%B3%E4%B9%90"), "音乐");
+    }
+
+    #[test]
+    fn test_repeated_params() {
+        let params = SubsonicParams::parse("u=admin&id=1&albumId=a&id=2&flag&&id=3");
+        assert_eq!(params.username(), Some("admin"));
+        assert_eq!(params.get("id"), Some("1"));
+        assert_eq!(params.get_all("id"), vec!["1", "2", "3"]);
+        assert_eq!(params.get_all("albumId"), vec!["a"]);
+        assert!(params.get_all("artistId").is_empty());
+        assert_eq!(params.get("flag"), Some(""));
+        assert_eq!(params.parse_all::<i32>("id"), Ok(vec![1, 2, 3]));
+        assert!(params.parse_all::<i32>("albumId").is_err());
+    }
+
+    #[test]
+    fn test_typed_getters() {
+        let params = SubsonicParams::parse("v=1.16.1&c=test&count=20&offset=x");
+        assert_eq!(params.version(), Some("1.16.1"));
+        assert_eq!(params.client(), Some("test"));
+        assert_eq!(params.format(), "xml");
+        assert_eq!(params.parse_opt::<u32>("count"), Ok(Some(20)));
+        assert_eq!(params.parse_opt::<u32>("size"), Ok(None));
+        assert_eq!(params.parse_required::<u32>("count"), Ok(20));
+
+        let missing = params.required("id").unwrap_err();
+        assert_eq!(missing, ParamError::Missing("id".to_string()));
+        assert_eq!(missing.code(), 10);
+        assert_eq!(missing.to_string(), "Missing required parameter: id");
+        let invalid = params.parse_required::<u32>("offset").unwrap_err();
+        assert_eq!(invalid.to_string(), "Invalid parameter: offset");
+    }
+}
@@ -1,23 +1,48 @@
 //! 播放列表相关端点处理器
+//!
+//! 创建、修改和删除播放列表需要 `playlistRole` 权限，修改和删除其他用户的播放列表还需要
+//! 管理员权限。
 
-use axum::{
-    extract::{Query, State},
-    response::Response,
-};
-use reverie_storage::SubsonicStorage;
-use std::collections::HashMap;
+use axum::{extract::State, response::Response, Extension};
+use reverie_storage::{error::StorageError, SubsonicStorage};
 
-use super::{error_response, format_response, ok_response, SubsonicState};
 use super::response::*;
+use super::{
+    error_response, format_response, ok_response, param_error, require_role, AuthContext,
+    AuthError, SubsonicParams, SubsonicState,
+};
+
+/// 没有编辑播放列表的权限时返回错误响应
+async fn require_playlist_role<S: SubsonicStorage + Clone>(
+    state: &SubsonicState<S>,
+    user: &AuthContext,
+    params: &SubsonicParams,
+) -> Option<Response> {
+    require_role(state.storage.as_ref(), user, params, |u| u.playlist_role).await
+}
+
+fn playlist_error_response(params: &SubsonicParams, e: StorageError) -> Response {
+    match e {
+        StorageError::NotFound(_) => error_response(params, 70, "Playlist not found"),
+        StorageError::PermissionDenied(_) => {
+            let e = AuthError::NotAuthorized;
+            error_response(params, e.code(), &e.to_string())
+        }
+        e => error_response(params, 0, &e.to_string()),
+    }
+}
 
 /// GET /rest/getPlaylists - 获取播放列表
+///
+/// 默认返回请求用户可以看到的播放列表，只有管理员可以通过 `username` 查看其他用户的
 pub async fn get_playlists_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let username = params.get("username").map(|s| s.as_str());
+    let username = params.get("username");
 
-    match state.storage.get_playlists(username).await {
+    match state.storage.get_playlists(&user, username).await {
         Ok(playlists) => {
             let items: Vec<PlaylistItem> = playlists.iter().map(PlaylistItem::from).collect();
             let data = PlaylistsData {
@@ -26,21 +51,22 @@ pub async fn get_playlists_handler<S: SubsonicStorage + Clone>(
             let response = SubsonicResponse::ok_with(ResponseData::Playlists(data));
             format_response(&params, response)
         }
-        Err(e) => error_response(&params, 0, &e.to_string()),
+        Err(e) => playlist_error_response(&params, e),
     }
 }
 
 /// GET /rest/getPlaylist - 获取播放列表详情
 pub async fn get_playlist_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let id = match params.get("id") {
-        Some(id) => id,
-        None => return error_response(&params, 10, "Missing required parameter: id"),
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
     };
 
-    match state.storage.get_playlist(id).await {
+    match state.storage.get_playlist(&user, id).await {
         Ok(Some(playlist)) => {
             let data = PlaylistData {
                 playlist: PlaylistWithEntries::from(&playlist),
@@ -49,30 +75,36 @@ pub async fn get_playlist_handler<S: SubsonicStorage + Clone>(
             format_response(&params, response)
         }
         Ok(None) => error_response(&params, 70, "Playlist not found"),
-        Err(e) => error_response(&params, 0, &e.to_string()),
+        Err(e) => playlist_error_response(&params, e),
     }
 }
 
 /// GET /rest/createPlaylist - 创建或更新播放列表
+///
+/// 指定 `playlistId` 时替换该播放列表的歌曲，只有所有者和管理员可以更新
 pub async fn create_playlist_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let playlist_id = params.get("playlistId").map(|s| s.as_str());
-    let name = params.get("name").map(|s| s.as_str());
-    
+    if let Some(response) = require_playlist_role(&state, &user, &params).await {
+        return response;
+    }
+    let playlist_id = params.get("playlistId");
+    let name = params.get("name");
+
     // 收集所有 songId 参数
-    let song_ids: Vec<&str> = params
-        .iter()
-        .filter(|(k, _)| k.starts_with("songId"))
-        .map(|(_, v)| v.as_str())
-        .collect();
+    let song_ids = params.get_all("songId");
 
     if playlist_id.is_none() && name.is_none() {
         return error_response(&params, 10, "Either playlistId or name must be provided");
     }
 
-    match state.storage.create_playlist(name, playlist_id, &song_ids).await {
+    match state
+        .storage
+        .create_playlist(&user, name, playlist_id, &song_ids)
+        .await
+    {
         Ok(playlist) => {
             let data = PlaylistData {
                 playlist: PlaylistWithEntries::from(&playlist),
@@ -80,41 +112,41 @@ pub async fn create_playlist_handler<S: SubsonicStorage + Clone>(
             let response = SubsonicResponse::ok_with(ResponseData::Playlist(data));
             format_response(&params, response)
         }
-        Err(e) => error_response(&params, 0, &e.to_string()),
+        Err(e) => playlist_error_response(&params, e),
     }
 }
 
 /// GET /rest/updatePlaylist - 更新播放列表
 pub async fn update_playlist_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let playlist_id = match params.get("playlistId") {
-        Some(id) => id,
-        None => return error_response(&params, 10, "Missing required parameter: playlistId"),
+    if let Some(response) = require_playlist_role(&state, &user, &params).await {
+        return response;
+    }
+    let playlist_id = match params.required("playlistId") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
     };
 
-    let name = params.get("name").map(|s| s.as_str());
-    let comment = params.get("comment").map(|s| s.as_str());
-    let public = params.get("public").and_then(|s| s.parse().ok());
+    let name = params.get("name");
+    let comment = params.get("comment");
+    let public = opt_param!(params, "public");
 
     // 收集要添加的歌曲
-    let song_ids_to_add: Vec<&str> = params
-        .iter()
-        .filter(|(k, _)| k.starts_with("songIdToAdd"))
-        .map(|(_, v)| v.as_str())
-        .collect();
+    let song_ids_to_add = params.get_all("songIdToAdd");
 
     // 收集要删除的索引
-    let indexes_to_remove: Vec<i32> = params
-        .iter()
-        .filter(|(k, _)| k.starts_with("songIndexToRemove"))
-        .filter_map(|(_, v)| v.parse().ok())
-        .collect();
+    let indexes_to_remove: Vec<i32> = match params.parse_all("songIndexToRemove") {
+        Ok(indexes) => indexes,
+        Err(e) => return param_error(&params, e),
+    };
 
     match state
         .storage
         .update_playlist(
+            &user,
             playlist_id,
             name,
             comment,
@@ -125,22 +157,26 @@ pub async fn update_playlist_handler<S: SubsonicStorage + Clone>(
         .await
     {
         Ok(()) => ok_response(&params),
-        Err(e) => error_response(&params, 0, &e.to_string()),
+        Err(e) => playlist_error_response(&params, e),
     }
 }
 
 /// GET /rest/deletePlaylist - 删除播放列表
 pub async fn delete_playlist_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let id = match params.get("id") {
-        Some(id) => id,
-        None => return error_response(&params, 10, "Missing required parameter: id"),
+    if let Some(response) = require_playlist_role(&state, &user, &params).await {
+        return response;
+    }
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
     };
 
-    match state.storage.delete_playlist(id).await {
+    match state.storage.delete_playlist(&user, id).await {
         Ok(()) => ok_response(&params),
-        Err(e) => error_response(&params, 0, &e.to_string()),
+        Err(e) => playlist_error_response(&params, e),
     }
 }
@@ -0,0 +1,354 @@
+//! HTTP Range 请求处理
+//!
+//! 为 `/rest/stream` 与 `/rest/download` 提供单区间 206 响应、`If-Range` 校验，
+//! 并按固定大小分块从存储读取文件，避免将整个媒体文件载入内存。
+
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+};
+use futures::Stream;
+use reverie_storage::{FileStorage, StorageError};
+use std::{sync::Arc, time::SystemTime};
+
+/// 每次从存储读取的块大小
+pub const CHUNK_SIZE: u64 = 256 * 1024;
+
+/// `Range` 请求头的解析结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// 无 Range 头或无法识别，返回完整内容
+    Full,
+    /// 闭区间 `[start, end]`
+    Partial { start: u64, end: u64 },
+    /// 区间无法满足或包含多个区间，返回 416
+    Unsatisfiable,
+}
+
+/// 解析 `Range` 请求头
+///
+/// 仅支持单个 `bytes` 区间；多区间请求按不可满足处理。
+/// 语法错误或未知单位的请求头按 RFC 9110 忽略。
+pub fn parse_range(value: &str, size: u64) -> ByteRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Unsatisfiable;
+    }
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (first, last) = (first.trim(), last.trim());
+
+    if first.is_empty() {
+        // 后缀区间：bytes=-N 表示最后 N 个字节
+        return match last.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if size == 0 => ByteRange::Unsatisfiable,
+            Ok(n) => ByteRange::Partial {
+                start: size.saturating_sub(n),
+                end: size - 1,
+            },
+            Err(_) => ByteRange::Full,
+        };
+    }
+
+    let Ok(start) = first.parse::<u64>() else {
+        return ByteRange::Full;
+    };
+    let end = if last.is_empty() {
+        size.saturating_sub(1)
+    } else {
+        match last.parse::<u64>() {
+            Ok(end) if end >= start => end.min(size.saturating_sub(1)),
+            _ => return ByteRange::Full,
+        }
+    };
+
+    if start >= size {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial { start, end }
+    }
+}
+
+/// 根据文件大小和修改时间生成 ETag
+pub fn etag_for(size: u64, modified: SystemTime) -> String {
+    let mtime = modified
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    format!("\"{:x}-{:x}\"", mtime, size)
+}
+
+/// `If-Range` 校验：值与当前 ETag 或 Last-Modified 匹配时才允许部分响应
+fn if_range_matches(value: &str, etag: &str, modified: SystemTime) -> bool {
+    let value = value.trim();
+    if value.starts_with('"') {
+        return value == etag;
+    }
+    if value.starts_with("W/") {
+        // 弱校验器不能用于 If-Range
+        return false;
+    }
+    match httpdate::parse_http_date(value) {
+        Ok(date) => httpdate::fmt_http_date(date) == httpdate::fmt_http_date(modified),
+        Err(_) => false,
+    }
+}
+
+/// 生成作为附件下载的 `Content-Disposition` 头
+///
+/// `filename` 为非 ASCII 字符替换为 `_` 的后备文件名，`filename*` 按 RFC 5987
+/// 保存 UTF-8 百分号编码的原文件名
+pub fn attachment_disposition(filename: &str) -> HeaderValue {
+    let fallback: String = filename
+        .chars()
+        .map(|c| {
+            if c == ' ' || (c.is_ascii_graphic() && !matches!(c, '"' | '\\')) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let mut encoded = String::new();
+    for &b in filename.as_bytes() {
+        if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    HeaderValue::from_str(&format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback, encoded
+    ))
+    .expect("Content-Disposition is always ASCII")
+}
+
+/// 以流的方式返回存储中的文件，支持 Range 与 If-Range
+///
+/// 指定 `attachment_filename` 时作为附件下载，见 [`attachment_disposition`]
+pub async fn serve_file<S: FileStorage + 'static>(
+    storage: Arc<S>,
+    path: String,
+    headers: &HeaderMap,
+    content_type: &str,
+    attachment_filename: Option<&str>,
+) -> Response {
+    let meta = match storage.get_file_metadata(&path).await {
+        Ok(meta) => meta,
+        Err(StorageError::NotFound(_)) => {
+            return plain_response(StatusCode::NOT_FOUND, "Media file not found".to_string())
+        }
+        Err(e) => {
+            return plain_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to read media file: {}", e),
+            )
+        }
+    };
+
+    let size = meta.size;
+    let etag = etag_for(size, meta.modified);
+
+    let range = match headers.get(header::RANGE).and_then(|v| v.to_str().ok()) {
+        Some(value) => {
+            let fresh = headers
+                .get(header::IF_RANGE)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| if_range_matches(v, &etag, meta.modified))
+                .unwrap_or(true);
+            if fresh {
+                parse_range(value, size)
+            } else {
+                ByteRange::Full
+            }
+        }
+        None => ByteRange::Full,
+    };
+
+    let mut builder = Response::builder()
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, &etag)
+        .header(
+            header::LAST_MODIFIED,
+            httpdate::fmt_http_date(meta.modified),
+        );
+
+    let (start, length) = match range {
+        ByteRange::Full => {
+            builder = builder.status(StatusCode::OK);
+            (0, size)
+        }
+        ByteRange::Partial { start, end } => {
+            builder = builder.status(StatusCode::PARTIAL_CONTENT).header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, size),
+            );
+            (start, end - start + 1)
+        }
+        ByteRange::Unsatisfiable => {
+            return builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+                .body(Body::empty())
+                .unwrap();
+        }
+    };
+
+    builder = builder
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, length);
+    if let Some(filename) = attachment_filename {
+        builder = builder.header(
+            header::CONTENT_DISPOSITION,
+            attachment_disposition(filename),
+        );
+    }
+
+    builder
+        .body(chunked_body(storage, path, start, length))
+        .unwrap()
+}
+
+/// 从 `start` 开始按块读取 `length` 字节的响应体
+fn chunked_body<S: FileStorage + 'static>(
+    storage: Arc<S>,
+    path: String,
+    start: u64,
+    length: u64,
+) -> Body {
+    Body::from_stream(file_stream(storage, path, start, length))
+}
+
+/// 从 `start` 开始按 [`CHUNK_SIZE`] 分块读取 `length` 字节
+pub fn file_stream<S: FileStorage + 'static>(
+    storage: Arc<S>,
+    path: String,
+    start: u64,
+    length: u64,
+) -> impl Stream<Item = std::result::Result<Bytes, StorageError>> + Send {
+    let end = start + length;
+    futures::stream::try_unfold(start, move |offset| {
+        let storage = storage.clone();
+        let path = path.clone();
+        async move {
+            if offset >= end {
+                return Ok(None);
+            }
+            let want = CHUNK_SIZE.min(end - offset);
+            let chunk = storage.read_file_range(&path, offset, want).await?;
+            if chunk.is_empty() {
+                // 文件在传输过程中被截断
+                return Err(StorageError::IoError(std::io::Error::from(
+                    std::io::ErrorKind::UnexpectedEof,
+                )));
+            }
+            let next = offset + chunk.len() as u64;
+            Ok(Some((Bytes::from(chunk), next)))
+        }
+    })
+}
+
+fn plain_response(status: StatusCode, message: String) -> Response {
+    Response::builder()
+        .status(status)
+        .body(Body::from(message))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(
+            parse_range("bytes=0-99", 1000),
+            ByteRange::Partial { start: 0, end: 99 }
+        );
+        assert_eq!(
+            parse_range("bytes=900-", 1000),
+            ByteRange::Partial {
+                start: 900,
+                end: 999
+            }
+        );
+        assert_eq!(
+            parse_range("bytes=-100", 1000),
+            ByteRange::Partial {
+                start: 900,
+                end: 999
+            }
+        );
+        assert_eq!(
+            parse_range("bytes=-5000", 1000),
+            ByteRange::Partial { start: 0, end: 999 }
+        );
+        assert_eq!(
+            parse_range("bytes=500-5000", 1000),
+            ByteRange::Partial {
+                start: 500,
+                end: 999
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_range_unsatisfiable() {
+        assert_eq!(parse_range("bytes=1000-", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(
+            parse_range("bytes=0-1,5-10", 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(parse_range("bytes=0-", 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn test_parse_range_ignores_malformed() {
+        assert_eq!(parse_range("items=0-10", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=abc", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=10-5", 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn test_attachment_disposition() {
+        assert_eq!(
+            attachment_disposition("test.mp3"),
+            "attachment; filename=\"test.mp3\"; filename*=UTF-8''test.mp3"
+        );
+        assert_eq!(
+            attachment_disposition("周杰伦 - 晴天.flac"),
+            "attachment; filename=\"___ - __.flac\"; \
+             filename*=UTF-8''%E5%91%A8%E6%9D%B0%E4%BC%A6%20-%20%E6%99%B4%E5%A4%A9.flac"
+        );
+        assert_eq!(
+            attachment_disposition("a\"b\\c.mp3"),
+            "attachment; filename=\"a_b_c.mp3\"; filename*=UTF-8''a%22b%5Cc.mp3"
+        );
+    }
+
+    #[test]
+    fn test_if_range() {
+        let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let etag = etag_for(1000, modified);
+        assert!(if_range_matches(&etag, &etag, modified));
+        assert!(!if_range_matches("\"other\"", &etag, modified));
+        assert!(!if_range_matches(&format!("W/{}", etag), &etag, modified));
+        assert!(if_range_matches(
+            &httpdate::fmt_http_date(modified),
+            &etag,
+            modified
+        ));
+        assert!(!if_range_matches(
+            "Mon, 01 Jan 2001 00:00:00 GMT",
+            &etag,
+            modified
+        ));
+    }
+}
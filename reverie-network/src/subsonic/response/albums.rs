@@ -149,6 +149,7 @@ impl From<AlbumList2Data> for super::ResponseData {
 
 // === 专辑信息 ===
 #[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct AlbumInfoData {
     pub album_info: AlbumInfo,
 }
@@ -159,8 +160,11 @@ pub struct AlbumInfo {
     pub notes: Option<String>,
     pub music_brainz_id: Option<String>,
     pub last_fm_url: Option<String>,
+    #[serde(rename = "smallImageUrl")]
     pub small_url: Option<String>,
+    #[serde(rename = "mediumImageUrl")]
     pub medium_url: Option<String>,
+    #[serde(rename = "largeImageUrl")]
     pub large_url: Option<String>,
 }
 
@@ -1,6 +1,9 @@
 //! 艺术家相关 DTO 类型
 
+use std::collections::BTreeMap;
+
 use reverie_core::{SubsonicArtist, SubsonicArtistInfo, SubsonicArtistIndex};
+use reverie_storage::LibraryFolder;
 use serde::Serialize;
 
 use super::AlbumID3Item;
@@ -40,6 +43,49 @@ impl From<MusicFoldersData> for super::ResponseData {
     }
 }
 
+/// 管理端点返回的单个音乐文件夹，包含扫描路径和 VFS 配置
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicFolderData {
+    pub music_folder: MusicFolderConfigItem,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicFolderConfigItem {
+    pub id: i32,
+    pub name: String,
+    pub path: String,
+    /// VFS 方案，使用默认 VFS 时省略
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vfs: Option<String>,
+    /// VFS 选项，凭据以 `***` 代替
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub vfs_options: BTreeMap<String, String>,
+}
+
+impl From<&LibraryFolder> for MusicFolderConfigItem {
+    fn from(f: &LibraryFolder) -> Self {
+        Self {
+            id: f.id,
+            name: f.name.clone(),
+            path: f.path.clone(),
+            vfs: f.vfs_config.as_ref().map(|c| c.scheme.clone()),
+            vfs_options: f
+                .vfs_config
+                .iter()
+                .flat_map(|c| c.redacted().options)
+                .collect(),
+        }
+    }
+}
+
+impl From<MusicFolderData> for super::ResponseData {
+    fn from(v: MusicFolderData) -> Self {
+        super::ResponseData::MusicFolder(v)
+    }
+}
+
 // === 索引 ===
 #[derive(Debug, Clone, Serialize)]
 pub struct IndexesData {
@@ -198,6 +244,7 @@ impl From<&SubsonicArtist> for ArtistWithAlbums {
 
 // === 艺术家信息 ===
 #[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ArtistInfoData {
     pub artist_info: ArtistInfo,
 }
@@ -208,8 +255,11 @@ pub struct ArtistInfo {
     pub biography: Option<String>,
     pub music_brainz_id: Option<String>,
     pub last_fm_url: Option<String>,
+    #[serde(rename = "smallImageUrl")]
     pub small_url: Option<String>,
+    #[serde(rename = "mediumImageUrl")]
     pub medium_url: Option<String>,
+    #[serde(rename = "largeImageUrl")]
     pub large_url: Option<String>,
     pub similar_artist: Vec<ArtistID3Item>,
 }
@@ -221,6 +271,7 @@ impl From<ArtistInfoData> for super::ResponseData {
 }
 
 #[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ArtistInfo2Data {
     pub artist_info2: ArtistInfo2,
 }
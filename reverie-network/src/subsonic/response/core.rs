@@ -96,6 +96,7 @@ pub struct ErrorResponse {
 pub enum ResponseData {
     License(LicenseData),
     MusicFolders(MusicFoldersData),
+    MusicFolder(MusicFolderData),
     Indexes(IndexesData),
     Artists(ArtistsData),
     Artist(ArtistData),
@@ -118,7 +119,9 @@ pub enum ResponseData {
     Users(UsersData),
     Bookmarks(BookmarksData),
     PlayQueue(PlayQueueData),
+    PlayQueueByIndex(PlayQueueByIndexData),
     Shares(SharesData),
+    ScrobbleAccounts(ScrobbleAccountsData),
     InternetRadioStations(InternetRadioStationsData),
     Lyrics(LyricsData),
     LyricsList(LyricsListData),
@@ -138,10 +141,11 @@ pub use super::{
     ArtistData, ArtistID3Item, ArtistInfo2Data, ArtistInfoData, ArtistItem, ArtistWithAlbums,
     ArtistsData, BookmarkItem, BookmarksData, Child, DirectoryData, DirectoryItem, GenreItem,
     GenresData, IndexesData, InternetRadioStationItem, InternetRadioStationsData, LicenseData,
-    LyricsData, LyricsListData, MusicFolderItem, MusicFoldersData, NowPlayingData,
-    OpenSubsonicExtensionItem, OpenSubsonicExtensionsData, PlayQueueData, PlaylistData,
-    PlaylistItem, PlaylistWithEntries, PlaylistsData, RandomSongsData, ScanStatusData,
-    SearchResult2Data, SearchResult3Data, ShareItem, SharesData, SimilarSongs2Data,
-    SimilarSongsData, SongData, SongsByGenreData, Starred2Data, StarredData, TopSongsData,
-    UserData, UserItem, UsersData,
+    LyricsData, LyricsListData, MusicFolderData, MusicFolderItem, MusicFoldersData, NowPlayingData,
+    OpenSubsonicExtensionItem, OpenSubsonicExtensionsData, PlayQueueByIndexData, PlayQueueData,
+    PlaylistData, PlaylistItem, PlaylistWithEntries, PlaylistsData, RandomSongsData,
+    ScanStatusData, ScrobbleAccountsData, SearchResult2Data, SearchResult3Data, ShareItem,
+    SharesData,
+    SimilarSongs2Data, SimilarSongsData, SongData, SongsByGenreData, Starred2Data, StarredData,
+    TopSongsData, UserData, UserItem, UsersData,
 };
@@ -144,13 +144,54 @@ impl From<PlayQueueData> for super::ResponseData {
     }
 }
 
+/// OpenSubsonic `getPlayQueueByIndex` 的响应
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayQueueByIndexData {
+    pub play_queue_by_index: PlayQueueByIndexInner,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayQueueByIndexInner {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_index: Option<usize>,
+    pub position: i64,
+    pub username: String,
+    pub changed: String,
+    pub changed_by: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entry: Vec<super::Child>,
+}
+
+impl From<&SubsonicPlayQueue> for PlayQueueByIndexInner {
+    fn from(p: &SubsonicPlayQueue) -> Self {
+        Self {
+            current_index: p.current_index,
+            position: p.position,
+            username: p.username.clone(),
+            changed: p.changed.to_rfc3339(),
+            changed_by: p.changed_by.clone(),
+            entry: p.entries.iter().map(super::Child::from).collect(),
+        }
+    }
+}
+
+impl From<PlayQueueByIndexData> for super::ResponseData {
+    fn from(v: PlayQueueByIndexData) -> Self {
+        super::ResponseData::PlayQueueByIndex(v)
+    }
+}
+
 // === 互联网广播 ===
 #[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct InternetRadioStationsData {
     pub internet_radio_stations: InternetRadioStationsList,
 }
 
 #[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct InternetRadioStationsList {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub internet_radio_station: Vec<InternetRadioStationItem>,
@@ -163,6 +204,9 @@ pub struct InternetRadioStationItem {
     pub name: String,
     pub stream_url: String,
     pub home_page_url: Option<String>,
+    /// 服务器转发电台时从 ICY 元数据读取的当前曲目，非 Subsonic 标准字段
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub now_playing: Option<String>,
 }
 
 impl From<&SubsonicInternetRadioStation> for InternetRadioStationItem {
@@ -172,6 +216,7 @@ impl From<&SubsonicInternetRadioStation> for InternetRadioStationItem {
             name: s.name.clone(),
             stream_url: s.stream_url.clone(),
             home_page_url: s.homepage_url.clone(),
+            now_playing: None,
         }
     }
 }
@@ -213,6 +258,7 @@ impl From<LyricsData> for super::ResponseData {
 }
 
 #[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct LyricsListData {
     pub lyrics_list: LyricsListInner,
 }
@@ -256,6 +302,7 @@ impl From<LyricsListData> for super::ResponseData {
 
 // === 扫描状态 ===
 #[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ScanStatusData {
     pub scan_status: ScanStatusItem,
 }
@@ -284,14 +331,10 @@ impl From<ScanStatusData> for super::ResponseData {
 
 // === OpenSubsonic 扩展 ===
 #[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct OpenSubsonicExtensionsData {
-    pub open_subsonic_extensions: OpenSubsonicExtensionsList,
-}
-
-#[derive(Debug, Clone, Serialize)]
-pub struct OpenSubsonicExtensionsList {
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub extension: Vec<OpenSubsonicExtensionItem>,
+    /// 规范中为扩展数组本身（XML 中为重复的 `openSubsonicExtensions` 元素）
+    pub open_subsonic_extensions: Vec<OpenSubsonicExtensionItem>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -13,11 +13,16 @@ pub mod playlists;
 pub mod songs;
 pub mod users;
 
+// XML serialization
+pub mod xml;
+
 // Re-export all types for convenience
 pub use core::{
     ErrorResponse, ResponseData, SubsonicResponse, SubsonicResponseInner,
 };
 
+pub use xml::{to_xml, SUBSONIC_XMLNS};
+
 pub use albums::{
     AlbumData, AlbumID3Item, AlbumInfo, AlbumInfoData, AlbumList2Data, AlbumListData,
     AlbumList2Inner, AlbumListInner, AlbumWithSongs, SimilarSongs2Data, SimilarSongsData,
@@ -27,16 +32,17 @@ pub use albums::{
 pub use artists::{
     build_artists, build_indexes, ArtistData, ArtistID3Item, ArtistInfo, ArtistInfo2,
     ArtistInfo2Data, ArtistInfoData, ArtistIndexItem, ArtistItem, ArtistWithAlbums, ArtistsData,
-    ArtistsList, ImageItem, IndexesData, IndexesList, IndexItem, LinkItem, MusicFolderItem,
-    MusicFoldersData, MusicFoldersList,
+    ArtistsList, ImageItem, IndexesData, IndexesList, IndexItem, LinkItem, MusicFolderConfigItem,
+    MusicFolderData, MusicFolderItem, MusicFoldersData, MusicFoldersList,
 };
 
 pub use misc::{
     BookmarksData, BookmarksList, BookmarkItem, GenresData, GenresInner, GenresList, GenreItem,
     InternetRadioStationItem, InternetRadioStationsData, InternetRadioStationsList,
     License, LicenseData, LyricsData, LyricsItem, LyricsListData, LyricsListInner,
-    OpenSubsonicExtensionItem, OpenSubsonicExtensionsData, OpenSubsonicExtensionsList,
-    PlayQueueData, PlayQueueInner, ScanStatusData, ScanStatusItem,
+    OpenSubsonicExtensionItem, OpenSubsonicExtensionsData,
+    PlayQueueByIndexData, PlayQueueByIndexInner, PlayQueueData, PlayQueueInner, ScanStatusData,
+    ScanStatusItem,
 };
 
 pub use playlists::{
@@ -51,5 +57,6 @@ pub use songs::{
 };
 
 pub use users::{
-    ShareItem, SharesData, SharesList, UserData, UserItem, UsersData, UsersInner, UsersList,
+    ScrobbleAccountItem, ScrobbleAccountsData, ScrobbleAccountsList, ShareItem, SharesData,
+    SharesList, UserData, UserItem, UsersData, UsersInner, UsersList,
 };
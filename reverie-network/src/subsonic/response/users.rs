@@ -53,7 +53,7 @@ impl From<&SubsonicUser> for UserItem {
             jukebox_role: u.jukebox_role,
             share_role: u.share_role,
             video_conversion_role: u.video_conversion_role,
-            folder: u.folders.clone(),
+            folder: u.folders.folder_ids().unwrap_or_default().to_vec(),
         }
     }
 }
@@ -104,9 +104,13 @@ pub struct SharesList {
 pub struct ShareItem {
     pub id: String,
     pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     pub username: String,
     pub created: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub expires: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub last_visited: Option<String>,
     pub visit_count: i32,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -118,6 +122,7 @@ impl From<&reverie_core::SubsonicShare> for ShareItem {
         Self {
             id: s.id.clone(),
             url: s.url.clone(),
+            description: s.description.clone(),
             username: s.username.clone(),
             created: s.created.to_rfc3339(),
             expires: s.expires.map(|d| d.to_rfc3339()),
@@ -133,3 +138,40 @@ impl From<SharesData> for super::ResponseData {
         super::ResponseData::Shares(v)
     }
 }
+
+// === 播放记录转发账号 ===
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrobbleAccountsData {
+    pub scrobble_accounts: ScrobbleAccountsList,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrobbleAccountsList {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scrobble_account: Vec<ScrobbleAccountItem>,
+}
+
+/// 转发账号，不包含令牌
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrobbleAccountItem {
+    pub service: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl From<&reverie_core::SubsonicScrobbleAccount> for ScrobbleAccountItem {
+    fn from(a: &reverie_core::SubsonicScrobbleAccount) -> Self {
+        Self {
+            service: a.service.clone(),
+            url: a.url.clone(),
+        }
+    }
+}
+
+impl From<ScrobbleAccountsData> for super::ResponseData {
+    fn from(v: ScrobbleAccountsData) -> Self {
+        super::ResponseData::ScrobbleAccounts(v)
+    }
+}
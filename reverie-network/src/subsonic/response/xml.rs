@@ -0,0 +1,127 @@
+//! Subsonic XML 序列化
+//!
+//! 响应 DTO 先序列化为 JSON 值，再按 Subsonic XML 约定转换：
+//! - 标量字段 → 属性
+//! - 对象 → 子元素
+//! - 数组 → 重复的同名子元素（标量数组为带文本的子元素，如 `versions`）
+//! - `value` 字段 → 元素的文本内容（如 `genre`、`lyrics`）
+//! - `artistInfo`/`artistInfo2`/`albumInfo` 下的标量字段 → 带文本的子元素
+
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::Writer;
+use serde_json::{Map, Value};
+
+use super::SubsonicResponse;
+use crate::error::{NetworkError, Result};
+
+/// Subsonic REST API 的 XML 命名空间
+pub const SUBSONIC_XMLNS: &str = "http://subsonic.org/restapi";
+
+/// 规范中其标量子项为元素而非属性的父元素
+const TEXT_CHILD_PARENTS: &[&str] = &["artistInfo", "artistInfo2", "albumInfo"];
+
+/// 将响应序列化为 Subsonic XML 文档
+pub fn to_xml(response: &SubsonicResponse) -> Result<String> {
+    let value = serde_json::to_value(&response.inner)
+        .map_err(|e| NetworkError::SerializationError(e.to_string()))?;
+    let Value::Object(root) = value else {
+        return Err(NetworkError::SerializationError(
+            "subsonic-response is not an object".to_string(),
+        ));
+    };
+
+    let mut writer = Writer::new(Vec::new());
+    writer
+        .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+        .map_err(xml_error)?;
+    write_element(
+        &mut writer,
+        "subsonic-response",
+        &root,
+        Some(SUBSONIC_XMLNS),
+    )?;
+
+    String::from_utf8(writer.into_inner())
+        .map_err(|e| NetworkError::SerializationError(e.to_string()))
+}
+
+fn write_element(
+    writer: &mut Writer<Vec<u8>>,
+    name: &str,
+    fields: &Map<String, Value>,
+    xmlns: Option<&str>,
+) -> Result<()> {
+    let text_children = TEXT_CHILD_PARENTS.contains(&name);
+
+    let mut start = BytesStart::new(name);
+    if let Some(ns) = xmlns {
+        start.push_attribute(("xmlns", ns));
+    }
+
+    let mut text = None;
+    let mut children = Vec::new();
+    for (key, value) in fields {
+        match value {
+            Value::Null => {}
+            Value::Object(_) | Value::Array(_) => children.push((key, value)),
+            _ if key == "value" => text = scalar_to_string(value),
+            _ if text_children => children.push((key, value)),
+            _ => {
+                if let Some(s) = scalar_to_string(value) {
+                    start.push_attribute((key.as_str(), s.as_str()));
+                }
+            }
+        }
+    }
+
+    if text.is_none() && children.is_empty() {
+        return writer.write_event(Event::Empty(start)).map_err(xml_error);
+    }
+
+    writer.write_event(Event::Start(start)).map_err(xml_error)?;
+    if let Some(text) = text {
+        writer
+            .write_event(Event::Text(BytesText::new(&text)))
+            .map_err(xml_error)?;
+    }
+    for (key, value) in children {
+        write_value(writer, key, value)?;
+    }
+    writer
+        .write_event(Event::End(BytesEnd::new(name)))
+        .map_err(xml_error)
+}
+
+fn write_value(writer: &mut Writer<Vec<u8>>, name: &str, value: &Value) -> Result<()> {
+    match value {
+        Value::Null => Ok(()),
+        Value::Object(fields) => write_element(writer, name, fields, None),
+        Value::Array(items) => {
+            for item in items {
+                write_value(writer, name, item)?;
+            }
+            Ok(())
+        }
+        scalar => {
+            let text = scalar_to_string(scalar).unwrap_or_default();
+            writer
+                .create_element(name)
+                .write_text_content(BytesText::new(&text))
+                .map_err(xml_error)?;
+            Ok(())
+        }
+    }
+}
+
+fn scalar_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn xml_error(e: quick_xml::Error) -> NetworkError {
+    NetworkError::SerializationError(e.to_string())
+}
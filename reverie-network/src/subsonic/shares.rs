@@ -0,0 +1,182 @@
+//! 分享端点处理器
+//!
+//! 分享生成一个无需 Subsonic 凭据即可访问的公开链接（`/share/{id}`），
+//! 创建和修改分享需要 `shareRole` 权限。
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::Response,
+    Extension,
+};
+use reverie_storage::{error::StorageError, SubsonicStorage};
+
+use super::response::*;
+use super::{
+    error_response, format_response, ok_response, param_error, require_role, AuthContext,
+    AuthError, SubsonicParams, SubsonicState,
+};
+
+/// 没有分享权限时返回错误响应
+async fn require_share_role<S: SubsonicStorage + Clone>(
+    state: &SubsonicState<S>,
+    user: &AuthContext,
+    params: &SubsonicParams,
+) -> Option<Response> {
+    require_role(state.storage.as_ref(), user, params, |u| u.share_role).await
+}
+
+fn storage_error_response(params: &SubsonicParams, e: StorageError) -> Response {
+    match e {
+        StorageError::NotFound(_) => error_response(params, 70, "Share not found"),
+        StorageError::PermissionDenied(_) => {
+            let e = AuthError::NotAuthorized;
+            error_response(params, e.code(), &e.to_string())
+        }
+        e => error_response(params, 0, &e.to_string()),
+    }
+}
+
+/// 将分享的相对链接补全为客户端访问服务器时使用的地址
+///
+/// 优先使用配置的公开地址；否则根据请求的 `Host` 头生成，只有信任反向代理时才使用
+/// 客户端可以任意设置的 `X-Forwarded-Proto` 和 `X-Forwarded-Host`
+fn public_url<S: Clone>(state: &SubsonicState<S>, headers: &HeaderMap, path: &str) -> String {
+    if let Some(base) = &state.public_base_url {
+        return format!("{}{}", base.trim_end_matches('/'), path);
+    }
+    let header_value = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let forwarded = |name: &str| {
+        state
+            .trust_forwarded_headers
+            .then(|| header_value(name))
+            .flatten()
+    };
+    let host = forwarded("x-forwarded-host").or_else(|| header_value(header::HOST.as_str()));
+    match host {
+        Some(host) => {
+            let scheme = forwarded("x-forwarded-proto").unwrap_or("http");
+            format!("{}://{}{}", scheme, host, path)
+        }
+        None => path.to_string(),
+    }
+}
+
+fn share_item<S: Clone>(
+    state: &SubsonicState<S>,
+    headers: &HeaderMap,
+    share: &reverie_core::SubsonicShare,
+) -> ShareItem {
+    let mut item = ShareItem::from(share);
+    item.url = public_url(state, headers, &share.url);
+    item
+}
+
+/// GET /rest/getShares - 获取当前用户创建的分享
+pub async fn get_shares_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    headers: HeaderMap,
+    params: SubsonicParams,
+) -> Response {
+    match state.storage.get_shares(&user).await {
+        Ok(shares) => {
+            let data = SharesData {
+                shares: SharesList {
+                    share: shares
+                        .iter()
+                        .map(|s| share_item(&state, &headers, s))
+                        .collect(),
+                },
+            };
+            let response = SubsonicResponse::ok_with(ResponseData::Shares(data));
+            format_response(&params, response)
+        }
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
+
+/// GET /rest/createShare - 分享歌曲、专辑或播放列表
+///
+/// `expires` 为过期时间的毫秒时间戳，省略时分享不过期
+pub async fn create_share_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    headers: HeaderMap,
+    params: SubsonicParams,
+) -> Response {
+    if let Some(response) = require_share_role(&state, &user, &params).await {
+        return response;
+    }
+    let ids = params.get_all("id");
+    if ids.is_empty() {
+        return error_response(&params, 10, "Missing required parameter: id");
+    }
+    // 部分客户端以 0 表示不过期
+    let expires = opt_param!(params, "expires").filter(|e| *e > 0);
+    let description = params.get("description");
+
+    match state
+        .storage
+        .create_share(&user, &ids, description, expires)
+        .await
+    {
+        Ok(share) => {
+            let data = SharesData {
+                shares: SharesList {
+                    share: vec![share_item(&state, &headers, &share)],
+                },
+            };
+            let response = SubsonicResponse::ok_with(ResponseData::Shares(data));
+            format_response(&params, response)
+        }
+        Err(StorageError::NotFound(_)) => error_response(&params, 70, "Item not found"),
+        Err(e) => storage_error_response(&params, e),
+    }
+}
+
+/// GET /rest/updateShare - 修改分享的描述和过期时间
+pub async fn update_share_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    if let Some(response) = require_share_role(&state, &user, &params).await {
+        return response;
+    }
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
+    };
+    let expires = opt_param!(params, "expires");
+    let description = params.get("description");
+
+    match state
+        .storage
+        .update_share(&user, id, description, expires)
+        .await
+    {
+        Ok(()) => ok_response(&params),
+        Err(e) => storage_error_response(&params, e),
+    }
+}
+
+/// GET /rest/deleteShare - 删除分享，公开链接随即失效
+pub async fn delete_share_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    if let Some(response) = require_share_role(&state, &user, &params).await {
+        return response;
+    }
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
+    };
+
+    match state.storage.delete_share(&user, id).await {
+        Ok(()) => ok_response(&params),
+        Err(e) => storage_error_response(&params, e),
+    }
+}
@@ -14,7 +14,7 @@ use tower::ServiceExt;
 pub fn create_test_router() -> axum::Router {
     let storage = Arc::new(MockSubsonicStorage::new());
     let state = crate::subsonic::SubsonicState::new(storage);
-    create_router::<MockSubsonicStorage>().with_state(state)
+    create_router::<MockSubsonicStorage>(state.clone()).with_state(state)
 }
 
 async fn get_json_response(router: axum::Router, uri: &str) -> serde_json::Value {
@@ -31,12 +31,27 @@ async fn get_json_response(router: axum::Router, uri: &str) -> serde_json::Value
     serde_json::from_slice(&body).unwrap()
 }
 
+async fn post_form_json(uri: &str, form: &str) -> serde_json::Value {
+    let request = Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from(form.to_string()))
+        .unwrap();
+    let response = create_test_router().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
 // === 测试用例 ===
 
 #[tokio::test]
 async fn test_ping_returns_ok() {
     let router = create_test_router();
-    let json = get_json_response(router, "/ping?f=json").await;
+    let json = get_json_response(router, "/ping?u=admin&p=admin&f=json").await;
 
     assert_eq!(json["subsonic-response"]["status"], "ok");
 }
@@ -44,7 +59,7 @@ async fn test_ping_returns_ok() {
 #[tokio::test]
 async fn test_get_license_returns_valid() {
     let router = create_test_router();
-    let json = get_json_response(router, "/getLicense?f=json").await;
+    let json = get_json_response(router, "/getLicense?u=admin&p=admin&f=json").await;
 
     assert_eq!(json["subsonic-response"]["status"], "ok");
     assert_eq!(json["subsonic-response"]["license"]["valid"], true);
@@ -53,17 +68,312 @@ async fn test_get_license_returns_valid() {
 #[tokio::test]
 async fn test_get_music_folders() {
     let router = create_test_router();
-    let json = get_json_response(router, "/getMusicFolders?f=json").await;
+    let json = get_json_response(router, "/getMusicFolders?u=admin&p=admin&f=json").await;
 
     assert_eq!(json["subsonic-response"]["status"], "ok");
     let folders = &json["subsonic-response"]["musicFolders"]["musicFolder"];
     assert!(folders.is_array());
 }
 
+#[tokio::test]
+async fn test_music_folders_respect_user_access() {
+    let json = get_json_response(
+        create_test_router(),
+        "/getMusicFolders?u=limited&p=limited&f=json",
+    )
+    .await;
+    let folders = json["subsonic-response"]["musicFolders"]["musicFolder"]
+        .as_array()
+        .unwrap()
+        .clone();
+    assert_eq!(folders.len(), 1);
+    assert_eq!(folders[0]["id"], 1);
+
+    // 未指定文件夹时限定在用户可访问的文件夹内
+    let json = get_json_response(
+        create_test_router(),
+        "/getRandomSongs?u=limited&p=limited&f=json",
+    )
+    .await;
+    let songs = json["subsonic-response"]["randomSongs"]["song"].as_array().unwrap().clone();
+    assert_eq!(songs.len(), 1);
+    assert_eq!(songs[0]["id"], "song-1");
+
+    let json = get_json_response(
+        create_test_router(),
+        "/getRandomSongs?u=admin&p=admin&f=json&musicFolderId=2",
+    )
+    .await;
+    let songs = json["subsonic-response"]["randomSongs"]["song"].as_array().unwrap().clone();
+    assert_eq!(songs.len(), 1);
+    assert_eq!(songs[0]["id"], "song-2");
+
+    // 请求无权访问的文件夹
+    for endpoint in ["getRandomSongs", "getAlbumList2?type=newest&", "search3?query=a&"] {
+        let separator = if endpoint.ends_with('&') { "" } else { "?" };
+        let uri = format!(
+            "/{}{}u=limited&p=limited&f=json&musicFolderId=2",
+            endpoint, separator
+        );
+        let json = get_json_response(create_test_router(), &uri).await;
+        assert_eq!(json["subsonic-response"]["status"], "failed", "{}", endpoint);
+        assert_eq!(json["subsonic-response"]["error"]["code"], 50, "{}", endpoint);
+    }
+
+    // 无权访问的文件夹中的歌曲视为不存在
+    for endpoint in ["getSong", "stream"] {
+        let uri = format!("/{}?u=limited&p=limited&f=json&id=song-2", endpoint);
+        let json = get_json_response(create_test_router(), &uri).await;
+        assert_eq!(json["subsonic-response"]["error"]["code"], 70, "{}", endpoint);
+    }
+    let response = get_with_headers("/getCoverArt?u=limited&p=limited&id=song-2", &[]).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = get_with_headers("/getCoverArt?u=admin&p=admin&id=song-2", &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let json = get_json_response(
+        create_test_router(),
+        "/getSong?u=admin&p=admin&f=json&id=song-2",
+    )
+    .await;
+    assert_eq!(json["subsonic-response"]["song"]["id"], "song-2");
+}
+
+#[tokio::test]
+async fn test_manage_music_folders_requires_admin() {
+    let json = get_json_response(
+        create_test_router(),
+        "/createMusicFolder?u=admin&p=admin&f=json&name=Books&path=books/&vfs=fs&vfs.root=/mnt",
+    )
+    .await;
+    let folder = &json["subsonic-response"]["musicFolder"];
+    assert_eq!(folder["id"], 3);
+    assert_eq!(folder["path"], "books/");
+    assert_eq!(folder["vfs"], "fs");
+    assert_eq!(folder["vfsOptions"]["root"], "/mnt");
+
+    // 响应中不包含凭据
+    let json = get_json_response(
+        create_test_router(),
+        "/createMusicFolder?u=admin&p=admin&f=json&name=S3&path=/&vfs=s3&vfs.bucket=music\
+         &vfs.access_key_id=AKID&vfs.secret_access_key=topsecret",
+    )
+    .await;
+    let folder = &json["subsonic-response"]["musicFolder"];
+    assert_eq!(folder["vfsOptions"]["access_key_id"], "AKID");
+    assert_eq!(folder["vfsOptions"]["secret_access_key"], "***");
+    assert!(!json.to_string().contains("topsecret"));
+
+    // 空的 vfs 参数改用默认 VFS，未传入的参数保持不变
+    let json = get_json_response(
+        create_test_router(),
+        "/updateMusicFolder?u=admin&p=admin&f=json&id=2&name=Books&vfs=",
+    )
+    .await;
+    let folder = &json["subsonic-response"]["musicFolder"];
+    assert_eq!(folder["name"], "Books");
+    assert_eq!(folder["path"], "books/");
+    assert!(folder.get("vfs").is_none());
+
+    let json = get_json_response(
+        create_test_router(),
+        "/deleteMusicFolder?u=admin&p=admin&f=json&id=9",
+    )
+    .await;
+    assert_eq!(json["subsonic-response"]["error"]["code"], 70);
+
+    for uri in [
+        "/createMusicFolder?u=limited&p=limited&f=json&name=Books&path=books/",
+        "/updateMusicFolder?u=limited&p=limited&f=json&id=1&name=Mine",
+        "/deleteMusicFolder?u=limited&p=limited&f=json&id=1",
+    ] {
+        let json = get_json_response(create_test_router(), uri).await;
+        assert_eq!(json["subsonic-response"]["error"]["code"], 50, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn test_user_roles_enforced() {
+    // "limited" 用户没有下载和播放列表权限，也不是管理员
+    for uri in [
+        "/download?u=limited&p=limited&f=json&id=1",
+        "/createPlaylist?u=limited&p=limited&f=json&name=Mine",
+        "/updatePlaylist?u=limited&p=limited&f=json&playlistId=1&name=Mine",
+        "/deletePlaylist?u=limited&p=limited&f=json&id=1",
+        "/stream?u=ldap&p=ldap&f=json&id=1",
+        "/updatePlaylist?u=admin&p=admin&f=json&playlistId=foreign&name=Mine",
+        "/deletePlaylist?u=admin&p=admin&f=json&id=foreign",
+        "/getPlaylists?u=limited&p=limited&f=json&username=admin",
+        "/getPlaylist?u=limited&p=limited&f=json&id=foreign",
+        "/createPlaylist?u=admin&p=admin&f=json&playlistId=foreign&songId=1",
+        "/getUsers?u=limited&p=limited&f=json",
+        "/getUser?u=limited&p=limited&f=json&username=admin",
+        "/startScan?u=limited&p=limited&f=json",
+    ] {
+        let json = get_json_response(create_test_router(), uri).await;
+        assert_eq!(json["subsonic-response"]["error"]["code"], 50, "{}", uri);
+    }
+
+    // 用户可以获取自己的信息
+    let json = get_json_response(
+        create_test_router(),
+        "/getUser?u=limited&p=limited&f=json&username=limited",
+    )
+    .await;
+    let user = &json["subsonic-response"]["user"];
+    assert_eq!(user["downloadRole"], false);
+    assert_eq!(user["playlistRole"], false);
+    assert_eq!(user["maxBitRate"], 64);
+
+    let json = get_json_response(
+        create_test_router(),
+        "/deletePlaylist?u=admin&p=admin&f=json&id=1",
+    )
+    .await;
+    assert_eq!(json["subsonic-response"]["status"], "ok");
+    let json = get_json_response(
+        create_test_router(),
+        "/getPlaylists?u=admin&p=admin&f=json&username=limited",
+    )
+    .await;
+    assert_eq!(json["subsonic-response"]["status"], "ok");
+}
+
+#[tokio::test]
+async fn test_scrobble_accounts() {
+    // 普通用户也可以管理自己的转发账号，响应中不包含令牌
+    let json = get_json_response(
+        create_test_router(),
+        "/getScrobbleAccounts?u=limited&p=limited&f=json",
+    )
+    .await;
+    let accounts = &json["subsonic-response"]["scrobbleAccounts"]["scrobbleAccount"];
+    assert_eq!(accounts[0]["service"], "listenbrainz");
+    assert!(accounts[0].get("token").is_none());
+
+    for (uri, code) in [
+        ("/updateScrobbleAccount?u=limited&p=limited&service=listenbrainz", 10),
+        ("/updateScrobbleAccount?u=limited&p=limited&service=unknown&token=t", 70),
+        // 自定义地址只有管理员可以设置，且不能指向内网
+        (
+            "/updateScrobbleAccount?u=limited&p=limited&service=listenbrainz&token=t&url=https://maloja.example",
+            50,
+        ),
+        (
+            "/updateScrobbleAccount?u=admin&p=admin&service=listenbrainz&token=t&url=http://127.0.0.1:42010",
+            10,
+        ),
+        ("/deleteScrobbleAccount?u=limited&p=limited", 10),
+        ("/getScrobbleAccounts?u=limited&p=wrong", 40),
+    ] {
+        let json = get_json_response(create_test_router(), &format!("{}&f=json", uri)).await;
+        assert_eq!(json["subsonic-response"]["error"]["code"], code, "{}", uri);
+    }
+
+    for uri in [
+        "/updateScrobbleAccount?u=limited&p=limited&service=listenbrainz&token=t",
+        "/updateScrobbleAccount?u=admin&p=admin&service=listenbrainz&token=t&url=https://maloja.example",
+        "/deleteScrobbleAccount?u=limited&p=limited&service=listenbrainz",
+    ] {
+        let json = get_json_response(create_test_router(), &format!("{}&f=json", uri)).await;
+        assert_eq!(json["subsonic-response"]["status"], "ok", "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn test_admin_folder_api() {
+    let storage = Arc::new(MockSubsonicStorage::new());
+    let state = crate::subsonic::SubsonicState::new(storage);
+    let router = crate::axum_server::admin::create_router::<MockSubsonicStorage>(state.clone())
+        .with_state(state);
+
+    let request = |method: &str, uri: &str, body: &str| {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+
+    let response = router
+        .clone()
+        .oneshot(request("GET", "/api/admin/folders?u=admin&p=admin", ""))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let folders: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(folders[1]["vfs_config"]["scheme"], "fs");
+    assert_eq!(folders[1]["vfs_config"]["options"]["root"], "/audiobooks");
+
+    let response = router
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/api/admin/folders?u=admin&p=admin",
+            r#"{"name": "Books", "path": "books/", "scan": true}"#,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let response = router
+        .clone()
+        .oneshot(request(
+            "POST",
+            "/api/admin/folders?u=admin&p=admin",
+            r#"{"name": "S3", "path": "/", "vfs_config": {"scheme": "s3",
+                "options": {"bucket": "music", "secret_access_key": "topsecret"}}}"#,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let folder: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(folder["vfs_config"]["options"]["bucket"], "music");
+    assert_eq!(folder["vfs_config"]["options"]["secret_access_key"], "***");
+    assert!(!String::from_utf8_lossy(&body).contains("topsecret"));
+
+    let response = router
+        .clone()
+        .oneshot(request(
+            "PATCH",
+            "/api/admin/folders/9?u=admin&p=admin",
+            r#"{"name": "Other"}"#,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = router
+        .clone()
+        .oneshot(request("DELETE", "/api/admin/folders/2?u=admin&p=admin", ""))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // 非管理员和未验证的请求
+    let response = router
+        .clone()
+        .oneshot(request("DELETE", "/api/admin/folders/2?u=limited&p=limited", ""))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = router
+        .oneshot(request("GET", "/api/admin/folders?u=admin&p=wrong", ""))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
 #[tokio::test]
 async fn test_get_artists() {
     let router = create_test_router();
-    let json = get_json_response(router, "/getArtists?f=json").await;
+    let json = get_json_response(router, "/getArtists?u=admin&p=admin&f=json").await;
 
     assert_eq!(json["subsonic-response"]["status"], "ok");
 }
@@ -71,7 +381,7 @@ async fn test_get_artists() {
 #[tokio::test]
 async fn test_get_album_list2() {
     let router = create_test_router();
-    let json = get_json_response(router, "/getAlbumList2?f=json&type=recent").await;
+    let json = get_json_response(router, "/getAlbumList2?u=admin&p=admin&f=json&type=recent").await;
 
     assert_eq!(json["subsonic-response"]["status"], "ok");
 }
@@ -79,7 +389,7 @@ async fn test_get_album_list2() {
 #[tokio::test]
 async fn test_search3() {
     let router = create_test_router();
-    let json = get_json_response(router, "/search3?f=json&query=test").await;
+    let json = get_json_response(router, "/search3?u=admin&p=admin&f=json&query=test").await;
 
     assert_eq!(json["subsonic-response"]["status"], "ok");
 }
@@ -87,7 +397,678 @@ async fn test_search3() {
 #[tokio::test]
 async fn test_get_scan_status() {
     let router = create_test_router();
-    let json = get_json_response(router, "/getScanStatus?f=json").await;
+    let json = get_json_response(router, "/getScanStatus?u=admin&p=admin&f=json").await;
+
+    assert_eq!(json["subsonic-response"]["status"], "ok");
+}
 
+// === 身份验证 ===
+
+#[tokio::test]
+async fn test_auth_plain_password() {
+    let json = get_json_response(create_test_router(), "/ping?u=admin&p=admin&f=json").await;
     assert_eq!(json["subsonic-response"]["status"], "ok");
 }
+
+#[tokio::test]
+async fn test_auth_hex_encoded_password() {
+    // "admin" 的十六进制编码
+    let json =
+        get_json_response(create_test_router(), "/ping?u=admin&p=enc:61646d696e&f=json").await;
+    assert_eq!(json["subsonic-response"]["status"], "ok");
+}
+
+#[tokio::test]
+async fn test_auth_token_and_salt() {
+    let token = format!("{:x}", md5::compute("adminc19b2d"));
+    let uri = format!("/ping?u=admin&t={}&s=c19b2d&f=json", token);
+    let json = get_json_response(create_test_router(), &uri).await;
+    assert_eq!(json["subsonic-response"]["status"], "ok");
+}
+
+#[tokio::test]
+async fn test_auth_api_key() {
+    let json = get_json_response(create_test_router(), "/ping?apiKey=test-api-key&f=json").await;
+    assert_eq!(json["subsonic-response"]["status"], "ok");
+}
+
+async fn assert_auth_error(uri: &str, code: i32) {
+    let json = get_json_response(create_test_router(), uri).await;
+    assert_eq!(json["subsonic-response"]["status"], "failed");
+    assert_eq!(json["subsonic-response"]["error"]["code"], code);
+}
+
+#[tokio::test]
+async fn test_auth_missing_parameters() {
+    assert_auth_error("/ping?f=json", 10).await;
+    assert_auth_error("/ping?u=admin&f=json", 10).await;
+    assert_auth_error("/ping?u=admin&t=abc&f=json", 10).await;
+}
+
+#[tokio::test]
+async fn test_auth_wrong_credentials() {
+    assert_auth_error("/ping?u=admin&p=wrong&f=json", 40).await;
+    assert_auth_error("/ping?u=nobody&p=admin&f=json", 40).await;
+    assert_auth_error("/ping?u=admin&t=deadbeef&s=c19b2d&f=json", 40).await;
+}
+
+#[tokio::test]
+async fn test_auth_token_not_supported_for_user() {
+    assert_auth_error("/ping?u=ldap&t=deadbeef&s=c19b2d&f=json", 41).await;
+}
+
+#[tokio::test]
+async fn test_auth_password_without_retrievable_password() {
+    // 只保存密码哈希的用户仍可使用明文密码登录
+    let json = get_json_response(create_test_router(), "/ping?u=ldap&p=ldap&f=json").await;
+    assert_eq!(json["subsonic-response"]["status"], "ok");
+    assert_auth_error("/ping?u=ldap&p=wrong&f=json", 40).await;
+}
+
+#[tokio::test]
+async fn test_password_change_required() {
+    // 修改密码之前只能访问 ping 和 changePassword
+    assert_auth_error("/getUsers?u=pending&p=pending&f=json", 50).await;
+    for uri in [
+        "/ping?u=pending&p=pending",
+        "/changePassword?u=pending&p=pending&username=pending&password=enc:736573616d65",
+    ] {
+        let json = get_json_response(create_test_router(), &format!("{}&f=json", uri)).await;
+        assert_eq!(json["subsonic-response"]["status"], "ok", "{}", uri);
+    }
+
+    for (uri, code) in [
+        // 只有管理员可以修改其他用户的密码
+        ("/changePassword?u=limited&p=limited&username=admin&password=x", 50),
+        ("/changePassword?u=limited&p=limited&username=limited", 10),
+        ("/changePassword?u=limited&p=limited&username=limited&password=", 10),
+        ("/changePassword?u=admin&p=admin&username=missing&password=x", 70),
+    ] {
+        let json = get_json_response(create_test_router(), &format!("{}&f=json", uri)).await;
+        assert_eq!(json["subsonic-response"]["error"]["code"], code, "{}", uri);
+    }
+
+    let json = get_json_response(
+        create_test_router(),
+        "/changePassword?u=admin&p=admin&f=json&username=limited&password=x",
+    )
+    .await;
+    assert_eq!(json["subsonic-response"]["status"], "ok");
+}
+
+#[tokio::test]
+async fn test_manage_users() {
+    for uri in [
+        "/createUser?u=admin&p=admin&username=bob&password=enc:736573616d65&email=bob@example.com\
+         &downloadRole=true&musicFolderId=1&musicFolderId=2",
+        "/updateUser?u=admin&p=admin&username=limited&password=x&shareRole=true&maxBitRate=128",
+        "/updateUser?u=admin&p=admin&username=limited&maxBitRate=0&musicFolderId=2",
+        "/deleteUser?u=admin&p=admin&username=limited",
+    ] {
+        let json = get_json_response(create_test_router(), &format!("{}&f=json", uri)).await;
+        assert_eq!(json["subsonic-response"]["status"], "ok", "{}", uri);
+    }
+
+    for (uri, code) in [
+        // 只有管理员可以管理用户
+        ("/createUser?u=limited&p=limited&username=bob&password=x", 50),
+        ("/updateUser?u=limited&p=limited&username=limited&adminRole=true", 50),
+        ("/deleteUser?u=limited&p=limited&username=admin", 50),
+        ("/createUser?u=admin&p=admin&password=x", 10),
+        ("/createUser?u=admin&p=admin&username=bob", 10),
+        ("/createUser?u=admin&p=admin&username=bob&password=enc:", 10),
+        ("/createUser?u=admin&p=admin&username=bob&password=x&adminRole=yes", 10),
+        ("/createUser?u=admin&p=admin&username=bob&password=x&musicFolderId=all", 10),
+        ("/createUser?u=admin&p=admin&username=limited&password=x", 0),
+        ("/updateUser?u=admin&p=admin&shareRole=true", 10),
+        ("/updateUser?u=admin&p=admin&username=limited&maxBitRate=100", 10),
+        ("/updateUser?u=admin&p=admin&username=missing&shareRole=true", 70),
+        ("/deleteUser?u=admin&p=admin", 10),
+        ("/deleteUser?u=admin&p=admin&username=admin", 0),
+        ("/deleteUser?u=admin&p=admin&username=missing", 70),
+    ] {
+        let json = get_json_response(create_test_router(), &format!("{}&f=json", uri)).await;
+        assert_eq!(json["subsonic-response"]["error"]["code"], code, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn test_form_post() {
+    // 凭据可以只放在请求体中
+    let json = post_form_json("/ping", "u=admin&p=enc:61646d696e&f=json").await;
+    assert_eq!(json["subsonic-response"]["status"], "ok");
+    let json = post_form_json("/ping", "u=admin&p=wrong&f=json").await;
+    assert_eq!(json["subsonic-response"]["error"]["code"], 40);
+
+    // 查询字符串和请求体中的参数合并，重复的参数都保留
+    let json =
+        post_form_json("/savePlayQueue?u=admin&p=admin&f=json&id=1", "id=2&current=2").await;
+    assert_eq!(json["subsonic-response"]["status"], "ok");
+    let json = post_form_json("/star?u=admin&p=admin&f=json", "id=1&id=missing").await;
+    assert_eq!(json["subsonic-response"]["status"], "failed");
+    let json = get_json_response(
+        create_test_router(),
+        "/star?u=admin&p=admin&f=json&id=1&id=missing",
+    )
+    .await;
+    assert_eq!(json["subsonic-response"]["status"], "failed");
+
+    // 其他类型的请求体被忽略
+    let request = Request::builder()
+        .method("POST")
+        .uri("/ping?f=json")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"u":"admin","p":"admin"}"#))
+        .unwrap();
+    let response = create_test_router().oneshot(request).await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["subsonic-response"]["error"]["code"], 10);
+}
+
+#[tokio::test]
+async fn test_repeated_params() {
+    let json = get_json_response(
+        create_test_router(),
+        "/createPlaylist?u=admin&p=admin&f=json&name=Mix&songId=1&songId=2&songId=3",
+    )
+    .await;
+    assert_eq!(json["subsonic-response"]["playlist"]["songCount"], 3);
+    let json = get_json_response(
+        create_test_router(),
+        "/createPlaylist?u=admin&p=admin&f=json&playlistId=missing&songId=1",
+    )
+    .await;
+    assert_eq!(json["subsonic-response"]["error"]["code"], 70);
+
+    for (uri, status) in [
+        ("/updatePlaylist?playlistId=1&songIdToAdd=1&songIdToAdd=2", "ok"),
+        ("/updatePlaylist?playlistId=1&songIdToAdd=1&songIdToAdd=missing", "failed"),
+        ("/star?id=1&id=2&albumId=3", "ok"),
+        ("/star?albumId=3&id=1&id=missing", "failed"),
+    ] {
+        let json = get_json_response(
+            create_test_router(),
+            &format!("{}&u=admin&p=admin&f=json", uri),
+        )
+        .await;
+        assert_eq!(json["subsonic-response"]["status"], status, "{}", uri);
+    }
+
+    // 缺少或无法解析的参数返回错误码 10
+    for uri in [
+        "/updatePlaylist?playlistId=1&songIndexToRemove=0&songIndexToRemove=x",
+        "/setRating?id=1&rating=five",
+        "/setRating?id=1",
+        "/getAlbumList2?size=10",
+        "/getAlbumList2?type=newest&size=ten",
+        "/search3?query=a&songCount=x",
+        "/getRandomSongs?musicFolderId=x",
+        "/stream?id=1&maxBitRate=abc",
+        "/startScan?fullScan=yes",
+    ] {
+        let json = get_json_response(
+            create_test_router(),
+            &format!("{}&u=admin&p=admin&f=json", uri),
+        )
+        .await;
+        assert_eq!(json["subsonic-response"]["error"]["code"], 10, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn test_get_open_subsonic_extensions() {
+    // 无需身份验证
+    let json = get_json_response(create_test_router(), "/getOpenSubsonicExtensions?f=json").await;
+    let extensions = json["subsonic-response"]["openSubsonicExtensions"]
+        .as_array()
+        .unwrap();
+    assert!(extensions
+        .iter()
+        .any(|e| e["name"] == "formPost" && e["versions"][0] == 1));
+    assert!(extensions.iter().any(|e| e["name"] == "apiKeyAuthentication"));
+}
+
+#[tokio::test]
+async fn test_auth_conflicting_mechanisms() {
+    assert_auth_error("/ping?u=admin&p=admin&t=abc&s=def&f=json", 43).await;
+    assert_auth_error("/ping?u=admin&apiKey=test-api-key&f=json", 43).await;
+}
+
+#[tokio::test]
+async fn test_auth_invalid_api_key() {
+    assert_auth_error("/ping?apiKey=bogus&f=json", 44).await;
+}
+
+// === 流式传输 / Range ===
+
+async fn get_with_headers(uri: &str, headers: &[(&str, &str)]) -> axum::response::Response {
+    let mut request = Request::builder().uri(uri);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    create_test_router()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+async fn body_bytes(response: axum::response::Response) -> Vec<u8> {
+    axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap()
+        .to_vec()
+}
+
+#[tokio::test]
+async fn test_stream_full_file() {
+    use crate::subsonic::tests::mock_storage::{mock_file_contents, MOCK_FILE_LEN};
+
+    let response = get_with_headers("/stream?u=admin&p=admin&id=1", &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["accept-ranges"], "bytes");
+    assert_eq!(response.headers()["content-type"], "audio/mpeg");
+    assert_eq!(
+        response.headers()["content-length"],
+        MOCK_FILE_LEN.to_string().as_str()
+    );
+    assert!(response.headers().contains_key("etag"));
+    assert_eq!(body_bytes(response).await, mock_file_contents());
+}
+
+#[tokio::test]
+async fn test_stream_partial_content() {
+    use crate::subsonic::tests::mock_storage::{mock_file_contents, MOCK_FILE_LEN};
+
+    let response =
+        get_with_headers("/stream?u=admin&p=admin&id=1", &[("range", "bytes=100-299")]).await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        response.headers()["content-range"],
+        format!("bytes 100-299/{}", MOCK_FILE_LEN).as_str()
+    );
+    assert_eq!(response.headers()["content-length"], "200");
+    assert_eq!(body_bytes(response).await, mock_file_contents()[100..300]);
+
+    // 跨越多个读取块的开放区间
+    let response =
+        get_with_headers("/stream?u=admin&p=admin&id=1", &[("range", "bytes=1000-")]).await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(body_bytes(response).await, mock_file_contents()[1000..]);
+
+    let response =
+        get_with_headers("/stream?u=admin&p=admin&id=1", &[("range", "bytes=-10")]).await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        body_bytes(response).await,
+        mock_file_contents()[MOCK_FILE_LEN - 10..]
+    );
+}
+
+#[tokio::test]
+async fn test_stream_range_not_satisfiable() {
+    use crate::subsonic::tests::mock_storage::MOCK_FILE_LEN;
+
+    for range in ["bytes=0-1,10-20", "bytes=99999999-"] {
+        let response =
+            get_with_headers("/stream?u=admin&p=admin&id=1", &[("range", range)]).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            response.headers()["content-range"],
+            format!("bytes */{}", MOCK_FILE_LEN).as_str()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_stream_if_range() {
+    let response = get_with_headers("/stream?u=admin&p=admin&id=1", &[]).await;
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+    let response = get_with_headers(
+        "/stream?u=admin&p=admin&id=1",
+        &[("range", "bytes=0-9"), ("if-range", &etag)],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+
+    // ETag 不匹配时返回完整内容
+    let response = get_with_headers(
+        "/stream?u=admin&p=admin&id=1",
+        &[("range", "bytes=0-9"), ("if-range", "\"stale\"")],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("content-range"));
+}
+
+#[tokio::test]
+async fn test_download_supports_range() {
+    use crate::subsonic::tests::mock_storage::mock_file_contents;
+
+    let response =
+        get_with_headers("/download?u=admin&p=admin&id=1", &[("range", "bytes=5-14")]).await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        response.headers()["content-disposition"],
+        "attachment; filename=\"test.mp3\"; filename*=UTF-8''test.mp3"
+    );
+    assert_eq!(body_bytes(response).await, mock_file_contents()[5..15]);
+}
+
+// === 转码 ===
+
+/// 使用假编码器脚本的路由：输出参数行，然后原样复制输入
+#[cfg(unix)]
+fn create_transcoding_router() -> (axum::Router, std::path::PathBuf) {
+    use crate::transcoding::{FfmpegTranscoder, TranscodeProfile, TranscodingConfig};
+    use std::collections::HashMap;
+    use std::os::unix::fs::PermissionsExt;
+
+    let script = std::env::temp_dir().join(format!("reverie-fake-ffmpeg-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&script, "#!/bin/sh\necho \"$@\"\ncat\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut profiles = HashMap::new();
+    for (format, content_type) in [("mp3", "audio/mpeg"), ("opus", "audio/ogg")] {
+        profiles.insert(
+            format.to_string(),
+            TranscodeProfile {
+                command: format!("{} -f {} -ss %t -b:a %bk", script.display(), format),
+                content_type: content_type.to_string(),
+                default_bit_rate: 192,
+            },
+        );
+    }
+    let transcoder = FfmpegTranscoder::new(TranscodingConfig {
+        profiles,
+        default_format: "mp3".to_string(),
+    });
+
+    let storage = Arc::new(MockSubsonicStorage::new());
+    let state = crate::subsonic::SubsonicState::new(storage).with_transcoder(Arc::new(transcoder));
+    (create_router::<MockSubsonicStorage>(state.clone()).with_state(state), script)
+}
+
+#[cfg(unix)]
+async fn transcoded(uri: &str) -> (axum::response::Response, Vec<u8>) {
+    let (router, script) = create_transcoding_router();
+    let response = router
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let (parts, body) = response.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX).await.unwrap().to_vec();
+    std::fs::remove_file(script).unwrap();
+    (axum::response::Response::from_parts(parts, Body::empty()), body)
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_stream_transcodes_requested_format() {
+    use crate::subsonic::tests::mock_storage::mock_file_contents;
+
+    let (response, body) =
+        transcoded("/stream?u=admin&p=admin&id=1&format=opus&maxBitRate=96&timeOffset=30").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "audio/ogg");
+    assert_eq!(response.headers()["accept-ranges"], "none");
+
+    let header = b"-f opus -ss 30 -b:a 96k\n";
+    assert_eq!(&body[..header.len()], header);
+    assert_eq!(&body[header.len()..], &mock_file_contents()[..]);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_stream_applies_user_max_bit_rate() {
+    // 源文件为 320 kbps 的 mp3，用户上限 64 kbps，请求 128 kbps
+    let (response, body) =
+        transcoded("/stream?u=limited&p=limited&id=1&maxBitRate=128").await;
+    assert_eq!(response.headers()["content-type"], "audio/mpeg");
+    assert!(body.starts_with(b"-f mp3 -ss 0 -b:a 64k\n"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_stream_raw_skips_transcoding() {
+    use crate::subsonic::tests::mock_storage::mock_file_contents;
+
+    let (response, body) = transcoded("/stream?u=limited&p=limited&id=1&format=raw").await;
+    assert_eq!(response.headers()["accept-ranges"], "bytes");
+    assert_eq!(body, mock_file_contents());
+
+    // 同格式且无需降低比特率时直接返回原始文件
+    let (response, _) = transcoded("/stream?u=admin&p=admin&id=1&format=mp3").await;
+    assert_eq!(response.headers()["accept-ranges"], "bytes");
+}
+
+#[tokio::test]
+async fn test_bookmarks() {
+    let json =
+        get_json_response(create_test_router(), "/getBookmarks?u=admin&p=admin&f=json").await;
+    let bookmarks = &json["subsonic-response"]["bookmarks"]["bookmark"];
+    assert_eq!(bookmarks[0]["position"], 42_000);
+    assert_eq!(bookmarks[0]["username"], "admin");
+    assert_eq!(bookmarks[0]["entry"]["id"], "song-1");
+
+    let json = get_json_response(
+        create_test_router(),
+        "/createBookmark?u=admin&p=admin&f=json&id=song-1&position=1000&comment=x",
+    )
+    .await;
+    assert_eq!(json["subsonic-response"]["status"], "ok");
+
+    let json = get_json_response(
+        create_test_router(),
+        "/createBookmark?u=admin&p=admin&f=json&id=song-1",
+    )
+    .await;
+    assert_eq!(json["subsonic-response"]["error"]["code"], 10);
+
+    let json = get_json_response(
+        create_test_router(),
+        "/createBookmark?u=admin&p=admin&f=json&id=missing&position=0",
+    )
+    .await;
+    assert_eq!(json["subsonic-response"]["error"]["code"], 70);
+
+    let json = get_json_response(
+        create_test_router(),
+        "/deleteBookmark?u=admin&p=admin&f=json&id=song-1",
+    )
+    .await;
+    assert_eq!(json["subsonic-response"]["status"], "ok");
+}
+
+#[tokio::test]
+async fn test_play_queue() {
+    let json =
+        get_json_response(create_test_router(), "/getPlayQueue?u=admin&p=admin&f=json").await;
+    let queue = &json["subsonic-response"]["playQueue"];
+    assert_eq!(queue["current"], "song-1");
+    assert_eq!(queue["position"], 1_500);
+    assert_eq!(queue["changedBy"], "desktop");
+    assert_eq!(queue["entry"].as_array().unwrap().len(), 3);
+
+    let json = get_json_response(
+        create_test_router(),
+        "/getPlayQueueByIndex?u=admin&p=admin&f=json",
+    )
+    .await;
+    let queue = &json["subsonic-response"]["playQueueByIndex"];
+    assert_eq!(queue["currentIndex"], 2);
+    assert_eq!(queue["username"], "admin");
+    assert_eq!(queue["entry"][2]["id"], "song-1");
+
+    for (uri, status) in [
+        ("/savePlayQueue?id=song-1&id=song-2&current=song-2&position=10", "ok"),
+        ("/savePlayQueue?id=song-1&current=song-2", "failed"),
+        ("/savePlayQueue?id=song-1&position=soon", "failed"),
+        // 不带 id 时清空队列
+        ("/savePlayQueue?", "ok"),
+        ("/savePlayQueueByIndex?id=song-1&id=song-1&currentIndex=1", "ok"),
+        ("/savePlayQueueByIndex?id=song-1&currentIndex=1", "failed"),
+        ("/savePlayQueueByIndex?id=song-1&currentIndex=-1", "failed"),
+    ] {
+        let uri = format!("{}&u=admin&p=admin&f=json&c=phone", uri);
+        let json = get_json_response(create_test_router(), &uri).await;
+        assert_eq!(json["subsonic-response"]["status"], status, "{}", uri);
+    }
+}
+
+/// 创建分享并返回响应中的分享，请求带有 `Host` 和 `X-Forwarded-*` 头
+async fn create_share_via(
+    state: crate::subsonic::SubsonicState<MockSubsonicStorage>,
+) -> serde_json::Value {
+    let router = create_router::<MockSubsonicStorage>(state.clone()).with_state(state);
+    let response = router
+        .oneshot(
+            Request::builder()
+                .uri("/createShare?u=admin&p=admin&f=json&id=song-1&description=Mix&expires=0")
+                .header("host", "music.example")
+                .header("x-forwarded-host", "proxy.example")
+                .header("x-forwarded-proto", "https")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    json["subsonic-response"]["shares"]["share"][0].clone()
+}
+
+#[tokio::test]
+async fn test_shares() {
+    use crate::subsonic::SubsonicState;
+
+    // 默认不信任 X-Forwarded-* 头，只使用 Host
+    let state = SubsonicState::new(Arc::new(MockSubsonicStorage::new()));
+    let share = create_share_via(state.clone()).await;
+    assert_eq!(share["url"], "http://music.example/share/share-1");
+    assert_eq!(share["description"], "Mix");
+    assert_eq!(share["entry"][0]["id"], "song-1");
+    let share = create_share_via(state.clone().trust_forwarded_headers()).await;
+    assert_eq!(share["url"], "https://proxy.example/share/share-1");
+    let share = create_share_via(state.with_public_base_url("https://music.example/")).await;
+    assert_eq!(share["url"], "https://music.example/share/share-1");
+
+    let json =
+        get_json_response(create_test_router(), "/getShares?u=limited&p=limited&f=json").await;
+    assert_eq!(json["subsonic-response"]["shares"]["share"][0]["username"], "limited");
+
+    for (uri, code) in [
+        // 没有分享权限
+        ("/createShare?u=limited&p=limited&id=song-1", 50),
+        ("/deleteShare?u=limited&p=limited&id=share-1", 50),
+        ("/createShare?u=admin&p=admin", 10),
+        ("/createShare?u=admin&p=admin&id=missing", 70),
+        ("/updateShare?u=admin&p=admin&id=share-1&expires=soon", 10),
+        ("/updateShare?u=admin&p=admin&id=share-other", 50),
+        ("/deleteShare?u=admin&p=admin&id=share-2", 70),
+    ] {
+        let json = get_json_response(create_test_router(), &format!("{}&f=json", uri)).await;
+        assert_eq!(json["subsonic-response"]["error"]["code"], code, "{}", uri);
+    }
+
+    let json = get_json_response(
+        create_test_router(),
+        "/updateShare?u=admin&p=admin&f=json&id=share-1&description=New",
+    )
+    .await;
+    assert_eq!(json["subsonic-response"]["status"], "ok");
+}
+
+#[tokio::test]
+async fn test_public_share() {
+    use crate::subsonic::tests::mock_storage::mock_file_contents;
+
+    let storage = Arc::new(MockSubsonicStorage::new());
+    let state = crate::subsonic::SubsonicState::new(storage);
+    let router = crate::axum_server::shares::create_router::<MockSubsonicStorage>()
+        .with_state(state);
+    let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+    // 无需凭据
+    let response = router.clone().oneshot(get("/share/share-1")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let page = String::from_utf8(body.to_vec()).unwrap();
+    assert!(page.contains(r#"src="/share/share-1/stream/song-1""#));
+
+    let response = router
+        .clone()
+        .oneshot(get("/share/share-1/stream/song-1"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "audio/mpeg");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(body.to_vec(), mock_file_contents());
+
+    // 分享之外的歌曲，以及已删除或过期的分享
+    for uri in ["/share/share-1/stream/song-2", "/share/expired", "/share/expired/stream/song-1"] {
+        let response = router.clone().oneshot(get(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn test_internet_radio_stations() {
+    let json = get_json_response(
+        create_test_router(),
+        "/getInternetRadioStations?u=limited&p=limited&f=json",
+    )
+    .await;
+    let station = &json["subsonic-response"]["internetRadioStations"]["internetRadioStation"][0];
+    assert_eq!(station["id"], "1");
+    assert_eq!(station["streamUrl"], "http://127.0.0.1:1/live");
+    assert_eq!(station["homePageUrl"], "http://radio.example");
+    assert!(station.get("nowPlaying").is_none());
+
+    for uri in [
+        "/createInternetRadioStation?u=admin&p=admin&streamUrl=http://a/live.pls&name=A",
+        "/updateInternetRadioStation?u=admin&p=admin&id=1&streamUrl=http://a/live&name=A",
+        "/deleteInternetRadioStation?u=admin&p=admin&id=1",
+    ] {
+        let json = get_json_response(create_test_router(), &format!("{}&f=json", uri)).await;
+        assert_eq!(json["subsonic-response"]["status"], "ok", "{}", uri);
+    }
+
+    for (uri, code) in [
+        // 只有管理员可以管理电台
+        ("/createInternetRadioStation?u=limited&p=limited&streamUrl=http://a&name=A", 50),
+        ("/deleteInternetRadioStation?u=limited&p=limited&id=1", 50),
+        ("/createInternetRadioStation?u=admin&p=admin&name=A", 10),
+        ("/updateInternetRadioStation?u=admin&p=admin&id=1&streamUrl=http://a", 10),
+        ("/updateInternetRadioStation?u=admin&p=admin&id=9&streamUrl=http://a&name=A", 70),
+        ("/deleteInternetRadioStation?u=admin&p=admin&id=9", 70),
+        // 未启用电台转发
+        ("/streamInternetRadio?u=limited&p=limited&id=1", 0),
+    ] {
+        let json = get_json_response(create_test_router(), &format!("{}&f=json", uri)).await;
+        assert_eq!(json["subsonic-response"]["error"]["code"], code, "{}", uri);
+    }
+
+    let storage = Arc::new(MockSubsonicStorage::new());
+    let state = crate::subsonic::SubsonicState::new(storage)
+        .with_radio_relay(crate::radio::RadioRelay::new());
+    let router = create_router::<MockSubsonicStorage>(state.clone()).with_state(state);
+    for (uri, code) in [
+        ("/streamInternetRadio?u=limited&p=limited&id=9", 70),
+        // 电台地址指向本机，默认拒绝连接
+        ("/streamInternetRadio?u=limited&p=limited&id=1", 0),
+    ] {
+        let json = get_json_response(router.clone(), &format!("{}&f=json", uri)).await;
+        assert_eq!(json["subsonic-response"]["error"]["code"], code, "{}", uri);
+    }
+}
@@ -1,7 +1,7 @@
 //! Mock Subsonic Storage 实现
 
-use reverie_core::{SubsonicAlbum, SubsonicAlbumInfo, SubsonicArtist, SubsonicArtistIndex, SubsonicArtistIndexes, SubsonicArtistInfo, SubsonicBookmark, SubsonicDirectory, SubsonicGenre, SubsonicInternetRadioStation, SubsonicLyrics, SubsonicMusicFolder, SubsonicNowPlaying, SubsonicPlaylist, SubsonicPlaylistWithSongs, SubsonicPlayQueue, SubsonicScanStatus, SubsonicShare, SubsonicStarred, SubsonicStructuredLyrics, SubsonicTopSongs, SubsonicUser, MediaFile};
-use reverie_storage::{error::StorageError, SubsonicStorage, FileStorage, FileMetadata};
+use reverie_core::{SubsonicAlbum, SubsonicAlbumInfo, SubsonicArtist, SubsonicArtistIndex, SubsonicArtistIndexes, SubsonicArtistInfo, SubsonicBookmark, SubsonicDirectory, SubsonicGenre, SubsonicInternetRadioStation, SubsonicLyrics, SubsonicMusicFolder, SubsonicNowPlaying, SubsonicPlaylist, SubsonicPlaylistWithSongs, SubsonicPlayQueue, SubsonicScanStatus, SubsonicScrobbleAccount, SubsonicShare, SubsonicStarred, SubsonicStructuredLyrics, SubsonicTopSongs, SubsonicUser, MediaFile, MusicFolderAccess, UserContext};
+use reverie_storage::{error::StorageError, SubsonicStorage, FileStorage, FileMetadata, LibraryFolder, VfsConfig};
 use std::fmt;
 
 type Result<T> = std::result::Result<T, StorageError>;
@@ -31,26 +31,34 @@ impl SubsonicStorage for MockSubsonicStorage {
 
     // === Browsing ===
     async fn get_music_folders(&self) -> Result<Vec<SubsonicMusicFolder>> {
-        Ok(vec![SubsonicMusicFolder {
-            id: 1,
-            name: "Music".to_string(),
-        }])
+        Ok(vec![
+            SubsonicMusicFolder {
+                id: 1,
+                name: "Music".to_string(),
+            },
+            SubsonicMusicFolder {
+                id: 2,
+                name: "Audiobooks".to_string(),
+            },
+        ])
     }
 
     async fn get_indexes(
         &self,
-        _music_folder_id: Option<i32>,
+        _user: &UserContext,
+        _music_folder_ids: Option<&[i32]>,
         _if_modified_since: Option<i64>,
     ) -> Result<SubsonicArtistIndexes> {
         Ok(vec![])
     }
 
-    async fn get_genres(&self) -> Result<Vec<SubsonicGenre>> {
+    async fn get_genres(&self, _music_folder_ids: Option<&[i32]>) -> Result<Vec<SubsonicGenre>> {
         Ok(vec![])
     }
 
     async fn get_music_directory(
         &self,
+        _user: &UserContext,
         _id: &str,
     ) -> Result<Option<SubsonicDirectory>> {
         Ok(None)
@@ -58,7 +66,8 @@ impl SubsonicStorage for MockSubsonicStorage {
 
     async fn get_artists(
         &self,
-        _music_folder_id: Option<i32>,
+        _user: &UserContext,
+        _music_folder_ids: Option<&[i32]>,
     ) -> Result<SubsonicArtistIndexes> {
         Ok(vec![SubsonicArtistIndex {
             id: "A".to_string(),
@@ -73,7 +82,7 @@ impl SubsonicStorage for MockSubsonicStorage {
         }])
     }
 
-    async fn get_artist(&self, _id: &str) -> Result<Option<SubsonicArtist>> {
+    async fn get_artist(&self, _user: &UserContext, _id: &str) -> Result<Option<SubsonicArtist>> {
         Ok(Some(SubsonicArtist {
             id: "artist-1".to_string(),
             name: "Test Artist".to_string(),
@@ -84,7 +93,7 @@ impl SubsonicStorage for MockSubsonicStorage {
         }))
     }
 
-    async fn get_album(&self, _id: &str) -> Result<Option<SubsonicAlbum>> {
+    async fn get_album(&self, _user: &UserContext, _id: &str) -> Result<Option<SubsonicAlbum>> {
         Ok(Some(SubsonicAlbum {
             id: "album-1".to_string(),
             name: "Test Album".to_string(),
@@ -104,8 +113,15 @@ impl SubsonicStorage for MockSubsonicStorage {
         }))
     }
 
-    async fn get_song(&self, _id: &str) -> Result<Option<MediaFile>> {
-        Ok(Some(MediaFile::default()))
+    async fn get_song(&self, _user: &UserContext, id: &str) -> Result<Option<MediaFile>> {
+        // "song-2" 位于音乐文件夹 2，其余歌曲位于文件夹 1
+        Ok(Some(MediaFile {
+            id: id.to_string(),
+            suffix: "mp3".to_string(),
+            bit_rate: 320,
+            library_id: if id == "song-2" { 2 } else { 1 },
+            ..MediaFile::default()
+        }))
     }
 
     async fn get_artist_info(
@@ -152,6 +168,7 @@ impl SubsonicStorage for MockSubsonicStorage {
 
     async fn get_similar_songs(
         &self,
+        _user: &UserContext,
         _id: &str,
         _count: Option<i32>,
     ) -> Result<Vec<MediaFile>> {
@@ -160,6 +177,7 @@ impl SubsonicStorage for MockSubsonicStorage {
 
     async fn get_similar_songs2(
         &self,
+        _user: &UserContext,
         _id: &str,
         _count: Option<i32>,
     ) -> Result<Vec<MediaFile>> {
@@ -168,6 +186,7 @@ impl SubsonicStorage for MockSubsonicStorage {
 
     async fn get_top_songs(
         &self,
+        _user: &UserContext,
         _artist: &str,
         _count: Option<i32>,
     ) -> Result<SubsonicTopSongs> {
@@ -176,26 +195,28 @@ impl SubsonicStorage for MockSubsonicStorage {
 
     async fn get_album_list(
         &self,
+        _user: &UserContext,
         _list_type: &str,
         _size: Option<i32>,
         _offset: Option<i32>,
         _from_year: Option<i32>,
         _to_year: Option<i32>,
         _genre: Option<&str>,
-        _music_folder_id: Option<i32>,
+        _music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<SubsonicAlbum>> {
         Ok(vec![])
     }
 
     async fn get_album_list2(
         &self,
+        _user: &UserContext,
         _list_type: &str,
         _size: Option<i32>,
         _offset: Option<i32>,
         _from_year: Option<i32>,
         _to_year: Option<i32>,
         _genre: Option<&str>,
-        _music_folder_id: Option<i32>,
+        _music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<SubsonicAlbum>> {
         Ok(vec![SubsonicAlbum {
             id: "album-1".to_string(),
@@ -218,32 +239,45 @@ impl SubsonicStorage for MockSubsonicStorage {
 
     async fn get_random_songs(
         &self,
+        _user: &UserContext,
         _size: Option<i32>,
         _genre: Option<&str>,
         _from_year: Option<i32>,
         _to_year: Option<i32>,
-        _music_folder_id: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<MediaFile>> {
-        Ok(vec![])
+        // 每个查询范围内的音乐文件夹返回一首歌曲
+        let folders = music_folder_ids.unwrap_or(&[1, 2]);
+        Ok(folders
+            .iter()
+            .map(|id| MediaFile {
+                id: format!("song-{}", id),
+                title: format!("Song {}", id),
+                library_id: *id,
+                ..Default::default()
+            })
+            .collect())
     }
 
     async fn get_songs_by_genre(
         &self,
+        _user: &UserContext,
         _genre: &str,
         _count: Option<i32>,
         _offset: Option<i32>,
-        _music_folder_id: Option<i32>,
+        _music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<MediaFile>> {
         Ok(vec![])
     }
 
-    async fn get_now_playing(&self) -> Result<Vec<SubsonicNowPlaying>> {
+    async fn get_now_playing(&self, _user: &UserContext) -> Result<Vec<SubsonicNowPlaying>> {
         Ok(vec![])
     }
 
     async fn get_starred(
         &self,
-        _music_folder_id: Option<i32>,
+        _user: &UserContext,
+        _music_folder_ids: Option<&[i32]>,
     ) -> Result<SubsonicStarred> {
         Ok(SubsonicStarred {
             artists: vec![],
@@ -254,13 +288,15 @@ impl SubsonicStorage for MockSubsonicStorage {
 
     async fn get_starred2(
         &self,
-        _music_folder_id: Option<i32>,
+        user: &UserContext,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<SubsonicStarred> {
-        self.get_starred(_music_folder_id).await
+        self.get_starred(user, music_folder_ids).await
     }
 
     async fn search2(
         &self,
+        _user: &UserContext,
         _query: &str,
         _artist_count: Option<i32>,
         _artist_offset: Option<i32>,
@@ -268,6 +304,7 @@ impl SubsonicStorage for MockSubsonicStorage {
         _album_offset: Option<i32>,
         _song_count: Option<i32>,
         _song_offset: Option<i32>,
+        _music_folder_ids: Option<&[i32]>,
     ) -> Result<reverie_core::SubsonicSearchResult2> {
         Ok(reverie_core::SubsonicSearchResult2 {
             artists: vec![],
@@ -278,6 +315,7 @@ impl SubsonicStorage for MockSubsonicStorage {
 
     async fn search3(
         &self,
+        _user: &UserContext,
         _query: &str,
         _artist_count: Option<i32>,
         _artist_offset: Option<i32>,
@@ -285,6 +323,7 @@ impl SubsonicStorage for MockSubsonicStorage {
         _album_offset: Option<i32>,
         _song_count: Option<i32>,
         _song_offset: Option<i32>,
+        _music_folder_ids: Option<&[i32]>,
     ) -> Result<reverie_core::SubsonicSearchResult3> {
         Ok(reverie_core::SubsonicSearchResult3 {
             artists: vec![],
@@ -295,31 +334,46 @@ impl SubsonicStorage for MockSubsonicStorage {
 
     async fn get_playlists(
         &self,
-        _username: Option<&str>,
+        user: &UserContext,
+        username: Option<&str>,
     ) -> Result<Vec<SubsonicPlaylist>> {
+        if username.is_some_and(|name| name != user.username) && !user.is_admin {
+            return Err(StorageError::PermissionDenied("playlists".to_string()));
+        }
         Ok(vec![])
     }
 
     async fn get_playlist(
         &self,
-        _id: &str,
+        _user: &UserContext,
+        id: &str,
     ) -> Result<Option<SubsonicPlaylistWithSongs>> {
+        // "foreign" 播放列表属于其他用户且未公开
+        if id == "foreign" {
+            return Err(StorageError::PermissionDenied(id.to_string()));
+        }
         Ok(None)
     }
 
     async fn create_playlist(
         &self,
+        _user: &UserContext,
         _name: Option<&str>,
-        _playlist_id: Option<&str>,
-        _song_ids: &[&str],
+        playlist_id: Option<&str>,
+        song_ids: &[&str],
     ) -> Result<SubsonicPlaylistWithSongs> {
+        match playlist_id {
+            Some("foreign") => return Err(StorageError::PermissionDenied("foreign".to_string())),
+            Some("missing") => return Err(StorageError::NotFound("missing".to_string())),
+            _ => {}
+        }
         Ok(SubsonicPlaylistWithSongs {
             id: "playlist-1".to_string(),
             name: "Test Playlist".to_string(),
             comment: None,
             owner: "admin".to_string(),
             public: false,
-            song_count: 0,
+            song_count: song_ids.len() as i32,
             duration: 0,
             created: chrono::Utc::now(),
             changed: chrono::Utc::now(),
@@ -330,17 +384,28 @@ impl SubsonicStorage for MockSubsonicStorage {
 
     async fn update_playlist(
         &self,
-        _playlist_id: &str,
+        _user: &UserContext,
+        playlist_id: &str,
         _name: Option<&str>,
         _comment: Option<&str>,
         _public: Option<bool>,
-        _song_ids_to_add: &[&str],
+        song_ids_to_add: &[&str],
         _song_indexes_to_remove: &[i32],
     ) -> Result<()> {
+        // "foreign" 播放列表属于其他用户
+        if playlist_id == "foreign" {
+            return Err(StorageError::PermissionDenied(playlist_id.to_string()));
+        }
+        if song_ids_to_add.contains(&"missing") {
+            return Err(StorageError::NotFound("missing".to_string()));
+        }
         Ok(())
     }
 
-    async fn delete_playlist(&self, _id: &str) -> Result<()> {
+    async fn delete_playlist(&self, _user: &UserContext, id: &str) -> Result<()> {
+        if id == "foreign" {
+            return Err(StorageError::PermissionDenied(id.to_string()));
+        }
         Ok(())
     }
 
@@ -348,7 +413,11 @@ impl SubsonicStorage for MockSubsonicStorage {
         Ok(Some("/music/test.mp3".to_string()))
     }
 
-    async fn get_cover_art_path(&self, _id: &str) -> Result<Option<String>> {
+    async fn get_cover_art_path(&self, user: &UserContext, id: &str) -> Result<Option<String>> {
+        // 与 get_song 相同，"song-2" 位于音乐文件夹 2
+        if id == "song-2" && !user.music_folders.allows(2) {
+            return Ok(None);
+        }
         Ok(Some("/covers/test.jpg".to_string()))
     }
 
@@ -371,92 +440,139 @@ impl SubsonicStorage for MockSubsonicStorage {
         Ok(None)
     }
 
-    async fn star(&self, _ids: &[&str], _album_ids: &[&str], _artist_ids: &[&str]) -> Result<()> {
+    async fn star(&self, _user: &UserContext, ids: &[&str], _album_ids: &[&str], _artist_ids: &[&str]) -> Result<()> {
+        if ids.contains(&"missing") {
+            return Err(StorageError::NotFound("missing".to_string()));
+        }
         Ok(())
     }
 
-    async fn unstar(&self, _ids: &[&str], _album_ids: &[&str], _artist_ids: &[&str]) -> Result<()> {
+    async fn unstar(&self, _user: &UserContext, _ids: &[&str], _album_ids: &[&str], _artist_ids: &[&str]) -> Result<()> {
         Ok(())
     }
 
-    async fn set_rating(&self, _id: &str, _rating: i32) -> Result<()> {
+    async fn set_rating(&self, _user: &UserContext, _id: &str, _rating: i32) -> Result<()> {
         Ok(())
     }
 
-    async fn scrobble(&self, _id: &str, _time: Option<i64>, _submission: bool) -> Result<()> {
+    async fn scrobble(&self, _user: &UserContext, _id: &str, _time: Option<i64>, _submission: bool, _client: Option<&str>) -> Result<()> {
         Ok(())
     }
 
-    async fn get_bookmarks(&self) -> Result<Vec<SubsonicBookmark>> {
-        Ok(vec![])
+    async fn get_bookmarks(&self, user: &UserContext) -> Result<Vec<SubsonicBookmark>> {
+        Ok(vec![SubsonicBookmark {
+            entry: mock_song("song-1"),
+            position: 42_000,
+            username: user.username.clone(),
+            comment: Some("chapter 2".to_string()),
+            created: chrono::Utc::now(),
+            changed: chrono::Utc::now(),
+        }])
     }
 
     async fn create_bookmark(
         &self,
-        _id: &str,
+        _user: &UserContext,
+        id: &str,
         _position: i64,
         _comment: Option<&str>,
     ) -> Result<()> {
-        Ok(())
+        match id {
+            "missing" => Err(StorageError::NotFound(format!("Song {}", id))),
+            _ => Ok(()),
+        }
     }
 
-    async fn delete_bookmark(&self, _id: &str) -> Result<()> {
+    async fn delete_bookmark(&self, _user: &UserContext, _id: &str) -> Result<()> {
         Ok(())
     }
 
-    async fn get_play_queue(&self) -> Result<Option<SubsonicPlayQueue>> {
-        Ok(None)
+    async fn get_play_queue(&self, user: &UserContext) -> Result<Option<SubsonicPlayQueue>> {
+        // 队列中 song-1 出现两次，当前播放的是第二次
+        Ok(Some(SubsonicPlayQueue {
+            entries: vec![
+                mock_song("song-1"),
+                mock_song("song-2"),
+                mock_song("song-1"),
+            ],
+            current: Some("song-1".to_string()),
+            current_index: Some(2),
+            position: 1_500,
+            username: user.username.clone(),
+            changed: chrono::Utc::now(),
+            changed_by: "desktop".to_string(),
+        }))
     }
 
     async fn save_play_queue(
         &self,
+        _user: &UserContext,
         _ids: &[&str],
-        _current: Option<&str>,
+        _current_index: Option<usize>,
         _position: Option<i64>,
+        _changed_by: &str,
     ) -> Result<()> {
         Ok(())
     }
 
-    async fn get_shares(&self) -> Result<Vec<SubsonicShare>> {
-        Ok(vec![])
+    async fn get_shares(&self, user: &UserContext) -> Result<Vec<SubsonicShare>> {
+        Ok(vec![mock_share(&user.username)])
     }
 
     async fn create_share(
         &self,
-        _ids: &[&str],
-        _description: Option<&str>,
+        user: &UserContext,
+        ids: &[&str],
+        description: Option<&str>,
         _expires: Option<i64>,
     ) -> Result<SubsonicShare> {
+        if ids.contains(&"missing") {
+            return Err(StorageError::NotFound("missing".to_string()));
+        }
         Ok(SubsonicShare {
-            id: "share-1".to_string(),
-            url: "http://example.com/share/1".to_string(),
-            description: None,
-            username: "admin".to_string(),
-            created: chrono::Utc::now(),
-            expires: None,
-            last_visited: None,
-            visit_count: 0,
-            entries: vec![],
+            description: description.map(str::to_string),
+            ..mock_share(&user.username)
         })
     }
 
     async fn update_share(
         &self,
-        _id: &str,
+        user: &UserContext,
+        id: &str,
         _description: Option<&str>,
         _expires: Option<i64>,
     ) -> Result<()> {
-        Ok(())
+        self.delete_share(user, id).await
+    }
+
+    async fn delete_share(&self, _user: &UserContext, id: &str) -> Result<()> {
+        match id {
+            "share-1" => Ok(()),
+            "share-other" => Err(StorageError::PermissionDenied(id.to_string())),
+            _ => Err(StorageError::NotFound(format!("Share {}", id))),
+        }
     }
 
-    async fn delete_share(&self, _id: &str) -> Result<()> {
+    async fn get_public_share(&self, id: &str) -> Result<Option<SubsonicShare>> {
+        // 其他 ID 视为已删除或已过期
+        Ok((id == "share-1").then(|| mock_share("admin")))
+    }
+
+    async fn record_share_visit(&self, _id: &str) -> Result<()> {
         Ok(())
     }
 
     async fn get_internet_radio_stations(
         &self,
     ) -> Result<Vec<SubsonicInternetRadioStation>> {
-        Ok(vec![])
+        Ok(vec![mock_radio_station()])
+    }
+
+    async fn get_internet_radio_station(
+        &self,
+        id: &str,
+    ) -> Result<Option<SubsonicInternetRadioStation>> {
+        Ok(Some(mock_radio_station()).filter(|s| s.id == id))
     }
 
     async fn create_internet_radio_station(
@@ -470,38 +586,82 @@ impl SubsonicStorage for MockSubsonicStorage {
 
     async fn update_internet_radio_station(
         &self,
-        _id: &str,
+        id: &str,
         _stream_url: &str,
         _name: &str,
         _homepage_url: Option<&str>,
     ) -> Result<()> {
+        if id != "1" {
+            return Err(StorageError::NotFound(format!("Internet radio station {}", id)));
+        }
         Ok(())
     }
 
-    async fn delete_internet_radio_station(&self, _id: &str) -> Result<()> {
+    async fn delete_internet_radio_station(&self, id: &str) -> Result<()> {
+        if id != "1" {
+            return Err(StorageError::NotFound(format!("Internet radio station {}", id)));
+        }
         Ok(())
     }
 
-    async fn get_user(&self, _username: &str) -> Result<Option<SubsonicUser>> {
+    async fn get_user_password(&self, username: &str) -> Result<Option<String>> {
+        // "ldap" 用户存在但密码不可取回
+        match username {
+            "admin" | "limited" | "pending" => Ok(Some(username.to_string())),
+            _ => Ok(None),
+        }
+    }
+
+    async fn verify_user_password(&self, username: &str, password: &str) -> Result<bool> {
+        // "ldap" 用户只保存密码哈希，可以用明文密码登录
+        Ok(matches!(username, "admin" | "limited" | "pending" | "ldap") && password == username)
+    }
+
+    async fn password_change_required(&self, username: &str) -> Result<bool> {
+        // "pending" 用户必须先修改密码
+        Ok(username == "pending")
+    }
+
+    fn supports_api_key_auth(&self) -> bool {
+        true
+    }
+
+    async fn get_username_by_api_key(&self, api_key: &str) -> Result<Option<String>> {
+        Ok((api_key == "test-api-key").then(|| "admin".to_string()))
+    }
+
+    async fn get_user(&self, username: &str) -> Result<Option<SubsonicUser>> {
+        if !matches!(username, "admin" | "ldap" | "limited" | "pending") {
+            return Ok(None);
+        }
         Ok(Some(SubsonicUser {
-            username: "admin".to_string(),
+            username: username.to_string(),
             email: None,
             scrobbling_enabled: true,
-            max_bit_rate: None,
-            admin_role: true,
+            // "limited" 用户的比特率上限为 64 kbps
+            max_bit_rate: (username == "limited").then_some(64),
+            // "limited" 和 "ldap" 用户不是管理员
+            admin_role: !matches!(username, "limited" | "ldap"),
             settings_role: true,
-            download_role: true,
+            // "limited" 用户不能下载和编辑播放列表
+            download_role: username != "limited",
             upload_role: true,
-            playlist_role: true,
+            playlist_role: username != "limited",
             cover_art_role: true,
             comment_role: true,
             podcast_role: true,
-            stream_role: true,
+            // "ldap" 用户不能流式播放
+            stream_role: username != "ldap",
             jukebox_role: true,
-            share_role: true,
+            share_role: username != "limited",
             video_conversion_role: false,
             avatar_last_changed: None,
-            folders: vec![1],
+            // "limited" 用户只能访问文件夹 1
+            folders: if username == "limited" {
+                MusicFolderAccess::Only(vec![1])
+            } else {
+                MusicFolderAccess::All
+            },
         }))
     }
 
@@ -526,14 +686,14 @@ impl SubsonicStorage for MockSubsonicStorage {
         _podcast_role: bool,
         _share_role: bool,
         _video_conversion_role: bool,
-        _music_folder_ids: &[i32],
+        _music_folders: &MusicFolderAccess,
     ) -> Result<()> {
         Ok(())
     }
 
     async fn update_user(
         &self,
-        _username: &str,
+        username: &str,
         _password: Option<&str>,
         _email: Option<&str>,
         _admin_role: Option<bool>,
@@ -548,20 +708,56 @@ impl SubsonicStorage for MockSubsonicStorage {
         _podcast_role: Option<bool>,
         _share_role: Option<bool>,
         _video_conversion_role: Option<bool>,
-        _music_folder_ids: Option<&[i32]>,
+        _music_folders: Option<&MusicFolderAccess>,
         _max_bit_rate: Option<i32>,
     ) -> Result<()> {
+        if username == "missing" {
+            return Err(StorageError::NotFound(format!("User {}", username)));
+        }
         Ok(())
     }
 
-    async fn delete_user(&self, _username: &str) -> Result<()> {
+    async fn delete_user(&self, username: &str) -> Result<()> {
+        if username == "missing" {
+            return Err(StorageError::NotFound(format!("User {}", username)));
+        }
         Ok(())
     }
 
-    async fn change_password(&self, _username: &str, _password: &str) -> Result<()> {
+    async fn change_password(&self, username: &str, _password: &str) -> Result<()> {
+        if username == "missing" {
+            return Err(StorageError::NotFound(format!("User {}", username)));
+        }
         Ok(())
     }
 
+    async fn get_scrobble_accounts(
+        &self,
+        _user: &UserContext,
+    ) -> Result<Vec<SubsonicScrobbleAccount>> {
+        Ok(vec![SubsonicScrobbleAccount {
+            service: "listenbrainz".to_string(),
+            url: None,
+        }])
+    }
+
+    async fn update_scrobble_account(
+        &self,
+        user: &UserContext,
+        service: &str,
+        _token: &str,
+        url: Option<&str>,
+    ) -> Result<()> {
+        if service != "listenbrainz" {
+            return Err(StorageError::NotFound(format!("Scrobble service {}", service)));
+        }
+        match url {
+            Some(_) if !user.is_admin => Err(StorageError::PermissionDenied(service.to_string())),
+            Some(url) => reverie_storage::outbound::parse_target(url, false).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
     async fn get_scan_status(&self) -> Result<SubsonicScanStatus> {
         Ok(SubsonicScanStatus {
             scanning: false,
@@ -574,15 +770,103 @@ impl SubsonicStorage for MockSubsonicStorage {
         })
     }
 
-    async fn start_scan(&self) -> Result<SubsonicScanStatus> {
+    async fn start_scan(&self, _full_scan: bool) -> Result<SubsonicScanStatus> {
         self.get_scan_status().await
     }
+
+    async fn get_music_folder_configs(&self) -> Result<Vec<LibraryFolder>> {
+        Ok(vec![
+            LibraryFolder {
+                id: 1,
+                name: "Music".to_string(),
+                path: "/music".to_string(),
+                vfs_config: None,
+            },
+            LibraryFolder {
+                id: 2,
+                name: "Audiobooks".to_string(),
+                path: "books/".to_string(),
+                vfs_config: Some(VfsConfig::local("/audiobooks")),
+            },
+        ])
+    }
+
+    async fn create_music_folder(
+        &self,
+        name: &str,
+        path: &str,
+        vfs_config: Option<&VfsConfig>,
+        _scan: bool,
+    ) -> Result<LibraryFolder> {
+        Ok(LibraryFolder {
+            id: 3,
+            name: name.to_string(),
+            path: path.to_string(),
+            vfs_config: vfs_config.cloned(),
+        })
+    }
+
+    async fn update_music_folder(&self, folder: &LibraryFolder) -> Result<()> {
+        match folder.id {
+            1 | 2 => Ok(()),
+            id => Err(StorageError::NotFound(format!("Music folder {}", id))),
+        }
+    }
+
+    async fn delete_music_folder(&self, id: i32) -> Result<()> {
+        match id {
+            1 | 2 => Ok(()),
+            _ => Err(StorageError::NotFound(format!("Music folder {}", id))),
+        }
+    }
+}
+
+/// 模拟媒体文件的长度，超过一个流式传输块
+pub const MOCK_FILE_LEN: usize = 600 * 1024;
+
+/// 模拟媒体文件的内容，每个字节由其偏移量决定
+pub fn mock_file_contents() -> Vec<u8> {
+    (0..MOCK_FILE_LEN).map(|i| (i % 251) as u8).collect()
+}
+
+/// 包含一首歌曲的模拟分享
+fn mock_share(username: &str) -> SubsonicShare {
+    SubsonicShare {
+        id: "share-1".to_string(),
+        url: "/share/share-1".to_string(),
+        description: None,
+        username: username.to_string(),
+        created: chrono::Utc::now(),
+        expires: None,
+        last_visited: None,
+        visit_count: 0,
+        entries: vec![mock_song("song-1")],
+    }
+}
+
+/// 指向无法连接地址的模拟电台
+fn mock_radio_station() -> SubsonicInternetRadioStation {
+    SubsonicInternetRadioStation {
+        id: "1".to_string(),
+        name: "Test Radio".to_string(),
+        stream_url: "http://127.0.0.1:1/live".to_string(),
+        homepage_url: Some("http://radio.example".to_string()),
+    }
+}
+
+/// 只有 ID 和标题的模拟歌曲
+fn mock_song(id: &str) -> MediaFile {
+    MediaFile {
+        id: id.to_string(),
+        title: id.to_string(),
+        ..MediaFile::default()
+    }
 }
 
 #[async_trait::async_trait]
 impl FileStorage for MockSubsonicStorage {
     async fn read_file(&self, _path: &str) -> Result<Vec<u8>> {
-        Ok(vec![0, 1, 2, 3]) // 返回一些虚拟数据
+        Ok(mock_file_contents())
     }
 
     async fn write_file(&self, _path: &str, _data: &[u8]) -> Result<()> {
@@ -603,8 +887,8 @@ impl FileStorage for MockSubsonicStorage {
 
     async fn get_file_metadata(&self, _path: &str) -> Result<FileMetadata> {
         Ok(FileMetadata {
-            size: 1024,
-            modified: std::time::SystemTime::now(),
+            size: MOCK_FILE_LEN as u64,
+            modified: std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000),
             is_file: true,
             is_dir: false,
         })
@@ -2,3 +2,4 @@
 
 pub mod mock_storage;
 pub mod api_tests;
+pub mod xml_tests;
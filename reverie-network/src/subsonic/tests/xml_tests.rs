@@ -0,0 +1,366 @@
+//! Subsonic XML 序列化测试
+//!
+//! 将序列化结果与 Subsonic API 文档中的示例 XML 解析为规范化的树后比较。
+
+use crate::subsonic::response::*;
+use crate::subsonic::tests::api_tests::create_test_router;
+use axum::{body::Body, http::Request};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::BTreeMap;
+use tower::ServiceExt;
+
+#[derive(Debug, PartialEq)]
+struct Node {
+    name: String,
+    attrs: BTreeMap<String, String>,
+    text: String,
+    children: Vec<Node>,
+}
+
+fn parse(xml: &str) -> Node {
+    let mut reader = Reader::from_str(xml);
+    let mut stack: Vec<Node> = Vec::new();
+
+    fn start(e: &quick_xml::events::BytesStart) -> Node {
+        Node {
+            name: String::from_utf8(e.name().as_ref().to_vec()).unwrap(),
+            attrs: e
+                .attributes()
+                .map(|a| {
+                    let a = a.unwrap();
+                    (
+                        String::from_utf8(a.key.as_ref().to_vec()).unwrap(),
+                        a.unescape_value().unwrap().into_owned(),
+                    )
+                })
+                .collect(),
+            text: String::new(),
+            children: Vec::new(),
+        }
+    }
+
+    loop {
+        match reader.read_event().unwrap() {
+            Event::Start(e) => stack.push(start(&e)),
+            Event::Empty(e) => {
+                let node = start(&e);
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => return node,
+                }
+            }
+            Event::Text(t) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text.push_str(t.unescape().unwrap().trim());
+                }
+            }
+            Event::End(_) => {
+                let node = stack.pop().unwrap();
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => return node,
+                }
+            }
+            Event::Eof => panic!("unexpected end of document"),
+            _ => {}
+        }
+    }
+}
+
+/// 比较根元素的状态、命名空间和全部子元素；版本和服务器信息属性不参与比较
+fn assert_matches_spec(response: SubsonicResponse, spec_xml: &str) {
+    let actual = parse(&to_xml(&response).unwrap());
+    let expected = parse(spec_xml);
+
+    assert_eq!(actual.name, "subsonic-response");
+    assert_eq!(actual.attrs.get("xmlns"), expected.attrs.get("xmlns"));
+    assert_eq!(actual.attrs.get("status"), expected.attrs.get("status"));
+    assert_eq!(actual.children, expected.children);
+}
+
+#[test]
+fn test_xml_ping() {
+    let xml = to_xml(&SubsonicResponse::ok()).unwrap();
+    assert!(xml.starts_with(r#"<?xml version="1.0" encoding="UTF-8"?>"#));
+
+    let root = parse(&xml);
+    assert_eq!(root.attrs["xmlns"], SUBSONIC_XMLNS);
+    assert_eq!(root.attrs["status"], "ok");
+    assert_eq!(root.attrs["version"], "1.16.1");
+    assert_eq!(root.attrs["openSubsonic"], "true");
+    assert!(root.children.is_empty());
+}
+
+#[test]
+fn test_xml_error() {
+    assert_matches_spec(
+        SubsonicResponse::error(40, "Wrong username or password"),
+        r#"<subsonic-response xmlns="http://subsonic.org/restapi" status="failed" version="1.1.1">
+               <error code="40" message="Wrong username or password"/>
+           </subsonic-response>"#,
+    );
+}
+
+#[test]
+fn test_xml_music_folders() {
+    let data = MusicFoldersData {
+        music_folders: MusicFoldersList {
+            music_folder: vec![
+                MusicFolderItem {
+                    id: 1,
+                    name: "Music".to_string(),
+                },
+                MusicFolderItem {
+                    id: 2,
+                    name: "Movies".to_string(),
+                },
+                MusicFolderItem {
+                    id: 3,
+                    name: "Incoming Podcasts".to_string(),
+                },
+            ],
+        },
+    };
+    assert_matches_spec(
+        SubsonicResponse::ok_with(data),
+        r#"<subsonic-response xmlns="http://subsonic.org/restapi" status="ok" version="1.1.1">
+               <musicFolders>
+                   <musicFolder id="1" name="Music"/>
+                   <musicFolder id="2" name="Movies"/>
+                   <musicFolder id="3" name="Incoming Podcasts"/>
+               </musicFolders>
+           </subsonic-response>"#,
+    );
+}
+
+#[test]
+fn test_xml_genres_use_text_content() {
+    let data = GenresData {
+        genres: GenresInner {
+            genre: vec![
+                GenreItem {
+                    value: "Electronic".to_string(),
+                    song_count: 28,
+                    album_count: 6,
+                },
+                GenreItem {
+                    value: "Hard Rock".to_string(),
+                    song_count: 6,
+                    album_count: 2,
+                },
+            ],
+        },
+    };
+    assert_matches_spec(
+        SubsonicResponse::ok_with(data),
+        r#"<subsonic-response xmlns="http://subsonic.org/restapi" status="ok" version="1.10.2">
+               <genres>
+                   <genre songCount="28" albumCount="6">Electronic</genre>
+                   <genre songCount="6" albumCount="2">Hard Rock</genre>
+               </genres>
+           </subsonic-response>"#,
+    );
+}
+
+#[test]
+fn test_xml_user_folders_are_elements() {
+    let data = UserData {
+        user: UserItem {
+            username: "sindre".to_string(),
+            email: Some("sindre@activeobjects.no".to_string()),
+            scrobbling_enabled: true,
+            max_bit_rate: None,
+            admin_role: false,
+            settings_role: true,
+            download_role: true,
+            upload_role: false,
+            playlist_role: true,
+            cover_art_role: true,
+            comment_role: true,
+            podcast_role: true,
+            stream_role: true,
+            jukebox_role: true,
+            share_role: false,
+            video_conversion_role: false,
+            folder: vec![0, 3],
+        },
+    };
+    assert_matches_spec(
+        SubsonicResponse::ok_with(data),
+        r#"<subsonic-response xmlns="http://subsonic.org/restapi" status="ok" version="1.13.0">
+               <user username="sindre" email="sindre@activeobjects.no" scrobblingEnabled="true"
+                     adminRole="false" settingsRole="true" downloadRole="true" uploadRole="false"
+                     playlistRole="true" coverArtRole="true" commentRole="true" podcastRole="true"
+                     streamRole="true" jukeboxRole="true" shareRole="false" videoConversionRole="false">
+                   <folder>0</folder>
+                   <folder>3</folder>
+               </user>
+           </subsonic-response>"#,
+    );
+}
+
+#[test]
+fn test_user_folders() {
+    // 受限用户列出可访问的文件夹，文件夹全部被删除后列表为空
+    let mut user = reverie_core::SubsonicUser {
+        username: "bob".to_string(),
+        email: None,
+        scrobbling_enabled: true,
+        max_bit_rate: None,
+        admin_role: false,
+        settings_role: true,
+        download_role: true,
+        upload_role: false,
+        playlist_role: true,
+        cover_art_role: true,
+        comment_role: true,
+        podcast_role: true,
+        stream_role: true,
+        jukebox_role: false,
+        share_role: false,
+        video_conversion_role: false,
+        avatar_last_changed: None,
+        folders: reverie_core::MusicFolderAccess::Only(vec![1, 2]),
+    };
+    assert_eq!(UserItem::from(&user).folder, vec![1, 2]);
+    user.folders = reverie_core::MusicFolderAccess::Only(vec![]);
+    assert!(UserItem::from(&user).folder.is_empty());
+}
+
+#[test]
+fn test_xml_artist_info_scalars_are_elements() {
+    let data = ArtistInfoData {
+        artist_info: ArtistInfo {
+            biography: Some("Black Sabbath are an English rock band".to_string()),
+            music_brainz_id: Some("5182c1d9-c7d2-4dad-afa0-ccfeada921a8".to_string()),
+            last_fm_url: Some("http://www.last.fm/music/Black+Sabbath".to_string()),
+            small_url: Some("http://userserve-ak.last.fm/serve/64/27904353.jpg".to_string()),
+            medium_url: None,
+            large_url: None,
+            similar_artist: vec![ArtistID3Item {
+                id: "22".to_string(),
+                name: "Accept".to_string(),
+                cover_art: None,
+                album_count: 3,
+                artist_image_url: None,
+                starred: None,
+                user_rating: None,
+            }],
+        },
+    };
+    assert_matches_spec(
+        SubsonicResponse::ok_with(data),
+        r#"<subsonic-response xmlns="http://subsonic.org/restapi" status="ok" version="1.11.0">
+               <artistInfo>
+                   <biography>Black Sabbath are an English rock band</biography>
+                   <musicBrainzId>5182c1d9-c7d2-4dad-afa0-ccfeada921a8</musicBrainzId>
+                   <lastFmUrl>http://www.last.fm/music/Black+Sabbath</lastFmUrl>
+                   <smallImageUrl>http://userserve-ak.last.fm/serve/64/27904353.jpg</smallImageUrl>
+                   <similarArtist id="22" name="Accept" albumCount="3"/>
+               </artistInfo>
+           </subsonic-response>"#,
+    );
+}
+
+#[test]
+fn test_xml_lyrics_escape_text() {
+    let data = LyricsData {
+        lyrics: LyricsItem {
+            artist: Some("Metallica".to_string()),
+            title: Some("Blitzkrieg".to_string()),
+            value: "Let us have peace & <quiet>".to_string(),
+        },
+    };
+    let xml = to_xml(&SubsonicResponse::ok_with(data)).unwrap();
+    assert!(xml.contains("&amp; &lt;quiet&gt;"));
+
+    let root = parse(&xml);
+    let lyrics = &root.children[0];
+    assert_eq!(lyrics.name, "lyrics");
+    assert_eq!(lyrics.attrs["artist"], "Metallica");
+    assert_eq!(lyrics.text, "Let us have peace & <quiet>");
+}
+
+#[test]
+fn test_xml_open_subsonic_extensions() {
+    let data = OpenSubsonicExtensionsData {
+        open_subsonic_extensions: vec![
+            OpenSubsonicExtensionItem {
+                name: "template".to_string(),
+                versions: vec![1, 2],
+            },
+            OpenSubsonicExtensionItem {
+                name: "transcodeOffset".to_string(),
+                versions: vec![1],
+            },
+        ],
+    };
+    assert_matches_spec(
+        SubsonicResponse::ok_with(data),
+        r#"<subsonic-response xmlns="http://subsonic.org/restapi" status="ok" version="1.16.1">
+               <openSubsonicExtensions name="template">
+                   <versions>1</versions>
+                   <versions>2</versions>
+               </openSubsonicExtensions>
+               <openSubsonicExtensions name="transcodeOffset">
+                   <versions>1</versions>
+               </openSubsonicExtensions>
+           </subsonic-response>"#,
+    );
+}
+
+async fn get_raw(uri: &str) -> (String, String) {
+    let response = create_test_router()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let content_type = response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (content_type, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_default_format_is_xml() {
+    let (content_type, body) = get_raw("/getMusicFolders?u=admin&p=admin").await;
+    assert!(content_type.starts_with("text/xml"));
+
+    let root = parse(&body);
+    assert_eq!(root.attrs["status"], "ok");
+    assert_eq!(root.children[0].name, "musicFolders");
+    assert_eq!(root.children[0].children[0].attrs["name"], "Music");
+}
+
+#[tokio::test]
+async fn test_auth_error_is_xml_by_default() {
+    let (_, body) = get_raw("/ping?u=admin&p=wrong").await;
+    let root = parse(&body);
+    assert_eq!(root.attrs["status"], "failed");
+    assert_eq!(root.children[0].attrs["code"], "40");
+}
+
+#[tokio::test]
+async fn test_jsonp_wraps_response_in_callback() {
+    let (content_type, body) = get_raw("/ping?u=admin&p=admin&f=jsonp&callback=handle").await;
+    assert_eq!(content_type, "application/javascript");
+    assert!(body.starts_with("handle({\"subsonic-response\":"));
+    assert!(body.ends_with("});"));
+}
+
+#[tokio::test]
+async fn test_jsonp_rejects_missing_or_unsafe_callback() {
+    for uri in [
+        "/ping?u=admin&p=admin&f=jsonp",
+        "/ping?u=admin&p=admin&f=jsonp&callback=alert(1)",
+    ] {
+        let (content_type, body) = get_raw(uri).await;
+        assert_eq!(content_type, "application/json");
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["subsonic-response"]["error"]["code"], 10);
+    }
+}
@@ -1,24 +1,33 @@
 //! 用户和系统相关端点处理器
 
-use axum::{
-    extract::{Query, State},
-    response::Response,
-};
-use reverie_storage::{FileStorage, SubsonicStorage};
-use std::collections::HashMap;
+use axum::{extract::State, response::Response, Extension};
+use reverie_core::MusicFolderAccess;
+use reverie_storage::{error::StorageError, FileStorage, SubsonicStorage};
 
-use super::{error_response, format_response, ok_response, SubsonicState};
+use super::auth::{accessible_song, decode_password, music_folder_scope};
 use super::response::*;
+use super::{
+    error_response, format_response, ok_response, param_error, require_admin, require_role,
+    AuthContext, AuthError, ParamError, SubsonicParams, SubsonicState,
+};
 
 /// GET /rest/getUser - 获取用户信息
+///
+/// 非管理员只能获取自己的信息
 pub async fn get_user_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let username = match params.get("username") {
-        Some(u) => u.as_str(),
-        None => return error_response(&params, 10, "Missing required parameter: username"),
+    let username = match params.required("username") {
+        Ok(u) => u,
+        Err(e) => return param_error(&params, e),
     };
+    if username != user.username {
+        if let Some(response) = require_admin(&user, &params) {
+            return response;
+        }
+    }
 
     match state.storage.get_user(username).await {
         Ok(Some(user)) => {
@@ -33,11 +42,15 @@ pub async fn get_user_handler<S: SubsonicStorage + Clone>(
     }
 }
 
-/// GET /rest/getUsers - 获取所有用户
+/// GET /rest/getUsers - 获取所有用户，仅管理员可用
 pub async fn get_users_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
+    if let Some(response) = require_admin(&user, &params) {
+        return response;
+    }
     match state.storage.get_users().await {
         Ok(users) => {
             let items: Vec<UserItem> = users.iter().map(UserItem::from).collect();
@@ -51,10 +64,245 @@ pub async fn get_users_handler<S: SubsonicStorage + Clone>(
     }
 }
 
+/// GET /rest/changePassword - 修改密码
+///
+/// 用户可以修改自己的密码，管理员可以修改任何用户的密码。`password` 可为 `enc:` 前缀的十六进制编码
+pub async fn change_password_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    let username = match params.required("username") {
+        Ok(username) => username,
+        Err(e) => return param_error(&params, e),
+    };
+    let password = match params.required("password") {
+        Ok(password) => decode_password(password),
+        Err(e) => return param_error(&params, e),
+    };
+    if username != user.username && !user.is_admin {
+        let e = AuthError::NotAuthorized;
+        return error_response(&params, e.code(), &e.to_string());
+    }
+    if password.is_empty() {
+        return error_response(&params, 10, "Password must not be empty");
+    }
+
+    match state.storage.change_password(username, &password).await {
+        Ok(()) => ok_response(&params),
+        Err(StorageError::NotFound(_)) => error_response(&params, 70, "User not found"),
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
+
+/// `createUser` 和 `updateUser` 的角色参数
+const ROLE_PARAMS: [&str; 12] = [
+    "adminRole",
+    "settingsRole",
+    "streamRole",
+    "jukeboxRole",
+    "downloadRole",
+    "uploadRole",
+    "playlistRole",
+    "coverArtRole",
+    "commentRole",
+    "podcastRole",
+    "shareRole",
+    "videoConversionRole",
+];
+
+/// Subsonic 允许的 `maxBitRate` 取值，0 表示不限制
+const MAX_BIT_RATES: [i32; 15] = [
+    0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+];
+
+/// 返回第一个值不是 `true` 或 `false` 的角色参数
+fn invalid_role_param(params: &SubsonicParams) -> Option<&'static str> {
+    ROLE_PARAMS
+        .into_iter()
+        .find(|name| params.parse_opt::<bool>(name).is_err())
+}
+
+/// 角色参数的值，调用前已由 [`invalid_role_param`] 检查
+fn role_param(params: &SubsonicParams, name: &str) -> Option<bool> {
+    params.parse_opt(name).ok().flatten()
+}
+
+/// 解析可重复的 `musicFolderId` 参数，传入时用户只能访问这些文件夹，未传时返回 `None`
+fn music_folder_params(params: &SubsonicParams) -> Result<Option<MusicFolderAccess>, ParamError> {
+    let ids = params.parse_all("musicFolderId")?;
+    Ok((!ids.is_empty()).then_some(MusicFolderAccess::Only(ids)))
+}
+
+fn invalid_param(params: &SubsonicParams, name: &str) -> Response {
+    param_error(params, ParamError::Invalid(name.to_string()))
+}
+
+fn user_error_response(params: &SubsonicParams, e: StorageError) -> Response {
+    match e {
+        StorageError::NotFound(_) => error_response(params, 70, "User not found"),
+        e => error_response(params, 0, &e.to_string()),
+    }
+}
+
+/// GET /rest/createUser - 创建用户，仅管理员可用
+///
+/// 未传的角色除 `settingsRole` 和 `streamRole` 外默认为 `false`；
+/// 未传 `musicFolderId` 时用户可以访问所有音乐文件夹
+pub async fn create_user_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    if let Some(response) = require_admin(&user, &params) {
+        return response;
+    }
+    let Some(username) = params
+        .get("username")
+        .map(|u| u.trim())
+        .filter(|u| !u.is_empty())
+    else {
+        return error_response(&params, 10, "Missing required parameter: username");
+    };
+    let password = match params.required("password") {
+        Ok(password) => decode_password(password),
+        Err(e) => return param_error(&params, e),
+    };
+    if password.is_empty() {
+        return error_response(&params, 10, "Password must not be empty");
+    }
+    let email = params.get("email").filter(|e| !e.is_empty());
+    if let Some(name) = invalid_role_param(&params) {
+        return invalid_param(&params, name);
+    }
+    let folders = match music_folder_params(&params) {
+        Ok(folders) => folders,
+        Err(e) => return param_error(&params, e),
+    };
+
+    let role = |name| role_param(&params, name);
+
+    match state.storage.get_user(username).await {
+        Ok(None) => {}
+        Ok(Some(_)) => return error_response(&params, 0, "User already exists"),
+        Err(e) => return error_response(&params, 0, &e.to_string()),
+    }
+    match state
+        .storage
+        .create_user(
+            username,
+            &password,
+            email,
+            role("adminRole").unwrap_or(false),
+            role("settingsRole").unwrap_or(true),
+            role("streamRole").unwrap_or(true),
+            role("jukeboxRole").unwrap_or(false),
+            role("downloadRole").unwrap_or(false),
+            role("uploadRole").unwrap_or(false),
+            role("playlistRole").unwrap_or(false),
+            role("coverArtRole").unwrap_or(false),
+            role("commentRole").unwrap_or(false),
+            role("podcastRole").unwrap_or(false),
+            role("shareRole").unwrap_or(false),
+            role("videoConversionRole").unwrap_or(false),
+            &folders.unwrap_or_default(),
+        )
+        .await
+    {
+        Ok(()) => ok_response(&params),
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
+
+/// GET /rest/updateUser - 修改用户，仅管理员可用
+///
+/// 只修改传入的参数，`password` 可为 `enc:` 前缀的十六进制编码
+pub async fn update_user_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    if let Some(response) = require_admin(&user, &params) {
+        return response;
+    }
+    let username = match params.required("username") {
+        Ok(username) => username,
+        Err(e) => return param_error(&params, e),
+    };
+    let password = params.get("password").map(decode_password);
+    if password.as_deref() == Some("") {
+        return error_response(&params, 10, "Password must not be empty");
+    }
+    let email = params.get("email");
+    if let Some(name) = invalid_role_param(&params) {
+        return invalid_param(&params, name);
+    }
+    let folders = match music_folder_params(&params) {
+        Ok(folders) => folders,
+        Err(e) => return param_error(&params, e),
+    };
+    let max_bit_rate = match params.get("maxBitRate").map(|b| b.parse::<i32>()) {
+        Some(Ok(rate)) if MAX_BIT_RATES.contains(&rate) => Some(rate),
+        Some(_) => return invalid_param(&params, "maxBitRate"),
+        None => None,
+    };
+    let role = |name| role_param(&params, name);
+
+    match state
+        .storage
+        .update_user(
+            username,
+            password.as_deref(),
+            email,
+            role("adminRole"),
+            role("settingsRole"),
+            role("streamRole"),
+            role("jukeboxRole"),
+            role("downloadRole"),
+            role("uploadRole"),
+            role("playlistRole"),
+            role("coverArtRole"),
+            role("commentRole"),
+            role("podcastRole"),
+            role("shareRole"),
+            role("videoConversionRole"),
+            folders.as_ref(),
+            max_bit_rate,
+        )
+        .await
+    {
+        Ok(()) => ok_response(&params),
+        Err(e) => user_error_response(&params, e),
+    }
+}
+
+/// GET /rest/deleteUser - 删除用户，仅管理员可用，不能删除自己
+pub async fn delete_user_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    if let Some(response) = require_admin(&user, &params) {
+        return response;
+    }
+    let username = match params.required("username") {
+        Ok(username) => username,
+        Err(e) => return param_error(&params, e),
+    };
+    if username == user.username {
+        return error_response(&params, 0, "Cannot delete the current user");
+    }
+
+    match state.storage.delete_user(username).await {
+        Ok(()) => ok_response(&params),
+        Err(e) => user_error_response(&params, e),
+    }
+}
+
 /// GET /rest/getScanStatus - 获取扫描状态
 pub async fn get_scan_status_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    params: SubsonicParams,
 ) -> Response {
     match state.storage.get_scan_status().await {
         Ok(status) => {
@@ -69,11 +317,19 @@ pub async fn get_scan_status_handler<S: SubsonicStorage + Clone>(
 }
 
 /// GET /rest/startScan - 开始媒体库扫描
+///
+/// 传入 `fullScan=true` 时强制重新解析所有文件
 pub async fn start_scan_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    match state.storage.start_scan().await {
+    if let Some(response) = require_admin(&user, &params) {
+        return response;
+    }
+    let full_scan = opt_param!(params, "fullScan").unwrap_or(false);
+
+    match state.storage.start_scan(full_scan).await {
         Ok(status) => {
             let data = ScanStatusData {
                 scan_status: ScanStatusItem::from(&status),
@@ -88,23 +344,29 @@ pub async fn start_scan_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/search2 - 搜索（基于文件夹）
 pub async fn search2_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let query = match params.get("query") {
-        Some(q) => q.as_str(),
-        None => return error_response(&params, 10, "Missing required parameter: query"),
+    let query = match params.required("query") {
+        Ok(q) => q,
+        Err(e) => return param_error(&params, e),
     };
 
-    let artist_count = params.get("artistCount").and_then(|s| s.parse().ok());
-    let artist_offset = params.get("artistOffset").and_then(|s| s.parse().ok());
-    let album_count = params.get("albumCount").and_then(|s| s.parse().ok());
-    let album_offset = params.get("albumOffset").and_then(|s| s.parse().ok());
-    let song_count = params.get("songCount").and_then(|s| s.parse().ok());
-    let song_offset = params.get("songOffset").and_then(|s| s.parse().ok());
+    let artist_count = opt_param!(params, "artistCount");
+    let artist_offset = opt_param!(params, "artistOffset");
+    let album_count = opt_param!(params, "albumCount");
+    let album_offset = opt_param!(params, "albumOffset");
+    let song_count = opt_param!(params, "songCount");
+    let song_offset = opt_param!(params, "songOffset");
+    let music_folders = match music_folder_scope(&user, &params) {
+        Ok(scope) => scope,
+        Err(e) => return error_response(&params, e.code(), &e.to_string()),
+    };
 
     match state
         .storage
         .search2(
+            &user,
             query,
             artist_count,
             artist_offset,
@@ -112,49 +374,58 @@ pub async fn search2_handler<S: SubsonicStorage + Clone>(
             album_offset,
             song_count,
             song_offset,
+            music_folders.as_deref(),
         )
         .await
     {
         Ok(result) => {
             // search2 使用 ArtistItem（非 ID3 版本）
-            let artists: Vec<ArtistItem> = result.artists.iter().map(|a| ArtistItem {
-                id: a.id.clone(),
-                name: a.name.clone(),
-                cover_art: a.cover_art.clone(),
-                artist_image_url: None,
-                starred: a.starred.map(|d| d.to_rfc3339()),
-                user_rating: a.user_rating,
-            }).collect();
-            
+            let artists: Vec<ArtistItem> = result
+                .artists
+                .iter()
+                .map(|a| ArtistItem {
+                    id: a.id.clone(),
+                    name: a.name.clone(),
+                    cover_art: a.cover_art.clone(),
+                    artist_image_url: None,
+                    starred: a.starred.map(|d| d.to_rfc3339()),
+                    user_rating: a.user_rating,
+                })
+                .collect();
+
             // albums 转换为 Child
-            let albums: Vec<Child> = result.albums.iter().map(|a| Child {
-                id: a.id.clone(),
-                parent: a.artist_id.clone(),
-                is_dir: true,
-                title: a.name.clone(),
-                album: Some(a.name.clone()),
-                artist: a.artist.clone(),
-                track: None,
-                year: a.year,
-                genre: a.genre.clone(),
-                cover_art: a.cover_art.clone(),
-                size: None,
-                content_type: None,
-                suffix: None,
-                duration: Some(a.duration as i32),
-                bit_rate: None,
-                path: None,
-                play_count: a.play_count,
-                disc_number: None,
-                created: a.created.map(|d| d.to_rfc3339()),
-                album_id: Some(a.id.clone()),
-                artist_id: a.artist_id.clone(),
-                starred: a.starred.map(|d| d.to_rfc3339()),
-                user_rating: a.user_rating,
-                media_type: Some("album".to_string()),
-                is_video: false,
-            }).collect();
-            
+            let albums: Vec<Child> = result
+                .albums
+                .iter()
+                .map(|a| Child {
+                    id: a.id.clone(),
+                    parent: a.artist_id.clone(),
+                    is_dir: true,
+                    title: a.name.clone(),
+                    album: Some(a.name.clone()),
+                    artist: a.artist.clone(),
+                    track: None,
+                    year: a.year,
+                    genre: a.genre.clone(),
+                    cover_art: a.cover_art.clone(),
+                    size: None,
+                    content_type: None,
+                    suffix: None,
+                    duration: Some(a.duration as i32),
+                    bit_rate: None,
+                    path: None,
+                    play_count: a.play_count,
+                    disc_number: None,
+                    created: a.created.map(|d| d.to_rfc3339()),
+                    album_id: Some(a.id.clone()),
+                    artist_id: a.artist_id.clone(),
+                    starred: a.starred.map(|d| d.to_rfc3339()),
+                    user_rating: a.user_rating,
+                    media_type: Some("album".to_string()),
+                    is_video: false,
+                })
+                .collect();
+
             let songs: Vec<Child> = result.songs.iter().map(Child::from).collect();
 
             let data = SearchResult2Data {
@@ -174,25 +445,18 @@ pub async fn search2_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/star - 收藏
 pub async fn star_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let ids: Vec<&str> = params
-        .iter()
-        .filter(|(k, _)| *k == "id")
-        .map(|(_, v)| v.as_str())
-        .collect();
-    let album_ids: Vec<&str> = params
-        .iter()
-        .filter(|(k, _)| *k == "albumId")
-        .map(|(_, v)| v.as_str())
-        .collect();
-    let artist_ids: Vec<&str> = params
-        .iter()
-        .filter(|(k, _)| *k == "artistId")
-        .map(|(_, v)| v.as_str())
-        .collect();
-
-    match state.storage.star(&ids, &album_ids, &artist_ids).await {
+    let ids = params.get_all("id");
+    let album_ids = params.get_all("albumId");
+    let artist_ids = params.get_all("artistId");
+
+    match state
+        .storage
+        .star(&user, &ids, &album_ids, &artist_ids)
+        .await
+    {
         Ok(()) => ok_response(&params),
         Err(e) => error_response(&params, 0, &e.to_string()),
     }
@@ -201,25 +465,18 @@ pub async fn star_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/unstar - 取消收藏
 pub async fn unstar_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let ids: Vec<&str> = params
-        .iter()
-        .filter(|(k, _)| *k == "id")
-        .map(|(_, v)| v.as_str())
-        .collect();
-    let album_ids: Vec<&str> = params
-        .iter()
-        .filter(|(k, _)| *k == "albumId")
-        .map(|(_, v)| v.as_str())
-        .collect();
-    let artist_ids: Vec<&str> = params
-        .iter()
-        .filter(|(k, _)| *k == "artistId")
-        .map(|(_, v)| v.as_str())
-        .collect();
-
-    match state.storage.unstar(&ids, &album_ids, &artist_ids).await {
+    let ids = params.get_all("id");
+    let album_ids = params.get_all("albumId");
+    let artist_ids = params.get_all("artistId");
+
+    match state
+        .storage
+        .unstar(&user, &ids, &album_ids, &artist_ids)
+        .await
+    {
         Ok(()) => ok_response(&params),
         Err(e) => error_response(&params, 0, &e.to_string()),
     }
@@ -228,18 +485,19 @@ pub async fn unstar_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/setRating - 设置评分
 pub async fn set_rating_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let id = match params.get("id") {
-        Some(id) => id,
-        None => return error_response(&params, 10, "Missing required parameter: id"),
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
     };
-    let rating = match params.get("rating").and_then(|s| s.parse().ok()) {
-        Some(r) => r,
-        None => return error_response(&params, 10, "Missing required parameter: rating"),
+    let rating = match params.parse_required("rating") {
+        Ok(rating) => rating,
+        Err(e) => return param_error(&params, e),
     };
 
-    match state.storage.set_rating(id, rating).await {
+    match state.storage.set_rating(&user, id, rating).await {
         Ok(()) => ok_response(&params),
         Err(e) => error_response(&params, 0, &e.to_string()),
     }
@@ -248,59 +506,145 @@ pub async fn set_rating_handler<S: SubsonicStorage + Clone>(
 /// GET /rest/scrobble - Scrobble
 pub async fn scrobble_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    let id = match params.get("id") {
-        Some(id) => id,
-        None => return error_response(&params, 10, "Missing required parameter: id"),
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
     };
-    let time = params.get("time").and_then(|s| s.parse().ok());
-    let submission = params.get("submission").and_then(|s| s.parse().ok()).unwrap_or(true);
+    let time = opt_param!(params, "time");
+    let submission = opt_param!(params, "submission").unwrap_or(true);
 
-    match state.storage.scrobble(id, time, submission).await {
+    let client = params.get("c");
+
+    match state
+        .storage
+        .scrobble(&user, id, time, submission, client)
+        .await
+    {
         Ok(()) => ok_response(&params),
+        Err(StorageError::NotFound(_)) => error_response(&params, 70, "Song not found"),
         Err(e) => error_response(&params, 0, &e.to_string()),
     }
 }
 
-/// GET /rest/download - 下载媒体文件
-pub async fn download_handler<S: SubsonicStorage + FileStorage + Clone>(
+/// GET /rest/getScrobbleAccounts - 获取当前用户的播放记录转发账号
+///
+/// 非 Subsonic 标准端点，响应中不包含令牌
+pub async fn get_scrobble_accounts_handler<S: SubsonicStorage + Clone>(
     State(state): State<SubsonicState<S>>,
-    Query(params): Query<HashMap<String, String>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
 ) -> Response {
-    use axum::http::{header, StatusCode};
+    match state.storage.get_scrobble_accounts(&user).await {
+        Ok(accounts) => {
+            let data = ScrobbleAccountsData {
+                scrobble_accounts: ScrobbleAccountsList {
+                    scrobble_account: accounts.iter().map(ScrobbleAccountItem::from).collect(),
+                },
+            };
+            let response = SubsonicResponse::ok_with(ResponseData::ScrobbleAccounts(data));
+            format_response(&params, response)
+        }
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
+
+/// GET /rest/updateScrobbleAccount - 设置当前用户在某个服务上的令牌
+///
+/// 非 Subsonic 标准端点。`service` 为 `listenbrainz` 或 `lastfm`（服务器配置了 Last.fm 应用密钥时），
+/// 可选的 `url` 指向兼容该协议的自建服务，只有管理员可以设置，默认只能指向公网地址
+pub async fn update_scrobble_account_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    let service = match params.required("service") {
+        Ok(service) => service,
+        Err(e) => return param_error(&params, e),
+    };
+    let token = match params.required("token") {
+        Ok(token) => token,
+        Err(e) => return param_error(&params, e),
+    };
+    let url = params.get("url").filter(|url| !url.is_empty());
+
+    match state
+        .storage
+        .update_scrobble_account(&user, service, token, url)
+        .await
+    {
+        Ok(()) => ok_response(&params),
+        Err(StorageError::NotFound(_)) => {
+            error_response(&params, 70, "Scrobble service not supported")
+        }
+        Err(StorageError::PermissionDenied(_)) => {
+            let e = AuthError::NotAuthorized;
+            error_response(&params, e.code(), &e.to_string())
+        }
+        Err(StorageError::InvalidInput(_)) => invalid_param(&params, "url"),
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
 
-    let id = match params.get("id") {
-        Some(id) => id,
-        None => return error_response(&params, 10, "Missing required parameter: id"),
+/// GET /rest/deleteScrobbleAccount - 删除当前用户在某个服务上的令牌
+///
+/// 非 Subsonic 标准端点，尚未发送的播放记录一并丢弃
+pub async fn delete_scrobble_account_handler<S: SubsonicStorage + Clone>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    params: SubsonicParams,
+) -> Response {
+    let service = match params.required("service") {
+        Ok(service) => service,
+        Err(e) => return param_error(&params, e),
     };
 
+    match state.storage.delete_scrobble_account(&user, service).await {
+        Ok(()) => ok_response(&params),
+        Err(e) => error_response(&params, 0, &e.to_string()),
+    }
+}
+
+/// GET /rest/download - 下载媒体文件
+pub async fn download_handler<S: SubsonicStorage + FileStorage + Clone + 'static>(
+    State(state): State<SubsonicState<S>>,
+    Extension(user): Extension<AuthContext>,
+    headers: axum::http::HeaderMap,
+    params: SubsonicParams,
+) -> Response {
+    let storage = state.storage.as_ref();
+    if let Some(response) = require_role(storage, &user, &params, |u| u.download_role).await {
+        return response;
+    }
+    let id = match params.required("id") {
+        Ok(id) => id,
+        Err(e) => return param_error(&params, e),
+    };
+    match accessible_song(storage, &user, id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return error_response(&params, 70, "Song not found"),
+        Err(e) => return error_response(&params, 0, &e.to_string()),
+    }
+
     match state.storage.get_stream_path(id).await {
         Ok(Some(path)) => {
-            match state.storage.read_file(&path).await {
-                Ok(data) => {
-                    // 获取文件名
-                    let filename = std::path::Path::new(&path)
-                        .file_name()
-                        .and_then(|s| s.to_str())
-                        .unwrap_or("download");
-
-                    Response::builder()
-                        .status(StatusCode::OK)
-                        .header(header::CONTENT_TYPE, "application/octet-stream")
-                        .header(
-                            header::CONTENT_DISPOSITION,
-                            format!("attachment; filename=\"{}\"", filename),
-                        )
-                        .header(header::CONTENT_LENGTH, data.len())
-                        .body(axum::body::Body::from(data))
-                        .unwrap()
-                }
-                Err(e) => Response::builder()
-                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .body(axum::body::Body::from(format!("Failed to read file: {}", e)))
-                    .unwrap(),
-            }
+            // 获取文件名
+            let filename = std::path::Path::new(&path)
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("download")
+                .to_string();
+
+            super::range::serve_file(
+                state.storage.clone(),
+                path,
+                &headers,
+                "application/octet-stream",
+                Some(&filename),
+            )
+            .await
         }
         Ok(None) => Response::builder()
             .status(axum::http::StatusCode::NOT_FOUND)
@@ -5,7 +5,10 @@
 //! 不同的 HTTP 服务器实现。
 use crate::error::Result;
 use async_trait::async_trait;
+use bytes::Bytes;
+use futures::Stream;
 use std::net::SocketAddr;
+use std::pin::Pin;
 
 /// HTTP 服务器实现的 trait
 #[async_trait]
@@ -90,22 +93,46 @@ impl Response {
     }
 }
 
-/// 流媒体文件的 trait
+/// 媒体数据的字节流
+pub type MediaStream = Pin<Box<dyn Stream<Item = std::io::Result<Bytes>> + Send>>;
+
+/// 转码的输入
+pub enum TranscodeInput {
+    /// 编码器可直接随机访问的文件
+    Location(reverie_storage::VfsLocation),
+    /// 顺序读取的源文件内容及其扩展名
+    Stream { data: MediaStream, suffix: String },
+}
+
+/// 转码参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscodeOptions {
+    /// 目标格式（如 `mp3`、`opus`、`aac`）
+    pub format: String,
+    /// 目标比特率 (kbps)，为 `None` 时使用该格式的默认值
+    pub bit_rate: Option<u32>,
+    /// 起始播放位置（秒）
+    pub time_offset: Option<u32>,
+}
+
+/// 流媒体转码的 trait
 #[async_trait]
 pub trait MediaStreamer: Send + Sync {
-    /// 将文件流式传输到客户端
-    async fn stream_file(&self, path: &str) -> Result<Vec<u8>>;
-
     /// 检查是否支持转码
     fn supports_transcoding(&self) -> bool;
 
-    /// 将文件转码为不同格式
-    async fn transcode_file(
-        &self,
-        path: &str,
-        format: &str,
-        bitrate: Option<u32>,
-    ) -> Result<Vec<u8>>;
+    /// 检查是否支持转码到指定格式
+    fn supports_format(&self, format: &str) -> bool;
+
+    /// 未指定格式时使用的目标格式
+    fn default_format(&self) -> &str;
+
+    /// 目标格式对应的 MIME 类型
+    fn content_type(&self, format: &str) -> Option<&str>;
+
+    /// 将输入转码为目标格式，输出在编码过程中逐块产出
+    async fn transcode(&self, input: TranscodeInput, options: &TranscodeOptions)
+        -> Result<MediaStream>;
 }
 
 /// 外部网络连接的 trait（例如，用于联合、云同步）
@@ -0,0 +1,760 @@
+//! 转码结果缓存
+//!
+//! 转码输出按 (曲目 ID, 源文件修改时间, 格式, 比特率) 存放在 VFS 的 `.cache/transcode/` 下，
+//! 总大小超过上限时淘汰最久未使用的条目，正在被读取的条目不会被淘汰。
+//!
+//! 同一键的编码进行中时，后续请求共享正在运行的编码输出，而不会再启动一个编码器。
+//! 编码输出先写入本地临时文件，各个读取者从临时文件读取已产出的部分，内存占用与输出大小无关。
+//! 所有读取者都已断开且无法写入缓存时停止编码。
+
+use bytes::Bytes;
+use futures::StreamExt;
+use reverie_storage::SharedVfs;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::watch;
+use tokio_util::io::ReaderStream;
+
+use crate::error::Result;
+use crate::traits::MediaStream;
+
+/// 缓存文件所在目录
+pub const CACHE_DIR: &str = ".cache/transcode/";
+
+/// 写入中的缓存文件后缀，完成后重命名为正式文件
+const PARTIAL_SUFFIX: &str = ".part";
+
+/// 读取者每次从临时文件读取的最大字节数
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// 转码缓存键
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub track_id: String,
+    /// 源文件修改时间（Unix 秒）
+    pub mtime: i64,
+    pub format: String,
+    /// 目标比特率 (kbps)，0 表示该格式的默认比特率
+    pub bit_rate: u32,
+}
+
+impl CacheKey {
+    /// 缓存文件在 VFS 中的路径
+    fn path(&self) -> String {
+        let digest = md5::compute(format!(
+            "{}\0{}\0{}\0{}",
+            self.track_id, self.mtime, self.format, self.bit_rate
+        ));
+        let extension: String = self
+            .format
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+        format!("{}{:x}.{}", CACHE_DIR, digest, extension)
+    }
+}
+
+struct CacheEntry {
+    size: u64,
+    last_used: u64,
+}
+
+/// 缓存索引：已完成的条目与进行中的编码
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, CacheEntry>,
+    total_size: u64,
+    clock: u64,
+    pending: HashMap<String, Arc<Tee>>,
+    /// 正在被读取的条目及其读取者数量
+    readers: HashMap<String, usize>,
+}
+
+impl CacheState {
+    /// 标记条目被访问，条目不存在时返回 false
+    fn touch(&mut self, path: &str) -> bool {
+        self.clock += 1;
+        match self.entries.get_mut(path) {
+            Some(entry) => {
+                entry.last_used = self.clock;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, path: String, size: u64) {
+        self.clock += 1;
+        let entry = CacheEntry {
+            size,
+            last_used: self.clock,
+        };
+        if let Some(old) = self.entries.insert(path, entry) {
+            self.total_size -= old.size;
+        }
+        self.total_size += size;
+    }
+
+    fn remove(&mut self, path: &str) {
+        if let Some(old) = self.entries.remove(path) {
+            self.total_size -= old.size;
+        }
+    }
+
+    fn pin(&mut self, path: &str) {
+        *self.readers.entry(path.to_string()).or_default() += 1;
+    }
+
+    fn unpin(&mut self, path: &str) {
+        if let Some(count) = self.readers.get_mut(path) {
+            *count -= 1;
+            if *count == 0 {
+                self.readers.remove(path);
+            }
+        }
+    }
+
+    /// 淘汰最久未使用的条目直到总大小不超过上限，返回需要删除的文件
+    ///
+    /// 正在被读取的条目跳过，只剩这些条目时总大小可能暂时超过上限
+    fn evict(&mut self, max_size: u64) -> Vec<String> {
+        let mut victims = Vec::new();
+        while self.total_size > max_size {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .filter(|(path, _)| !self.readers.contains_key(*path))
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+            victims.push(oldest);
+        }
+        victims
+    }
+}
+
+/// 读取缓存文件期间固定条目，丢弃时解除
+struct ReaderPin {
+    state: Arc<Mutex<CacheState>>,
+    path: String,
+}
+
+impl Drop for ReaderPin {
+    fn drop(&mut self) {
+        self.state.lock().unwrap().unpin(&self.path);
+    }
+}
+
+/// 进行中的编码输出，供多个请求同时读取
+struct Tee {
+    /// 编码输出的本地副本，最后一个读取者结束后删除
+    spool: tempfile::TempPath,
+    /// 编码结果，结束前为 `None`
+    done: Mutex<Option<std::result::Result<(), String>>>,
+    /// 已写入临时文件的字节数；每个读取者持有一个接收端
+    progress: watch::Sender<u64>,
+}
+
+impl Tee {
+    /// 创建临时文件，返回共享状态和写入端
+    fn new() -> std::io::Result<(Arc<Self>, tokio::fs::File)> {
+        let (file, spool) = tempfile::NamedTempFile::new()?.into_parts();
+        let tee = Arc::new(Self {
+            spool,
+            done: Mutex::new(None),
+            progress: watch::channel(0).0,
+        });
+        Ok((tee, tokio::fs::File::from_std(file)))
+    }
+
+    fn advance(&self, len: u64) {
+        self.progress.send_modify(|n| *n += len);
+    }
+
+    fn finish(&self, result: std::result::Result<(), String>) {
+        *self.done.lock().unwrap() = Some(result);
+        self.progress.send_modify(|_| {});
+    }
+
+    /// 仍在读取编码输出的请求数
+    fn subscribers(&self) -> usize {
+        self.progress.receiver_count()
+    }
+
+    /// 从头开始读取编码输出，并跟随后续产出的数据
+    fn subscribe(self: &Arc<Self>) -> MediaStream {
+        struct Reader {
+            tee: Arc<Tee>,
+            progress: watch::Receiver<u64>,
+            file: Option<tokio::fs::File>,
+            offset: u64,
+        }
+
+        let reader = Reader {
+            tee: self.clone(),
+            progress: self.progress.subscribe(),
+            file: None,
+            offset: 0,
+        };
+        let stream = futures::stream::unfold(Some(reader), |reader| async move {
+            let mut r = reader?;
+            loop {
+                let written = *r.progress.borrow_and_update();
+                if r.offset < written {
+                    let file = match r.file.as_mut() {
+                        Some(file) => file,
+                        None => match tokio::fs::File::open(&r.tee.spool).await {
+                            Ok(file) => r.file.insert(file),
+                            Err(e) => return Some((Err(e), None)),
+                        },
+                    };
+                    let len = (written - r.offset).min(READ_CHUNK_SIZE as u64) as usize;
+                    let mut buf = vec![0; len];
+                    return match file.read(&mut buf).await {
+                        Ok(0) => Some((Err(std::io::ErrorKind::UnexpectedEof.into()), None)),
+                        Ok(n) => {
+                            buf.truncate(n);
+                            r.offset += n as u64;
+                            Some((Ok(Bytes::from(buf)), Some(r)))
+                        }
+                        Err(e) => Some((Err(e), None)),
+                    };
+                }
+                match r.tee.done.lock().unwrap().clone() {
+                    Some(Ok(())) => return None,
+                    Some(Err(e)) => return Some((Err(std::io::Error::other(e)), None)),
+                    None => {}
+                }
+                if r.progress.changed().await.is_err() {
+                    return None;
+                }
+            }
+        });
+        Box::pin(stream)
+    }
+}
+
+/// 基于 VFS 的转码结果缓存
+#[derive(Clone)]
+pub struct TranscodeCache {
+    vfs: SharedVfs,
+    max_size: u64,
+    state: Arc<Mutex<CacheState>>,
+}
+
+impl TranscodeCache {
+    /// 打开缓存，载入已有的缓存文件并清理上次未完成的写入
+    pub async fn open(vfs: SharedVfs, max_size: u64) -> Result<Self> {
+        let mut files: Vec<_> = vfs
+            .list(CACHE_DIR)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|e| e.metadata.is_file)
+            .collect();
+        // 以修改时间近似上次使用时间
+        files.sort_by_key(|e| e.metadata.last_modified);
+
+        let mut state = CacheState::default();
+        for file in files {
+            if file.path.ends_with(PARTIAL_SUFFIX) {
+                let _ = vfs.delete(&file.path).await;
+            } else {
+                // 部分后端的列表结果不包含文件大小
+                let size = match file.metadata.size {
+                    0 => vfs.stat(&file.path).await.map(|m| m.size).unwrap_or(0),
+                    size => size,
+                };
+                state.insert(file.path, size);
+            }
+        }
+        for victim in state.evict(max_size) {
+            let _ = vfs.delete(&victim).await;
+        }
+
+        Ok(Self {
+            vfs,
+            max_size,
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    /// 缓存上限（字节）
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    /// 当前缓存的总大小（字节）
+    pub fn size(&self) -> u64 {
+        self.state.lock().unwrap().total_size
+    }
+
+    /// 返回缓存的转码结果；未命中时调用 `encode` 启动编码，并在编码的同时写入缓存
+    pub async fn get_or_encode<F, Fut>(&self, key: &CacheKey, encode: F) -> Result<MediaStream>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<MediaStream>>,
+    {
+        enum Lookup {
+            Hit(ReaderPin),
+            Pending(MediaStream),
+            Miss(Arc<Tee>, tokio::fs::File),
+            Uncached,
+        }
+
+        let path = key.path();
+        loop {
+            let lookup = {
+                let mut state = self.state.lock().unwrap();
+                if state.touch(&path) {
+                    state.pin(&path);
+                    Lookup::Hit(ReaderPin {
+                        state: self.state.clone(),
+                        path: path.clone(),
+                    })
+                } else if let Some(tee) = state.pending.get(&path) {
+                    // 在锁内订阅，编码任务不会在此期间因无人读取而停止
+                    Lookup::Pending(tee.subscribe())
+                } else {
+                    match Tee::new() {
+                        Ok((tee, spool)) => {
+                            state.pending.insert(path.clone(), tee.clone());
+                            Lookup::Miss(tee, spool)
+                        }
+                        Err(e) => {
+                            tracing::warn!("Failed to create transcode spool file: {}", e);
+                            Lookup::Uncached
+                        }
+                    }
+                }
+            };
+
+            match lookup {
+                Lookup::Hit(pin) => match self.vfs.reader(&path).await {
+                    Ok(reader) => {
+                        // 流被丢弃时随之解除固定
+                        let stream = ReaderStream::new(reader).map(move |chunk| {
+                            let _ = &pin;
+                            chunk
+                        });
+                        return Ok(Box::pin(stream));
+                    }
+                    Err(e) => {
+                        // 缓存文件被外部删除，重新编码
+                        tracing::warn!("Transcode cache entry {} unreadable: {}", path, e);
+                        drop(pin);
+                        self.state.lock().unwrap().remove(&path);
+                    }
+                },
+                Lookup::Pending(subscriber) => return Ok(subscriber),
+                Lookup::Miss(tee, spool) => {
+                    let output = match encode().await {
+                        Ok(output) => output,
+                        Err(e) => {
+                            self.state.lock().unwrap().pending.remove(&path);
+                            tee.finish(Err(e.to_string()));
+                            return Err(e);
+                        }
+                    };
+                    let subscriber = tee.subscribe();
+                    tokio::spawn(self.clone().fill(path, tee, spool, output));
+                    return Ok(subscriber);
+                }
+                Lookup::Uncached => return encode().await,
+            }
+        }
+    }
+
+    /// 驱动编码输出：写入临时文件供所有读取者读取，同时写入缓存文件
+    async fn fill(
+        self,
+        path: String,
+        tee: Arc<Tee>,
+        mut spool: tokio::fs::File,
+        mut output: MediaStream,
+    ) {
+        let partial = format!("{}{}", path, PARTIAL_SUFFIX);
+        let mut writer = match self.vfs.writer(&partial).await {
+            Ok(writer) => Some(writer),
+            Err(e) => {
+                tracing::warn!("Failed to open transcode cache file {}: {}", partial, e);
+                None
+            }
+        };
+
+        let mut size = 0u64;
+        let mut failure = None;
+        while let Some(chunk) = output.next().await {
+            match chunk {
+                Ok(data) => {
+                    size += data.len() as u64;
+                    let spooled = match spool.write_all(&data).await {
+                        Ok(()) => spool.flush().await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = spooled {
+                        failure = Some(format!("failed to write transcode spool file: {}", e));
+                        break;
+                    }
+                    tee.advance(data.len() as u64);
+                    if let Some(w) = writer.as_mut() {
+                        if let Err(e) = w.write(data).await {
+                            tracing::warn!(
+                                "Failed to write transcode cache file {}: {}",
+                                partial,
+                                e
+                            );
+                            let _ = w.abort().await;
+                            writer = None;
+                        }
+                    }
+                    if writer.is_none() {
+                        // 输出既无人读取也无法缓存时停止编码；在锁内检查，避免与新的订阅竞争
+                        let mut state = self.state.lock().unwrap();
+                        if tee.subscribers() == 0 {
+                            state.pending.remove(&path);
+                            failure = Some("transcode abandoned".to_string());
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    failure = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+        // 丢弃编码输出以结束编码进程
+        drop(output);
+
+        let cached = match writer {
+            Some(mut w) if failure.is_none() => {
+                w.close().await.is_ok() && self.commit(&partial, &path).await
+            }
+            Some(mut w) => {
+                let _ = w.abort().await;
+                false
+            }
+            None => false,
+        };
+        if !cached {
+            let _ = self.vfs.delete(&partial).await;
+        }
+
+        let victims = {
+            let mut state = self.state.lock().unwrap();
+            state.pending.remove(&path);
+            if cached {
+                state.insert(path, size);
+            }
+            state.evict(self.max_size)
+        };
+        for victim in victims {
+            if let Err(e) = self.vfs.delete(&victim).await {
+                tracing::warn!("Failed to evict transcode cache file {}: {}", victim, e);
+            }
+        }
+
+        tee.finish(failure.map_or(Ok(()), Err));
+    }
+
+    /// 将写完的临时文件转为正式缓存文件；后端不支持重命名时改为复制
+    async fn commit(&self, partial: &str, path: &str) -> bool {
+        if self.vfs.rename(partial, path).await.is_ok() {
+            return true;
+        }
+        match self.vfs.read(partial).await {
+            Ok(data) => {
+                let written = self.vfs.write(path, data).await.is_ok();
+                let _ = self.vfs.delete(partial).await;
+                written
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reverie_storage::{OpendalVfs, VfsConfig};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn memory_vfs() -> SharedVfs {
+        Arc::new(OpendalVfs::new(VfsConfig::memory()).unwrap())
+    }
+
+    fn key(track_id: &str) -> CacheKey {
+        CacheKey {
+            track_id: track_id.to_string(),
+            mtime: 1_700_000_000,
+            format: "mp3".to_string(),
+            bit_rate: 128,
+        }
+    }
+
+    fn output_of(data: &'static [u8]) -> MediaStream {
+        Box::pin(futures::stream::iter(
+            data.chunks(4)
+                .map(|c| Ok(Bytes::from_static(c)))
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    async fn collect(mut stream: MediaStream) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            out.extend_from_slice(&chunk?);
+        }
+        Ok(out)
+    }
+
+    /// 等待后台写入完成
+    async fn settle(cache: &TranscodeCache) {
+        for _ in 0..100 {
+            if cache.state.lock().unwrap().pending.is_empty() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("transcode cache fill did not finish");
+    }
+
+    #[test]
+    fn test_cache_key_path() {
+        let base = key("track-1");
+        assert!(base.path().starts_with(CACHE_DIR));
+        assert!(base.path().ends_with(".mp3"));
+
+        // 任一字段变化都会得到不同的缓存文件
+        let variants = [
+            CacheKey {
+                track_id: "track-2".to_string(),
+                ..base.clone()
+            },
+            CacheKey {
+                mtime: 1,
+                ..base.clone()
+            },
+            CacheKey {
+                format: "opus".to_string(),
+                ..base.clone()
+            },
+            CacheKey {
+                bit_rate: 64,
+                ..base.clone()
+            },
+        ];
+        for variant in variants {
+            assert_ne!(variant.path(), base.path());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit_skips_encoder() {
+        let vfs = memory_vfs();
+        let cache = TranscodeCache::open(vfs.clone(), 1024).await.unwrap();
+        let encodes = AtomicUsize::new(0);
+
+        for _ in 0..2 {
+            let stream = cache
+                .get_or_encode(&key("t1"), || async {
+                    encodes.fetch_add(1, Ordering::SeqCst);
+                    Ok(output_of(b"encoded audio"))
+                })
+                .await
+                .unwrap();
+            assert_eq!(collect(stream).await.unwrap(), b"encoded audio");
+            settle(&cache).await;
+        }
+
+        assert_eq!(encodes.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.size(), 13);
+        assert_eq!(
+            &vfs.read(&key("t1").path()).await.unwrap()[..],
+            b"encoded audio"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_share_encode() {
+        let cache = TranscodeCache::open(memory_vfs(), 1024).await.unwrap();
+        let (tx, rx) = futures::channel::mpsc::unbounded::<std::io::Result<Bytes>>();
+
+        let first = cache
+            .get_or_encode(
+                &key("t1"),
+                || async move { Ok(Box::pin(rx) as MediaStream) },
+            )
+            .await
+            .unwrap();
+        tx.unbounded_send(Ok(Bytes::from_static(b"abc"))).unwrap();
+
+        // 编码尚未结束，第二个请求不应再次编码
+        let second = cache
+            .get_or_encode(&key("t1"), || async {
+                panic!("second request must not start another encode")
+            })
+            .await
+            .unwrap();
+        tx.unbounded_send(Ok(Bytes::from_static(b"def"))).unwrap();
+        drop(tx);
+
+        assert_eq!(collect(first).await.unwrap(), b"abcdef");
+        assert_eq!(collect(second).await.unwrap(), b"abcdef");
+        settle(&cache).await;
+        assert_eq!(cache.size(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_lru_eviction() {
+        let vfs = memory_vfs();
+        let cache = TranscodeCache::open(vfs.clone(), 20).await.unwrap();
+
+        for id in ["a", "b"] {
+            let stream = cache
+                .get_or_encode(&key(id), || async { Ok(output_of(b"0123456789")) })
+                .await
+                .unwrap();
+            collect(stream).await.unwrap();
+            settle(&cache).await;
+        }
+
+        // 访问 a 后再写入 c，应淘汰最久未使用的 b
+        let stream = cache
+            .get_or_encode(&key("a"), || async { panic!("a should be cached") })
+            .await
+            .unwrap();
+        collect(stream).await.unwrap();
+        let stream = cache
+            .get_or_encode(&key("c"), || async { Ok(output_of(b"0123456789")) })
+            .await
+            .unwrap();
+        collect(stream).await.unwrap();
+        settle(&cache).await;
+
+        assert_eq!(cache.size(), 20);
+        assert!(vfs.exists(&key("a").path()).await.unwrap());
+        assert!(!vfs.exists(&key("b").path()).await.unwrap());
+        assert!(vfs.exists(&key("c").path()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_eviction_skips_entries_being_read() {
+        let vfs = memory_vfs();
+        let cache = TranscodeCache::open(vfs.clone(), 20).await.unwrap();
+
+        for id in ["a", "b"] {
+            let stream = cache
+                .get_or_encode(&key(id), || async { Ok(output_of(b"0123456789")) })
+                .await
+                .unwrap();
+            collect(stream).await.unwrap();
+            settle(&cache).await;
+        }
+
+        // 打开 a 的读取流后访问 b，a 成为最久未使用但仍在被读取的条目
+        let reading = cache
+            .get_or_encode(&key("a"), || async { panic!("a should be cached") })
+            .await
+            .unwrap();
+        let stream = cache
+            .get_or_encode(&key("b"), || async { panic!("b should be cached") })
+            .await
+            .unwrap();
+        collect(stream).await.unwrap();
+        let stream = cache
+            .get_or_encode(&key("c"), || async { Ok(output_of(b"0123456789")) })
+            .await
+            .unwrap();
+        collect(stream).await.unwrap();
+        settle(&cache).await;
+
+        assert!(vfs.exists(&key("a").path()).await.unwrap());
+        assert!(!vfs.exists(&key("b").path()).await.unwrap());
+        assert_eq!(collect(reading).await.unwrap(), b"0123456789");
+        assert!(cache.state.lock().unwrap().readers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_encode_stops_without_cache_writer() {
+        // 无法在 /proc 下创建缓存文件，编码输出只能直接交给读取者
+        let vfs: SharedVfs = Arc::new(OpendalVfs::new(VfsConfig::local("/proc")).unwrap());
+        let cache = TranscodeCache::open(vfs, 1024).await.unwrap();
+        let (tx, rx) = futures::channel::mpsc::unbounded::<std::io::Result<Bytes>>();
+
+        let stream = cache
+            .get_or_encode(
+                &key("t1"),
+                || async move { Ok(Box::pin(rx) as MediaStream) },
+            )
+            .await
+            .unwrap();
+        tx.unbounded_send(Ok(Bytes::from_static(b"abc"))).unwrap();
+        drop(stream);
+        tx.unbounded_send(Ok(Bytes::from_static(b"def"))).unwrap();
+        settle(&cache).await;
+
+        // 编码任务已丢弃编码输出
+        assert!(tx.is_closed());
+        assert_eq!(cache.size(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_encode_is_not_cached() {
+        let vfs = memory_vfs();
+        let cache = TranscodeCache::open(vfs.clone(), 1024).await.unwrap();
+
+        let failing: MediaStream = Box::pin(futures::stream::iter(vec![
+            Ok(Bytes::from_static(b"partial")),
+            Err(std::io::Error::other("encoder crashed")),
+        ]));
+        let stream = cache
+            .get_or_encode(&key("t1"), || async { Ok(failing) })
+            .await
+            .unwrap();
+        assert!(collect(stream).await.is_err());
+        settle(&cache).await;
+
+        assert_eq!(cache.size(), 0);
+        assert!(!vfs.exists(&key("t1").path()).await.unwrap());
+        assert!(vfs
+            .list(CACHE_DIR)
+            .await
+            .unwrap_or_default()
+            .iter()
+            .all(|e| !e.metadata.is_file));
+    }
+
+    #[tokio::test]
+    async fn test_open_loads_existing_entries() {
+        let vfs = memory_vfs();
+        vfs.write(&key("a").path(), Bytes::from_static(b"0123456789"))
+            .await
+            .unwrap();
+        vfs.write(
+            &format!("{}{}", key("b").path(), PARTIAL_SUFFIX),
+            Bytes::from_static(b"incomplete"),
+        )
+        .await
+        .unwrap();
+
+        let cache = TranscodeCache::open(vfs.clone(), 1024).await.unwrap();
+        assert_eq!(cache.size(), 10);
+        assert!(!vfs
+            .exists(&format!("{}{}", key("b").path(), PARTIAL_SUFFIX))
+            .await
+            .unwrap());
+
+        let stream = cache
+            .get_or_encode(&key("a"), || async { panic!("a should be cached") })
+            .await
+            .unwrap();
+        assert_eq!(collect(stream).await.unwrap(), b"0123456789");
+    }
+}
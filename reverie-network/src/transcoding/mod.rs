@@ -0,0 +1,601 @@
+//! 基于外部编码器的实时转码
+//!
+//! 通过可配置的命令模板启动编码器（默认为 ffmpeg），编码结果从 stdout 逐块读出并直接作为响应体，
+//! 不会在内存中缓冲整首曲目。
+//!
+//! 存储后端能提供本地路径或预签名 URL 时，编码器直接读取源文件，可以随机访问；否则原始音频经
+//! stdin 输入。MP4 系列容器的 `moov` 可能位于文件末尾，无法从管道解码，这类输入先写入临时文件。
+//!
+//! 命令模板中的占位符：
+//! - `%s` 输入（`file:` 路径、URL 或 `pipe:0`）
+//! - `%b` 目标比特率 (kbps)
+//! - `%t` 起始位置（秒），用于 OpenSubsonic `transcodeOffset` 扩展
+
+pub mod cache;
+
+pub use cache::{CacheKey, TranscodeCache};
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use reverie_storage::VfsLocation;
+use std::collections::HashMap;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, ChildStdout, Command};
+use tokio_util::io::ReaderStream;
+
+use crate::error::{NetworkError, Result};
+use crate::traits::{MediaStream, MediaStreamer, TranscodeInput, TranscodeOptions};
+
+/// 需要可随机访问输入的容器格式（MP4 系列）
+const SEEKABLE_INPUT_SUFFIXES: &[&str] = &["m4a", "m4b", "m4p", "m4v", "mp4", "mov", "3gp"];
+
+/// 单个目标格式的转码配置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscodeProfile {
+    /// 编码器命令模板
+    pub command: String,
+    /// 输出的 MIME 类型
+    pub content_type: String,
+    /// 未指定比特率时使用的比特率 (kbps)
+    pub default_bit_rate: u32,
+}
+
+/// 转码子系统配置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscodingConfig {
+    /// 按目标格式索引的转码配置
+    pub profiles: HashMap<String, TranscodeProfile>,
+    /// 仅因比特率限制而转码时使用的格式
+    pub default_format: String,
+}
+
+impl Default for TranscodingConfig {
+    fn default() -> Self {
+        let profile = |command: &str, content_type: &str, default_bit_rate| TranscodeProfile {
+            command: command.to_string(),
+            content_type: content_type.to_string(),
+            default_bit_rate,
+        };
+
+        let mut profiles = HashMap::new();
+        profiles.insert(
+            "mp3".to_string(),
+            profile(
+                "ffmpeg -v 0 -ss %t -i %s -map 0:a:0 -b:a %bk -f mp3 -",
+                "audio/mpeg",
+                192,
+            ),
+        );
+        profiles.insert(
+            "opus".to_string(),
+            profile(
+                "ffmpeg -v 0 -ss %t -i %s -map 0:a:0 -c:a libopus -b:a %bk -f opus -",
+                "audio/ogg",
+                128,
+            ),
+        );
+        profiles.insert(
+            "aac".to_string(),
+            profile(
+                "ffmpeg -v 0 -ss %t -i %s -map 0:a:0 -c:a aac -b:a %bk -f adts -",
+                "audio/aac",
+                192,
+            ),
+        );
+
+        Self {
+            profiles,
+            default_format: "mp3".to_string(),
+        }
+    }
+}
+
+/// 启动外部编码器进程的转码器
+#[derive(Debug, Clone)]
+pub struct FfmpegTranscoder {
+    config: TranscodingConfig,
+}
+
+impl FfmpegTranscoder {
+    pub fn new(config: TranscodingConfig) -> Self {
+        Self { config }
+    }
+
+    /// 展开命令模板，返回程序名与参数
+    fn build_command(
+        &self,
+        profile: &TranscodeProfile,
+        input: &str,
+        options: &TranscodeOptions,
+    ) -> Result<(String, Vec<String>)> {
+        let bit_rate = options
+            .bit_rate
+            .unwrap_or(profile.default_bit_rate)
+            .to_string();
+        let offset = options.time_offset.unwrap_or(0).to_string();
+
+        let mut args = profile.command.split_whitespace().map(|token| {
+            expand_placeholders(token, |key| match key {
+                's' => Some(input),
+                'b' => Some(bit_rate.as_str()),
+                't' => Some(offset.as_str()),
+                _ => None,
+            })
+        });
+        let program = args.next().ok_or_else(|| {
+            NetworkError::Internal(format!("Empty transcode command for {}", options.format))
+        })?;
+        Ok((program, args.collect()))
+    }
+}
+
+/// 单次扫描展开模板参数中的占位符，替换结果不会被再次展开；未知占位符原样保留
+fn expand_placeholders<'a>(token: &str, value: impl Fn(char) -> Option<&'a str>) -> String {
+    let mut expanded = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(key) => match value(key) {
+                Some(v) => expanded.push_str(v),
+                None => {
+                    expanded.push('%');
+                    expanded.push(key);
+                }
+            },
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+#[async_trait]
+impl MediaStreamer for FfmpegTranscoder {
+    fn supports_transcoding(&self) -> bool {
+        !self.config.profiles.is_empty()
+    }
+
+    fn supports_format(&self, format: &str) -> bool {
+        self.config.profiles.contains_key(format)
+    }
+
+    fn default_format(&self) -> &str {
+        &self.config.default_format
+    }
+
+    fn content_type(&self, format: &str) -> Option<&str> {
+        self.config
+            .profiles
+            .get(format)
+            .map(|p| p.content_type.as_str())
+    }
+
+    async fn transcode(
+        &self,
+        input: TranscodeInput,
+        options: &TranscodeOptions,
+    ) -> Result<MediaStream> {
+        let profile = self.config.profiles.get(&options.format).ok_or_else(|| {
+            NetworkError::InvalidRequest(format!(
+                "Unsupported transcode format: {}",
+                options.format
+            ))
+        })?;
+
+        let (source, spool, piped) = match input {
+            TranscodeInput::Location(VfsLocation::Local(path)) => {
+                (format!("file:{}", path.display()), None, None)
+            }
+            TranscodeInput::Location(VfsLocation::Url(url)) => (url, None, None),
+            TranscodeInput::Stream { data, suffix } if needs_seekable_input(&suffix) => {
+                let spool = spool_input(data, &suffix).await?;
+                (format!("file:{}", spool.display()), Some(spool), None)
+            }
+            TranscodeInput::Stream { data, .. } => ("pipe:0".to_string(), None, Some(data)),
+        };
+        let (program, args) = self.build_command(profile, &source, options)?;
+
+        let mut child = Command::new(&program)
+            .args(&args)
+            .stdin(if piped.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            // 客户端断开后响应流被丢弃，编码器随之终止
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| NetworkError::Internal(format!("Failed to start {}: {}", program, e)))?;
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let Some(mut input) = piped else {
+            return Ok(encoder_output(child, stdout, spool));
+        };
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+
+        tokio::spawn(async move {
+            while let Some(chunk) = input.next().await {
+                match chunk {
+                    Ok(data) => {
+                        // 编码器提前退出时写入会失败，此时停止输入即可
+                        if stdin.write_all(&data).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to read transcode input: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(encoder_output(child, stdout, None))
+    }
+}
+
+/// 源文件是否需要以可随机访问的方式交给编码器
+fn needs_seekable_input(suffix: &str) -> bool {
+    SEEKABLE_INPUT_SUFFIXES
+        .iter()
+        .any(|s| s.eq_ignore_ascii_case(suffix))
+}
+
+/// 将输入流写入临时文件，返回的路径在丢弃时删除
+async fn spool_input(mut input: MediaStream, suffix: &str) -> Result<tempfile::TempPath> {
+    let spool_error = |e: std::io::Error| {
+        NetworkError::Internal(format!("Failed to spool transcode input: {}", e))
+    };
+
+    let (file, path) = tempfile::Builder::new()
+        .prefix("reverie-transcode-")
+        .suffix(&format!(".{}", suffix))
+        .tempfile()
+        .map_err(spool_error)?
+        .into_parts();
+    let mut file = tokio::fs::File::from_std(file);
+    while let Some(chunk) = input.next().await {
+        file.write_all(&chunk.map_err(spool_error)?)
+            .await
+            .map_err(spool_error)?;
+    }
+    file.flush().await.map_err(spool_error)?;
+    Ok(path)
+}
+
+/// 将编码器的 stdout 包装为字节流，结束时检查退出状态；临时输入文件在编码器退出后删除
+fn encoder_output(
+    child: Child,
+    stdout: ChildStdout,
+    spool: Option<tempfile::TempPath>,
+) -> MediaStream {
+    let state = Some((child, ReaderStream::new(stdout), spool));
+    let stream = futures::stream::unfold(state, |state| async move {
+        let (mut child, mut stdout, spool) = state?;
+        match stdout.next().await {
+            Some(Ok(data)) => Some((Ok(data), Some((child, stdout, spool)))),
+            Some(Err(e)) => Some((Err(e), None)),
+            None => match child.wait().await {
+                Ok(status) if status.success() => None,
+                Ok(status) => Some((
+                    Err(std::io::Error::other(format!(
+                        "Encoder exited with {}",
+                        status
+                    ))),
+                    None,
+                )),
+                Err(e) => Some((Err(e), None)),
+            },
+        }
+    });
+    Box::pin(stream)
+}
+
+/// 根据 Subsonic `stream` 参数决定是否转码
+///
+/// - `format=raw` 或转码器不可用时始终返回原始文件
+/// - 指定了支持的格式时转码，除非与源格式相同且无需降低比特率或跳转
+/// - 未指定格式但比特率上限低于源文件时，转码为默认格式
+pub fn select_transcode(
+    streamer: &dyn MediaStreamer,
+    source_suffix: &str,
+    source_bit_rate: i32,
+    format: Option<&str>,
+    max_bit_rate: Option<u32>,
+    time_offset: Option<u32>,
+) -> Option<TranscodeOptions> {
+    if !streamer.supports_transcoding() || format == Some("raw") {
+        return None;
+    }
+
+    let exceeds_limit =
+        max_bit_rate.is_some_and(|max| source_bit_rate <= 0 || max < source_bit_rate as u32);
+    let time_offset = time_offset.filter(|t| *t > 0);
+
+    let target = match format.filter(|f| streamer.supports_format(f)) {
+        Some(f) => {
+            if f.eq_ignore_ascii_case(source_suffix) && !exceeds_limit && time_offset.is_none() {
+                return None;
+            }
+            f
+        }
+        None if exceeds_limit => streamer.default_format(),
+        None => return None,
+    };
+
+    Some(TranscodeOptions {
+        format: target.to_string(),
+        bit_rate: max_bit_rate,
+        time_offset,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn transcoder_with(command: &str) -> FfmpegTranscoder {
+        let mut profiles = HashMap::new();
+        profiles.insert(
+            "mp3".to_string(),
+            TranscodeProfile {
+                command: command.to_string(),
+                content_type: "audio/mpeg".to_string(),
+                default_bit_rate: 192,
+            },
+        );
+        FfmpegTranscoder::new(TranscodingConfig {
+            profiles,
+            default_format: "mp3".to_string(),
+        })
+    }
+
+    fn stream_of(chunks: &[&'static [u8]]) -> MediaStream {
+        let chunks: Vec<std::io::Result<Bytes>> =
+            chunks.iter().map(|c| Ok(Bytes::from_static(c))).collect();
+        Box::pin(futures::stream::iter(chunks))
+    }
+
+    fn input_of(chunks: &[&'static [u8]]) -> TranscodeInput {
+        TranscodeInput::Stream {
+            data: stream_of(chunks),
+            suffix: "flac".to_string(),
+        }
+    }
+
+    async fn collect(mut stream: MediaStream) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            out.extend_from_slice(&chunk?);
+        }
+        Ok(out)
+    }
+
+    /// 写出一个假的编码器脚本：先输出参数，再原样复制 stdin
+    #[cfg(unix)]
+    fn fake_encoder(body: &str) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path =
+            std::env::temp_dir().join(format!("reverie-fake-encoder-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_build_command_expands_placeholders() {
+        let transcoder = transcoder_with("ffmpeg -ss %t -i %s -b:a %bk -f mp3 -");
+        let profile = &transcoder.config.profiles["mp3"];
+
+        let options = TranscodeOptions {
+            format: "mp3".to_string(),
+            bit_rate: Some(128),
+            time_offset: Some(42),
+        };
+        let (program, args) = transcoder
+            .build_command(profile, "pipe:0", &options)
+            .unwrap();
+        assert_eq!(program, "ffmpeg");
+        assert_eq!(
+            args,
+            ["-ss", "42", "-i", "pipe:0", "-b:a", "128k", "-f", "mp3", "-"]
+        );
+
+        let options = TranscodeOptions {
+            format: "mp3".to_string(),
+            bit_rate: None,
+            time_offset: None,
+        };
+        let (_, args) = transcoder
+            .build_command(profile, "pipe:0", &options)
+            .unwrap();
+        assert_eq!(args[1], "0");
+        assert_eq!(args[5], "192k");
+    }
+
+    #[test]
+    fn test_build_command_does_not_expand_input_path() {
+        let transcoder = transcoder_with("ffmpeg -ss %t -i %s -b:a %bk -f mp3 -");
+        let profile = &transcoder.config.profiles["mp3"];
+        let options = TranscodeOptions {
+            format: "mp3".to_string(),
+            bit_rate: Some(128),
+            time_offset: Some(42),
+        };
+
+        let (_, args) = transcoder
+            .build_command(profile, "file:/music/100%bass %t.flac", &options)
+            .unwrap();
+        assert_eq!(args[3], "file:/music/100%bass %t.flac");
+        assert_eq!(args[1], "42");
+        assert_eq!(args[5], "128k");
+    }
+
+    #[test]
+    fn test_select_transcode() {
+        let transcoder = FfmpegTranscoder::new(TranscodingConfig::default());
+
+        // 无参数：原始文件
+        assert_eq!(
+            select_transcode(&transcoder, "flac", 900, None, None, None),
+            None
+        );
+        // raw 优先于比特率限制
+        assert_eq!(
+            select_transcode(&transcoder, "flac", 900, Some("raw"), Some(128), None),
+            None
+        );
+        // 比特率限制低于源文件：转码为默认格式
+        let options = select_transcode(&transcoder, "flac", 900, None, Some(128), None).unwrap();
+        assert_eq!(options.format, "mp3");
+        assert_eq!(options.bit_rate, Some(128));
+        // 比特率限制高于源文件：无需转码
+        assert_eq!(
+            select_transcode(&transcoder, "mp3", 128, None, Some(320), None),
+            None
+        );
+        // 同格式且无限制：无需转码；带起始位置时需要转码
+        assert_eq!(
+            select_transcode(&transcoder, "mp3", 320, Some("mp3"), None, None),
+            None
+        );
+        let options =
+            select_transcode(&transcoder, "mp3", 320, Some("mp3"), None, Some(30)).unwrap();
+        assert_eq!(options.time_offset, Some(30));
+        // 指定格式
+        let options = select_transcode(&transcoder, "flac", 900, Some("opus"), None, None).unwrap();
+        assert_eq!(options.format, "opus");
+        assert_eq!(options.bit_rate, None);
+        // 不支持的格式按未指定处理
+        assert_eq!(
+            select_transcode(&transcoder, "flac", 900, Some("wma"), None, None),
+            None
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_transcode_streams_encoder_output() {
+        let script = fake_encoder("echo \"$@\"\ncat");
+        let transcoder = transcoder_with(&format!("{} -ss %t -b:a %bk", script.display()));
+
+        let options = TranscodeOptions {
+            format: "mp3".to_string(),
+            bit_rate: Some(96),
+            time_offset: Some(10),
+        };
+        let output = transcoder
+            .transcode(input_of(&[b"first ", b"second"]), &options)
+            .await
+            .unwrap();
+        let output = collect(output).await.unwrap();
+        std::fs::remove_file(&script).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "-ss 10 -b:a 96k\nfirst second"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_transcode_moov_at_end_m4a_uses_seekable_input() {
+        // ftyp + mdat + moov：moov 位于文件末尾，编码器需要先定位到末尾
+        let m4a: &'static [u8] =
+            b"\0\0\0\x10ftypM4A \0\0\0\0\0\0\0\x0cmdat\x01\x02\x03\x04\0\0\0\x08moov";
+        let script = fake_encoder(
+            "input=\"${2#file:}\"\necho \"$input\"\ntest -f \"$input\" && tail -c 8 \"$input\"",
+        );
+        let transcoder = transcoder_with(&format!("{} -i %s", script.display()));
+        let options = TranscodeOptions {
+            format: "mp3".to_string(),
+            bit_rate: None,
+            time_offset: None,
+        };
+
+        let input = TranscodeInput::Stream {
+            data: stream_of(&[&m4a[..10], &m4a[10..]]),
+            suffix: "m4a".to_string(),
+        };
+        let output = transcoder.transcode(input, &options).await.unwrap();
+        let output = collect(output).await.unwrap();
+        std::fs::remove_file(&script).unwrap();
+
+        let newline = output.iter().position(|b| *b == b'\n').unwrap();
+        let spool = String::from_utf8(output[..newline].to_vec()).unwrap();
+        let spool = std::path::PathBuf::from(spool);
+        assert_eq!(spool.extension().unwrap(), "m4a");
+        assert_eq!(&output[newline + 1..], b"\0\0\0\x08moov");
+        // 编码结束后临时文件被删除
+        assert!(!spool.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_transcode_reads_direct_location() {
+        let script = fake_encoder("echo \"$2\"");
+        let transcoder = transcoder_with(&format!("{} -i %s", script.display()));
+        let options = TranscodeOptions {
+            format: "mp3".to_string(),
+            bit_rate: None,
+            time_offset: None,
+        };
+
+        let local = TranscodeInput::Location(VfsLocation::Local("/music/a b.m4a".into()));
+        let output = transcoder.transcode(local, &options).await.unwrap();
+        assert_eq!(collect(output).await.unwrap(), b"file:/music/a b.m4a\n");
+
+        let url = "https://bucket.example.com/a.m4a?X-Amz-Signature=abc";
+        let remote = TranscodeInput::Location(VfsLocation::Url(url.to_string()));
+        let output = transcoder.transcode(remote, &options).await.unwrap();
+        let output = collect(output).await.unwrap();
+        std::fs::remove_file(&script).unwrap();
+        assert_eq!(output, format!("{}\n", url).into_bytes());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_transcode_reports_encoder_failure() {
+        let script = fake_encoder("cat > /dev/null\nexit 3");
+        let transcoder = transcoder_with(&script.display().to_string());
+
+        let options = TranscodeOptions {
+            format: "mp3".to_string(),
+            bit_rate: None,
+            time_offset: None,
+        };
+        let output = transcoder
+            .transcode(input_of(&[b"data"]), &options)
+            .await
+            .unwrap();
+        let result = collect(output).await;
+        std::fs::remove_file(&script).unwrap();
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transcode_missing_encoder() {
+        let transcoder = transcoder_with("/nonexistent/reverie-encoder");
+        let options = TranscodeOptions {
+            format: "mp3".to_string(),
+            bit_rate: None,
+            time_offset: None,
+        };
+        assert!(transcoder.transcode(input_of(&[]), &options).await.is_err());
+
+        let options = TranscodeOptions {
+            format: "flac".to_string(),
+            ..options
+        };
+        assert!(transcoder.transcode(input_of(&[]), &options).await.is_err());
+    }
+}
@@ -1,11 +1,17 @@
 //! Reverie 服务器应用连接
 
 use anyhow::Result;
-use reverie_network::{axum_server::AxumServer, HttpServer, NetworkConfig};
-use reverie_storage::{Storage, SubsonicStorage};
+use reverie_network::{
+    axum_server::AxumServer, FfmpegTranscoder, HttpServer, NetworkConfig, RadioRelay,
+    TranscodeCache, TranscodingConfig,
+};
+use reverie_storage::{
+    DatabaseStorage, ListenBrainzForwarder, Storage, SubsonicStorage, VfsConfig, WatcherConfig,
+};
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Clone, Debug)]
 pub struct ServerRunConfig {
@@ -15,6 +21,43 @@ pub struct ServerRunConfig {
     pub max_body_size: usize,
     pub timeout_seconds: u64,
     pub ui_dir: Option<PathBuf>,
+    /// 实时转码配置，为 `None` 时禁用转码
+    pub transcoding: Option<TranscodingConfig>,
+    /// 转码结果缓存，为 `None` 时不缓存
+    pub transcode_cache: Option<TranscodeCacheConfig>,
+    /// 是否由服务器转发网络电台的音频流
+    pub radio_relay: bool,
+    /// 是否允许转发本机和内网中的电台，默认只连接公网地址
+    pub radio_allow_private: bool,
+    /// 分享链接使用的公开地址（如 `https://music.example`），为 `None` 时根据请求的 `Host` 头生成
+    pub public_base_url: Option<String>,
+    /// 是否信任反向代理设置的 `X-Forwarded-Host` 和 `X-Forwarded-Proto`，
+    /// 仅在服务器只能通过反向代理访问时启用
+    pub trust_forwarded_headers: bool,
+    /// 重试发送播放记录的间隔，仅用于数据库存储
+    pub scrobble_retry_interval: Duration,
+    /// 是否允许把播放记录转发到本机和内网中的自建服务，默认只连接公网地址，仅用于数据库存储
+    pub scrobble_allow_private: bool,
+    /// 媒体库监视配置，为 `None` 时不监视文件变化，仅用于数据库存储
+    pub library_watcher: Option<WatcherConfig>,
+}
+
+/// 转码缓存配置
+#[derive(Clone, Debug)]
+pub struct TranscodeCacheConfig {
+    /// 存放缓存文件的 VFS
+    pub vfs: VfsConfig,
+    /// 缓存总大小上限（字节）
+    pub max_size: u64,
+}
+
+impl Default for TranscodeCacheConfig {
+    fn default() -> Self {
+        Self {
+            vfs: VfsConfig::local("data"),
+            max_size: 1024 * 1024 * 1024,
+        }
+    }
 }
 
 impl Default for ServerRunConfig {
@@ -26,6 +69,15 @@ impl Default for ServerRunConfig {
             max_body_size: 10 * 1024 * 1024,
             timeout_seconds: 30,
             ui_dir: None,
+            transcoding: Some(TranscodingConfig::default()),
+            transcode_cache: None,
+            radio_relay: false,
+            radio_allow_private: false,
+            public_base_url: None,
+            trust_forwarded_headers: false,
+            scrobble_retry_interval: Duration::from_secs(60),
+            scrobble_allow_private: false,
+            library_watcher: Some(WatcherConfig::default()),
         }
     }
 }
@@ -34,11 +86,48 @@ pub async fn run_with_storage<S>(storage: Arc<S>, config: ServerRunConfig) -> Re
 where
     S: Storage + SubsonicStorage + Clone + 'static,
 {
+    initialize(storage.as_ref()).await?;
+    serve(storage, config).await
+}
+
+/// 使用数据库存储运行服务器
+///
+/// 同时在后台重试发送失败的播放记录，并按配置监视音乐文件夹的变化
+pub async fn run_with_database(
+    storage: Arc<DatabaseStorage>,
+    config: ServerRunConfig,
+) -> Result<()> {
+    initialize(storage.as_ref()).await?;
+    if config.scrobble_allow_private {
+        storage.register_scrobble_forwarder(Arc::new(
+            ListenBrainzForwarder::new().allow_private_targets(),
+        ));
+    }
+    // 服务器停止时随之停止
+    let _scrobble_retry = storage.start_scrobble_retry(config.scrobble_retry_interval);
+    if let Some(watcher) = config.library_watcher.clone() {
+        storage
+            .watch_library(watcher)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to watch the music library: {}", e))?;
+    }
+
+    let result = serve(storage.clone(), config).await;
+    storage.unwatch_library();
+    result
+}
+
+async fn initialize<S: Storage>(storage: &S) -> Result<()> {
     storage
         .initialize()
         .await
-        .map_err(|e| anyhow::anyhow!("Failed to initialize storage: {}", e))?;
+        .map_err(|e| anyhow::anyhow!("Failed to initialize storage: {}", e))
+}
 
+async fn serve<S>(storage: Arc<S>, config: ServerRunConfig) -> Result<()>
+where
+    S: Storage + SubsonicStorage + Clone + 'static,
+{
     let network_config = NetworkConfig {
         host: config.host.clone(),
         port: config.port,
@@ -51,6 +140,30 @@ where
     if let Some(ui_dir) = config.ui_dir.clone() {
         server = server.with_ui_dir(ui_dir);
     }
+    if let Some(transcoding) = config.transcoding.clone() {
+        server = server.with_transcoder(Arc::new(FfmpegTranscoder::new(transcoding)));
+    }
+    if let Some(cache_config) = config.transcode_cache.clone() {
+        let vfs = reverie_storage::create_vfs(cache_config.vfs)
+            .map_err(|e| anyhow::anyhow!("Failed to open transcode cache: {}", e))?;
+        let cache = TranscodeCache::open(vfs, cache_config.max_size)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to open transcode cache: {}", e))?;
+        server = server.with_transcode_cache(cache);
+    }
+    if config.radio_relay {
+        let mut relay = RadioRelay::new();
+        if config.radio_allow_private {
+            relay = relay.allow_private_targets();
+        }
+        server = server.with_radio_relay(relay);
+    }
+    if let Some(url) = config.public_base_url.clone() {
+        server = server.with_public_base_url(url);
+    }
+    if config.trust_forwarded_headers {
+        server = server.trust_forwarded_headers();
+    }
 
     let addr: SocketAddr = format!("{}:{}", network_config.host, network_config.port)
         .parse()
@@ -0,0 +1,233 @@
+//! 用户凭据
+//!
+//! 每个用户保存两份凭据：
+//!
+//! - 登录用的 Argon2 哈希，只能验证密码，无法还原
+//! - Subsonic 令牌验证（`t = md5(password + salt)`）需要原始密码，因此另存一份用配置的密钥
+//!   以 AES-256-GCM 加密的密码
+//!
+//! 未配置密钥时，首次启动生成随机密钥并保存在数据库之外的密钥文件中，见 [`load_or_create_key`]。
+//! 密钥更换后已加密的密码无法解密，这些用户在重新设置密码之前只能使用明文密码登录。
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+
+use crate::error::{Result, StorageError};
+
+/// 加密密码的格式版本前缀
+const CIPHER_PREFIX: &str = "v1:";
+
+const NONCE_LEN: usize = 12;
+
+/// 计算密码的 Argon2id 哈希（PHC 字符串格式）
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| StorageError::CredentialError(format!("Failed to hash password: {}", e)))
+}
+
+/// 验证密码与 Argon2 哈希是否匹配，哈希格式无效时视为不匹配
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// 是否为 [`hash_password`] 生成的哈希；旧版本直接在 `password_hash` 列保存明文密码
+pub fn is_password_hash(value: &str) -> bool {
+    value.starts_with("$argon2")
+}
+
+/// 生成随机密码，用于首次启动时创建的管理员
+pub fn generate_password() -> String {
+    const CHARSET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz23456789";
+    let mut rng = rand::thread_rng();
+    (0..16)
+        .map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char)
+        .collect()
+}
+
+/// 生成随机的加密密钥（256 位，十六进制）
+pub fn generate_key() -> String {
+    hex::encode(rand::thread_rng().gen::<[u8; 32]>())
+}
+
+/// 读取密钥文件，文件不存在时生成随机密钥并写入
+///
+/// 返回密钥以及是否为新生成的密钥。密钥文件只有所有者可以读写
+pub fn load_or_create_key(path: &Path) -> Result<(String, bool)> {
+    match fs::read_to_string(path) {
+        Ok(key) if !key.trim().is_empty() => return Ok((key.trim().to_string(), false)),
+        Ok(_) => {
+            return Err(StorageError::CredentialError(format!(
+                "Encryption key file {} is empty",
+                path.display()
+            )))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    let key = generate_key();
+    write_private_file(path, &key)?;
+    Ok((key, true))
+}
+
+/// 创建只有所有者可以读写的文件，文件已存在时返回错误
+pub(crate) fn write_private_file(path: &Path, contents: &str) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// 将首次启动时生成的初始密码写入只有所有者可以读写的文件，日志中只记录文件路径
+///
+/// 之前留下的文件已经没有用处，先删除
+pub(crate) fn write_initial_password(path: &Path, username: &str, password: &str) -> Result<()> {
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    write_private_file(path, &format!("{}\n", password))?;
+    tracing::warn!(
+        "Created user '{}', its initial password is in {}; \
+         it must be changed at first sign-in, delete the file afterwards",
+        username,
+        path.display()
+    );
+    Ok(())
+}
+
+/// 加密保存 Subsonic 密码
+#[derive(Clone)]
+pub struct PasswordCipher {
+    cipher: Aes256Gcm,
+}
+
+impl PasswordCipher {
+    /// 由配置的密钥创建，任意长度的密钥都经 SHA-256 派生为 256 位
+    pub fn new(key: &str) -> Self {
+        let key = Sha256::digest(key.as_bytes());
+        Self {
+            cipher: Aes256Gcm::new(&key),
+        }
+    }
+
+    /// 加密密码，每次使用随机 nonce，结果为 `v1:<nonce>:<密文>` 的十六进制形式
+    pub fn encrypt(&self, password: &str) -> Result<String> {
+        let nonce: [u8; NONCE_LEN] = rand::thread_rng().gen();
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), password.as_bytes())
+            .map_err(|_| StorageError::CredentialError("Failed to encrypt password".into()))?;
+        Ok(format!(
+            "{}{}:{}",
+            CIPHER_PREFIX,
+            hex::encode(nonce),
+            hex::encode(ciphertext)
+        ))
+    }
+
+    /// 解密 [`encrypt`](Self::encrypt) 的结果，密钥不匹配或数据损坏时返回错误
+    pub fn decrypt(&self, encrypted: &str) -> Result<String> {
+        let invalid = || StorageError::CredentialError("Invalid encrypted password".into());
+        let (nonce, ciphertext) = encrypted
+            .strip_prefix(CIPHER_PREFIX)
+            .and_then(|rest| rest.split_once(':'))
+            .ok_or_else(invalid)?;
+        let nonce = hex::decode(nonce).map_err(|_| invalid())?;
+        let ciphertext = hex::decode(ciphertext).map_err(|_| invalid())?;
+        if nonce.len() != NONCE_LEN {
+            return Err(invalid());
+        }
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| {
+                StorageError::CredentialError(
+                    "Failed to decrypt password, the encryption key may have changed".into(),
+                )
+            })?;
+        String::from_utf8(plaintext).map_err(|_| invalid())
+    }
+}
+
+impl fmt::Debug for PasswordCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PasswordCipher")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_and_verify_password() {
+        let hash = hash_password("sesame").unwrap();
+        assert!(is_password_hash(&hash));
+        assert_ne!(hash, hash_password("sesame").unwrap());
+        assert!(verify_password("sesame", &hash));
+        assert!(!verify_password("Sesame", &hash));
+        // 旧版本保存的明文不是有效哈希
+        assert!(!is_password_hash("sesame"));
+        assert!(!verify_password("sesame", "sesame"));
+    }
+
+    #[test]
+    fn test_encrypt_round_trip() {
+        let cipher = PasswordCipher::new("secret");
+        let encrypted = cipher.encrypt("sesame").unwrap();
+        assert!(!encrypted.contains("sesame"));
+        assert_ne!(encrypted, cipher.encrypt("sesame").unwrap());
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), "sesame");
+
+        // 密钥不同或数据被篡改时无法解密
+        assert!(PasswordCipher::new("other").decrypt(&encrypted).is_err());
+        let mut tampered = encrypted.clone();
+        tampered.pop();
+        tampered.push(if encrypted.ends_with('0') { '1' } else { '0' });
+        assert!(cipher.decrypt(&tampered).is_err());
+        assert!(cipher.decrypt("sesame").is_err());
+    }
+
+    #[test]
+    fn test_load_or_create_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reverie.db.key");
+        let (key, created) = load_or_create_key(&path).unwrap();
+        assert!(created);
+        assert_eq!(key.len(), 64);
+        assert_eq!(load_or_create_key(&path).unwrap(), (key, false));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_generate_password() {
+        let password = generate_password();
+        assert_eq!(password.len(), 16);
+        assert_ne!(password, generate_password());
+    }
+}
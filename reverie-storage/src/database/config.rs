@@ -1,5 +1,7 @@
 //! 数据库存储配置
 
+use std::path::PathBuf;
+
 use crate::vfs::VfsConfig;
 
 /// 数据库存储配置
@@ -11,6 +13,17 @@ pub struct DatabaseConfig {
     pub max_connections: u32,
     /// 媒体文件存储的 VFS 配置
     pub vfs_config: VfsConfig,
+    /// 加密保存 Subsonic 密码的密钥
+    ///
+    /// 为 `None` 时使用数据库旁的密钥文件（`<数据库路径>.key`），首次启动时生成随机密钥；
+    /// 内存数据库使用仅在本次运行中有效的随机密钥
+    ///
+    /// 更换密钥后，已保存的密码无法用于令牌验证，直到用户重新设置密码
+    pub password_encryption_key: Option<String>,
+    /// 保存专辑封面的 VFS 配置，与音乐文件夹分开，音乐文件夹可以是只读的
+    ///
+    /// 为 `None` 时使用数据库旁的 `<数据库路径>.covers` 目录，内存数据库的封面保存在内存中
+    pub cover_vfs_config: Option<VfsConfig>,
 }
 
 impl Default for DatabaseConfig {
@@ -19,6 +32,8 @@ impl Default for DatabaseConfig {
             database_url: "reverie.db".to_string(),
             max_connections: 5,
             vfs_config: VfsConfig::local("./music"),
+            password_encryption_key: None,
+            cover_vfs_config: None,
         }
     }
 }
@@ -30,15 +45,46 @@ impl DatabaseConfig {
             database_url: database_url.into(),
             max_connections: 5,
             vfs_config,
+            password_encryption_key: None,
+            cover_vfs_config: None,
+        }
+    }
+
+    /// 设置加密保存 Subsonic 密码的密钥
+    pub fn with_password_encryption_key(mut self, key: impl Into<String>) -> Self {
+        self.password_encryption_key = Some(key.into());
+        self
+    }
+
+    /// 设置保存专辑封面的 VFS
+    pub fn with_cover_vfs_config(mut self, config: VfsConfig) -> Self {
+        self.cover_vfs_config = Some(config);
+        self
+    }
+
+    /// 实际使用的专辑封面 VFS 配置
+    pub(crate) fn effective_cover_vfs_config(&self) -> VfsConfig {
+        match (&self.cover_vfs_config, self.sidecar_path("covers")) {
+            (Some(config), _) => config.clone(),
+            (None, Some(path)) => VfsConfig::local(path.to_string_lossy()),
+            (None, None) => VfsConfig::memory(),
         }
     }
 
+    /// 与数据库文件放在一起的附属文件（`<数据库路径>.<extension>`），内存数据库没有附属文件
+    pub(crate) fn sidecar_path(&self, extension: &str) -> Option<PathBuf> {
+        (self.database_url != ":memory:")
+            .then(|| PathBuf::from(format!("{}.{}", self.database_url, extension)))
+    }
+
     /// 创建内存数据库配置（用于测试）
     pub fn memory() -> Self {
         Self {
             database_url: ":memory:".to_string(),
             max_connections: 1,
             vfs_config: VfsConfig::memory(),
+            password_encryption_key: None,
+            cover_vfs_config: None,
         }
     }
 }
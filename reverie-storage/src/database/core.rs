@@ -1,24 +1,44 @@
 //! DatabaseStorage 核心实现
 
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use async_trait::async_trait;
 use chrono::Utc;
 use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
+use tokio::sync::RwLock;
+use tracing::warn;
 use uuid::Uuid;
 
+use crate::credentials::{self, PasswordCipher};
 use crate::error::{Result, StorageError};
 use crate::traits::*;
 use crate::vfs::{create_vfs, SharedVfs};
-use reverie_core::User;
 
 use super::config::DatabaseConfig;
+use super::migrations;
 
 /// 基于 SQLite 的存储实现
 #[derive(Clone)]
 pub struct DatabaseStorage {
     pool: Pool<Sqlite>,
     vfs: SharedVfs,
-    #[allow(dead_code)]
+    /// 保存专辑封面的 VFS
+    cover_vfs: SharedVfs,
     config: DatabaseConfig,
+    /// 加密保存 Subsonic 密码
+    cipher: PasswordCipher,
+    /// 各音乐文件夹的 VFS 实例，按需创建
+    libraries: Arc<RwLock<HashMap<i32, SharedVfs>>>,
+    /// 播放记录转发器和重试队列的状态
+    #[cfg(feature = "scrobbler")]
+    scrobblers: Arc<super::scrobble::Scrobblers>,
+    /// 已启动的媒体库监视器
+    #[cfg(feature = "scanner")]
+    watchers: Arc<std::sync::Mutex<super::scan::LibraryWatchers>>,
+    /// 同一时间只运行一次扫描
+    #[cfg(feature = "scanner")]
+    scan_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl DatabaseStorage {
@@ -37,8 +57,39 @@ impl DatabaseStorage {
             .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
         let vfs = create_vfs(config.vfs_config.clone())?;
+        let cover_vfs = create_vfs(config.effective_cover_vfs_config())?;
+        let cipher = match (&config.password_encryption_key, config.sidecar_path("key")) {
+            (Some(key), _) => PasswordCipher::new(key),
+            (None, Some(path)) => {
+                let (key, created) = credentials::load_or_create_key(&path)?;
+                if created {
+                    warn!(
+                        "No password encryption key configured; generated one in {}. \
+                         Back it up with the database, stored passwords cannot be used for \
+                         token authentication without it",
+                        path.display()
+                    );
+                }
+                PasswordCipher::new(&key)
+            }
+            // 内存数据库随进程一起消失，不需要保存密钥
+            (None, None) => PasswordCipher::new(&credentials::generate_key()),
+        };
 
-        let storage = Self { pool, vfs, config };
+        let storage = Self {
+            pool,
+            vfs,
+            cover_vfs,
+            config,
+            cipher,
+            libraries: Arc::new(RwLock::new(HashMap::new())),
+            #[cfg(feature = "scrobbler")]
+            scrobblers: Arc::default(),
+            #[cfg(feature = "scanner")]
+            watchers: Arc::default(),
+            #[cfg(feature = "scanner")]
+            scan_lock: Arc::default(),
+        };
         storage.run_migrations().await?;
 
         Ok(storage)
@@ -46,204 +97,91 @@ impl DatabaseStorage {
 
     /// 运行数据库迁移
     async fn run_migrations(&self) -> Result<()> {
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS artists (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                bio TEXT,
-                image_url TEXT,
-                starred_at TEXT,
-                play_count INTEGER DEFAULT 0,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            );
-
-            CREATE TABLE IF NOT EXISTS albums (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                artist_id TEXT,
-                year INTEGER,
-                genre TEXT,
-                cover_art_path TEXT,
-                song_count INTEGER DEFAULT 0,
-                duration REAL DEFAULT 0,
-                play_count INTEGER DEFAULT 0,
-                starred_at TEXT,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                FOREIGN KEY (artist_id) REFERENCES artists(id)
-            );
-
-            CREATE TABLE IF NOT EXISTS tracks (
-                id TEXT PRIMARY KEY,
-                title TEXT NOT NULL,
-                album_id TEXT,
-                artist_id TEXT,
-                duration INTEGER NOT NULL,
-                file_path TEXT NOT NULL,
-                file_size INTEGER NOT NULL,
-                bitrate INTEGER NOT NULL,
-                sample_rate INTEGER DEFAULT 44100,
-                channels INTEGER DEFAULT 2,
-                format TEXT NOT NULL,
-                track_number INTEGER,
-                disc_number INTEGER,
-                year INTEGER,
-                genre TEXT,
-                cover_art_path TEXT,
-                play_count INTEGER DEFAULT 0,
-                starred_at TEXT,
-                rating INTEGER,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                FOREIGN KEY (album_id) REFERENCES albums(id),
-                FOREIGN KEY (artist_id) REFERENCES artists(id)
-            );
-
-            CREATE TABLE IF NOT EXISTS users (
-                id TEXT PRIMARY KEY,
-                username TEXT UNIQUE NOT NULL,
-                password_hash TEXT NOT NULL,
-                email TEXT,
-                is_admin INTEGER NOT NULL DEFAULT 0,
-                last_login_at TEXT,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            );
-
-            CREATE TABLE IF NOT EXISTS playlists (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                description TEXT,
-                user_id TEXT NOT NULL,
-                is_public INTEGER NOT NULL DEFAULT 0,
-                cover_art_path TEXT,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                FOREIGN KEY (user_id) REFERENCES users(id)
-            );
-
-            CREATE TABLE IF NOT EXISTS playlist_tracks (
-                playlist_id TEXT NOT NULL,
-                track_id TEXT NOT NULL,
-                position INTEGER NOT NULL,
-                added_at TEXT NOT NULL,
-                PRIMARY KEY (playlist_id, track_id),
-                FOREIGN KEY (playlist_id) REFERENCES playlists(id),
-                FOREIGN KEY (track_id) REFERENCES tracks(id)
-            );
-
-            CREATE TABLE IF NOT EXISTS music_folders (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL,
-                path TEXT NOT NULL UNIQUE
-            );
-
-            CREATE TABLE IF NOT EXISTS genres (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL UNIQUE
-            );
+        migrations::run(self.pool()).await
+    }
 
-            CREATE TABLE IF NOT EXISTS scrobbles (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                track_id TEXT NOT NULL,
-                user_id TEXT NOT NULL,
-                played_at TEXT NOT NULL,
-                FOREIGN KEY (track_id) REFERENCES tracks(id),
-                FOREIGN KEY (user_id) REFERENCES users(id)
-            );
+    /// 获取数据库当前的表结构版本
+    pub async fn schema_version(&self) -> Result<i64> {
+        let mut conn = self
+            .pool()
+            .acquire()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        migrations::schema_version(&mut conn).await
+    }
 
-            CREATE TABLE IF NOT EXISTS play_queue (
-                user_id TEXT PRIMARY KEY,
-                track_ids TEXT NOT NULL,
-                current_track_id TEXT,
-                position INTEGER DEFAULT 0,
-                changed_at TEXT NOT NULL,
-                changed_by TEXT NOT NULL,
-                FOREIGN KEY (user_id) REFERENCES users(id)
-            );
+    /// 获取 VFS 实例
+    pub fn vfs(&self) -> &SharedVfs {
+        &self.vfs
+    }
 
-            CREATE TABLE IF NOT EXISTS bookmarks (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                user_id TEXT NOT NULL,
-                track_id TEXT NOT NULL,
-                position INTEGER NOT NULL,
-                comment TEXT,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                FOREIGN KEY (user_id) REFERENCES users(id),
-                FOREIGN KEY (track_id) REFERENCES tracks(id),
-                UNIQUE(user_id, track_id)
-            );
+    /// 获取保存专辑封面的 VFS 实例
+    pub fn cover_vfs(&self) -> &SharedVfs {
+        &self.cover_vfs
+    }
 
-            CREATE TABLE IF NOT EXISTS internet_radio_stations (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL,
-                stream_url TEXT NOT NULL,
-                homepage_url TEXT,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            );
+    /// 获取存储配置
+    pub fn config(&self) -> &DatabaseConfig {
+        &self.config
+    }
 
-            CREATE TABLE IF NOT EXISTS shares (
-                id TEXT PRIMARY KEY,
-                user_id TEXT NOT NULL,
-                description TEXT,
-                expires_at TEXT,
-                visit_count INTEGER DEFAULT 0,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                FOREIGN KEY (user_id) REFERENCES users(id)
-            );
+    pub(crate) fn cipher(&self) -> &PasswordCipher {
+        &self.cipher
+    }
 
-            CREATE TABLE IF NOT EXISTS share_items (
-                share_id TEXT NOT NULL,
-                item_type TEXT NOT NULL,
-                item_id TEXT NOT NULL,
-                PRIMARY KEY (share_id, item_type, item_id),
-                FOREIGN KEY (share_id) REFERENCES shares(id)
-            );
+    /// 已创建的音乐文件夹 VFS 实例缓存
+    pub(crate) fn libraries(&self) -> &RwLock<HashMap<i32, SharedVfs>> {
+        &self.libraries
+    }
 
-            CREATE TABLE IF NOT EXISTS scan_status (
-                id INTEGER PRIMARY KEY CHECK (id = 1),
-                scanning INTEGER NOT NULL DEFAULT 0,
-                count INTEGER DEFAULT 0,
-                folder_count INTEGER DEFAULT 0,
-                last_scan TEXT,
-                error TEXT,
-                scan_type TEXT,
-                elapsed_time INTEGER
-            );
+    /// 获取数据库连接池
+    pub fn pool(&self) -> &Pool<Sqlite> {
+        &self.pool
+    }
 
-            -- Indexes for common queries
-            CREATE INDEX IF NOT EXISTS idx_tracks_album ON tracks(album_id);
-            CREATE INDEX IF NOT EXISTS idx_tracks_artist ON tracks(artist_id);
-            CREATE INDEX IF NOT EXISTS idx_tracks_title ON tracks(title);
-            CREATE INDEX IF NOT EXISTS idx_albums_artist ON albums(artist_id);
-            CREATE INDEX IF NOT EXISTS idx_albums_name ON albums(name);
-            CREATE INDEX IF NOT EXISTS idx_artists_name ON artists(name);
-            CREATE INDEX IF NOT EXISTS idx_playlist_tracks ON playlist_tracks(playlist_id, position);
+    #[cfg(feature = "scrobbler")]
+    pub(crate) fn scrobblers(&self) -> &super::scrobble::Scrobblers {
+        &self.scrobblers
+    }
 
-            -- Insert default scan status row
-            INSERT OR IGNORE INTO scan_status (id, scanning, count, folder_count) VALUES (1, 0, 0, 0);
-            "#,
-        )
-        .execute(self.pool())
-        .await
-        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+    #[cfg(feature = "scanner")]
+    pub(crate) fn watchers(&self) -> &std::sync::Mutex<super::scan::LibraryWatchers> {
+        &self.watchers
+    }
 
-        Ok(())
+    #[cfg(feature = "scanner")]
+    pub(crate) fn scan_lock(&self) -> &tokio::sync::Mutex<()> {
+        &self.scan_lock
     }
 
-    /// 获取 VFS 实例
-    pub fn vfs(&self) -> &SharedVfs {
-        &self.vfs
+    /// 为用户生成新的 OpenSubsonic API 密钥
+    pub async fn create_api_key(&self, username: &str) -> Result<String> {
+        let row: Option<(String,)> = sqlx::query_as("SELECT id FROM users WHERE username = ?")
+            .bind(username)
+            .fetch_optional(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        let (user_id,) = row.ok_or_else(|| StorageError::NotFound(username.to_string()))?;
+
+        let api_key = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        sqlx::query("INSERT INTO api_keys (api_key, user_id, created_at) VALUES (?, ?, ?)")
+            .bind(&api_key)
+            .bind(&user_id)
+            .bind(Utc::now().to_rfc3339())
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        Ok(api_key)
     }
 
-    /// 获取数据库连接池
-    pub fn pool(&self) -> &Pool<Sqlite> {
-        &self.pool
+    /// 撤销 API 密钥
+    pub async fn revoke_api_key(&self, api_key: &str) -> Result<()> {
+        sqlx::query("DELETE FROM api_keys WHERE api_key = ?")
+            .bind(api_key)
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        Ok(())
     }
 }
 
@@ -251,24 +189,16 @@ impl DatabaseStorage {
 impl Storage for DatabaseStorage {
     async fn initialize(&self) -> Result<()> {
         // 迁移已在 new() 中运行
-        // 确保默认管理员用户存在
+        // 确保管理员用户存在，并转换旧版本保存的明文密码
         let user_count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users")
             .fetch_one(self.pool())
             .await
             .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
         if user_count.0 == 0 {
-            let admin_user = User {
-                id: Uuid::new_v4(),
-                username: "admin".to_string(),
-                password_hash: "admin".to_string(), // TODO: proper hashing
-                email: Some("admin@reverie.local".to_string()),
-                is_admin: true,
-                created_at: Utc::now(),
-                updated_at: Utc::now(),
-            };
-            self.save_user(&admin_user).await?;
+            self.create_initial_admin_internal().await?;
         }
+        self.upgrade_legacy_passwords_internal().await?;
 
         // Insert default music folder if none exists
         let folder_count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM music_folders")
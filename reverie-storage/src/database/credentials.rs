@@ -0,0 +1,181 @@
+//! 用户凭据的保存和验证
+//!
+//! `password_hash` 列保存 Argon2 哈希，`subsonic_password` 列保存加密后的密码，
+//! 见 [`crate::credentials`]。Argon2 计算量较大，在阻塞线程池中执行。
+
+use chrono::Utc;
+use subtle::ConstantTimeEq;
+use tracing::{info, warn};
+
+use crate::credentials::{self, generate_password, is_password_hash, write_initial_password};
+use crate::error::{Result, StorageError};
+use crate::DatabaseStorage;
+
+/// 首次启动时创建的管理员用户名
+const INITIAL_ADMIN: &str = "admin";
+
+async fn hash_password(password: &str) -> Result<String> {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || credentials::hash_password(&password))
+        .await
+        .map_err(|e| StorageError::CredentialError(e.to_string()))?
+}
+
+impl DatabaseStorage {
+    /// 内部方法：计算新密码的哈希和加密值，依次对应 `password_hash` 和 `subsonic_password` 列
+    pub(crate) async fn credentials_internal(&self, password: &str) -> Result<(String, String)> {
+        let hash = hash_password(password).await?;
+        let encrypted = self.cipher().encrypt(password)?;
+        Ok((hash, encrypted))
+    }
+
+    /// 内部方法：设置用户密码，`must_change` 表示用户登录后必须先修改密码
+    ///
+    /// 用户不存在时返回 `NotFound`
+    pub(crate) async fn set_password_internal(
+        &self,
+        username: &str,
+        password: &str,
+        must_change: bool,
+    ) -> Result<()> {
+        let (hash, encrypted) = self.credentials_internal(password).await?;
+        let result = sqlx::query(
+            "UPDATE users SET password_hash = ?, subsonic_password = ?, must_change_password = ?,
+                 updated_at = ?
+             WHERE username = ?",
+        )
+        .bind(hash)
+        .bind(encrypted)
+        .bind(must_change)
+        .bind(Utc::now().to_rfc3339())
+        .bind(username)
+        .execute(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("User {}", username)));
+        }
+        Ok(())
+    }
+
+    /// 内部方法：解密用户的 Subsonic 密码
+    ///
+    /// 密钥更换后无法解密，此时返回 `None`，用户只能使用明文密码登录
+    pub(crate) async fn subsonic_password_internal(
+        &self,
+        username: &str,
+    ) -> Result<Option<String>> {
+        let encrypted: Option<Option<String>> =
+            sqlx::query_scalar("SELECT subsonic_password FROM users WHERE username = ?")
+                .bind(username)
+                .fetch_optional(self.pool())
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        let Some(encrypted) = encrypted.flatten() else {
+            return Ok(None);
+        };
+        match self.cipher().decrypt(&encrypted) {
+            Ok(password) => Ok(Some(password)),
+            Err(e) => {
+                warn!("Cannot decrypt the Subsonic password of {}: {}", username, e);
+                Ok(None)
+            }
+        }
+    }
+
+    /// 内部方法：验证用户的登录密码
+    ///
+    /// `p` 参数随每个 Subsonic 请求发送，优先与解密后的密码做常量时间比较；
+    /// 只有密码无法解密（例如密钥已更换）时才计算 Argon2 哈希
+    pub(crate) async fn verify_password_internal(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<bool> {
+        let row: Option<(String, Option<String>)> =
+            sqlx::query_as("SELECT password_hash, subsonic_password FROM users WHERE username = ?")
+                .bind(username)
+                .fetch_optional(self.pool())
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        let Some((hash, encrypted)) = row else {
+            return Ok(false);
+        };
+        if let Some(Ok(stored)) = encrypted.map(|e| self.cipher().decrypt(&e)) {
+            return Ok(bool::from(stored.as_bytes().ct_eq(password.as_bytes())));
+        }
+        let password = password.to_string();
+        tokio::task::spawn_blocking(move || credentials::verify_password(&password, &hash))
+            .await
+            .map_err(|e| StorageError::CredentialError(e.to_string()))
+    }
+
+    /// 内部方法：用户是否必须先修改密码
+    pub(crate) async fn must_change_password_internal(&self, username: &str) -> Result<bool> {
+        let flag: Option<bool> =
+            sqlx::query_scalar("SELECT must_change_password FROM users WHERE username = ?")
+                .bind(username)
+                .fetch_optional(self.pool())
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        Ok(flag.unwrap_or(false))
+    }
+
+    /// 内部方法：首次启动时创建管理员
+    ///
+    /// 不再使用固定的 `admin/admin`，而是生成随机密码，管理员登录后必须先修改密码。
+    /// 密码不写入日志：保存在数据库旁只有所有者可读的 `<数据库路径>.admin-password` 文件中，
+    /// 日志只记录文件路径；内存数据库不保存初始密码
+    pub(crate) async fn create_initial_admin_internal(&self) -> Result<()> {
+        let password = generate_password();
+        let (hash, encrypted) = self.credentials_internal(&password).await?;
+        let now = Utc::now().to_rfc3339();
+        sqlx::query(
+            "INSERT INTO users (id, username, password_hash, subsonic_password, email, is_admin,
+                 must_change_password, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, 1, 1, ?, ?)",
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(INITIAL_ADMIN)
+        .bind(hash)
+        .bind(encrypted)
+        .bind("admin@reverie.local")
+        .bind(&now)
+        .bind(&now)
+        .execute(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        match self.config().sidecar_path("admin-password") {
+            Some(path) => write_initial_password(&path, INITIAL_ADMIN, &password)?,
+            None => warn!(
+                "Created user '{}' with a random initial password that is not saved anywhere",
+                INITIAL_ADMIN
+            ),
+        }
+        Ok(())
+    }
+
+    /// 内部方法：转换旧版本以明文保存在 `password_hash` 列中的密码
+    ///
+    /// 仍在使用默认 `admin/admin` 的管理员需要在登录后修改密码
+    pub(crate) async fn upgrade_legacy_passwords_internal(&self) -> Result<()> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT username, password_hash FROM users WHERE subsonic_password IS NULL",
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        for (username, password) in rows {
+            if is_password_hash(&password) {
+                continue;
+            }
+            let must_change = username == INITIAL_ADMIN && password == INITIAL_ADMIN;
+            self.set_password_internal(&username, &password, must_change)
+                .await?;
+            info!("Upgraded the stored password of {}", username);
+        }
+        Ok(())
+    }
+}
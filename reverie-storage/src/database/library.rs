@@ -0,0 +1,328 @@
+//! 音乐文件夹（媒体库）管理
+//!
+//! 每个音乐文件夹可以配置独立的 VFS，未配置时使用 [`DatabaseConfig::vfs_config`]
+//! 对应的默认 VFS。曲目的 `file_path` 相对于所属文件夹的 VFS 根目录，对外提供的
+//! 媒体文件路径带有 `lib://<文件夹 ID>/` 前缀，由 `FileStorage` 实现解析到对应的 VFS。
+//!
+//! 文件夹可以在运行时添加、修改和删除，修改后缓存的 VFS 实例随之失效，启用了媒体库监视时
+//! 监视器也随之更新。
+//!
+//! [`DatabaseConfig::vfs_config`]: super::DatabaseConfig::vfs_config
+
+use std::collections::HashSet;
+
+use sqlx::Row;
+
+use crate::error::{Result, StorageError};
+use crate::vfs::{create_vfs, SharedVfs, VfsConfig};
+use crate::{DatabaseStorage, LibraryFolder};
+
+/// 带媒体库前缀的文件路径的前缀
+const LIBRARY_PATH_PREFIX: &str = "lib://";
+
+/// 专辑封面路径的前缀，这些文件位于封面 VFS 中
+const COVER_PATH_PREFIX: &str = "covers://";
+
+/// 生成专辑封面的文件路径
+pub(crate) fn cover_path(name: &str) -> String {
+    format!("{}{}", COVER_PATH_PREFIX, name)
+}
+
+/// 生成带媒体库前缀的文件路径
+pub(crate) fn library_path(library_id: i32, path: &str) -> String {
+    format!("{}{}/{}", LIBRARY_PATH_PREFIX, library_id, path)
+}
+
+/// 解析带媒体库前缀的文件路径，返回文件夹 ID 和文件夹内的路径
+fn parse_library_path(path: &str) -> Option<(i32, &str)> {
+    let (id, path) = path.strip_prefix(LIBRARY_PATH_PREFIX)?.split_once('/')?;
+    Some((id.parse().ok()?, path))
+}
+
+impl DatabaseStorage {
+    /// 获取所有音乐文件夹的配置
+    pub async fn library_folders(&self) -> Result<Vec<LibraryFolder>> {
+        let rows = sqlx::query("SELECT id, name, path, vfs_config FROM music_folders ORDER BY id")
+            .fetch_all(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        rows.iter().map(row_to_folder).collect()
+    }
+
+    /// 获取单个音乐文件夹的配置
+    pub async fn library_folder(&self, id: i32) -> Result<Option<LibraryFolder>> {
+        let row = sqlx::query("SELECT id, name, path, vfs_config FROM music_folders WHERE id = ?")
+            .bind(id)
+            .fetch_optional(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        row.as_ref().map(row_to_folder).transpose()
+    }
+
+    /// 添加音乐文件夹，返回新文件夹的 ID
+    pub async fn add_music_folder(
+        &self,
+        name: &str,
+        path: &str,
+        vfs_config: Option<&VfsConfig>,
+    ) -> Result<i32> {
+        // 先创建一次 VFS 以拒绝无效的配置
+        if let Some(config) = vfs_config {
+            create_vfs(config.clone())?;
+        }
+        let vfs_config = vfs_config.map(serde_json::to_string).transpose()?;
+
+        let (id,): (i32,) = sqlx::query_as(
+            "INSERT INTO music_folders (name, path, vfs_config) VALUES (?, ?, ?) RETURNING id",
+        )
+        .bind(name)
+        .bind(path)
+        .bind(vfs_config)
+        .fetch_one(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        #[cfg(feature = "scanner")]
+        self.rewatch_folder(id).await?;
+        Ok(id)
+    }
+
+    /// 更新音乐文件夹的名称、扫描路径和 VFS 配置
+    ///
+    /// 已入库曲目的路径仍相对于原来的位置，修改路径或 VFS 后需要重新扫描
+    pub async fn update_library_folder(&self, folder: &LibraryFolder) -> Result<()> {
+        if let Some(config) = &folder.vfs_config {
+            create_vfs(config.clone())?;
+        }
+        let vfs_config = folder
+            .vfs_config
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+
+        let result =
+            sqlx::query("UPDATE music_folders SET name = ?, path = ?, vfs_config = ? WHERE id = ?")
+                .bind(&folder.name)
+                .bind(&folder.path)
+                .bind(vfs_config)
+                .bind(folder.id)
+                .execute(self.pool())
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("Music folder {}", folder.id)));
+        }
+
+        self.libraries().write().await.remove(&folder.id);
+        #[cfg(feature = "scanner")]
+        self.rewatch_folder(folder.id).await?;
+        Ok(())
+    }
+
+    /// 删除音乐文件夹
+    ///
+    /// 文件夹中的曲目及其播放列表条目、播放队列条目、书签、播放记录、正在播放记录和标注一并删除，
+    /// 随后删除不再包含任何曲目的专辑和艺术家。只能访问这个文件夹的用户删除后看不到任何内容
+    pub async fn remove_library_folder(&self, id: i32) -> Result<()> {
+        if self.library_folder(id).await?.is_none() {
+            return Err(StorageError::NotFound(format!("Music folder {}", id)));
+        }
+
+        // 停止监视并等待正在进行的扫描结束，避免删除后又写入曲目
+        #[cfg(feature = "scanner")]
+        self.unwatch_folder(id);
+        #[cfg(feature = "scanner")]
+        let _scan = self.scan_lock().lock().await;
+
+        let mut tx = self
+            .pool()
+            .begin()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        // 删除曲目前记下受影响的专辑和艺术家
+        let albums: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT album_id FROM tracks WHERE library_id = ? AND album_id IS NOT NULL",
+        )
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        let artists: Vec<String> = sqlx::query_scalar(
+            r#"SELECT artist_id FROM tracks WHERE library_id = ?1 AND artist_id IS NOT NULL
+               UNION
+               SELECT a.artist_id FROM albums a JOIN tracks t ON t.album_id = a.id
+               WHERE t.library_id = ?1 AND a.artist_id IS NOT NULL"#,
+        )
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        // 播放队列以 JSON 数组保存曲目 ID，移除这些曲目并调整当前位置
+        let removed: HashSet<String> =
+            sqlx::query_scalar("SELECT id FROM tracks WHERE library_id = ?")
+                .bind(id)
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?
+                .into_iter()
+                .collect();
+        let queues: Vec<(String, String, Option<i64>)> =
+            sqlx::query_as("SELECT user_id, track_ids, current_index FROM play_queue")
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        for (user_id, track_ids, current_index) in queues {
+            let track_ids: Vec<String> = serde_json::from_str(&track_ids)?;
+            if !track_ids.iter().any(|t| removed.contains(t)) {
+                continue;
+            }
+            let kept = |ids: &[String]| ids.iter().filter(|t| !removed.contains(*t)).count();
+            // 当前曲目被移除时不再有当前位置
+            let current_index = current_index
+                .and_then(|i| usize::try_from(i).ok())
+                .filter(|&i| track_ids.get(i).is_some_and(|t| !removed.contains(t)))
+                .map(|i| kept(&track_ids[..i]) as i64);
+            let remaining: Vec<&String> =
+                track_ids.iter().filter(|t| !removed.contains(*t)).collect();
+            sqlx::query("UPDATE play_queue SET track_ids = ?, current_index = ? WHERE user_id = ?")
+                .bind(serde_json::to_string(&remaining)?)
+                .bind(current_index)
+                .bind(&user_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        }
+
+        let tracks = "SELECT id FROM tracks WHERE library_id = ?1";
+        let statements = [
+            format!("DELETE FROM playlist_tracks WHERE track_id IN ({})", tracks),
+            format!("DELETE FROM bookmarks WHERE track_id IN ({})", tracks),
+            format!("DELETE FROM scrobbles WHERE track_id IN ({})", tracks),
+            format!("DELETE FROM now_playing WHERE track_id IN ({})", tracks),
+            format!(
+                "UPDATE play_queue SET current_track_id = NULL WHERE current_track_id IN ({})",
+                tracks
+            ),
+            format!(
+                "DELETE FROM annotations WHERE item_type = 'song' AND item_id IN ({})",
+                tracks
+            ),
+            format!(
+                "DELETE FROM share_items WHERE item_type = 'song' AND item_id IN ({})",
+                tracks
+            ),
+            "DELETE FROM tracks WHERE library_id = ?1".to_string(),
+            "DELETE FROM user_music_folders WHERE folder_id = ?1".to_string(),
+            "DELETE FROM music_folders WHERE id = ?1".to_string(),
+        ];
+        for statement in &statements {
+            sqlx::query(statement)
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        }
+
+        // 其他文件夹中仍有曲目的专辑和艺术家保留
+        for (item_type, table, ids, orphaned) in [
+            (
+                "album",
+                "albums",
+                &albums,
+                "NOT EXISTS (SELECT 1 FROM tracks WHERE album_id = ?1)",
+            ),
+            (
+                "artist",
+                "artists",
+                &artists,
+                "NOT EXISTS (SELECT 1 FROM tracks WHERE artist_id = ?1)
+                 AND NOT EXISTS (SELECT 1 FROM albums WHERE artist_id = ?1)",
+            ),
+        ] {
+            for item_id in ids {
+                let deleted = sqlx::query(&format!(
+                    "DELETE FROM {} WHERE id = ?1 AND {}",
+                    table, orphaned
+                ))
+                .bind(item_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+                if deleted.rows_affected() == 0 {
+                    continue;
+                }
+                for ref_table in ["annotations", "share_items"] {
+                    sqlx::query(&format!(
+                        "DELETE FROM {} WHERE item_type = ? AND item_id = ?",
+                        ref_table
+                    ))
+                    .bind(item_type)
+                    .bind(item_id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+                }
+            }
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        self.libraries().write().await.remove(&id);
+        Ok(())
+    }
+
+    /// 获取音乐文件夹的 VFS 实例，首次访问时创建
+    pub async fn library_vfs(&self, library_id: i32) -> Result<SharedVfs> {
+        if let Some(vfs) = self.libraries().read().await.get(&library_id) {
+            return Ok(vfs.clone());
+        }
+
+        let folder = self
+            .library_folder(library_id)
+            .await?
+            .ok_or_else(|| StorageError::NotFound(format!("Music folder {}", library_id)))?;
+        let vfs = match folder.vfs_config {
+            Some(config) => create_vfs(config)?,
+            None => self.vfs().clone(),
+        };
+
+        self.libraries()
+            .write()
+            .await
+            .insert(library_id, vfs.clone());
+        Ok(vfs)
+    }
+
+    /// 将文件路径解析为所在的 VFS 和 VFS 内的路径
+    ///
+    /// 专辑封面位于封面 VFS；不带前缀的路径（包括旧版本保存的专辑封面）位于默认 VFS
+    pub(crate) async fn resolve_path<'a>(&self, path: &'a str) -> Result<(SharedVfs, &'a str)> {
+        if let Some(path) = path.strip_prefix(COVER_PATH_PREFIX) {
+            return Ok((self.cover_vfs().clone(), path));
+        }
+        match parse_library_path(path) {
+            Some((library_id, path)) => Ok((self.library_vfs(library_id).await?, path)),
+            None => Ok((self.vfs().clone(), path)),
+        }
+    }
+}
+
+fn row_to_folder(row: &sqlx::sqlite::SqliteRow) -> Result<LibraryFolder> {
+    let vfs_config = row
+        .get::<Option<String>, _>("vfs_config")
+        .map(|json| serde_json::from_str(&json))
+        .transpose()?;
+
+    Ok(LibraryFolder {
+        id: row.get("id"),
+        name: row.get("name"),
+        path: row.get("path"),
+        vfs_config,
+    })
+}
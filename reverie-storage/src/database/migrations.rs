@@ -0,0 +1,206 @@
+//! 数据库表结构的版本化迁移
+//!
+//! 每个迁移是 `migrations/` 目录下的一个 SQL 脚本，按版本号顺序执行。已执行的版本记录在
+//! `schema_version` 表中，每个迁移与其版本记录在同一个事务中提交。
+//!
+//! 引入版本号之前创建的数据库没有 `schema_version` 表，根据已有的表和列推断其版本。
+
+use chrono::Utc;
+use sqlx::{Connection, Pool, Sqlite, SqliteConnection};
+use tracing::info;
+
+use crate::error::{Result, StorageError};
+
+/// 记录已执行迁移的 SQL
+const RECORD_MIGRATION: &str =
+    "INSERT INTO schema_version (version, description, applied_at) VALUES (?, ?, ?)";
+
+/// 单个迁移步骤
+struct Migration {
+    version: i64,
+    description: &'static str,
+    sql: &'static str,
+}
+
+/// 按版本号排列的全部迁移
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sql: include_str!("migrations/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        description: "api keys and annotations",
+        sql: include_str!("migrations/0002_api_keys_annotations.sql"),
+    },
+    Migration {
+        version: 3,
+        description: "incremental scan",
+        sql: include_str!("migrations/0003_incremental_scan.sql"),
+    },
+    Migration {
+        version: 4,
+        description: "music folders",
+        sql: include_str!("migrations/0004_music_folders.sql"),
+    },
+    Migration {
+        version: 5,
+        description: "track details",
+        sql: include_str!("migrations/0005_track_details.sql"),
+    },
+    Migration {
+        version: 6,
+        description: "full-text search index",
+        sql: include_str!("migrations/0006_search_index.sql"),
+    },
+    Migration {
+        version: 7,
+        description: "now playing",
+        sql: include_str!("migrations/0007_now_playing.sql"),
+    },
+    Migration {
+        version: 8,
+        description: "scrobble forwarding",
+        sql: include_str!("migrations/0008_scrobble_forwarding.sql"),
+    },
+    Migration {
+        version: 9,
+        description: "play queue index",
+        sql: include_str!("migrations/0009_play_queue_index.sql"),
+    },
+    Migration {
+        version: 10,
+        description: "share visits",
+        sql: include_str!("migrations/0010_share_visits.sql"),
+    },
+    Migration {
+        version: 11,
+        description: "credentials",
+        sql: include_str!("migrations/0011_credentials.sql"),
+    },
+    Migration {
+        version: 12,
+        description: "user roles",
+        sql: include_str!("migrations/0012_user_roles.sql"),
+    },
+    Migration {
+        version: 13,
+        description: "music folder restriction",
+        sql: include_str!("migrations/0013_music_folder_restriction.sql"),
+    },
+];
+
+/// 当前程序支持的表结构版本
+pub const SCHEMA_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// 将数据库升级到 [`SCHEMA_VERSION`]
+///
+/// 数据库版本高于当前程序时返回错误，避免旧程序破坏新版本写入的数据。
+/// 全部迁移在同一个连接上执行：SQLite 连接会缓存表结构，其他连接上在迁移前准备的
+/// `SELECT *` 语句读取到的列数可能与实际不符。
+pub(crate) async fn run(pool: &Pool<Sqlite>) -> Result<()> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+    let conn = &mut *conn;
+
+    sqlx::query(
+        r#"CREATE TABLE IF NOT EXISTS schema_version (
+               version INTEGER PRIMARY KEY,
+               description TEXT NOT NULL,
+               applied_at TEXT NOT NULL
+           )"#,
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+    let current = match schema_version(conn).await? {
+        0 => detect_legacy_version(conn).await?,
+        version => version,
+    };
+    if current > SCHEMA_VERSION {
+        return Err(StorageError::DatabaseError(format!(
+            "Database schema version {} is newer than the supported version {}",
+            current, SCHEMA_VERSION
+        )));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let mut tx = conn
+            .begin()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        sqlx::query(migration.sql)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                StorageError::DatabaseError(format!(
+                    "Migration {} ({}) failed: {}",
+                    migration.version, migration.description, e
+                ))
+            })?;
+        sqlx::query(RECORD_MIGRATION)
+            .bind(migration.version)
+            .bind(migration.description)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        tx.commit()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        info!(
+            "Applied database migration {} ({})",
+            migration.version, migration.description
+        );
+    }
+
+    Ok(())
+}
+
+/// 已记录的表结构版本，尚未执行任何迁移时为 0
+pub(crate) async fn schema_version(conn: &mut SqliteConnection) -> Result<i64> {
+    let (version,): (Option<i64>,) = sqlx::query_as("SELECT MAX(version) FROM schema_version")
+        .fetch_one(conn)
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+    Ok(version.unwrap_or(0))
+}
+
+/// 记录引入版本号之前创建的数据库的版本
+///
+/// 版本号随第一个迁移一同引入，已有 `tracks` 表但没有版本记录的数据库即为第 1 版
+async fn detect_legacy_version(conn: &mut SqliteConnection) -> Result<i64> {
+    if !table_exists(conn, "tracks").await? {
+        return Ok(0);
+    }
+
+    let initial = &MIGRATIONS[0];
+    sqlx::query(RECORD_MIGRATION)
+        .bind(initial.version)
+        .bind(initial.description)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+    info!(
+        "Existing database adopted at schema version {}",
+        initial.version
+    );
+    Ok(initial.version)
+}
+
+async fn table_exists(conn: &mut SqliteConnection, table: &str) -> Result<bool> {
+    let (count,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?")
+            .bind(table)
+            .fetch_one(conn)
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+    Ok(count > 0)
+}
@@ -0,0 +1,22 @@
+-- v2：OpenSubsonic API 密钥和按用户区分的收藏、评分
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    api_key TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS annotations (
+    user_id TEXT NOT NULL,
+    item_type TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    starred_at TEXT,
+    rating INTEGER,
+    play_count INTEGER NOT NULL DEFAULT 0,
+    last_played TEXT,
+    PRIMARY KEY (user_id, item_type, item_id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_annotations_item ON annotations(item_type, item_id);
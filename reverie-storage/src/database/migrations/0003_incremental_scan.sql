@@ -0,0 +1,5 @@
+-- v3：增量扫描所需的文件状态
+
+ALTER TABLE tracks ADD COLUMN file_mtime INTEGER;
+ALTER TABLE tracks ADD COLUMN file_etag TEXT;
+ALTER TABLE tracks ADD COLUMN missing INTEGER NOT NULL DEFAULT 0;
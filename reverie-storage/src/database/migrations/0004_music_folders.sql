@@ -0,0 +1,14 @@
+-- v4：多个音乐文件夹，已有的曲目都属于默认文件夹
+
+ALTER TABLE tracks ADD COLUMN library_id INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE music_folders ADD COLUMN vfs_config TEXT;
+
+CREATE TABLE IF NOT EXISTS user_music_folders (
+    user_id TEXT NOT NULL,
+    folder_id INTEGER NOT NULL,
+    PRIMARY KEY (user_id, folder_id),
+    FOREIGN KEY (user_id) REFERENCES users(id),
+    FOREIGN KEY (folder_id) REFERENCES music_folders(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tracks_library ON tracks(library_id);
@@ -0,0 +1,10 @@
+-- v5：曲目的 MusicBrainz ID、位深、ReplayGain 和内嵌歌词
+-- 文件修改时间已在 v3 中以 file_mtime 记录
+
+ALTER TABLE tracks ADD COLUMN mbid TEXT;
+ALTER TABLE tracks ADD COLUMN bit_depth INTEGER;
+ALTER TABLE tracks ADD COLUMN replaygain_track_gain REAL;
+ALTER TABLE tracks ADD COLUMN replaygain_track_peak REAL;
+ALTER TABLE tracks ADD COLUMN replaygain_album_gain REAL;
+ALTER TABLE tracks ADD COLUMN replaygain_album_peak REAL;
+ALTER TABLE tracks ADD COLUMN lyrics TEXT;
@@ -0,0 +1,147 @@
+-- v6：艺术家、专辑和曲目的 FTS5 全文索引
+-- search_items 为每个条目分配索引行号，search_index 的 rowid 与之对应；
+-- 触发器在源表变化时同步索引，艺术家和专辑改名时同步更新引用它们的条目
+
+CREATE TABLE IF NOT EXISTS search_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    item_type TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    UNIQUE(item_type, item_id)
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS search_index USING fts5(
+    title,
+    artist,
+    album,
+    genre,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+-- 曲目
+CREATE TRIGGER IF NOT EXISTS tracks_search_insert AFTER INSERT ON tracks
+BEGIN
+    INSERT OR IGNORE INTO search_items (item_type, item_id) VALUES ('song', NEW.id);
+    INSERT INTO search_index (rowid, title, artist, album, genre)
+    SELECT id, NEW.title,
+           (SELECT name FROM artists WHERE id = NEW.artist_id),
+           (SELECT name FROM albums WHERE id = NEW.album_id),
+           NEW.genre
+    FROM search_items WHERE item_type = 'song' AND item_id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS tracks_search_update AFTER UPDATE ON tracks
+WHEN OLD.id IS NOT NEW.id OR OLD.title IS NOT NEW.title OR OLD.genre IS NOT NEW.genre
+  OR OLD.artist_id IS NOT NEW.artist_id OR OLD.album_id IS NOT NEW.album_id
+BEGIN
+    DELETE FROM search_index WHERE rowid =
+        (SELECT id FROM search_items WHERE item_type = 'song' AND item_id = OLD.id);
+    DELETE FROM search_items WHERE item_type = 'song' AND item_id = OLD.id;
+    INSERT OR IGNORE INTO search_items (item_type, item_id) VALUES ('song', NEW.id);
+    INSERT INTO search_index (rowid, title, artist, album, genre)
+    SELECT id, NEW.title,
+           (SELECT name FROM artists WHERE id = NEW.artist_id),
+           (SELECT name FROM albums WHERE id = NEW.album_id),
+           NEW.genre
+    FROM search_items WHERE item_type = 'song' AND item_id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS tracks_search_delete AFTER DELETE ON tracks
+BEGIN
+    DELETE FROM search_index WHERE rowid =
+        (SELECT id FROM search_items WHERE item_type = 'song' AND item_id = OLD.id);
+    DELETE FROM search_items WHERE item_type = 'song' AND item_id = OLD.id;
+END;
+
+-- 专辑
+CREATE TRIGGER IF NOT EXISTS albums_search_insert AFTER INSERT ON albums
+BEGIN
+    INSERT OR IGNORE INTO search_items (item_type, item_id) VALUES ('album', NEW.id);
+    INSERT INTO search_index (rowid, artist, album, genre)
+    SELECT id, (SELECT name FROM artists WHERE id = NEW.artist_id), NEW.name, NEW.genre
+    FROM search_items WHERE item_type = 'album' AND item_id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS albums_search_update AFTER UPDATE ON albums
+WHEN OLD.id IS NOT NEW.id OR OLD.name IS NOT NEW.name OR OLD.genre IS NOT NEW.genre
+  OR OLD.artist_id IS NOT NEW.artist_id
+BEGIN
+    DELETE FROM search_index WHERE rowid =
+        (SELECT id FROM search_items WHERE item_type = 'album' AND item_id = OLD.id);
+    DELETE FROM search_items WHERE item_type = 'album' AND item_id = OLD.id;
+    INSERT OR IGNORE INTO search_items (item_type, item_id) VALUES ('album', NEW.id);
+    INSERT INTO search_index (rowid, artist, album, genre)
+    SELECT id, (SELECT name FROM artists WHERE id = NEW.artist_id), NEW.name, NEW.genre
+    FROM search_items WHERE item_type = 'album' AND item_id = NEW.id;
+    UPDATE search_index SET album = NEW.name
+    WHERE OLD.name IS NOT NEW.name AND rowid IN (
+        SELECT si.id FROM search_items si JOIN tracks t ON t.id = si.item_id
+        WHERE si.item_type = 'song' AND t.album_id = NEW.id
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS albums_search_delete AFTER DELETE ON albums
+BEGIN
+    DELETE FROM search_index WHERE rowid =
+        (SELECT id FROM search_items WHERE item_type = 'album' AND item_id = OLD.id);
+    DELETE FROM search_items WHERE item_type = 'album' AND item_id = OLD.id;
+END;
+
+-- 艺术家
+CREATE TRIGGER IF NOT EXISTS artists_search_insert AFTER INSERT ON artists
+BEGIN
+    INSERT OR IGNORE INTO search_items (item_type, item_id) VALUES ('artist', NEW.id);
+    INSERT INTO search_index (rowid, artist)
+    SELECT id, NEW.name FROM search_items WHERE item_type = 'artist' AND item_id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS artists_search_update AFTER UPDATE ON artists
+WHEN OLD.id IS NOT NEW.id OR OLD.name IS NOT NEW.name
+BEGIN
+    DELETE FROM search_index WHERE rowid =
+        (SELECT id FROM search_items WHERE item_type = 'artist' AND item_id = OLD.id);
+    DELETE FROM search_items WHERE item_type = 'artist' AND item_id = OLD.id;
+    INSERT OR IGNORE INTO search_items (item_type, item_id) VALUES ('artist', NEW.id);
+    INSERT INTO search_index (rowid, artist)
+    SELECT id, NEW.name FROM search_items WHERE item_type = 'artist' AND item_id = NEW.id;
+    UPDATE search_index SET artist = NEW.name
+    WHERE OLD.name IS NOT NEW.name AND rowid IN (
+        SELECT si.id FROM search_items si JOIN tracks t ON t.id = si.item_id
+        WHERE si.item_type = 'song' AND t.artist_id = NEW.id
+        UNION
+        SELECT si.id FROM search_items si JOIN albums a ON a.id = si.item_id
+        WHERE si.item_type = 'album' AND a.artist_id = NEW.id
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS artists_search_delete AFTER DELETE ON artists
+BEGIN
+    DELETE FROM search_index WHERE rowid =
+        (SELECT id FROM search_items WHERE item_type = 'artist' AND item_id = OLD.id);
+    DELETE FROM search_items WHERE item_type = 'artist' AND item_id = OLD.id;
+END;
+
+-- 索引已有的条目
+INSERT OR IGNORE INTO search_items (item_type, item_id)
+SELECT 'artist', id FROM artists
+UNION ALL SELECT 'album', id FROM albums
+UNION ALL SELECT 'song', id FROM tracks;
+
+INSERT INTO search_index (rowid, artist)
+SELECT si.id, ar.name
+FROM search_items si JOIN artists ar ON ar.id = si.item_id
+WHERE si.item_type = 'artist';
+
+INSERT INTO search_index (rowid, artist, album, genre)
+SELECT si.id, ar.name, a.name, a.genre
+FROM search_items si
+JOIN albums a ON a.id = si.item_id
+LEFT JOIN artists ar ON ar.id = a.artist_id
+WHERE si.item_type = 'album';
+
+INSERT INTO search_index (rowid, title, artist, album, genre)
+SELECT si.id, t.title, ar.name, a.name, t.genre
+FROM search_items si
+JOIN tracks t ON t.id = si.item_id
+LEFT JOIN albums a ON a.id = t.album_id
+LEFT JOIN artists ar ON ar.id = t.artist_id
+WHERE si.item_type = 'song';
@@ -0,0 +1,15 @@
+-- v7：正在播放的曲目
+-- 每个用户的每个客户端保留一条记录，过期后不再出现在 getNowPlaying 中；
+-- 记录的 ID 作为 playerId 返回
+
+CREATE TABLE IF NOT EXISTS now_playing (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL,
+    player_name TEXT NOT NULL,
+    track_id TEXT NOT NULL,
+    started_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    UNIQUE(user_id, player_name),
+    FOREIGN KEY (track_id) REFERENCES tracks(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
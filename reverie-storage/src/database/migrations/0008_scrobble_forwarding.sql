@@ -0,0 +1,26 @@
+-- v8：向外部服务转发播放记录
+-- scrobble_accounts 保存用户在各服务上的令牌；scrobble_queue 保存尚未成功发送的播放记录，
+-- 曲目信息以 JSON 快照保存，曲目删除后仍可发送
+
+CREATE TABLE IF NOT EXISTS scrobble_accounts (
+    user_id TEXT NOT NULL,
+    service TEXT NOT NULL,
+    token TEXT NOT NULL,
+    url TEXT,
+    PRIMARY KEY (user_id, service),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS scrobble_queue (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL,
+    service TEXT NOT NULL,
+    track TEXT NOT NULL,
+    listened_at TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT NOT NULL,
+    last_error TEXT,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_scrobble_queue_next_attempt ON scrobble_queue(next_attempt_at);
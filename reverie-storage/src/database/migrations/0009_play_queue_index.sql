@@ -0,0 +1,3 @@
+-- v9：播放队列记录当前曲目的位置，队列中有重复曲目时仍能定位
+
+ALTER TABLE play_queue ADD COLUMN current_index INTEGER;
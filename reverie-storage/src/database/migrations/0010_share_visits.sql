@@ -0,0 +1,3 @@
+-- v10：记录分享链接最后一次被访问的时间
+
+ALTER TABLE shares ADD COLUMN last_visited_at TEXT;
@@ -0,0 +1,5 @@
+-- v11：password_hash 改为保存 Argon2 哈希，令牌验证所需的密码加密后保存在 subsonic_password 中
+-- 旧数据库中的明文密码在启动时由 DatabaseStorage::initialize 转换
+
+ALTER TABLE users ADD COLUMN subsonic_password TEXT;
+ALTER TABLE users ADD COLUMN must_change_password INTEGER NOT NULL DEFAULT 0;
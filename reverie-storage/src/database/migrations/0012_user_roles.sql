@@ -0,0 +1,16 @@
+-- v12：保存用户角色和最大码率，max_bit_rate 为 NULL 表示不限制
+-- 默认值与旧版本的实际权限一致，已有用户升级后仍可以播放、下载和编辑播放列表
+
+ALTER TABLE users ADD COLUMN settings_role INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE users ADD COLUMN stream_role INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE users ADD COLUMN jukebox_role INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN download_role INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE users ADD COLUMN upload_role INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN playlist_role INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE users ADD COLUMN cover_art_role INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN comment_role INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN podcast_role INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN share_role INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN video_conversion_role INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN scrobbling_enabled INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE users ADD COLUMN max_bit_rate INTEGER;
@@ -0,0 +1,7 @@
+-- v13：记录用户是否限制了音乐文件夹
+-- 限制的文件夹全部被删除后用户仍受限制，不会因为文件夹列表为空而变为可以访问所有文件夹
+
+ALTER TABLE users ADD COLUMN music_folders_restricted INTEGER NOT NULL DEFAULT 0;
+
+UPDATE users SET music_folders_restricted = 1
+WHERE id IN (SELECT user_id FROM user_music_folders);
@@ -4,13 +4,21 @@
 
 pub mod config;
 pub mod core;
+pub mod credentials;
+pub mod library;
+pub mod migrations;
 pub mod track;
 pub mod album;
 pub mod user_playlist;
 pub mod subsonic;
 #[cfg(feature = "scanner")]
 pub mod scan;
+#[cfg(feature = "scrobbler")]
+pub mod scrobble;
 
 // 重新导出主要类型
 pub use config::DatabaseConfig;
 pub use core::DatabaseStorage;
+pub use migrations::SCHEMA_VERSION;
+#[cfg(feature = "scrobbler")]
+pub use scrobble::ScrobbleRetryWorker;
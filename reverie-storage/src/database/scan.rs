@@ -1,55 +1,273 @@
 //! 媒体库扫描功能实现
 //!
 //! 为 DatabaseStorage 提供媒体库扫描和数据持久化功能
+//!
+//! 同一时间只运行一次扫描，监视器触发的扫描和手动扫描排队依次执行，不会同时写入同一文件夹。
+//! 启用监视后，添加、修改和删除音乐文件夹时随之启动、重启或停止对应的监视器。
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, MutexGuard};
 
+use async_trait::async_trait;
 use chrono::Utc;
-use tracing::{error, info};
+use sqlx::Row;
+use tracing::{error, info, warn};
 
+use crate::database::library::cover_path;
 use crate::error::{Result, StorageError};
-use crate::scanner::{MediaScanner, ScanResult};
-use crate::DatabaseStorage;
-use reverie_core::SubsonicScanStatus;
+use crate::scanner::{
+    ids, FileState, LibraryWatcher, MediaScanner, RescanTarget, ScanResult, WatcherConfig,
+};
+use crate::{DatabaseStorage, LibraryFolder};
+use reverie_core::{ScanStats, SubsonicScanStatus};
+
+/// 已入库曲目的文件信息
+struct StoredTrack {
+    id: String,
+    state: FileState,
+    missing: bool,
+}
+
+/// 各音乐文件夹的监视器
+#[derive(Default)]
+pub(crate) struct LibraryWatchers {
+    /// 为 `None` 时未启用监视
+    config: Option<WatcherConfig>,
+    watchers: HashMap<i32, LibraryWatcher>,
+}
 
 impl DatabaseStorage {
-    /// 执行媒体库扫描
+    /// 扫描音乐文件夹 `library_id` 中的 `path` 目录
     ///
-    /// 扫描指定路径下的所有音频文件，提取元数据并存储到数据库
-    pub async fn perform_scan(&self, path: &str) -> Result<ScanResult> {
-        let scanner = MediaScanner::new(self.vfs().clone());
-        
+    /// 默认只重新解析新增或修改过的文件，文件已消失的曲目会被标记为缺失。
+    /// `full_scan` 为 true 时忽略已记录的文件状态，重新解析所有文件。
+    pub async fn perform_scan(
+        &self,
+        library_id: i32,
+        path: &str,
+        full_scan: bool,
+    ) -> Result<ScanStats> {
+        self.scan_folders(&[(library_id, path.to_string())], full_scan).await
+    }
+
+    /// 依次扫描多个音乐文件夹中的目录，参数为文件夹 ID 和扫描路径
+    ///
+    /// 扫描状态在开始时设为进行中，全部文件夹扫描完后才结束。某个文件夹扫描失败时
+    /// 继续扫描其余文件夹，最后返回第一个错误。
+    pub async fn scan_folders(
+        &self,
+        folders: &[(i32, String)],
+        full_scan: bool,
+    ) -> Result<ScanStats> {
+        let _guard = self.scan_lock().lock().await;
+
         // 更新扫描状态为正在扫描
         self.set_scan_status(true, None).await?;
+        self.set_scan_type(if full_scan { "full" } else { "quick" }).await?;
+
+        let mut total = ScanStats::default();
+        let mut first_error = None;
+        for (library_id, path) in folders {
+            match self.scan_folder(*library_id, path, full_scan).await {
+                Ok(stats) => {
+                    info!(
+                        "Scan of {} finished: {} new, {} updated, {} missing, {} errors",
+                        path,
+                        stats.new_tracks,
+                        stats.updated_tracks,
+                        stats.deleted_tracks,
+                        stats.errors
+                    );
+                    total.scanned_files += stats.scanned_files;
+                    total.new_tracks += stats.new_tracks;
+                    total.updated_tracks += stats.updated_tracks;
+                    total.deleted_tracks += stats.deleted_tracks;
+                    total.errors += stats.errors;
+                    // 库的总数在每次保存后重新统计，以最后一次为准
+                    total.total_tracks = stats.total_tracks;
+                    total.total_albums = stats.total_albums;
+                    total.total_artists = stats.total_artists;
+                }
+                Err(e) => {
+                    error!("Scan of music folder {} failed: {}", library_id, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
 
-        // 执行扫描
-        let result = scanner.scan(path).await;
-
-        match &result {
-            Ok(scan_result) => {
-                // 将扫描结果保存到数据库
-                self.save_scan_result(scan_result).await?;
-                
-                // 更新扫描状态
-                let count = scan_result.tracks.len() as i64;
-                self.set_scan_status(false, Some(count)).await?;
+        match first_error {
+            None => {
+                self.set_scan_status(false, Some(total.total_tracks as i64)).await?;
+                Ok(total)
             }
-            Err(e) => {
+            Some(e) => {
                 // 记录错误
                 self.set_scan_error(&e.to_string()).await?;
+                Err(e)
+            }
+        }
+    }
+
+    /// 扫描一个文件夹并保存结果，调用方负责加锁和更新扫描状态
+    async fn scan_folder(&self, library_id: i32, path: &str, full_scan: bool) -> Result<ScanStats> {
+        let scanner =
+            MediaScanner::new(self.library_vfs(library_id).await?).with_library(library_id);
+
+        let stored = self.load_stored_tracks(library_id, path).await?;
+        // 仍使用旧 ID 的曲目需要重新解析，以便迁移其专辑和艺术家的 ID
+        let known: HashMap<String, FileState> = if full_scan {
+            HashMap::new()
+        } else {
+            stored
+                .iter()
+                .filter(|(path, track)| track.id == ids::track_id(library_id, path))
+                .map(|(path, track)| (path.clone(), track.state.clone()))
+                .collect()
+        };
+
+        // 执行扫描并将结果保存到数据库
+        let scan_result = scanner.scan_incremental(path, &known).await?;
+        self.save_scan_result(library_id, &scan_result, &stored).await
+    }
+
+    /// 监视所有音乐文件夹，文件变化时增量扫描受影响的目录
+    ///
+    /// 之后添加、修改或删除的文件夹随之开始、重新开始或停止监视。
+    /// 无法监视的文件夹只记录日志，不影响其他文件夹
+    pub async fn watch_library(&self, config: WatcherConfig) -> Result<()> {
+        let folders = self.library_folders().await?;
+        let mut watchers = self.library_watchers();
+        watchers.config = Some(config);
+        watchers.watchers.clear();
+        for folder in folders {
+            self.start_watcher(&mut watchers, folder);
+        }
+        Ok(())
+    }
+
+    /// 停止监视所有音乐文件夹
+    pub fn unwatch_library(&self) {
+        let mut watchers = self.library_watchers();
+        watchers.config = None;
+        watchers.watchers.clear();
+    }
+
+    /// 文件夹添加或修改后重新监视，未启用监视时不做任何事
+    pub(crate) async fn rewatch_folder(&self, id: i32) -> Result<()> {
+        if self.library_watchers().config.is_none() {
+            return Ok(());
+        }
+        let folder = self.library_folder(id).await?;
+        let mut watchers = self.library_watchers();
+        watchers.watchers.remove(&id);
+        if let Some(folder) = folder {
+            self.start_watcher(&mut watchers, folder);
+        }
+        Ok(())
+    }
+
+    /// 文件夹删除后停止监视
+    pub(crate) fn unwatch_folder(&self, id: i32) {
+        self.library_watchers().watchers.remove(&id);
+    }
+
+    fn library_watchers(&self) -> MutexGuard<'_, LibraryWatchers> {
+        self.watchers().lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn start_watcher(&self, watchers: &mut LibraryWatchers, folder: LibraryFolder) {
+        let Some(config) = watchers.config.clone() else {
+            return;
+        };
+        let vfs_config = folder
+            .vfs_config
+            .unwrap_or_else(|| self.config().vfs_config.clone());
+        let target = Arc::new(FolderRescan {
+            storage: self.clone(),
+            library_id: folder.id,
+        });
+        match LibraryWatcher::start(&vfs_config, vec![folder.path], target, config) {
+            Ok(watcher) => {
+                watchers.watchers.insert(folder.id, watcher);
             }
+            Err(e) => warn!("Cannot watch music folder {}: {}", folder.name, e),
+        }
+    }
+
+    /// 加载音乐文件夹中扫描路径下已入库的曲目，以文件路径为键
+    async fn load_stored_tracks(
+        &self,
+        library_id: i32,
+        root: &str,
+    ) -> Result<HashMap<String, StoredTrack>> {
+        let mut prefix = root.trim_start_matches('/').to_string();
+        if !prefix.is_empty() && !prefix.ends_with('/') {
+            prefix.push('/');
         }
 
-        result
+        let rows = sqlx::query(
+            r#"SELECT id, file_path, file_size, file_mtime, file_etag, missing
+               FROM tracks WHERE library_id = ?"#,
+        )
+        .bind(library_id)
+        .fetch_all(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|r| {
+                let path: String = r.get("file_path");
+                if !path.starts_with(&prefix) {
+                    return None;
+                }
+                let track = StoredTrack {
+                    id: r.get("id"),
+                    state: FileState {
+                        size: r.get("file_size"),
+                        last_modified: r.get("file_mtime"),
+                        etag: r.get("file_etag"),
+                    },
+                    missing: r.get::<i64, _>("missing") != 0,
+                };
+                Some((path, track))
+            })
+            .collect())
     }
 
     /// 将扫描结果保存到数据库
-    async fn save_scan_result(&self, result: &ScanResult) -> Result<()> {
+    async fn save_scan_result(
+        &self,
+        library_id: i32,
+        result: &ScanResult,
+        stored: &HashMap<String, StoredTrack>,
+    ) -> Result<ScanStats> {
         let now = Utc::now().to_rfc3339();
+        let mut stats = ScanStats {
+            scanned_files: result.files.len() as u64,
+            errors: result.errors,
+            ..Default::default()
+        };
 
-        // 保存艺术家
+        // 保存艺术家，同名艺术家的旧 ID 迁移为确定性 ID
         for artist in result.artists.values() {
+            let legacy: Vec<String> =
+                sqlx::query_scalar("SELECT id FROM artists WHERE name = ? COLLATE NOCASE AND id != ?")
+                    .bind(&artist.name)
+                    .bind(&artist.id)
+                    .fetch_all(self.pool())
+                    .await
+                    .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+            for old in legacy.iter().filter(|id| !ids::is_stable_id(id)) {
+                self.migrate_id("artist", old, &artist.id).await?;
+            }
+
             sqlx::query(
-                r#"INSERT OR REPLACE INTO artists (id, name, created_at, updated_at)
-                   VALUES (?, ?, ?, ?)"#,
+                r#"INSERT INTO artists (id, name, created_at, updated_at)
+                   VALUES (?, ?, ?, ?)
+                   ON CONFLICT(id) DO UPDATE SET
+                       name = excluded.name,
+                       updated_at = excluded.updated_at"#,
             )
             .bind(&artist.id)
             .bind(&artist.name)
@@ -62,28 +280,36 @@ impl DatabaseStorage {
 
         info!("Saved {} artists", result.artists.len());
 
-        // 保存专辑
+        // 保存专辑，曲目数和时长在曲目保存后统一计算
         for album in result.albums.values() {
-            // 计算专辑总时长
-            let duration: f32 = result
-                .tracks
-                .iter()
-                .filter(|t| album.tracks.contains(&t.id))
-                .map(|t| t.duration)
-                .sum();
+            let legacy: Vec<String> = sqlx::query_scalar(
+                "SELECT id FROM albums WHERE name = ? COLLATE NOCASE AND artist_id IS ? AND id != ?",
+            )
+            .bind(&album.name)
+            .bind(&album.artist_id)
+            .bind(&album.id)
+            .fetch_all(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+            for old in legacy.iter().filter(|id| !ids::is_stable_id(id)) {
+                self.migrate_id("album", old, &album.id).await?;
+            }
 
             sqlx::query(
-                r#"INSERT OR REPLACE INTO albums 
-                   (id, name, artist_id, year, genre, song_count, duration, created_at, updated_at)
-                   VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+                r#"INSERT INTO albums (id, name, artist_id, year, genre, created_at, updated_at)
+                   VALUES (?, ?, ?, ?, ?, ?, ?)
+                   ON CONFLICT(id) DO UPDATE SET
+                       name = excluded.name,
+                       artist_id = excluded.artist_id,
+                       year = COALESCE(excluded.year, albums.year),
+                       genre = COALESCE(excluded.genre, albums.genre),
+                       updated_at = excluded.updated_at"#,
             )
             .bind(&album.id)
             .bind(&album.name)
             .bind(&album.artist_id)
             .bind(album.year)
             .bind(&album.genre)
-            .bind(album.tracks.len() as i32)
-            .bind(duration)
             .bind(&now)
             .bind(&now)
             .execute(self.pool())
@@ -95,34 +321,50 @@ impl DatabaseStorage {
 
         // 保存曲目
         for track in &result.tracks {
-            // 查找对应的专辑 ID
-            let album_id = result
-                .albums
-                .values()
-                .find(|a| a.tracks.contains(&track.id))
-                .map(|a| a.id.clone());
-
-            // 查找对应的艺术家 ID
-            let artist_key = track
-                .album_artist
-                .as_ref()
-                .or(track.artist.as_ref())
-                .map(|s| s.to_lowercase());
-            let artist_id = artist_key
-                .and_then(|k| result.artists.get(&k))
-                .map(|a| a.id.clone());
+            let album_id = &track.album_id;
+            let artist_id = &track.artist_id;
+
+            match stored.get(&track.file_path) {
+                Some(existing) => {
+                    // 旧版本扫描器生成的随机 ID 迁移为确定性 ID
+                    if existing.id != track.id {
+                        self.migrate_id("song", &existing.id, &track.id).await?;
+                    }
+                    stats.updated_tracks += 1;
+                }
+                None => stats.new_tracks += 1,
+            }
 
             sqlx::query(
-                r#"INSERT OR REPLACE INTO tracks 
+                r#"INSERT INTO tracks 
                    (id, title, album_id, artist_id, duration, file_path, file_size, 
                     bitrate, sample_rate, channels, format, track_number, disc_number, 
-                    year, genre, created_at, updated_at)
-                   VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+                    year, genre, file_mtime, file_etag, missing, library_id, created_at, updated_at)
+                   VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 0, ?, ?, ?)
+                   ON CONFLICT(id) DO UPDATE SET
+                       title = excluded.title,
+                       album_id = excluded.album_id,
+                       artist_id = excluded.artist_id,
+                       duration = excluded.duration,
+                       file_path = excluded.file_path,
+                       file_size = excluded.file_size,
+                       bitrate = excluded.bitrate,
+                       sample_rate = excluded.sample_rate,
+                       channels = excluded.channels,
+                       format = excluded.format,
+                       track_number = excluded.track_number,
+                       disc_number = excluded.disc_number,
+                       year = excluded.year,
+                       genre = excluded.genre,
+                       file_mtime = excluded.file_mtime,
+                       file_etag = excluded.file_etag,
+                       missing = 0,
+                       updated_at = excluded.updated_at"#,
             )
             .bind(&track.id)
             .bind(&track.title)
-            .bind(&album_id)
-            .bind(&artist_id)
+            .bind(album_id)
+            .bind(artist_id)
             .bind(track.duration as i64)
             .bind(&track.file_path)
             .bind(track.file_size)
@@ -134,6 +376,9 @@ impl DatabaseStorage {
             .bind(track.disc_number)
             .bind(track.year)
             .bind(&track.genre)
+            .bind(track.last_modified)
+            .bind(&track.etag)
+            .bind(library_id)
             .bind(&now)
             .bind(&now)
             .execute(self.pool())
@@ -141,14 +386,14 @@ impl DatabaseStorage {
             .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
             // 如果有封面图片，保存到专辑
-            if let (Some(album_id), Some(cover_data)) = (&album_id, &track.cover_data) {
-                // 生成封面路径
-                let cover_path = format!(".covers/{}.jpg", album_id);
-                
-                // 通过 VFS 保存封面
+            if let (Some(album_id), Some(cover_data)) = (album_id, &track.cover_data) {
+                let cover_name = format!("{}.jpg", album_id);
+                let cover_path = cover_path(&cover_name);
+
+                // 封面保存在封面 VFS 中，而不是曲目所在的音乐文件夹
                 if let Err(e) = self
-                    .vfs()
-                    .write(&cover_path, bytes::Bytes::from(cover_data.clone()))
+                    .cover_vfs()
+                    .write(&cover_name, bytes::Bytes::from(cover_data.clone()))
                     .await
                 {
                     error!("Failed to save cover art for album {}: {}", album_id, e);
@@ -165,9 +410,138 @@ impl DatabaseStorage {
 
         info!("Saved {} tracks", result.tracks.len());
 
+        // 文件已消失的曲目标记为缺失，重新出现的取消标记
+        let files: HashSet<&str> = result.files.iter().map(String::as_str).collect();
+        for (path, track) in stored {
+            let present = files.contains(path.as_str());
+            if present != track.missing {
+                continue;
+            }
+            sqlx::query(
+                "UPDATE tracks SET missing = ?, updated_at = ? WHERE library_id = ? AND file_path = ?",
+            )
+            .bind(!present)
+            .bind(&now)
+            .bind(library_id)
+            .bind(path)
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+            if !present {
+                stats.deleted_tracks += 1;
+            }
+        }
+
+        // 增量扫描的结果只包含变化的曲目，专辑统计需按数据库中的曲目重新计算
+        sqlx::query(
+            r#"UPDATE albums SET
+                   song_count = (SELECT COUNT(*) FROM tracks
+                                 WHERE tracks.album_id = albums.id AND tracks.missing = 0),
+                   duration = (SELECT COALESCE(SUM(tracks.duration), 0) FROM tracks
+                               WHERE tracks.album_id = albums.id AND tracks.missing = 0)"#,
+        )
+        .execute(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
         // 更新流派表
         self.update_genres().await?;
 
+        let (tracks, albums, artists): (i64, i64, i64) = sqlx::query_as(
+            r#"SELECT (SELECT COUNT(*) FROM tracks WHERE missing = 0),
+                      (SELECT COUNT(*) FROM albums),
+                      (SELECT COUNT(*) FROM artists)"#,
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        stats.total_tracks = tracks as u64;
+        stats.total_albums = albums as u64;
+        stats.total_artists = artists as u64;
+
+        Ok(stats)
+    }
+
+    /// 将旧的随机 ID 迁移为新 ID，并更新所有引用该 ID 的记录
+    ///
+    /// 新 ID 已存在时保留新记录，旧记录及与之冲突的引用被删除
+    async fn migrate_id(&self, item_type: &str, old: &str, new: &str) -> Result<()> {
+        let (table, references): (&str, &[(&str, &str)]) = match item_type {
+            "song" => (
+                "tracks",
+                &[
+                    ("playlist_tracks", "track_id"),
+                    ("bookmarks", "track_id"),
+                    ("scrobbles", "track_id"),
+                    ("now_playing", "track_id"),
+                    ("play_queue", "current_track_id"),
+                ],
+            ),
+            "album" => ("albums", &[("tracks", "album_id")]),
+            "artist" => ("artists", &[("albums", "artist_id"), ("tracks", "artist_id")]),
+            _ => {
+                return Err(StorageError::DatabaseError(format!(
+                    "Unknown item type: {}",
+                    item_type
+                )))
+            }
+        };
+
+        // UPDATE OR IGNORE 跳过的行与新 ID 的已有记录冲突，随后直接删除
+        let mut statements = vec![
+            format!("UPDATE OR IGNORE {} SET id = ?2 WHERE id = ?1", table),
+            format!("DELETE FROM {} WHERE id = ?1", table),
+        ];
+        for (ref_table, column) in references {
+            statements.push(format!(
+                "UPDATE OR IGNORE {0} SET {1} = ?2 WHERE {1} = ?1",
+                ref_table, column
+            ));
+            statements.push(format!("DELETE FROM {0} WHERE {1} = ?1", ref_table, column));
+        }
+        for (ref_table, column) in [("annotations", "item_id"), ("share_items", "item_id")] {
+            statements.push(format!(
+                "UPDATE OR IGNORE {0} SET {1} = ?2 WHERE item_type = ?3 AND {1} = ?1",
+                ref_table, column
+            ));
+            statements.push(format!(
+                "DELETE FROM {0} WHERE item_type = ?3 AND {1} = ?1",
+                ref_table, column
+            ));
+        }
+        if item_type == "song" {
+            // 播放队列以 JSON 数组保存曲目 ID
+            statements.push(
+                r#"UPDATE play_queue
+                   SET track_ids = REPLACE(track_ids, '"' || ?1 || '"', '"' || ?2 || '"')"#
+                    .to_string(),
+            );
+        }
+
+        // 外键检查推迟到提交时，此时所有引用均已指向新 ID
+        let mut tx = self
+            .pool()
+            .begin()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        sqlx::query("PRAGMA defer_foreign_keys = ON")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        for statement in &statements {
+            sqlx::query(statement)
+                .bind(old)
+                .bind(new)
+                .bind(item_type)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        }
+        tx.commit()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        info!("Migrated {} ID {} to {}", item_type, old, new);
         Ok(())
     }
 
@@ -210,6 +584,17 @@ impl DatabaseStorage {
         Ok(())
     }
 
+    /// 记录本次扫描的类型
+    async fn set_scan_type(&self, scan_type: &str) -> Result<()> {
+        sqlx::query("UPDATE scan_status SET scan_type = ? WHERE id = 1")
+            .bind(scan_type)
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
     /// 设置扫描错误
     async fn set_scan_error(&self, error: &str) -> Result<()> {
         sqlx::query("UPDATE scan_status SET scanning = 0, error = ? WHERE id = 1")
@@ -227,3 +612,31 @@ impl DatabaseStorage {
         self.get_scan_status().await
     }
 }
+
+/// 监视器触发时扫描所属的音乐文件夹
+struct FolderRescan {
+    storage: DatabaseStorage,
+    library_id: i32,
+}
+
+#[async_trait]
+impl RescanTarget for FolderRescan {
+    async fn rescan(&self, path: &str) -> Result<()> {
+        self.storage
+            .perform_scan(self.library_id, path, false)
+            .await
+            .map(|_| ())
+    }
+
+    async fn has_tracks_under(&self, dir: &str) -> Result<bool> {
+        sqlx::query_scalar(
+            r#"SELECT EXISTS (SELECT 1 FROM tracks
+                              WHERE library_id = ?1 AND substr(file_path, 1, length(?2)) = ?2)"#,
+        )
+        .bind(self.library_id)
+        .bind(dir)
+        .fetch_one(self.storage.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))
+    }
+}
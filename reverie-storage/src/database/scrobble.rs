@@ -0,0 +1,393 @@
+//! 播放记录转发
+//!
+//! 用户在外部服务上的账号保存在 `scrobble_accounts` 表中，令牌与 Subsonic 密码一样加密保存。
+//! 提交的播放记录与本地播放记录在同一个事务中写入 `scrobble_queue`，随后在后台发送；
+//! 发送失败的记录留在队列中，由 [`ScrobbleRetryWorker`] 按退避间隔重试，
+//! 超过 [`MAX_ATTEMPTS`] 次后丢弃。
+//!
+//! 发送前先在一个短事务中领取到期的记录，把 `next_attempt_at` 推迟 [`CLAIM_TIMEOUT`]，
+//! 同时处理队列的其他任务不会再取到这些记录；请求外部服务时不持有任何锁。
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::{sqlite::SqliteRow, Row, SqliteConnection};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::error::{Result, StorageError};
+use crate::scrobbler::{ListenBrainzForwarder, ScrobbleAccount, ScrobbleForwarder, ScrobbleTrack};
+use crate::DatabaseStorage;
+
+/// 播放记录最多尝试发送的次数
+pub const MAX_ATTEMPTS: i64 = 12;
+
+/// 第一次重试前的等待时间，之后每次失败翻倍
+const RETRY_BASE_DELAY: chrono::Duration = chrono::Duration::minutes(1);
+
+/// 重试间隔的上限
+const RETRY_MAX_DELAY: chrono::Duration = chrono::Duration::hours(6);
+
+/// 领取的记录在这段时间后未处理完（例如进程退出）时重新发送
+const CLAIM_TIMEOUT: chrono::Duration = chrono::Duration::minutes(5);
+
+/// 以 JSON 形式读取曲目信息，`t` 为曲目表的别名
+const TRACK_JSON: &str = r#"SELECT json_object('title', t.title, 'artist', ar.name,
+                                                'album', a.name, 'duration', t.duration,
+                                                'track_number', t.track_number)
+                            FROM tracks t
+                            LEFT JOIN albums a ON t.album_id = a.id
+                            LEFT JOIN artists ar ON t.artist_id = ar.id
+                            WHERE t.id = ?"#;
+
+/// 已注册的转发器
+pub(crate) struct Scrobblers {
+    forwarders: RwLock<HashMap<String, Arc<dyn ScrobbleForwarder>>>,
+}
+
+impl Default for Scrobblers {
+    /// ListenBrainz 不需要服务端配置，默认注册；Last.fm 需要应用密钥，由调用方注册
+    fn default() -> Self {
+        let listenbrainz: Arc<dyn ScrobbleForwarder> = Arc::new(ListenBrainzForwarder::new());
+        let forwarders = HashMap::from([(listenbrainz.service().to_string(), listenbrainz)]);
+        Self {
+            forwarders: RwLock::new(forwarders),
+        }
+    }
+}
+
+/// 第 `attempts` 次发送失败后等待的时间
+fn retry_delay(attempts: i64) -> chrono::Duration {
+    let exponent = (attempts - 1).clamp(0, 16) as u32;
+    (RETRY_BASE_DELAY * 2i32.pow(exponent)).min(RETRY_MAX_DELAY)
+}
+
+/// 定期重试发送队列中的播放记录，丢弃时停止
+pub struct ScrobbleRetryWorker {
+    task: JoinHandle<()>,
+}
+
+impl Drop for ScrobbleRetryWorker {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl DatabaseStorage {
+    /// 注册转发器，替换同一服务已注册的转发器
+    pub fn register_scrobble_forwarder(&self, forwarder: Arc<dyn ScrobbleForwarder>) {
+        self.scrobblers()
+            .forwarders
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(forwarder.service().to_string(), forwarder);
+    }
+
+    /// 服务是否注册了转发器
+    pub fn has_scrobble_forwarder(&self, service: &str) -> bool {
+        self.scrobble_forwarder(service).is_some()
+    }
+
+    pub(crate) fn scrobble_forwarder(&self, service: &str) -> Option<Arc<dyn ScrobbleForwarder>> {
+        self.scrobblers()
+            .forwarders
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(service)
+            .cloned()
+    }
+
+    /// 已注册转发器的服务名称，JSON 数组形式，用于 `json_each`
+    fn scrobble_services(&self) -> Result<String> {
+        let forwarders = self
+            .scrobblers()
+            .forwarders
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+        Ok(serde_json::to_string(&forwarders.keys().collect::<Vec<_>>())?)
+    }
+
+    async fn scrobble_user_id(&self, username: &str) -> Result<String> {
+        let user_id: Option<String> = sqlx::query_scalar("SELECT id FROM users WHERE username = ?")
+            .bind(username)
+            .fetch_optional(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        user_id.ok_or_else(|| StorageError::NotFound(format!("User {}", username)))
+    }
+
+    /// 解密保存的令牌，密钥更换后无法解密时返回 `None`
+    fn scrobble_token(&self, service: &str, encrypted: &str) -> Option<String> {
+        match self.cipher().decrypt(encrypted) {
+            Ok(token) => Some(token),
+            Err(e) => {
+                warn!("Cannot decrypt the {} token: {}", service, e);
+                None
+            }
+        }
+    }
+
+    /// 获取用户配置的外部服务账号，无法解密令牌的账号不返回
+    pub async fn scrobble_accounts(&self, username: &str) -> Result<Vec<ScrobbleAccount>> {
+        let rows = sqlx::query(
+            r#"SELECT sa.service, sa.token, sa.url
+               FROM scrobble_accounts sa JOIN users u ON sa.user_id = u.id
+               WHERE u.username = ? ORDER BY sa.service"#,
+        )
+        .bind(username)
+        .fetch_all(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|r| {
+                let service: String = r.get("service");
+                let token = self.scrobble_token(&service, r.get("token"))?;
+                Some(ScrobbleAccount {
+                    service,
+                    token,
+                    url: r.get("url"),
+                })
+            })
+            .collect())
+    }
+
+    /// 添加或更新用户在某个服务上的账号，令牌加密保存
+    pub async fn set_scrobble_account(
+        &self,
+        username: &str,
+        account: &ScrobbleAccount,
+    ) -> Result<()> {
+        let user_id = self.scrobble_user_id(username).await?;
+        let token = self.cipher().encrypt(&account.token)?;
+        sqlx::query(
+            r#"INSERT INTO scrobble_accounts (user_id, service, token, url) VALUES (?, ?, ?, ?)
+               ON CONFLICT(user_id, service)
+               DO UPDATE SET token = excluded.token, url = excluded.url"#,
+        )
+        .bind(&user_id)
+        .bind(&account.service)
+        .bind(token)
+        .bind(&account.url)
+        .execute(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// 删除用户在某个服务上的账号及其尚未发送的播放记录
+    pub async fn remove_scrobble_account(&self, username: &str, service: &str) -> Result<()> {
+        let user_id = self.scrobble_user_id(username).await?;
+        for table in ["scrobble_queue", "scrobble_accounts"] {
+            sqlx::query(&format!(
+                "DELETE FROM {} WHERE user_id = ? AND service = ?",
+                table
+            ))
+            .bind(&user_id)
+            .bind(service)
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// 在后台通知用户的各个服务正在播放的曲目，失败时只记录日志
+    pub(crate) async fn forward_now_playing(&self, user_id: &str, track_id: &str) -> Result<()> {
+        let accounts: Vec<(String, String, Option<String>)> = sqlx::query_as(
+            "SELECT service, token, url FROM scrobble_accounts WHERE user_id = ?",
+        )
+        .bind(user_id)
+        .fetch_all(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        if accounts.is_empty() {
+            return Ok(());
+        }
+
+        let track: Option<String> = sqlx::query_scalar(TRACK_JSON)
+            .bind(track_id)
+            .fetch_optional(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        let Some(track) = track else {
+            return Ok(());
+        };
+        let track: ScrobbleTrack = serde_json::from_str(&track)?;
+
+        for (service, token, url) in accounts {
+            let Some(forwarder) = self.scrobble_forwarder(&service) else {
+                continue;
+            };
+            let Some(token) = self.scrobble_token(&service, &token) else {
+                continue;
+            };
+            let account = ScrobbleAccount { service, token, url };
+            let track = track.clone();
+            tokio::spawn(async move {
+                if let Err(e) = forwarder.now_playing(&account, &track).await {
+                    warn!("Now playing notification to {} failed: {}", account.service, e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// 为用户已注册转发器的各个服务加入一条待发送的播放记录，返回加入的条数
+    pub(crate) async fn enqueue_scrobble(
+        &self,
+        conn: &mut SqliteConnection,
+        user_id: &str,
+        track_id: &str,
+        listened_at: &str,
+    ) -> Result<u64> {
+        let result = sqlx::query(&format!(
+            r#"INSERT INTO scrobble_queue (user_id, service, track, listened_at, next_attempt_at)
+               SELECT user_id, service, ({}), ?, ? FROM scrobble_accounts
+               WHERE user_id = ? AND service IN (SELECT value FROM json_each(?))
+                 AND EXISTS (SELECT 1 FROM tracks WHERE id = ?)"#,
+            TRACK_JSON
+        ))
+        .bind(track_id)
+        .bind(listened_at)
+        .bind(Utc::now().to_rfc3339())
+        .bind(user_id)
+        .bind(self.scrobble_services()?)
+        .bind(track_id)
+        .execute(conn)
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        Ok(result.rows_affected())
+    }
+
+    /// 在后台发送队列中到期的播放记录
+    pub(crate) fn spawn_scrobble_delivery(&self) {
+        let storage = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = storage.process_scrobble_queue().await {
+                warn!("Scrobble delivery failed: {}", e);
+            }
+        });
+    }
+
+    /// 领取队列中到期的播放记录，领取后 [`CLAIM_TIMEOUT`] 内不会被再次领取
+    async fn claim_queued_scrobbles(&self, now: DateTime<Utc>) -> Result<Vec<SqliteRow>> {
+        let mut tx = self
+            .pool()
+            .begin()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        let rows = sqlx::query(
+            r#"SELECT q.id, q.service, q.track, q.listened_at, q.attempts, sa.token, sa.url
+               FROM scrobble_queue q
+               LEFT JOIN scrobble_accounts sa ON sa.user_id = q.user_id AND sa.service = q.service
+               WHERE q.next_attempt_at <= ?
+               ORDER BY q.id"#,
+        )
+        .bind(now.to_rfc3339())
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        sqlx::query("UPDATE scrobble_queue SET next_attempt_at = ? WHERE next_attempt_at <= ?")
+            .bind((now + CLAIM_TIMEOUT).to_rfc3339())
+            .bind(now.to_rfc3339())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        tx.commit()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        Ok(rows)
+    }
+
+    /// 发送队列中到期的播放记录，返回发送成功的条数
+    ///
+    /// 账号已删除、令牌无法解密或服务没有注册转发器的记录直接丢弃
+    pub async fn process_scrobble_queue(&self) -> Result<usize> {
+        let now = Utc::now();
+        let rows = self.claim_queued_scrobbles(now).await?;
+
+        let mut delivered = 0;
+        for r in &rows {
+            let id: i64 = r.get("id");
+            let service: String = r.get("service");
+            let token = r
+                .get::<Option<String>, _>("token")
+                .and_then(|token| self.scrobble_token(&service, &token));
+            let (Some(token), Some(forwarder)) = (token, self.scrobble_forwarder(&service)) else {
+                debug!("Discarding queued scrobble {} for {}", id, service);
+                self.delete_queued_scrobble(id).await?;
+                continue;
+            };
+
+            let account = ScrobbleAccount {
+                service,
+                token,
+                url: r.get("url"),
+            };
+            let track: ScrobbleTrack = serde_json::from_str(r.get("track"))?;
+            let listened_at = DateTime::parse_from_rfc3339(r.get("listened_at"))
+                .map(|d| d.with_timezone(&Utc))
+                .unwrap_or(now);
+
+            let e = match forwarder.submit(&account, &track, listened_at).await {
+                Ok(()) => {
+                    self.delete_queued_scrobble(id).await?;
+                    delivered += 1;
+                    continue;
+                }
+                Err(e) => e,
+            };
+            let attempts = r.get::<i64, _>("attempts") + 1;
+            if attempts >= MAX_ATTEMPTS {
+                warn!(
+                    "Giving up scrobble to {} after {} attempts: {}",
+                    account.service, attempts, e
+                );
+                self.delete_queued_scrobble(id).await?;
+                continue;
+            }
+            debug!("Scrobble to {} failed, will retry: {}", account.service, e);
+            sqlx::query(
+                r#"UPDATE scrobble_queue SET attempts = ?, next_attempt_at = ?, last_error = ?
+                   WHERE id = ?"#,
+            )
+            .bind(attempts)
+            .bind((Utc::now() + retry_delay(attempts)).to_rfc3339())
+            .bind(e.to_string())
+            .bind(id)
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        }
+
+        Ok(delivered)
+    }
+
+    async fn delete_queued_scrobble(&self, id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM scrobble_queue WHERE id = ?")
+            .bind(id)
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// 每隔 `interval` 重试发送队列中到期的播放记录，启动时立即处理一次
+    pub fn start_scrobble_retry(&self, interval: Duration) -> ScrobbleRetryWorker {
+        let storage = self.clone();
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = storage.process_scrobble_queue().await {
+                    warn!("Scrobble retry failed: {}", e);
+                }
+            }
+        });
+        ScrobbleRetryWorker { task }
+    }
+}
@@ -2,49 +2,62 @@
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use sqlx::Row;
+use sqlx::{sqlite::SqliteArguments, Row, Sqlite};
 use uuid::Uuid;
 
+use crate::database::library::library_path;
 use crate::error::{Result, StorageError};
 use crate::traits::*;
+use crate::vfs::{VfsConfig, VfsLocation};
 use crate::DatabaseStorage;
 use reverie_core::{
-    MediaFile, SubsonicAlbum, SubsonicAlbumInfo, SubsonicArtist, SubsonicArtistIndex,
-    SubsonicArtistIndexes, SubsonicArtistInfo, SubsonicBookmark, SubsonicDirectory,
-    SubsonicGenre, SubsonicInternetRadioStation, SubsonicLyrics, SubsonicMusicFolder,
-    SubsonicNowPlaying, SubsonicPlayQueue, SubsonicPlaylist, SubsonicPlaylistWithSongs,
-    SubsonicScanStatus, SubsonicSearchResult2, SubsonicSearchResult3, SubsonicShare,
-    SubsonicStarred, SubsonicStructuredLyrics, SubsonicTopSongs, SubsonicUser,
+    MediaFile, MusicFolderAccess, SubsonicAlbum, SubsonicAlbumInfo, SubsonicArtist,
+    SubsonicArtistIndex, SubsonicArtistIndexes, SubsonicArtistInfo, SubsonicBookmark,
+    SubsonicDirectory, SubsonicGenre, SubsonicInternetRadioStation, SubsonicLyrics,
+    SubsonicMusicFolder, SubsonicNowPlaying, SubsonicPlayQueue, SubsonicPlaylist,
+    SubsonicPlaylistWithSongs, SubsonicScanStatus, SubsonicSearchResult2, SubsonicSearchResult3,
+    SubsonicShare, SubsonicStarred, SubsonicStructuredLyrics, SubsonicTopSongs, SubsonicUser,
+    UserContext,
 };
 
 #[async_trait]
 impl FileStorage for DatabaseStorage {
     async fn read_file(&self, path: &str) -> Result<Vec<u8>> {
-        let data = self.vfs().read(path).await?;
+        let (vfs, path) = self.resolve_path(path).await?;
+        let data = vfs.read(path).await?;
+        Ok(data.to_vec())
+    }
+
+    async fn read_file_range(&self, path: &str, offset: u64, length: u64) -> Result<Vec<u8>> {
+        let (vfs, path) = self.resolve_path(path).await?;
+        let data = vfs.read_range(path, offset, length).await?;
         Ok(data.to_vec())
     }
 
     async fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
-        self.vfs()
-            .write(path, bytes::Bytes::copy_from_slice(data))
-            .await
+        let (vfs, path) = self.resolve_path(path).await?;
+        vfs.write(path, bytes::Bytes::copy_from_slice(data)).await
     }
 
     async fn file_exists(&self, path: &str) -> Result<bool> {
-        self.vfs().exists(path).await
+        let (vfs, path) = self.resolve_path(path).await?;
+        vfs.exists(path).await
     }
 
     async fn delete_file(&self, path: &str) -> Result<()> {
-        self.vfs().delete(path).await
+        let (vfs, path) = self.resolve_path(path).await?;
+        vfs.delete(path).await
     }
 
     async fn list_files(&self, path: &str) -> Result<Vec<String>> {
-        let entries = self.vfs().list(path).await?;
+        let (vfs, path) = self.resolve_path(path).await?;
+        let entries = vfs.list(path).await?;
         Ok(entries.into_iter().map(|e| e.path).collect())
     }
 
     async fn get_file_metadata(&self, path: &str) -> Result<FileMetadata> {
-        let meta = self.vfs().stat(path).await?;
+        let (vfs, path) = self.resolve_path(path).await?;
+        let meta = vfs.stat(path).await?;
         Ok(FileMetadata {
             size: meta.size,
             modified: meta
@@ -55,6 +68,11 @@ impl FileStorage for DatabaseStorage {
             is_dir: meta.is_dir,
         })
     }
+
+    async fn direct_location(&self, path: &str) -> Option<VfsLocation> {
+        let (vfs, path) = self.resolve_path(path).await.ok()?;
+        vfs.direct_location(path).await
+    }
 }
 
 impl DatabaseStorage {
@@ -85,6 +103,17 @@ impl DatabaseStorage {
             suffix: r.get::<Option<String>, _>("format").unwrap_or_default(),
             track_number: r.get::<Option<i64>, _>("track_number").map(|v| v as i32),
             disc_number: r.get::<Option<i64>, _>("disc_number").map(|v| v as i32),
+            missing: r
+                .try_get::<Option<i64>, _>("missing")
+                .ok()
+                .flatten()
+                .is_some_and(|v| v != 0),
+            library_id: r
+                .try_get::<Option<i32>, _>("library_id")
+                .ok()
+                .flatten()
+                .unwrap_or(1),
+            bit_depth: r.try_get::<Option<i32>, _>("bit_depth").ok().flatten(),
             ..Default::default()
         }
     }
@@ -96,7 +125,8 @@ impl DatabaseStorage {
                FROM tracks t
                LEFT JOIN albums a ON t.album_id = a.id
                LEFT JOIN artists ar ON t.artist_id = ar.id
-               WHERE t.album_id = ? ORDER BY t.disc_number, t.track_number"#,
+               WHERE t.album_id = ? AND t.missing = 0
+               ORDER BY t.disc_number, t.track_number"#,
         )
         .bind(album_id)
         .fetch_all(self.pool())
@@ -145,6 +175,575 @@ impl DatabaseStorage {
     }
 }
 
+impl DatabaseStorage {
+    /// 内部方法：获取当前用户的数据库 ID
+    async fn user_id_internal(&self, user: &UserContext) -> Result<String> {
+        let row: Option<(String,)> = sqlx::query_as("SELECT id FROM users WHERE username = ?")
+            .bind(&user.username)
+            .fetch_optional(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        row.map(|(id,)| id)
+            .ok_or_else(|| StorageError::NotFound(format!("User {}", user.username)))
+    }
+
+    /// 内部方法：填充用户对一组条目的收藏时间和评分
+    async fn annotate<'a, T: Annotated + 'a>(
+        &self,
+        user: &UserContext,
+        items: impl IntoIterator<Item = &'a mut T>,
+    ) -> Result<()> {
+        let mut items: Vec<&mut T> = items.into_iter().collect();
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let ids = serde_json::to_string(&items.iter().map(|i| i.item_id()).collect::<Vec<_>>())?;
+        let rows: Vec<AnnotationRow> = sqlx::query_as(
+            r#"SELECT an.item_type, an.item_id, an.starred_at, an.rating, an.play_count
+               FROM annotations an JOIN users u ON an.user_id = u.id
+               WHERE u.username = ? AND an.item_id IN (SELECT value FROM json_each(?))"#,
+        )
+        .bind(&user.username)
+        .bind(ids)
+        .fetch_all(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        let annotations: std::collections::HashMap<_, _> = rows
+            .into_iter()
+            .map(|(item_type, item_id, starred, rating, plays)| {
+                ((item_type, item_id), (starred, rating, plays))
+            })
+            .collect();
+        for item in items.iter_mut() {
+            let key = (item.item_type().to_string(), item.item_id().to_string());
+            let (starred, rating, plays) = annotations.get(&key).cloned().unwrap_or_default();
+            item.set_annotation(parse_timestamp(starred), rating, plays);
+        }
+        Ok(())
+    }
+
+    /// 内部方法：判断 ID 属于歌曲、专辑还是艺术家
+    async fn item_type_internal(&self, id: &str) -> Result<&'static str> {
+        self.find_item_type_internal(
+            id,
+            &[("tracks", "song"), ("albums", "album"), ("artists", "artist")],
+        )
+        .await
+    }
+
+    /// 内部方法：判断分享的 ID 属于歌曲、专辑还是播放列表
+    async fn share_item_type_internal(&self, id: &str) -> Result<&'static str> {
+        self.find_item_type_internal(
+            id,
+            &[("tracks", "song"), ("albums", "album"), ("playlists", "playlist")],
+        )
+        .await
+    }
+
+    /// 内部方法：检查用户是否可以分享条目
+    ///
+    /// 歌曲和专辑须位于用户可访问的音乐文件夹中，否则视为不存在；播放列表须属于用户或已公开
+    async fn check_share_item_internal(
+        &self,
+        user: &UserContext,
+        item_type: &str,
+        id: &str,
+    ) -> Result<()> {
+        let folders = user.music_folders.folder_ids();
+        let sql = match item_type {
+            "song" => format!(
+                "SELECT 1 FROM tracks t WHERE t.id = ? AND t.missing = 0{}",
+                track_folder_filter("t", folders)
+            ),
+            "album" => format!(
+                "SELECT 1 FROM albums a WHERE a.id = ?{}",
+                album_folder_filter("a", folders)
+            ),
+            "playlist" => return self.check_playlist_visible_internal(user, id).await,
+            _ => return Err(StorageError::NotFound(id.to_string())),
+        };
+        let found: Option<(i64,)> = sqlx::query_as(&sql)
+            .bind(id)
+            .fetch_optional(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        found
+            .map(|_| ())
+            .ok_or_else(|| StorageError::NotFound(id.to_string()))
+    }
+
+    /// 内部方法：依次在各表中查找 ID，返回对应的条目类型
+    async fn find_item_type_internal(
+        &self,
+        id: &str,
+        tables: &[(&str, &'static str)],
+    ) -> Result<&'static str> {
+        for &(table, item_type) in tables {
+            let found: Option<(i64,)> =
+                sqlx::query_as(&format!("SELECT 1 FROM {} WHERE id = ?", table))
+                    .bind(id)
+                    .fetch_optional(self.pool())
+                    .await
+                    .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+            if found.is_some() {
+                return Ok(item_type);
+            }
+        }
+        Err(StorageError::NotFound(id.to_string()))
+    }
+
+    /// 内部方法：设置（或清除）一组项目的收藏时间
+    async fn set_starred_internal(
+        &self,
+        user_id: &str,
+        item_type: &str,
+        ids: &[&str],
+        starred_at: Option<&str>,
+    ) -> Result<()> {
+        for id in ids {
+            sqlx::query(
+                r#"INSERT INTO annotations (user_id, item_type, item_id, starred_at)
+                   VALUES (?, ?, ?, ?)
+                   ON CONFLICT(user_id, item_type, item_id)
+                   DO UPDATE SET starred_at = excluded.starred_at"#,
+            )
+            .bind(user_id)
+            .bind(item_type)
+            .bind(id)
+            .bind(starred_at)
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// 内部方法：获取分享，包括已过期的分享
+    async fn share_internal(&self, id: &str) -> Result<Option<SubsonicShare>> {
+        let row = sqlx::query(
+            r#"SELECT s.*, u.username, u.is_admin AS owner_is_admin
+               FROM shares s JOIN users u ON s.user_id = u.id WHERE s.id = ?"#,
+        )
+        .bind(id)
+        .fetch_optional(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        match row {
+            Some(row) => Ok(Some(self.row_to_share_internal(&row).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 内部方法：将分享记录转换为分享，其中的专辑和播放列表展开为歌曲
+    async fn row_to_share_internal(&self, row: &sqlx::sqlite::SqliteRow) -> Result<SubsonicShare> {
+        let id: String = row.get("id");
+        let username: String = row.get("username");
+        let items: Vec<(String, String)> = sqlx::query_as(
+            "SELECT item_type, item_id FROM share_items WHERE share_id = ? ORDER BY rowid",
+        )
+        .bind(&id)
+        .fetch_all(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        // 分享中的曲目按创建者当前的身份和音乐文件夹读取，创建者已无权访问的条目不再公开
+        let folders = self.user_folders_internal(row.get("user_id")).await?;
+        let owner = UserContext::new(username.clone(), row.get("owner_is_admin"))
+            .with_music_folders(folders);
+        let mut entries = Vec::new();
+        for (item_type, item_id) in items {
+            match self.check_share_item_internal(&owner, &item_type, &item_id).await {
+                Ok(()) => {}
+                Err(StorageError::NotFound(_) | StorageError::PermissionDenied(_)) => continue,
+                Err(e) => return Err(e),
+            }
+            match item_type.as_str() {
+                "song" => entries.extend(self.get_song(&owner, &item_id).await?),
+                "album" => entries.extend(self.get_songs_by_album_internal(&item_id).await?),
+                "playlist" => {
+                    let playlist = SubsonicStorage::get_playlist(self, &owner, &item_id).await?;
+                    if let Some(playlist) = playlist {
+                        entries.extend(playlist.entries);
+                    }
+                }
+                _ => {}
+            }
+        }
+        // 专辑和播放列表中可能有位于其他音乐文件夹的曲目
+        entries.retain(|song| owner.music_folders.allows(song.library_id));
+
+        Ok(SubsonicShare {
+            url: format!("/share/{}", id),
+            id,
+            description: row.get("description"),
+            username,
+            created: parse_timestamp(row.get("created_at")).unwrap_or_else(Utc::now),
+            expires: parse_timestamp(row.get("expires_at")),
+            last_visited: parse_timestamp(row.get("last_visited_at")),
+            visit_count: row.get::<Option<i64>, _>("visit_count").unwrap_or(0),
+            entries,
+        })
+    }
+
+    /// 内部方法：检查用户是否可以修改分享，只有创建者和管理员可以修改
+    async fn check_share_owner_internal(&self, user: &UserContext, id: &str) -> Result<()> {
+        let owner: Option<(String,)> = sqlx::query_as(
+            "SELECT u.username FROM shares s JOIN users u ON s.user_id = u.id WHERE s.id = ?",
+        )
+        .bind(id)
+        .fetch_optional(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        match owner {
+            None => Err(StorageError::NotFound(format!("Share {}", id))),
+            Some((owner,)) if owner != user.username && !user.is_admin => Err(
+                StorageError::PermissionDenied(format!("Share {} belongs to {}", id, owner)),
+            ),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// 内部方法：检查用户是否可以修改播放列表，只有所有者和管理员可以修改
+    async fn check_playlist_owner_internal(&self, user: &UserContext, id: &str) -> Result<()> {
+        let owner: Option<(String,)> = sqlx::query_as(
+            "SELECT u.username FROM playlists p JOIN users u ON p.user_id = u.id WHERE p.id = ?",
+        )
+        .bind(id)
+        .fetch_optional(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        match owner {
+            None => Err(StorageError::NotFound(format!("Playlist {}", id))),
+            Some((owner,)) if owner != user.username && !user.is_admin => Err(
+                StorageError::PermissionDenied(format!("Playlist {} belongs to {}", id, owner)),
+            ),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// 内部方法：检查用户是否可以读取播放列表，所有者、管理员可以读取，公开的播放列表所有人可读
+    async fn check_playlist_visible_internal(&self, user: &UserContext, id: &str) -> Result<()> {
+        let owner: Option<(String, bool)> = sqlx::query_as(
+            r#"SELECT u.username, COALESCE(p.is_public, 0)
+               FROM playlists p JOIN users u ON p.user_id = u.id WHERE p.id = ?"#,
+        )
+        .bind(id)
+        .fetch_optional(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        match owner {
+            None => Err(StorageError::NotFound(format!("Playlist {}", id))),
+            Some((owner, false)) if owner != user.username && !user.is_admin => Err(
+                StorageError::PermissionDenied(format!("Playlist {} belongs to {}", id, owner)),
+            ),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// 内部方法：去掉位于用户无权访问的音乐文件夹中的歌曲
+    async fn accessible_song_ids_internal<'a>(
+        &self,
+        user: &UserContext,
+        song_ids: &[&'a str],
+    ) -> Result<Vec<&'a str>> {
+        let Some(folders) = user.music_folders.folder_ids() else {
+            return Ok(song_ids.to_vec());
+        };
+        let sql = format!(
+            "SELECT 1 FROM tracks t WHERE t.id = ?{}",
+            track_folder_filter("t", Some(folders))
+        );
+        let mut accessible = Vec::with_capacity(song_ids.len());
+        for &song_id in song_ids {
+            let found: Option<(i64,)> = sqlx::query_as(&sql)
+                .bind(song_id)
+                .fetch_optional(self.pool())
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+            if found.is_some() {
+                accessible.push(song_id);
+            }
+        }
+        Ok(accessible)
+    }
+
+    /// 内部方法：获取用户可访问的音乐文件夹
+    async fn user_folders_internal(&self, user_id: &str) -> Result<MusicFolderAccess> {
+        let restricted: Option<bool> =
+            sqlx::query_scalar("SELECT music_folders_restricted FROM users WHERE id = ?")
+                .bind(user_id)
+                .fetch_optional(self.pool())
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        if !restricted.unwrap_or(false) {
+            return Ok(MusicFolderAccess::All);
+        }
+        let folders: Vec<i32> = sqlx::query_scalar(
+            "SELECT folder_id FROM user_music_folders WHERE user_id = ? ORDER BY folder_id",
+        )
+        .bind(user_id)
+        .fetch_all(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        Ok(MusicFolderAccess::Only(folders))
+    }
+
+    /// 内部方法：替换用户可访问的音乐文件夹
+    async fn set_user_folders_internal(
+        &self,
+        user_id: &str,
+        music_folders: &MusicFolderAccess,
+    ) -> Result<()> {
+        let mut tx = self
+            .pool()
+            .begin()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        sqlx::query("DELETE FROM user_music_folders WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        sqlx::query("UPDATE users SET music_folders_restricted = ? WHERE id = ?")
+            .bind(music_folders.folder_ids().is_some())
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        for folder_id in music_folders.folder_ids().unwrap_or_default() {
+            sqlx::query(
+                "INSERT OR IGNORE INTO user_music_folders (user_id, folder_id) VALUES (?, ?)",
+            )
+            .bind(user_id)
+            .bind(folder_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        }
+        tx.commit()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))
+    }
+}
+
+/// 标注表中的一行：条目类型、条目 ID、收藏时间、评分和播放次数
+type AnnotationRow = (String, String, Option<String>, Option<i32>, i64);
+
+/// 带有按用户区分的收藏时间、评分和播放次数的条目
+trait Annotated {
+    /// 标注表中的条目类型
+    fn item_type(&self) -> &'static str;
+    fn item_id(&self) -> &str;
+    fn set_annotation(
+        &mut self,
+        starred: Option<DateTime<Utc>>,
+        rating: Option<i32>,
+        plays: i64,
+    );
+}
+
+impl Annotated for MediaFile {
+    fn item_type(&self) -> &'static str {
+        // 文件夹浏览中艺术家的子目录是专辑
+        if self.is_dir {
+            "album"
+        } else {
+            "song"
+        }
+    }
+
+    fn item_id(&self) -> &str {
+        &self.id
+    }
+
+    fn set_annotation(
+        &mut self,
+        starred: Option<DateTime<Utc>>,
+        rating: Option<i32>,
+        plays: i64,
+    ) {
+        self.starred = starred;
+        self.user_rating = rating;
+        self.play_count = Some(plays);
+    }
+}
+
+impl Annotated for SubsonicAlbum {
+    fn item_type(&self) -> &'static str {
+        "album"
+    }
+
+    fn item_id(&self) -> &str {
+        &self.id
+    }
+
+    fn set_annotation(
+        &mut self,
+        starred: Option<DateTime<Utc>>,
+        rating: Option<i32>,
+        plays: i64,
+    ) {
+        self.starred = starred;
+        self.user_rating = rating;
+        self.play_count = Some(plays);
+    }
+}
+
+impl Annotated for SubsonicArtist {
+    fn item_type(&self) -> &'static str {
+        "artist"
+    }
+
+    fn item_id(&self) -> &str {
+        &self.id
+    }
+
+    fn set_annotation(&mut self, starred: Option<DateTime<Utc>>, rating: Option<i32>, _: i64) {
+        self.starred = starred;
+        self.user_rating = rating;
+    }
+}
+
+/// 正在播放的记录在曲目播放完后继续保留的时间，容许暂停和客户端上报的延迟
+const NOW_PLAYING_GRACE: chrono::Duration = chrono::Duration::minutes(5);
+
+fn row_to_radio_station(row: &sqlx::sqlite::SqliteRow) -> SubsonicInternetRadioStation {
+    SubsonicInternetRadioStation {
+        id: row.get::<i64, _>("id").to_string(),
+        name: row.get("name"),
+        stream_url: row.get("stream_url"),
+        homepage_url: row.get("homepage_url"),
+    }
+}
+
+/// 读取用户时查询的列
+const USER_COLUMNS: &str = "id, username, email, is_admin, settings_role, stream_role,
+    jukebox_role, download_role, upload_role, playlist_role, cover_art_role, comment_role,
+    podcast_role, share_role, video_conversion_role, scrobbling_enabled, max_bit_rate";
+
+fn row_to_user(row: &sqlx::sqlite::SqliteRow, folders: MusicFolderAccess) -> SubsonicUser {
+    let role = |column: &str| row.get::<bool, _>(column);
+    SubsonicUser {
+        username: row.get("username"),
+        email: row.get("email"),
+        scrobbling_enabled: role("scrobbling_enabled"),
+        max_bit_rate: row.get("max_bit_rate"),
+        admin_role: role("is_admin"),
+        settings_role: role("settings_role"),
+        download_role: role("download_role"),
+        upload_role: role("upload_role"),
+        playlist_role: role("playlist_role"),
+        cover_art_role: role("cover_art_role"),
+        comment_role: role("comment_role"),
+        podcast_role: role("podcast_role"),
+        stream_role: role("stream_role"),
+        jukebox_role: role("jukebox_role"),
+        share_role: role("share_role"),
+        video_conversion_role: role("video_conversion_role"),
+        avatar_last_changed: None,
+        folders,
+    }
+}
+
+/// 将 Subsonic 使用的毫秒时间戳转换为 RFC 3339 格式
+fn millis_to_rfc3339(millis: i64) -> Option<String> {
+    DateTime::from_timestamp_millis(millis).map(|d| d.to_rfc3339())
+}
+
+fn parse_timestamp(value: Option<String>) -> Option<DateTime<Utc>> {
+    value
+        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+        .map(|d| d.with_timezone(&Utc))
+}
+
+/// 音乐文件夹 ID 列表，用于 `IN (...)` 条件；为 `None` 时不限制
+fn folder_list(music_folder_ids: Option<&[i32]>) -> Option<String> {
+    music_folder_ids.map(|ids| {
+        ids.iter()
+            .map(i32::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    })
+}
+
+/// 限定曲目所在音乐文件夹的 SQL 条件，`track` 为曲目表的别名
+fn track_folder_filter(track: &str, music_folder_ids: Option<&[i32]>) -> String {
+    folder_list(music_folder_ids)
+        .map(|ids| format!(" AND {}.library_id IN ({})", track, ids))
+        .unwrap_or_default()
+}
+
+/// 限定专辑在指定音乐文件夹中有曲目的 SQL 条件，`album` 为专辑表的别名
+fn album_folder_filter(album: &str, music_folder_ids: Option<&[i32]>) -> String {
+    folder_list(music_folder_ids)
+        .map(|ids| {
+            format!(
+                r#" AND EXISTS (SELECT 1 FROM tracks ft WHERE ft.album_id = {}.id
+                               AND ft.missing = 0 AND ft.library_id IN ({}))"#,
+                album, ids
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// 限定艺术家在指定音乐文件夹中有曲目（作为曲目艺术家或专辑艺术家）的 SQL 条件
+fn artist_folder_filter(artist: &str, music_folder_ids: Option<&[i32]>) -> String {
+    folder_list(music_folder_ids)
+        .map(|ids| {
+            format!(
+                r#" AND EXISTS (SELECT 1 FROM tracks ft LEFT JOIN albums fa ON ft.album_id = fa.id
+                               WHERE (ft.artist_id = {0}.id OR fa.artist_id = {0}.id)
+                                 AND ft.missing = 0 AND ft.library_id IN ({1}))"#,
+                artist, ids
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// 将搜索词转换为 FTS5 查询表达式
+///
+/// 每个词按前缀匹配，多个词之间为 AND 关系，可分别匹配标题、艺术家、专辑和流派。
+/// 没有可搜索的词（如 `*`、`-`）时返回 `None`
+fn match_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| term.replace('"', ""))
+        .filter(|term| term.chars().any(char::is_alphanumeric))
+        .map(|term| format!("\"{}\"*", term))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// 全文搜索的 JOIN 子句（含 MATCH 条件）和排序前缀，`item` 为条目表的别名
+///
+/// 按 bm25 排序时标题的权重最高，流派最低。没有搜索表达式时两者均为空，按名称列出全部条目
+fn search_clauses(item: &str, item_type: &str, expression: Option<&str>) -> (String, &'static str) {
+    match expression {
+        Some(_) => (
+            format!(
+                r#" JOIN search_items si ON si.item_type = '{}' AND si.item_id = {}.id
+               JOIN search_index ON search_index.rowid = si.id AND search_index MATCH ?"#,
+                item_type, item
+            ),
+            "bm25(search_index, 4.0, 2.0, 2.0, 1.0), ",
+        ),
+        None => (String::new(), ""),
+    }
+}
+
+/// 绑定 [`search_clauses`] 中 MATCH 条件的参数
+fn bind_match<'q>(
+    query: sqlx::query::Query<'q, Sqlite, SqliteArguments<'q>>,
+    expression: Option<&'q str>,
+) -> sqlx::query::Query<'q, Sqlite, SqliteArguments<'q>> {
+    match expression {
+        Some(expression) => query.bind(expression),
+        None => query,
+    }
+}
+
 #[async_trait]
 impl SubsonicStorage for DatabaseStorage {
     // === System ===
@@ -165,14 +764,17 @@ impl SubsonicStorage for DatabaseStorage {
 
     async fn get_indexes(
         &self,
-        _music_folder_id: Option<i32>,
+        user: &UserContext,
+        music_folder_ids: Option<&[i32]>,
         _if_modified_since: Option<i64>,
     ) -> Result<SubsonicArtistIndexes> {
-        let rows = sqlx::query(
+        let rows = sqlx::query(&format!(
             r#"SELECT id, name, 
-                      (SELECT COUNT(*) FROM albums WHERE albums.artist_id = artists.id) as album_count
-               FROM artists ORDER BY name"#,
-        )
+                      (SELECT COUNT(*) FROM albums WHERE albums.artist_id = artists.id{}) as album_count
+               FROM artists WHERE 1=1{} ORDER BY name"#,
+            album_folder_filter("albums", music_folder_ids),
+            artist_folder_filter("artists", music_folder_ids),
+        ))
         .fetch_all(self.pool())
         .await
         .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
@@ -211,17 +813,21 @@ impl SubsonicStorage for DatabaseStorage {
             .map(|(id, artists)| SubsonicArtistIndex { id, artists })
             .collect();
         result.sort_by(|a, b| a.id.cmp(&b.id));
+        for index in &mut result {
+            self.annotate(user, &mut index.artists).await?;
+        }
 
         Ok(result)
     }
 
-    async fn get_genres(&self) -> Result<Vec<SubsonicGenre>> {
-        let rows = sqlx::query(
-            r#"SELECT genre, COUNT(DISTINCT id) as song_count, 
-                      COUNT(DISTINCT album_id) as album_count
-               FROM tracks WHERE genre IS NOT NULL 
-               GROUP BY genre ORDER BY genre"#,
-        )
+    async fn get_genres(&self, music_folder_ids: Option<&[i32]>) -> Result<Vec<SubsonicGenre>> {
+        let rows = sqlx::query(&format!(
+            r#"SELECT t.genre, COUNT(DISTINCT t.id) as song_count,
+                      COUNT(DISTINCT t.album_id) as album_count
+               FROM tracks t WHERE t.genre IS NOT NULL AND t.missing = 0{}
+               GROUP BY t.genre ORDER BY t.genre"#,
+            track_folder_filter("t", music_folder_ids),
+        ))
         .fetch_all(self.pool())
         .await
         .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
@@ -236,17 +842,22 @@ impl SubsonicStorage for DatabaseStorage {
             .collect())
     }
 
-    async fn get_music_directory(&self, id: &str) -> Result<Option<SubsonicDirectory>> {
+    async fn get_music_directory(
+        &self,
+        user: &UserContext,
+        id: &str,
+    ) -> Result<Option<SubsonicDirectory>> {
         // 首先尝试作为专辑查找
-        if let Some(album) = SubsonicStorage::get_album(self, id).await? {
-            let songs = self.get_songs_by_album_internal(id).await?;
+        if let Some(album) = SubsonicStorage::get_album(self, user, id).await? {
+            let mut songs = self.get_songs_by_album_internal(id).await?;
+            self.annotate(user, &mut songs).await?;
             return Ok(Some(SubsonicDirectory::from_album(&album, songs)));
         }
 
         // 尝试作为艺术家
-        if let Some(artist) = SubsonicStorage::get_artist(self, id).await? {
+        if let Some(artist) = SubsonicStorage::get_artist(self, user, id).await? {
             let albums = self.get_albums_by_artist_internal(id).await?;
-            let children: Vec<MediaFile> = albums
+            let mut children: Vec<MediaFile> = albums
                 .into_iter()
                 .map(|a| MediaFile {
                     id: a.id.clone(),
@@ -263,6 +874,7 @@ impl SubsonicStorage for DatabaseStorage {
                     ..Default::default()
                 })
                 .collect();
+            self.annotate(user, &mut children).await?;
 
             return Ok(Some(SubsonicDirectory {
                 id: artist.id.clone(),
@@ -284,13 +896,17 @@ impl SubsonicStorage for DatabaseStorage {
         Ok(None)
     }
 
-    async fn get_artists(&self, _music_folder_id: Option<i32>) -> Result<SubsonicArtistIndexes> {
-        self.get_indexes(None, None).await
+    async fn get_artists(
+        &self,
+        user: &UserContext,
+        music_folder_ids: Option<&[i32]>,
+    ) -> Result<SubsonicArtistIndexes> {
+        self.get_indexes(user, music_folder_ids, None).await
     }
 
-    async fn get_artist(&self, id: &str) -> Result<Option<SubsonicArtist>> {
+    async fn get_artist(&self, user: &UserContext, id: &str) -> Result<Option<SubsonicArtist>> {
         let row = sqlx::query(
-            r#"SELECT id, name, image_url, starred_at,
+            r#"SELECT id, name, image_url,
                       (SELECT COUNT(*) FROM albums WHERE albums.artist_id = artists.id) as album_count
                FROM artists WHERE id = ?"#,
         )
@@ -299,23 +915,22 @@ impl SubsonicStorage for DatabaseStorage {
         .await
         .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-        Ok(row.map(|r| SubsonicArtist {
+        let mut artist = row.map(|r| SubsonicArtist {
             id: r.get("id"),
             name: r.get("name"),
             cover_art: r.get("image_url"),
             album_count: r.get::<i32, _>("album_count"),
-            starred: r
-                .get::<Option<String>, _>("starred_at")
-                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                .map(|d| d.with_timezone(&Utc)),
+            starred: None,
             user_rating: None,
-        }))
+        });
+        self.annotate(user, &mut artist).await?;
+        Ok(artist)
     }
 
-    async fn get_album(&self, id: &str) -> Result<Option<SubsonicAlbum>> {
+    async fn get_album(&self, user: &UserContext, id: &str) -> Result<Option<SubsonicAlbum>> {
         let row = sqlx::query(
             r#"SELECT a.id, a.name, a.artist_id, a.year, a.genre, a.cover_art_path,
-                      a.song_count, a.duration, a.play_count, a.starred_at, a.created_at,
+                      a.song_count, a.duration, a.play_count, a.created_at,
                       ar.name as artist_name
                FROM albums a
                LEFT JOIN artists ar ON a.artist_id = ar.id
@@ -326,7 +941,7 @@ impl SubsonicStorage for DatabaseStorage {
         .await
         .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-        Ok(row.map(|r| SubsonicAlbum {
+        let mut album = row.map(|r| SubsonicAlbum {
             id: r.get("id"),
             name: r.get("name"),
             album_artist: r.get("artist_name"),
@@ -343,15 +958,14 @@ impl SubsonicStorage for DatabaseStorage {
                 .get::<Option<String>, _>("created_at")
                 .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
                 .map(|d| d.with_timezone(&Utc)),
-            starred: r
-                .get::<Option<String>, _>("starred_at")
-                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                .map(|d| d.with_timezone(&Utc)),
+            starred: None,
             user_rating: None,
-        }))
+        });
+        self.annotate(user, &mut album).await?;
+        Ok(album)
     }
 
-    async fn get_song(&self, id: &str) -> Result<Option<MediaFile>> {
+    async fn get_song(&self, user: &UserContext, id: &str) -> Result<Option<MediaFile>> {
         let row = sqlx::query(
             r#"SELECT t.*, a.name as album_name, ar.name as artist_name
                FROM tracks t
@@ -364,7 +978,9 @@ impl SubsonicStorage for DatabaseStorage {
         .await
         .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-        Ok(row.map(|r| self.row_to_media_file(&r)))
+        let mut song = row.map(|r| self.row_to_media_file(&r));
+        self.annotate(user, &mut song).await?;
+        Ok(song)
     }
 
     async fn get_artist_info(
@@ -408,24 +1024,39 @@ impl SubsonicStorage for DatabaseStorage {
         self.get_album_info(id).await
     }
 
-    async fn get_similar_songs(&self, _id: &str, count: Option<i32>) -> Result<Vec<MediaFile>> {
+    async fn get_similar_songs(
+        &self,
+        user: &UserContext,
+        _id: &str,
+        count: Option<i32>,
+    ) -> Result<Vec<MediaFile>> {
         let limit = count.unwrap_or(50);
-        self.get_random_songs(Some(limit), None, None, None, None)
+        self.get_random_songs(user, Some(limit), None, None, None, None)
             .await
     }
 
-    async fn get_similar_songs2(&self, id: &str, count: Option<i32>) -> Result<Vec<MediaFile>> {
-        self.get_similar_songs(id, count).await
+    async fn get_similar_songs2(
+        &self,
+        user: &UserContext,
+        id: &str,
+        count: Option<i32>,
+    ) -> Result<Vec<MediaFile>> {
+        self.get_similar_songs(user, id, count).await
     }
 
-    async fn get_top_songs(&self, artist: &str, count: Option<i32>) -> Result<SubsonicTopSongs> {
+    async fn get_top_songs(
+        &self,
+        user: &UserContext,
+        artist: &str,
+        count: Option<i32>,
+    ) -> Result<SubsonicTopSongs> {
         let limit = count.unwrap_or(50);
         let rows = sqlx::query(
             r#"SELECT t.*, a.name as album_name, ar.name as artist_name
                FROM tracks t
                LEFT JOIN albums a ON t.album_id = a.id
                LEFT JOIN artists ar ON t.artist_id = ar.id
-               WHERE ar.name LIKE ?
+               WHERE ar.name LIKE ? AND t.missing = 0
                ORDER BY t.play_count DESC
                LIMIT ?"#,
         )
@@ -435,47 +1066,61 @@ impl SubsonicStorage for DatabaseStorage {
         .await
         .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-        Ok(SubsonicTopSongs {
-            songs: rows.iter().map(|r| self.row_to_media_file(r)).collect(),
-        })
+        let mut songs: Vec<MediaFile> = rows.iter().map(|r| self.row_to_media_file(r)).collect();
+        self.annotate(user, &mut songs).await?;
+        Ok(SubsonicTopSongs { songs })
     }
 
     // === Album/Song Lists ===
     async fn get_album_list(
         &self,
+        user: &UserContext,
         list_type: &str,
         size: Option<i32>,
         offset: Option<i32>,
         from_year: Option<i32>,
         to_year: Option<i32>,
         genre: Option<&str>,
-        _music_folder_id: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<SubsonicAlbum>> {
         let limit = size.unwrap_or(10);
         let off = offset.unwrap_or(0);
 
         let order_clause = match list_type {
             "newest" => "a.created_at DESC",
-            "recent" => "a.updated_at DESC",
-            "frequent" => "a.play_count DESC",
-            "highest" => "a.play_count DESC",
+            "recent" => "an.last_played DESC",
+            "frequent" => "an.play_count DESC, an.last_played DESC",
+            "highest" => "an.rating DESC, a.name ASC",
             "alphabeticalByName" => "a.name ASC",
             "alphabeticalByArtist" => "ar.name ASC, a.name ASC",
-            "starred" => "a.starred_at DESC",
+            "starred" => "an.starred_at DESC",
             "byYear" => "a.year ASC",
             "byGenre" => "a.genre ASC",
             "random" => "RANDOM()",
             _ => "a.name ASC",
         };
 
+        // 收藏、评分和播放次数来自当前用户的标注
         let mut query = r#"SELECT a.id, a.name, a.artist_id, a.year, a.genre, a.cover_art_path,
-                      a.song_count, a.duration, a.play_count, a.starred_at, a.created_at,
-                      ar.name as artist_name
+                      a.song_count, a.duration, COALESCE(an.play_count, 0) as play_count,
+                      a.created_at,
+                      ar.name as artist_name,
+                      an.starred_at as user_starred_at, an.rating as user_rating
                FROM albums a
                LEFT JOIN artists ar ON a.artist_id = ar.id
+               LEFT JOIN annotations an ON an.item_type = 'album' AND an.item_id = a.id
+                    AND an.user_id = (SELECT id FROM users WHERE username = ?)
                WHERE 1=1"#
             .to_string();
 
+        match list_type {
+            "starred" => query.push_str(" AND an.starred_at IS NOT NULL"),
+            "highest" => query.push_str(" AND an.rating > 0"),
+            "recent" => query.push_str(" AND an.last_played IS NOT NULL"),
+            "frequent" => query.push_str(" AND an.play_count > 0"),
+            _ => {}
+        }
+
         if let Some(fy) = from_year {
             query.push_str(&format!(" AND a.year >= {}", fy));
         }
@@ -485,6 +1130,7 @@ impl SubsonicStorage for DatabaseStorage {
         if let Some(g) = genre {
             query.push_str(&format!(" AND a.genre = '{}'", g.replace('\'', "''")));
         }
+        query.push_str(&album_folder_filter("a", music_folder_ids));
 
         query.push_str(&format!(
             " ORDER BY {} LIMIT {} OFFSET {}",
@@ -492,6 +1138,7 @@ impl SubsonicStorage for DatabaseStorage {
         ));
 
         let rows = sqlx::query(&query)
+            .bind(&user.username)
             .fetch_all(self.pool())
             .await
             .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
@@ -515,44 +1162,44 @@ impl SubsonicStorage for DatabaseStorage {
                     .get::<Option<String>, _>("created_at")
                     .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
                     .map(|d| d.with_timezone(&Utc)),
-                starred: r
-                    .get::<Option<String>, _>("starred_at")
-                    .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                    .map(|d| d.with_timezone(&Utc)),
-                user_rating: None,
+                starred: parse_timestamp(r.get("user_starred_at")),
+                user_rating: r.get::<Option<i32>, _>("user_rating"),
             })
             .collect())
     }
 
     async fn get_album_list2(
         &self,
+        user: &UserContext,
         list_type: &str,
         size: Option<i32>,
         offset: Option<i32>,
         from_year: Option<i32>,
         to_year: Option<i32>,
         genre: Option<&str>,
-        music_folder_id: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<SubsonicAlbum>> {
         self.get_album_list(
+            user,
             list_type,
             size,
             offset,
             from_year,
             to_year,
             genre,
-            music_folder_id,
+            music_folder_ids,
         )
         .await
     }
 
     async fn get_random_songs(
         &self,
+        user: &UserContext,
         size: Option<i32>,
         genre: Option<&str>,
         from_year: Option<i32>,
         to_year: Option<i32>,
-        _music_folder_id: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<MediaFile>> {
         let limit = size.unwrap_or(10);
 
@@ -561,7 +1208,7 @@ impl SubsonicStorage for DatabaseStorage {
                FROM tracks t
                LEFT JOIN albums a ON t.album_id = a.id
                LEFT JOIN artists ar ON t.artist_id = ar.id
-               WHERE 1=1"#,
+               WHERE t.missing = 0"#,
         );
 
         if let Some(g) = genre {
@@ -573,6 +1220,7 @@ impl SubsonicStorage for DatabaseStorage {
         if let Some(ty) = to_year {
             query.push_str(&format!(" AND t.year <= {}", ty));
         }
+        query.push_str(&track_folder_filter("t", music_folder_ids));
 
         query.push_str(&format!(" ORDER BY RANDOM() LIMIT {}", limit));
 
@@ -581,28 +1229,32 @@ impl SubsonicStorage for DatabaseStorage {
             .await
             .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-        Ok(rows.iter().map(|r| self.row_to_media_file(r)).collect())
+        let mut songs: Vec<MediaFile> = rows.iter().map(|r| self.row_to_media_file(r)).collect();
+        self.annotate(user, &mut songs).await?;
+        Ok(songs)
     }
 
     async fn get_songs_by_genre(
         &self,
+        user: &UserContext,
         genre: &str,
         count: Option<i32>,
         offset: Option<i32>,
-        _music_folder_id: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<MediaFile>> {
         let limit = count.unwrap_or(10);
         let off = offset.unwrap_or(0);
 
-        let rows = sqlx::query(
+        let rows = sqlx::query(&format!(
             r#"SELECT t.*, a.name as album_name, ar.name as artist_name
                FROM tracks t
                LEFT JOIN albums a ON t.album_id = a.id
                LEFT JOIN artists ar ON t.artist_id = ar.id
-               WHERE t.genre = ?
+               WHERE t.genre = ? AND t.missing = 0{}
                ORDER BY t.title
                LIMIT ? OFFSET ?"#,
-        )
+            track_folder_filter("t", music_folder_ids),
+        ))
         .bind(genre)
         .bind(limit)
         .bind(off)
@@ -610,41 +1262,103 @@ impl SubsonicStorage for DatabaseStorage {
         .await
         .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-        Ok(rows.iter().map(|r| self.row_to_media_file(r)).collect())
+        let mut songs: Vec<MediaFile> = rows.iter().map(|r| self.row_to_media_file(r)).collect();
+        self.annotate(user, &mut songs).await?;
+        Ok(songs)
     }
 
-    async fn get_now_playing(&self) -> Result<Vec<SubsonicNowPlaying>> {
-        Ok(vec![])
-    }
+    async fn get_now_playing(&self, user: &UserContext) -> Result<Vec<SubsonicNowPlaying>> {
+        let now = Utc::now();
+        sqlx::query("DELETE FROM now_playing WHERE expires_at <= ?")
+            .bind(now.to_rfc3339())
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-    // === Starred ===
-    async fn get_starred(&self, _music_folder_id: Option<i32>) -> Result<SubsonicStarred> {
-        let artist_rows = sqlx::query(
-            r#"SELECT id, name, image_url, starred_at,
-                      (SELECT COUNT(*) FROM albums WHERE albums.artist_id = artists.id) as album_count
-               FROM artists WHERE starred_at IS NOT NULL ORDER BY starred_at DESC"#,
+        let rows = sqlx::query(
+            r#"SELECT t.*, a.name as album_name, ar.name as artist_name,
+                      np.id as player_id, np.player_name, np.started_at, u.username
+               FROM now_playing np
+               JOIN users u ON np.user_id = u.id
+               JOIN tracks t ON np.track_id = t.id
+               LEFT JOIN albums a ON t.album_id = a.id
+               LEFT JOIN artists ar ON t.artist_id = ar.id
+               WHERE t.missing = 0
+               ORDER BY np.started_at DESC"#,
         )
         .fetch_all(self.pool())
         .await
         .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-        let album_rows = sqlx::query(
-            r#"SELECT a.*, ar.name as artist_name
-               FROM albums a
-               LEFT JOIN artists ar ON a.artist_id = ar.id
-               WHERE a.starred_at IS NOT NULL ORDER BY a.starred_at DESC"#,
-        )
+        let mut entries: Vec<SubsonicNowPlaying> = rows
+            .iter()
+            .map(|r| {
+                let started = parse_timestamp(r.get("started_at")).unwrap_or(now);
+                let player_name: String = r.get("player_name");
+                SubsonicNowPlaying {
+                    entry: self.row_to_media_file(r),
+                    username: r.get("username"),
+                    minutes_ago: (now - started).num_minutes().max(0) as i32,
+                    player_id: Some(r.get::<i64, _>("player_id").to_string()),
+                    player_name: (!player_name.is_empty()).then_some(player_name),
+                }
+            })
+            .collect();
+        self.annotate(user, entries.iter_mut().map(|e| &mut e.entry))
+            .await?;
+        Ok(entries)
+    }
+
+    // === Starred ===
+    async fn get_starred(
+        &self,
+        user: &UserContext,
+        music_folder_ids: Option<&[i32]>,
+    ) -> Result<SubsonicStarred> {
+        let user_id = self.user_id_internal(user).await?;
+
+        let artist_rows = sqlx::query(&format!(
+            r#"SELECT ar.id, ar.name, ar.image_url, an.starred_at, an.rating,
+                      (SELECT COUNT(*) FROM albums WHERE albums.artist_id = ar.id{}) as album_count
+               FROM artists ar
+               JOIN annotations an ON an.item_type = 'artist' AND an.item_id = ar.id
+               WHERE an.user_id = ? AND an.starred_at IS NOT NULL{}
+               ORDER BY an.starred_at DESC"#,
+            album_folder_filter("albums", music_folder_ids),
+            artist_folder_filter("ar", music_folder_ids),
+        ))
+        .bind(&user_id)
         .fetch_all(self.pool())
         .await
         .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-        let song_rows = sqlx::query(
-            r#"SELECT t.*, a.name as album_name, ar.name as artist_name
+        let album_rows = sqlx::query(&format!(
+            r#"SELECT a.*, ar.name as artist_name,
+                      an.starred_at as user_starred_at, an.rating as user_rating
+               FROM albums a
+               LEFT JOIN artists ar ON a.artist_id = ar.id
+               JOIN annotations an ON an.item_type = 'album' AND an.item_id = a.id
+               WHERE an.user_id = ? AND an.starred_at IS NOT NULL{}
+               ORDER BY an.starred_at DESC"#,
+            album_folder_filter("a", music_folder_ids),
+        ))
+        .bind(&user_id)
+        .fetch_all(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        let song_rows = sqlx::query(&format!(
+            r#"SELECT t.*, a.name as album_name, ar.name as artist_name,
+                      an.starred_at as user_starred_at, an.rating as user_rating
                FROM tracks t
                LEFT JOIN albums a ON t.album_id = a.id
                LEFT JOIN artists ar ON t.artist_id = ar.id
-               WHERE t.starred_at IS NOT NULL ORDER BY t.starred_at DESC"#,
-        )
+               JOIN annotations an ON an.item_type = 'song' AND an.item_id = t.id
+               WHERE an.user_id = ? AND an.starred_at IS NOT NULL AND t.missing = 0{}
+               ORDER BY an.starred_at DESC"#,
+            track_folder_filter("t", music_folder_ids),
+        ))
+        .bind(&user_id)
         .fetch_all(self.pool())
         .await
         .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
@@ -657,11 +1371,8 @@ impl SubsonicStorage for DatabaseStorage {
                     name: r.get("name"),
                     cover_art: r.get("image_url"),
                     album_count: r.get::<i32, _>("album_count"),
-                    starred: r
-                        .get::<Option<String>, _>("starred_at")
-                        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                        .map(|d| d.with_timezone(&Utc)),
-                    user_rating: None,
+                    starred: parse_timestamp(r.get("starred_at")),
+                    user_rating: r.get::<Option<i32>, _>("rating"),
                 })
                 .collect(),
             albums: album_rows
@@ -680,27 +1391,33 @@ impl SubsonicStorage for DatabaseStorage {
                     duration: r.get::<Option<f32>, _>("duration").unwrap_or(0.0),
                     play_count: r.get::<Option<i64>, _>("play_count"),
                     created: None,
-                    starred: r
-                        .get::<Option<String>, _>("starred_at")
-                        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                        .map(|d| d.with_timezone(&Utc)),
-                    user_rating: None,
+                    starred: parse_timestamp(r.get("user_starred_at")),
+                    user_rating: r.get::<Option<i32>, _>("user_rating"),
                 })
                 .collect(),
             songs: song_rows
                 .iter()
-                .map(|r| self.row_to_media_file(r))
+                .map(|r| MediaFile {
+                    starred: parse_timestamp(r.get("user_starred_at")),
+                    user_rating: r.get::<Option<i32>, _>("user_rating"),
+                    ..self.row_to_media_file(r)
+                })
                 .collect(),
         })
     }
 
-    async fn get_starred2(&self, music_folder_id: Option<i32>) -> Result<SubsonicStarred> {
-        self.get_starred(music_folder_id).await
+    async fn get_starred2(
+        &self,
+        user: &UserContext,
+        music_folder_ids: Option<&[i32]>,
+    ) -> Result<SubsonicStarred> {
+        self.get_starred(user, music_folder_ids).await
     }
 
     // === Searching ===
     async fn search2(
         &self,
+        user: &UserContext,
         query: &str,
         artist_count: Option<i32>,
         artist_offset: Option<i32>,
@@ -708,8 +1425,8 @@ impl SubsonicStorage for DatabaseStorage {
         album_offset: Option<i32>,
         song_count: Option<i32>,
         song_offset: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<SubsonicSearchResult2> {
-        let pattern = format!("%{}%", query);
         let ar_limit = artist_count.unwrap_or(20);
         let ar_off = artist_offset.unwrap_or(0);
         let al_limit = album_count.unwrap_or(20);
@@ -717,45 +1434,72 @@ impl SubsonicStorage for DatabaseStorage {
         let s_limit = song_count.unwrap_or(20);
         let s_off = song_offset.unwrap_or(0);
 
-        let artists = sqlx::query(
-            r#"SELECT id, name, image_url, starred_at,
-                      (SELECT COUNT(*) FROM albums WHERE albums.artist_id = artists.id) as album_count
-               FROM artists WHERE name LIKE ? ORDER BY name LIMIT ? OFFSET ?"#,
-        )
-        .bind(&pattern)
-        .bind(ar_limit)
-        .bind(ar_off)
-        .fetch_all(self.pool())
-        .await
-        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        // 空查询或 `""` 返回整个媒体库，部分客户端以此同步曲库；
+        // 其他没有可搜索内容的查询不匹配任何条目
+        let expression = match_expression(query);
+        if expression.is_none() && !matches!(query.trim(), "" | "\"\"") {
+            return Ok(SubsonicSearchResult2 {
+                artists: Vec::new(),
+                albums: Vec::new(),
+                songs: Vec::new(),
+            });
+        }
+        let expression = expression.as_deref();
+
+        let (join, rank) = search_clauses("artists", "artist", expression);
+        let sql = format!(
+            r#"SELECT artists.id, artists.name, artists.image_url, artists.starred_at,
+                      (SELECT COUNT(*) FROM albums WHERE albums.artist_id = artists.id{}) as album_count
+               FROM artists{}
+               WHERE 1=1{} ORDER BY {}artists.name LIMIT ? OFFSET ?"#,
+            album_folder_filter("albums", music_folder_ids),
+            join,
+            artist_folder_filter("artists", music_folder_ids),
+            rank,
+        );
+        let artists = bind_match(sqlx::query(&sql), expression)
+            .bind(ar_limit)
+            .bind(ar_off)
+            .fetch_all(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-        let albums = sqlx::query(
+        let (join, rank) = search_clauses("a", "album", expression);
+        let sql = format!(
             r#"SELECT a.*, ar.name as artist_name
-               FROM albums a LEFT JOIN artists ar ON a.artist_id = ar.id
-               WHERE a.name LIKE ? ORDER BY a.name LIMIT ? OFFSET ?"#,
-        )
-        .bind(&pattern)
-        .bind(al_limit)
-        .bind(al_off)
-        .fetch_all(self.pool())
-        .await
-        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+               FROM albums a{}
+               LEFT JOIN artists ar ON a.artist_id = ar.id
+               WHERE 1=1{} ORDER BY {}a.name LIMIT ? OFFSET ?"#,
+            join,
+            album_folder_filter("a", music_folder_ids),
+            rank,
+        );
+        let albums = bind_match(sqlx::query(&sql), expression)
+            .bind(al_limit)
+            .bind(al_off)
+            .fetch_all(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-        let songs = sqlx::query(
+        let (join, rank) = search_clauses("t", "song", expression);
+        let sql = format!(
             r#"SELECT t.*, a.name as album_name, ar.name as artist_name
-               FROM tracks t
+               FROM tracks t{}
                LEFT JOIN albums a ON t.album_id = a.id
                LEFT JOIN artists ar ON t.artist_id = ar.id
-               WHERE t.title LIKE ? ORDER BY t.title LIMIT ? OFFSET ?"#,
-        )
-        .bind(&pattern)
-        .bind(s_limit)
-        .bind(s_off)
-        .fetch_all(self.pool())
-        .await
-        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+               WHERE t.missing = 0{} ORDER BY {}t.title LIMIT ? OFFSET ?"#,
+            join,
+            track_folder_filter("t", music_folder_ids),
+            rank,
+        );
+        let songs = bind_match(sqlx::query(&sql), expression)
+            .bind(s_limit)
+            .bind(s_off)
+            .fetch_all(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-        Ok(SubsonicSearchResult2 {
+        let mut result = SubsonicSearchResult2 {
             artists: artists
                 .into_iter()
                 .map(|r| SubsonicArtist {
@@ -788,11 +1532,16 @@ impl SubsonicStorage for DatabaseStorage {
                 })
                 .collect(),
             songs: songs.iter().map(|r| self.row_to_media_file(r)).collect(),
-        })
+        };
+        self.annotate(user, &mut result.artists).await?;
+        self.annotate(user, &mut result.albums).await?;
+        self.annotate(user, &mut result.songs).await?;
+        Ok(result)
     }
 
     async fn search3(
         &self,
+        user: &UserContext,
         query: &str,
         artist_count: Option<i32>,
         artist_offset: Option<i32>,
@@ -800,9 +1549,11 @@ impl SubsonicStorage for DatabaseStorage {
         album_offset: Option<i32>,
         song_count: Option<i32>,
         song_offset: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<SubsonicSearchResult3> {
         let result = self
             .search2(
+                user,
                 query,
                 artist_count,
                 artist_offset,
@@ -810,6 +1561,7 @@ impl SubsonicStorage for DatabaseStorage {
                 album_offset,
                 song_count,
                 song_offset,
+                music_folder_ids,
             )
             .await?;
         Ok(SubsonicSearchResult3 {
@@ -820,28 +1572,33 @@ impl SubsonicStorage for DatabaseStorage {
     }
 
     // === Playlists ===
-    async fn get_playlists(&self, username: Option<&str>) -> Result<Vec<SubsonicPlaylist>> {
-        let rows = if let Some(user) = username {
-            sqlx::query(
-                r#"SELECT p.*, u.username as owner_name,
-                          (SELECT COUNT(*) FROM playlist_tracks WHERE playlist_id = p.id) as entry_count
-                   FROM playlists p LEFT JOIN users u ON p.user_id = u.id
-                   WHERE u.username = ? OR p.is_public = 1 ORDER BY p.name"#,
-            )
-            .bind(user)
-            .fetch_all(self.pool())
-            .await
-        } else {
-            sqlx::query(
-                r#"SELECT p.*, u.username as owner_name,
-                          (SELECT COUNT(*) FROM playlist_tracks WHERE playlist_id = p.id) as entry_count
-                   FROM playlists p LEFT JOIN users u ON p.user_id = u.id
-                   ORDER BY p.name"#,
-            )
+    async fn get_playlists(
+        &self,
+        user: &UserContext,
+        username: Option<&str>,
+    ) -> Result<Vec<SubsonicPlaylist>> {
+        let username = username.unwrap_or(&user.username);
+        if username != user.username && !user.is_admin {
+            return Err(StorageError::PermissionDenied(format!(
+                "Playlists of {}",
+                username
+            )));
+        }
+        // 歌曲数量不含位于请求用户无权访问的音乐文件夹中的歌曲
+        let folders = user.music_folders.folder_ids();
+        let sql = format!(
+            r#"SELECT p.*, u.username as owner_name,
+                      (SELECT COUNT(*) FROM playlist_tracks pt JOIN tracks t ON pt.track_id = t.id
+                       WHERE pt.playlist_id = p.id AND t.missing = 0{}) as entry_count
+               FROM playlists p LEFT JOIN users u ON p.user_id = u.id
+               WHERE u.username = ? OR p.is_public = 1 ORDER BY p.name"#,
+            track_folder_filter("t", folders)
+        );
+        let rows = sqlx::query(&sql)
+            .bind(username)
             .fetch_all(self.pool())
             .await
-        }
-        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
         Ok(rows
             .into_iter()
@@ -863,7 +1620,16 @@ impl SubsonicStorage for DatabaseStorage {
             .collect())
     }
 
-    async fn get_playlist(&self, id: &str) -> Result<Option<SubsonicPlaylistWithSongs>> {
+    async fn get_playlist(
+        &self,
+        user: &UserContext,
+        id: &str,
+    ) -> Result<Option<SubsonicPlaylistWithSongs>> {
+        match self.check_playlist_visible_internal(user, id).await {
+            Ok(()) => {}
+            Err(StorageError::NotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        }
         let row = sqlx::query(
             r#"SELECT p.*, u.username as owner_name
                FROM playlists p LEFT JOIN users u ON p.user_id = u.id WHERE p.id = ?"#,
@@ -877,18 +1643,26 @@ impl SubsonicStorage for DatabaseStorage {
             return Ok(None);
         };
 
-        let entries = sqlx::query(
+        // 位于用户无权访问的音乐文件夹中的歌曲视为不存在
+        let folders = user.music_folders.folder_ids();
+        let sql = format!(
             r#"SELECT t.*, a.name as album_name, ar.name as artist_name
                FROM playlist_tracks pt
                JOIN tracks t ON pt.track_id = t.id
                LEFT JOIN albums a ON t.album_id = a.id
                LEFT JOIN artists ar ON t.artist_id = ar.id
-               WHERE pt.playlist_id = ? ORDER BY pt.position"#,
-        )
-        .bind(id)
-        .fetch_all(self.pool())
-        .await
-        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+               WHERE pt.playlist_id = ? AND t.missing = 0{} ORDER BY pt.position"#,
+            track_folder_filter("t", folders)
+        );
+        let entries = sqlx::query(&sql)
+            .bind(id)
+            .fetch_all(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        let mut entries: Vec<MediaFile> =
+            entries.iter().map(|r| self.row_to_media_file(r)).collect();
+        self.annotate(user, &mut entries).await?;
 
         Ok(Some(SubsonicPlaylistWithSongs {
             id: row.get("id"),
@@ -906,36 +1680,57 @@ impl SubsonicStorage for DatabaseStorage {
             created: Utc::now(),
             changed: Utc::now(),
             cover_art: row.get("cover_art_path"),
-            entries: entries.iter().map(|r| self.row_to_media_file(r)).collect(),
+            entries,
         }))
     }
 
     async fn create_playlist(
         &self,
+        user: &UserContext,
         name: Option<&str>,
         playlist_id: Option<&str>,
         song_ids: &[&str],
     ) -> Result<SubsonicPlaylistWithSongs> {
         let now = Utc::now().to_rfc3339();
-        let id = playlist_id
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| Uuid::new_v4().to_string());
-        let playlist_name = name.unwrap_or("New Playlist");
-
-        sqlx::query(
-            "INSERT INTO playlists (id, name, description, user_id, is_public, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)",
-        )
-        .bind(&id)
-        .bind(playlist_name)
-        .bind("")
-        .bind("system") // user_id
-        .bind(0i64) // is_public
-        .bind(&now)
-        .bind(&now)
-        .execute(self.pool())
-        .await
-        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        let id = if let Some(id) = playlist_id {
+            // 更新已有的播放列表：替换全部歌曲，指定名称时一并重命名
+            self.check_playlist_owner_internal(user, id).await?;
+            sqlx::query(
+                "UPDATE playlists SET name = COALESCE(?, name), updated_at = ? WHERE id = ?",
+            )
+            .bind(name)
+            .bind(&now)
+            .bind(id)
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+            sqlx::query("DELETE FROM playlist_tracks WHERE playlist_id = ?")
+                .bind(id)
+                .execute(self.pool())
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+            id.to_string()
+        } else {
+            let user_id = self.user_id_internal(user).await?;
+            let id = Uuid::new_v4().to_string();
+            sqlx::query(
+                "INSERT INTO playlists (id, name, description, user_id, is_public, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&id)
+            .bind(name.unwrap_or("New Playlist"))
+            .bind("")
+            .bind(&user_id)
+            .bind(0i64) // is_public
+            .bind(&now)
+            .bind(&now)
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+            id
+        };
 
+        // 用户无权访问的歌曲不加入播放列表
+        let song_ids = self.accessible_song_ids_internal(user, song_ids).await?;
         for (pos, song_id) in song_ids.iter().enumerate() {
             sqlx::query(
                 "INSERT INTO playlist_tracks (playlist_id, track_id, position, added_at) VALUES (?, ?, ?, ?)",
@@ -949,13 +1744,14 @@ impl SubsonicStorage for DatabaseStorage {
             .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
         }
 
-        SubsonicStorage::get_playlist(self, &id)
+        SubsonicStorage::get_playlist(self, user, &id)
             .await?
             .ok_or_else(|| StorageError::NotFound(format!("Playlist {} not found", id)))
     }
 
     async fn update_playlist(
         &self,
+        user: &UserContext,
         playlist_id: &str,
         name: Option<&str>,
         comment: Option<&str>,
@@ -963,6 +1759,7 @@ impl SubsonicStorage for DatabaseStorage {
         song_ids_to_add: &[&str],
         song_indexes_to_remove: &[i32],
     ) -> Result<()> {
+        self.check_playlist_owner_internal(user, playlist_id).await?;
         let now = Utc::now().to_rfc3339();
 
         if let Some(n) = name {
@@ -996,6 +1793,8 @@ impl SubsonicStorage for DatabaseStorage {
         }
 
         // Add songs
+        // 用户无权访问的歌曲不加入播放列表
+        let song_ids_to_add = self.accessible_song_ids_internal(user, song_ids_to_add).await?;
         for song_id in song_ids_to_add {
             let pos: i64 = sqlx::query_scalar(
                 "SELECT COALESCE(MAX(position), -1) + 1 FROM playlist_tracks WHERE playlist_id = ?",
@@ -1018,10 +1817,25 @@ impl SubsonicStorage for DatabaseStorage {
         }
 
         // Remove songs by index
+        // 索引对应 get_playlist 返回的曲目，不含文件已消失和用户无权访问的曲目
+        let folders = user.music_folders.folder_ids();
+        let sql = format!(
+            r#"SELECT pt.position FROM playlist_tracks pt JOIN tracks t ON pt.track_id = t.id
+               WHERE pt.playlist_id = ? AND t.missing = 0{} ORDER BY pt.position"#,
+            track_folder_filter("t", folders)
+        );
+        let positions: Vec<i64> = sqlx::query_scalar(&sql)
+            .bind(playlist_id)
+            .fetch_all(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
         for &idx in song_indexes_to_remove {
+            let Some(position) = usize::try_from(idx).ok().and_then(|i| positions.get(i)) else {
+                continue;
+            };
             sqlx::query("DELETE FROM playlist_tracks WHERE playlist_id = ? AND position = ?")
                 .bind(playlist_id)
-                .bind(idx)
+                .bind(position)
                 .execute(self.pool())
                 .await
                 .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
@@ -1030,7 +1844,8 @@ impl SubsonicStorage for DatabaseStorage {
         Ok(())
     }
 
-    async fn delete_playlist(&self, id: &str) -> Result<()> {
+    async fn delete_playlist(&self, user: &UserContext, id: &str) -> Result<()> {
+        self.check_playlist_owner_internal(user, id).await?;
         sqlx::query("DELETE FROM playlist_tracks WHERE playlist_id = ?")
             .bind(id)
             .execute(self.pool())
@@ -1048,18 +1863,26 @@ impl SubsonicStorage for DatabaseStorage {
 
     // === Media Retrieval ===
     async fn get_stream_path(&self, id: &str) -> Result<Option<String>> {
-        let row = sqlx::query("SELECT file_path FROM tracks WHERE id = ?")
-            .bind(id)
-            .fetch_optional(self.pool())
-            .await
-            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        let row: Option<(i32, String)> =
+            sqlx::query_as("SELECT library_id, file_path FROM tracks WHERE id = ?")
+                .bind(id)
+                .fetch_optional(self.pool())
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-        Ok(row.map(|r| r.get("file_path")))
+        // 文件位于所属音乐文件夹的 VFS 中
+        Ok(row.map(|(library_id, path)| library_path(library_id, &path)))
     }
 
-    async fn get_cover_art_path(&self, id: &str) -> Result<Option<String>> {
+    async fn get_cover_art_path(&self, user: &UserContext, id: &str) -> Result<Option<String>> {
+        let folders = user.music_folders.folder_ids();
+
         // First check if it's an album
-        let row = sqlx::query("SELECT cover_art_path FROM albums WHERE id = ?")
+        let sql = format!(
+            "SELECT cover_art_path FROM albums a WHERE a.id = ?{}",
+            album_folder_filter("a", folders)
+        );
+        let row = sqlx::query(&sql)
             .bind(id)
             .fetch_optional(self.pool())
             .await
@@ -1070,7 +1893,11 @@ impl SubsonicStorage for DatabaseStorage {
         }
 
         // Then check if it's a track
-        let row = sqlx::query("SELECT cover_art_path FROM tracks WHERE id = ?")
+        let sql = format!(
+            "SELECT cover_art_path FROM tracks t WHERE t.id = ?{}",
+            track_folder_filter("t", folders)
+        );
+        let row = sqlx::query(&sql)
             .bind(id)
             .fetch_optional(self.pool())
             .await
@@ -1096,178 +1923,617 @@ impl SubsonicStorage for DatabaseStorage {
     }
 
     // === Media Annotation ===
-    async fn star(&self, _ids: &[&str], _album_ids: &[&str], _artist_ids: &[&str]) -> Result<()> {
-        Ok(())
+    async fn star(
+        &self,
+        user: &UserContext,
+        ids: &[&str],
+        album_ids: &[&str],
+        artist_ids: &[&str],
+    ) -> Result<()> {
+        let user_id = self.user_id_internal(user).await?;
+        let now = Utc::now().to_rfc3339();
+        for id in ids {
+            // Subsonic 的 id 参数可以是歌曲、专辑或艺术家
+            let item_type = self.item_type_internal(id).await?;
+            self.set_starred_internal(&user_id, item_type, &[id], Some(&now))
+                .await?;
+        }
+        self.set_starred_internal(&user_id, "album", album_ids, Some(&now))
+            .await?;
+        self.set_starred_internal(&user_id, "artist", artist_ids, Some(&now))
+            .await
     }
 
-    async fn unstar(&self, _ids: &[&str], _album_ids: &[&str], _artist_ids: &[&str]) -> Result<()> {
-        Ok(())
+    async fn unstar(
+        &self,
+        user: &UserContext,
+        ids: &[&str],
+        album_ids: &[&str],
+        artist_ids: &[&str],
+    ) -> Result<()> {
+        let user_id = self.user_id_internal(user).await?;
+        for id in ids {
+            let item_type = self.item_type_internal(id).await?;
+            self.set_starred_internal(&user_id, item_type, &[id], None)
+                .await?;
+        }
+        self.set_starred_internal(&user_id, "album", album_ids, None)
+            .await?;
+        self.set_starred_internal(&user_id, "artist", artist_ids, None)
+            .await
     }
 
-    async fn set_rating(&self, _id: &str, _rating: i32) -> Result<()> {
+    async fn set_rating(&self, user: &UserContext, id: &str, rating: i32) -> Result<()> {
+        let user_id = self.user_id_internal(user).await?;
+        let item_type = self.item_type_internal(id).await?;
+        // 评分为 0 表示清除评分
+        let rating = (rating > 0).then_some(rating.min(5));
+
+        sqlx::query(
+            r#"INSERT INTO annotations (user_id, item_type, item_id, rating)
+               VALUES (?, ?, ?, ?)
+               ON CONFLICT(user_id, item_type, item_id)
+               DO UPDATE SET rating = excluded.rating"#,
+        )
+        .bind(&user_id)
+        .bind(item_type)
+        .bind(id)
+        .bind(rating)
+        .execute(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
         Ok(())
     }
 
-    async fn scrobble(&self, _id: &str, _time: Option<i64>, _submission: bool) -> Result<()> {
+    async fn scrobble(
+        &self,
+        user: &UserContext,
+        id: &str,
+        time: Option<i64>,
+        submission: bool,
+        client: Option<&str>,
+    ) -> Result<()> {
+        let user_id = self.user_id_internal(user).await?;
+        let played_at = time
+            .and_then(DateTime::from_timestamp_millis)
+            .unwrap_or_else(Utc::now);
+
+        if !submission {
+            let duration: Option<i64> =
+                sqlx::query_scalar("SELECT duration FROM tracks WHERE id = ?")
+                    .bind(id)
+                    .fetch_optional(self.pool())
+                    .await
+                    .map_err(|e| StorageError::DatabaseError(e.to_string()))?
+                    .ok_or_else(|| StorageError::NotFound(format!("Song {}", id)))?;
+            let expires_at =
+                played_at + chrono::Duration::seconds(duration.unwrap_or(0)) + NOW_PLAYING_GRACE;
+
+            sqlx::query(
+                r#"INSERT INTO now_playing (user_id, player_name, track_id, started_at, expires_at)
+                   VALUES (?, ?, ?, ?, ?)
+                   ON CONFLICT(user_id, player_name) DO UPDATE SET
+                       track_id = excluded.track_id,
+                       started_at = excluded.started_at,
+                       expires_at = excluded.expires_at"#,
+            )
+            .bind(&user_id)
+            .bind(client.unwrap_or(""))
+            .bind(id)
+            .bind(played_at.to_rfc3339())
+            .bind(expires_at.to_rfc3339())
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+            #[cfg(feature = "scrobbler")]
+            self.forward_now_playing(&user_id, id).await?;
+            return Ok(());
+        }
+
+        let played_at = played_at.to_rfc3339();
+        let mut tx = self
+            .pool()
+            .begin()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        sqlx::query("INSERT INTO scrobbles (track_id, user_id, played_at) VALUES (?, ?, ?)")
+            .bind(id)
+            .bind(&user_id)
+            .bind(&played_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        // 曲目和所属专辑的播放次数：按用户记在标注中，全局计数记在曲目和专辑表中
+        let statements = [
+            r#"INSERT INTO annotations (user_id, item_type, item_id, play_count, last_played)
+               VALUES (?1, 'song', ?2, 1, ?3)
+               ON CONFLICT(user_id, item_type, item_id)
+               DO UPDATE SET play_count = play_count + 1, last_played = excluded.last_played"#,
+            r#"INSERT INTO annotations (user_id, item_type, item_id, play_count, last_played)
+               SELECT ?1, 'album', album_id, 1, ?3 FROM tracks
+               WHERE id = ?2 AND album_id IS NOT NULL
+               ON CONFLICT(user_id, item_type, item_id)
+               DO UPDATE SET play_count = play_count + 1, last_played = excluded.last_played"#,
+            "UPDATE tracks SET play_count = COALESCE(play_count, 0) + 1 WHERE id = ?2",
+            r#"UPDATE albums SET play_count = COALESCE(play_count, 0) + 1
+               WHERE id = (SELECT album_id FROM tracks WHERE id = ?2)"#,
+        ];
+        for statement in statements {
+            sqlx::query(statement)
+                .bind(&user_id)
+                .bind(id)
+                .bind(&played_at)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        }
+        #[cfg(feature = "scrobbler")]
+        let queued = self.enqueue_scrobble(&mut tx, &user_id, id, &played_at).await?;
+
+        tx.commit()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        #[cfg(feature = "scrobbler")]
+        if queued > 0 {
+            self.spawn_scrobble_delivery();
+        }
         Ok(())
     }
 
     // === Bookmarks ===
-    async fn get_bookmarks(&self) -> Result<Vec<SubsonicBookmark>> {
-        Ok(vec![])
+    async fn get_bookmarks(&self, user: &UserContext) -> Result<Vec<SubsonicBookmark>> {
+        let user_id = self.user_id_internal(user).await?;
+        let rows = sqlx::query(
+            r#"SELECT t.*, a.name as album_name, ar.name as artist_name,
+                      b.position as bookmark_position, b.comment as bookmark_comment,
+                      b.created_at as bookmark_created, b.updated_at as bookmark_changed
+               FROM bookmarks b
+               JOIN tracks t ON b.track_id = t.id
+               LEFT JOIN albums a ON t.album_id = a.id
+               LEFT JOIN artists ar ON t.artist_id = ar.id
+               WHERE b.user_id = ? AND t.missing = 0
+               ORDER BY b.updated_at DESC"#,
+        )
+        .bind(&user_id)
+        .fetch_all(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        let mut bookmarks: Vec<SubsonicBookmark> = rows
+            .iter()
+            .map(|r| SubsonicBookmark {
+                position: r.get("bookmark_position"),
+                username: user.username.clone(),
+                comment: r.get("bookmark_comment"),
+                created: parse_timestamp(r.get("bookmark_created")).unwrap_or_else(Utc::now),
+                changed: parse_timestamp(r.get("bookmark_changed")).unwrap_or_else(Utc::now),
+                entry: self.row_to_media_file(r),
+            })
+            .collect();
+        self.annotate(user, bookmarks.iter_mut().map(|b| &mut b.entry))
+            .await?;
+        Ok(bookmarks)
     }
 
-    async fn create_bookmark(&self, _id: &str, _position: i64, _comment: Option<&str>) -> Result<()> {
+    async fn create_bookmark(
+        &self,
+        user: &UserContext,
+        id: &str,
+        position: i64,
+        comment: Option<&str>,
+    ) -> Result<()> {
+        if self.item_type_internal(id).await? != "song" {
+            return Err(StorageError::NotFound(format!("Song {}", id)));
+        }
+        let user_id = self.user_id_internal(user).await?;
+        let now = Utc::now().to_rfc3339();
+        sqlx::query(
+            r#"INSERT INTO bookmarks (user_id, track_id, position, comment, created_at, updated_at)
+               VALUES (?, ?, ?, ?, ?, ?)
+               ON CONFLICT(user_id, track_id)
+               DO UPDATE SET position = excluded.position, comment = excluded.comment,
+                             updated_at = excluded.updated_at"#,
+        )
+        .bind(&user_id)
+        .bind(id)
+        .bind(position)
+        .bind(comment)
+        .bind(&now)
+        .bind(&now)
+        .execute(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
         Ok(())
     }
 
-    async fn delete_bookmark(&self, _id: &str) -> Result<()> {
+    async fn delete_bookmark(&self, user: &UserContext, id: &str) -> Result<()> {
+        let user_id = self.user_id_internal(user).await?;
+        sqlx::query("DELETE FROM bookmarks WHERE user_id = ? AND track_id = ?")
+            .bind(&user_id)
+            .bind(id)
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
         Ok(())
     }
 
-    async fn get_play_queue(&self) -> Result<Option<SubsonicPlayQueue>> {
-        Ok(None)
+    async fn get_play_queue(&self, user: &UserContext) -> Result<Option<SubsonicPlayQueue>> {
+        let user_id = self.user_id_internal(user).await?;
+        let row = sqlx::query(
+            r#"SELECT track_ids, current_track_id, current_index, position, changed_at, changed_by
+               FROM play_queue WHERE user_id = ?"#,
+        )
+        .bind(&user_id)
+        .fetch_optional(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let track_ids: Vec<String> = serde_json::from_str(&row.get::<String, _>("track_ids"))?;
+        // 早期版本只记录了当前曲目的 ID
+        let current_track_id: Option<String> = row.get("current_track_id");
+        let stored_index = row
+            .get::<Option<i64>, _>("current_index")
+            .map(|i| i as usize)
+            .or_else(|| {
+                track_ids
+                    .iter()
+                    .position(|id| Some(id) == current_track_id.as_ref())
+            });
+
+        // 已删除的曲目不再出现在队列中，当前位置随之调整
+        let mut entries = Vec::with_capacity(track_ids.len());
+        let mut current_index = None;
+        for (index, id) in track_ids.iter().enumerate() {
+            if let Some(song) = self.get_song(user, id).await? {
+                if stored_index == Some(index) {
+                    current_index = Some(entries.len());
+                }
+                entries.push(song);
+            }
+        }
+
+        Ok(Some(SubsonicPlayQueue {
+            current: current_index.map(|i| entries[i].id.clone()),
+            current_index,
+            entries,
+            position: row.get::<Option<i64>, _>("position").unwrap_or(0),
+            username: user.username.clone(),
+            changed: parse_timestamp(row.get("changed_at")).unwrap_or_else(Utc::now),
+            changed_by: row.get("changed_by"),
+        }))
     }
 
     async fn save_play_queue(
         &self,
-        _ids: &[&str],
-        _current: Option<&str>,
-        _position: Option<i64>,
+        user: &UserContext,
+        ids: &[&str],
+        current_index: Option<usize>,
+        position: Option<i64>,
+        changed_by: &str,
     ) -> Result<()> {
+        let user_id = self.user_id_internal(user).await?;
+        if ids.is_empty() {
+            sqlx::query("DELETE FROM play_queue WHERE user_id = ?")
+                .bind(&user_id)
+                .execute(self.pool())
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+            return Ok(());
+        }
+
+        let track_ids = serde_json::to_string(ids)?;
+        let current_index = current_index.filter(|&i| i < ids.len());
+        sqlx::query(
+            r#"INSERT OR REPLACE INTO play_queue
+               (user_id, track_ids, current_track_id, current_index, position,
+                changed_at, changed_by)
+               VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(&user_id)
+        .bind(&track_ids)
+        .bind(current_index.map(|i| ids[i]))
+        .bind(current_index.map(|i| i as i64))
+        .bind(position.unwrap_or(0))
+        .bind(Utc::now().to_rfc3339())
+        .bind(changed_by)
+        .execute(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
         Ok(())
     }
 
     // === Shares ===
-    async fn get_shares(&self) -> Result<Vec<SubsonicShare>> {
-        Ok(vec![])
+    async fn get_shares(&self, user: &UserContext) -> Result<Vec<SubsonicShare>> {
+        let rows = sqlx::query(
+            r#"SELECT s.*, u.username, u.is_admin AS owner_is_admin
+               FROM shares s JOIN users u ON s.user_id = u.id
+               WHERE u.username = ? ORDER BY s.created_at"#,
+        )
+        .bind(&user.username)
+        .fetch_all(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        let mut shares = Vec::with_capacity(rows.len());
+        for row in &rows {
+            shares.push(self.row_to_share_internal(row).await?);
+        }
+        Ok(shares)
     }
 
     async fn create_share(
         &self,
-        _ids: &[&str],
-        _description: Option<&str>,
-        _expires: Option<i64>,
+        user: &UserContext,
+        ids: &[&str],
+        description: Option<&str>,
+        expires: Option<i64>,
     ) -> Result<SubsonicShare> {
-        Ok(SubsonicShare {
-            id: Uuid::new_v4().to_string(),
-            url: "".to_string(),
-            description: None,
-            username: "".to_string(),
-            created: Utc::now(),
-            expires: None,
-            last_visited: None,
-            visit_count: 0,
-            entries: vec![],
-        })
+        let user_id = self.user_id_internal(user).await?;
+        let mut items = Vec::with_capacity(ids.len());
+        for id in ids {
+            let item_type = self.share_item_type_internal(id).await?;
+            self.check_share_item_internal(user, item_type, id).await?;
+            items.push((item_type, *id));
+        }
+
+        // 分享 ID 同时是公开链接的令牌，使用随机 UUID 避免被猜到
+        let id = Uuid::new_v4().simple().to_string();
+        let now = Utc::now().to_rfc3339();
+        let mut tx = self
+            .pool()
+            .begin()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        sqlx::query(
+            r#"INSERT INTO shares (id, user_id, description, expires_at, created_at, updated_at)
+               VALUES (?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(&id)
+        .bind(&user_id)
+        .bind(description)
+        .bind(expires.and_then(millis_to_rfc3339))
+        .bind(&now)
+        .bind(&now)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        for (item_type, item_id) in items {
+            sqlx::query(
+                "INSERT OR IGNORE INTO share_items (share_id, item_type, item_id) VALUES (?, ?, ?)",
+            )
+            .bind(&id)
+            .bind(item_type)
+            .bind(item_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        }
+        tx.commit()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        self.share_internal(&id)
+            .await?
+            .ok_or_else(|| StorageError::NotFound(format!("Share {}", id)))
     }
 
     async fn update_share(
         &self,
-        _id: &str,
-        _description: Option<&str>,
-        _expires: Option<i64>,
+        user: &UserContext,
+        id: &str,
+        description: Option<&str>,
+        expires: Option<i64>,
     ) -> Result<()> {
+        self.check_share_owner_internal(user, id).await?;
+        sqlx::query(
+            r#"UPDATE shares SET description = COALESCE(?, description),
+                   expires_at = COALESCE(?, expires_at), updated_at = ?
+               WHERE id = ?"#,
+        )
+        .bind(description)
+        .bind(expires.and_then(millis_to_rfc3339))
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
         Ok(())
     }
 
-    async fn delete_share(&self, _id: &str) -> Result<()> {
+    async fn delete_share(&self, user: &UserContext, id: &str) -> Result<()> {
+        self.check_share_owner_internal(user, id).await?;
+        let mut tx = self
+            .pool()
+            .begin()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        for sql in [
+            "DELETE FROM share_items WHERE share_id = ?",
+            "DELETE FROM shares WHERE id = ?",
+        ] {
+            sqlx::query(sql)
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        }
+        tx.commit()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))
+    }
+
+    async fn get_public_share(&self, id: &str) -> Result<Option<SubsonicShare>> {
+        Ok(self
+            .share_internal(id)
+            .await?
+            .filter(|share| share.expires.is_none_or(|expires| expires > Utc::now())))
+    }
+
+    async fn record_share_visit(&self, id: &str) -> Result<()> {
+        sqlx::query(
+            r#"UPDATE shares SET visit_count = COALESCE(visit_count, 0) + 1, last_visited_at = ?
+               WHERE id = ?"#,
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
         Ok(())
     }
 
     // === Internet Radio ===
     async fn get_internet_radio_stations(&self) -> Result<Vec<SubsonicInternetRadioStation>> {
-        Ok(vec![])
+        let rows = sqlx::query(
+            "SELECT id, name, stream_url, homepage_url FROM internet_radio_stations ORDER BY name",
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        Ok(rows.iter().map(row_to_radio_station).collect())
+    }
+
+    async fn get_internet_radio_station(
+        &self,
+        id: &str,
+    ) -> Result<Option<SubsonicInternetRadioStation>> {
+        let row = sqlx::query(
+            "SELECT id, name, stream_url, homepage_url FROM internet_radio_stations WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        Ok(row.as_ref().map(row_to_radio_station))
     }
 
     async fn create_internet_radio_station(
         &self,
-        _stream_url: &str,
-        _name: &str,
-        _homepage_url: Option<&str>,
+        stream_url: &str,
+        name: &str,
+        homepage_url: Option<&str>,
     ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        sqlx::query(
+            r#"INSERT INTO internet_radio_stations
+               (name, stream_url, homepage_url, created_at, updated_at)
+               VALUES (?, ?, ?, ?, ?)"#,
+        )
+        .bind(name)
+        .bind(stream_url)
+        .bind(homepage_url)
+        .bind(&now)
+        .bind(&now)
+        .execute(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
         Ok(())
     }
 
     async fn update_internet_radio_station(
         &self,
-        _id: &str,
-        _stream_url: &str,
-        _name: &str,
-        _homepage_url: Option<&str>,
+        id: &str,
+        stream_url: &str,
+        name: &str,
+        homepage_url: Option<&str>,
     ) -> Result<()> {
+        let result = sqlx::query(
+            r#"UPDATE internet_radio_stations
+               SET name = ?, stream_url = ?, homepage_url = ?, updated_at = ?
+               WHERE id = ?"#,
+        )
+        .bind(name)
+        .bind(stream_url)
+        .bind(homepage_url)
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("Internet radio station {}", id)));
+        }
         Ok(())
     }
 
-    async fn delete_internet_radio_station(&self, _id: &str) -> Result<()> {
+    async fn delete_internet_radio_station(&self, id: &str) -> Result<()> {
+        let result = sqlx::query("DELETE FROM internet_radio_stations WHERE id = ?")
+            .bind(id)
+            .execute(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("Internet radio station {}", id)));
+        }
         Ok(())
     }
 
-    // === User Management ===
-    async fn get_user(&self, username: &str) -> Result<Option<SubsonicUser>> {
-        let row = sqlx::query(
-            r#"SELECT id, username, email, is_admin, created_at
-               FROM users WHERE username = ?"#,
+    // === Authentication ===
+    async fn get_user_password(&self, username: &str) -> Result<Option<String>> {
+        self.subsonic_password_internal(username).await
+    }
+
+    async fn verify_user_password(&self, username: &str, password: &str) -> Result<bool> {
+        self.verify_password_internal(username, password).await
+    }
+
+    async fn password_change_required(&self, username: &str) -> Result<bool> {
+        self.must_change_password_internal(username).await
+    }
+
+    fn supports_api_key_auth(&self) -> bool {
+        true
+    }
+
+    async fn get_username_by_api_key(&self, api_key: &str) -> Result<Option<String>> {
+        let row: Option<(String,)> = sqlx::query_as(
+            r#"SELECT u.username FROM api_keys k
+               JOIN users u ON u.id = k.user_id
+               WHERE k.api_key = ?"#,
         )
-        .bind(username)
+        .bind(api_key)
         .fetch_optional(self.pool())
         .await
         .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        Ok(row.map(|(username,)| username))
+    }
 
-        Ok(row.map(|r| SubsonicUser {
-            username: r.get("username"),
-            email: r.get("email"),
-            scrobbling_enabled: true,
-            max_bit_rate: None,
-            admin_role: r.get::<i64, _>("is_admin") != 0,
-            settings_role: true,
-            download_role: false,
-            upload_role: false,
-            playlist_role: true,
-            cover_art_role: false,
-            comment_role: false,
-            podcast_role: false,
-            stream_role: true,
-            jukebox_role: false,
-            share_role: false,
-            video_conversion_role: false,
-            avatar_last_changed: None,
-            folders: vec![],
-        }))
+    // === User Management ===
+    async fn get_user(&self, username: &str) -> Result<Option<SubsonicUser>> {
+        let row = sqlx::query(&format!("SELECT {} FROM users WHERE username = ?", USER_COLUMNS))
+            .bind(username)
+            .fetch_optional(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+
+        let Some(r) = row else {
+            return Ok(None);
+        };
+        let folders = self.user_folders_internal(r.get("id")).await?;
+        Ok(Some(row_to_user(&r, folders)))
     }
 
     async fn get_users(&self) -> Result<Vec<SubsonicUser>> {
-        let rows = sqlx::query(
-            r#"SELECT id, username, email, is_admin, created_at
-               FROM users ORDER BY username"#,
-        )
-        .fetch_all(self.pool())
-        .await
-        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        let rows = sqlx::query(&format!("SELECT {} FROM users ORDER BY username", USER_COLUMNS))
+            .fetch_all(self.pool())
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
 
-        Ok(rows
-            .into_iter()
-            .map(|r| SubsonicUser {
-                username: r.get("username"),
-                email: r.get("email"),
-                scrobbling_enabled: true,
-                max_bit_rate: None,
-                admin_role: r.get::<i64, _>("is_admin") != 0,
-                settings_role: true,
-                download_role: false,
-                upload_role: false,
-                playlist_role: true,
-                cover_art_role: false,
-                comment_role: false,
-                podcast_role: false,
-                stream_role: true,
-                jukebox_role: false,
-                share_role: false,
-                video_conversion_role: false,
-                avatar_last_changed: None,
-                folders: vec![],
-            })
-            .collect())
+        let mut users = Vec::with_capacity(rows.len());
+        for r in rows {
+            let folders = self.user_folders_internal(r.get("id")).await?;
+            users.push(row_to_user(&r, folders));
+        }
+        Ok(users)
     }
 
     async fn create_user(
@@ -1275,36 +2541,55 @@ impl SubsonicStorage for DatabaseStorage {
         username: &str,
         password: &str,
         email: Option<&str>,
-        _admin_role: bool,
-        _settings_role: bool,
-        _stream_role: bool,
-        _jukebox_role: bool,
-        _download_role: bool,
-        _upload_role: bool,
-        _playlist_role: bool,
-        _cover_art_role: bool,
-        _comment_role: bool,
-        _podcast_role: bool,
-        _share_role: bool,
-        _video_conversion_role: bool,
-        _music_folder_ids: &[i32],
+        admin_role: bool,
+        settings_role: bool,
+        stream_role: bool,
+        jukebox_role: bool,
+        download_role: bool,
+        upload_role: bool,
+        playlist_role: bool,
+        cover_art_role: bool,
+        comment_role: bool,
+        podcast_role: bool,
+        share_role: bool,
+        video_conversion_role: bool,
+        music_folders: &MusicFolderAccess,
     ) -> Result<()> {
         let now = Utc::now().to_rfc3339();
         let id = Uuid::new_v4().to_string();
 
+        let (hash, encrypted) = self.credentials_internal(password).await?;
+
         sqlx::query(
-            "INSERT INTO users (id, username, password_hash, email, is_admin, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO users (id, username, password_hash, subsonic_password, email, is_admin,
+                 settings_role, stream_role, jukebox_role, download_role, upload_role,
+                 playlist_role, cover_art_role, comment_role, podcast_role, share_role,
+                 video_conversion_role, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(&id)
         .bind(username)
-        .bind(password)
+        .bind(hash)
+        .bind(encrypted)
         .bind(email)
-        .bind(0i64)
+        .bind(admin_role)
+        .bind(settings_role)
+        .bind(stream_role)
+        .bind(jukebox_role)
+        .bind(download_role)
+        .bind(upload_role)
+        .bind(playlist_role)
+        .bind(cover_art_role)
+        .bind(comment_role)
+        .bind(podcast_role)
+        .bind(share_role)
+        .bind(video_conversion_role)
         .bind(&now)
         .bind(&now)
         .execute(self.pool())
         .await
         .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        self.set_user_folders_internal(&id, music_folders).await?;
 
         Ok(())
     }
@@ -1314,86 +2599,133 @@ impl SubsonicStorage for DatabaseStorage {
         username: &str,
         password: Option<&str>,
         email: Option<&str>,
-        _admin_role: Option<bool>,
-        _settings_role: Option<bool>,
-        _stream_role: Option<bool>,
-        _jukebox_role: Option<bool>,
-        _download_role: Option<bool>,
-        _upload_role: Option<bool>,
-        _playlist_role: Option<bool>,
-        _cover_art_role: Option<bool>,
-        _comment_role: Option<bool>,
-        _podcast_role: Option<bool>,
-        _share_role: Option<bool>,
-        _video_conversion_role: Option<bool>,
-        _music_folder_ids: Option<&[i32]>,
-        _max_bit_rate: Option<i32>,
+        admin_role: Option<bool>,
+        settings_role: Option<bool>,
+        stream_role: Option<bool>,
+        jukebox_role: Option<bool>,
+        download_role: Option<bool>,
+        upload_role: Option<bool>,
+        playlist_role: Option<bool>,
+        cover_art_role: Option<bool>,
+        comment_role: Option<bool>,
+        podcast_role: Option<bool>,
+        share_role: Option<bool>,
+        video_conversion_role: Option<bool>,
+        music_folders: Option<&MusicFolderAccess>,
+        max_bit_rate: Option<i32>,
     ) -> Result<()> {
-        let now = Utc::now().to_rfc3339();
-
-        // 动态构建更新语句
-        let mut updates = vec!["updated_at = ?".to_string()];
-        let mut params: Vec<String> = vec![now];
-
-        if let Some(pwd) = password {
-            updates.push("password_hash = ?".to_string());
-            params.push(pwd.to_string());
+        // 参数为 NULL 的列保持原值；max_bit_rate 为 0 时清除限制
+        let result = sqlx::query(
+            "UPDATE users SET
+                 email = COALESCE(?, email),
+                 is_admin = COALESCE(?, is_admin),
+                 settings_role = COALESCE(?, settings_role),
+                 stream_role = COALESCE(?, stream_role),
+                 jukebox_role = COALESCE(?, jukebox_role),
+                 download_role = COALESCE(?, download_role),
+                 upload_role = COALESCE(?, upload_role),
+                 playlist_role = COALESCE(?, playlist_role),
+                 cover_art_role = COALESCE(?, cover_art_role),
+                 comment_role = COALESCE(?, comment_role),
+                 podcast_role = COALESCE(?, podcast_role),
+                 share_role = COALESCE(?, share_role),
+                 video_conversion_role = COALESCE(?, video_conversion_role),
+                 max_bit_rate = CASE WHEN ? IS NULL THEN max_bit_rate ELSE NULLIF(?, 0) END,
+                 updated_at = ?
+             WHERE username = ?",
+        )
+        .bind(email)
+        .bind(admin_role)
+        .bind(settings_role)
+        .bind(stream_role)
+        .bind(jukebox_role)
+        .bind(download_role)
+        .bind(upload_role)
+        .bind(playlist_role)
+        .bind(cover_art_role)
+        .bind(comment_role)
+        .bind(podcast_role)
+        .bind(share_role)
+        .bind(video_conversion_role)
+        .bind(max_bit_rate)
+        .bind(max_bit_rate)
+        .bind(Utc::now().to_rfc3339())
+        .bind(username)
+        .execute(self.pool())
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("User {}", username)));
         }
 
-        if let Some(mail) = email {
-            updates.push("email = ?".to_string());
-            params.push(mail.to_string());
+        if let Some(pwd) = password {
+            self.set_password_internal(username, pwd, false).await?;
         }
-
-        let query = format!(
-            "UPDATE users SET {} WHERE username = ?",
-            updates.join(", ")
-        );
-        params.push(username.to_string());
-
-        // 根据参数数量执行查询
-        match params.len() {
-            2 => {
-                sqlx::query(&query)
-                    .bind(&params[0])
-                    .bind(&params[1])
-                    .execute(self.pool())
-                    .await
-                    .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
-            }
-            3 => {
-                sqlx::query(&query)
-                    .bind(&params[0])
-                    .bind(&params[1])
-                    .bind(&params[2])
-                    .execute(self.pool())
-                    .await
-                    .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
-            }
-            4 => {
-                sqlx::query(&query)
-                    .bind(&params[0])
-                    .bind(&params[1])
-                    .bind(&params[2])
-                    .bind(&params[3])
-                    .execute(self.pool())
-                    .await
-                    .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
-            }
-            _ => {}
+        if let Some(music_folders) = music_folders {
+            let user_id = self.user_id_internal(&UserContext::new(username, false)).await?;
+            self.set_user_folders_internal(&user_id, music_folders).await?;
         }
 
         Ok(())
     }
 
     async fn delete_user(&self, username: &str) -> Result<()> {
-        sqlx::query("DELETE FROM users WHERE username = ?")
+        // 任一步失败时整体回滚，避免留下删了一半的用户
+        let mut tx = self
+            .pool()
+            .begin()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        sqlx::query(
+            r#"DELETE FROM share_items WHERE share_id IN
+               (SELECT s.id FROM shares s JOIN users u ON s.user_id = u.id WHERE u.username = ?)"#,
+        )
+        .bind(username)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        sqlx::query(
+            r#"DELETE FROM playlist_tracks WHERE playlist_id IN
+               (SELECT p.id FROM playlists p JOIN users u ON p.user_id = u.id
+                WHERE u.username = ?)"#,
+        )
+        .bind(username)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        for table in [
+            "user_music_folders",
+            "now_playing",
+            "scrobble_accounts",
+            "scrobble_queue",
+            "shares",
+            "playlists",
+            "scrobbles",
+            "play_queue",
+            "bookmarks",
+            "api_keys",
+            "annotations",
+        ] {
+            sqlx::query(&format!(
+                "DELETE FROM {} WHERE user_id IN (SELECT id FROM users WHERE username = ?)",
+                table
+            ))
             .bind(username)
-            .execute(self.pool())
+            .execute(&mut *tx)
             .await
             .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
-
-        Ok(())
+        }
+        let result = sqlx::query("DELETE FROM users WHERE username = ?")
+            .bind(username)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("User {}", username)));
+        }
+        tx.commit()
+            .await
+            .map_err(|e| StorageError::DatabaseError(e.to_string()))
     }
 
     // === Scanning ===
@@ -1413,7 +2745,7 @@ impl SubsonicStorage for DatabaseStorage {
                     .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
                     .map(|d| d.with_timezone(&Utc)),
                 error: r.get("error"),
-                scan_type: None,
+                scan_type: r.get("scan_type"),
                 elapsed_time: None,
             },
             None => SubsonicScanStatus {
@@ -1428,10 +2760,9 @@ impl SubsonicStorage for DatabaseStorage {
         })
     }
 
-    async fn start_scan(&self) -> Result<SubsonicScanStatus> {
-        // 获取第一个音乐文件夹路径进行扫描
-        let folders = self.get_music_folders().await?;
-        
+    async fn start_scan(&self, full_scan: bool) -> Result<SubsonicScanStatus> {
+        let folders = self.library_folders().await?;
+
         if folders.is_empty() {
             return Ok(SubsonicScanStatus {
                 scanning: false,
@@ -1444,40 +2775,113 @@ impl SubsonicStorage for DatabaseStorage {
             });
         }
 
-        // 获取第一个文件夹的路径
-        let folder_path = sqlx::query_scalar::<_, String>(
-            "SELECT path FROM music_folders WHERE id = ?",
-        )
-        .bind(folders[0].id)
-        .fetch_optional(self.pool())
-        .await
-        .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
-
-        if let Some(path) = folder_path {
-            // 在后台启动扫描
-            #[cfg(feature = "scanner")]
-            {
-                let storage = self.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = storage.perform_scan(&path).await {
-                        tracing::error!("Scan failed: {}", e);
-                    }
-                });
-            }
+        // 在后台依次扫描所有音乐文件夹，失败的文件夹在扫描时记录日志
+        #[cfg(feature = "scanner")]
+        {
+            let storage = self.clone();
+            let folders: Vec<(i32, String)> =
+                folders.into_iter().map(|folder| (folder.id, folder.path)).collect();
+            tokio::spawn(async move {
+                let _ = storage.scan_folders(&folders, full_scan).await;
+            });
         }
 
         self.get_scan_status().await
     }
 
+    async fn get_music_folder_configs(&self) -> Result<Vec<LibraryFolder>> {
+        self.library_folders().await
+    }
+
+    async fn create_music_folder(
+        &self,
+        name: &str,
+        path: &str,
+        vfs_config: Option<&VfsConfig>,
+        scan: bool,
+    ) -> Result<LibraryFolder> {
+        let id = self.add_music_folder(name, path, vfs_config).await?;
+        let folder = LibraryFolder {
+            id,
+            name: name.to_string(),
+            path: path.to_string(),
+            vfs_config: vfs_config.cloned(),
+        };
+
+        // 只在后台扫描新添加的文件夹，失败时在扫描中记录日志
+        #[cfg(feature = "scanner")]
+        if scan {
+            let storage = self.clone();
+            let folder = folder.clone();
+            tokio::spawn(async move {
+                let _ = storage.perform_scan(folder.id, &folder.path, false).await;
+            });
+        }
+        #[cfg(not(feature = "scanner"))]
+        let _ = scan;
+
+        Ok(folder)
+    }
+
+    async fn update_music_folder(&self, folder: &LibraryFolder) -> Result<()> {
+        self.update_library_folder(folder).await
+    }
+
+    async fn delete_music_folder(&self, id: i32) -> Result<()> {
+        self.remove_library_folder(id).await
+    }
+
     async fn change_password(&self, username: &str, password: &str) -> Result<()> {
-        let now = Utc::now().to_rfc3339();
-        sqlx::query("UPDATE users SET password_hash = ?, updated_at = ? WHERE username = ?")
-            .bind(password)
-            .bind(&now)
-            .bind(username)
-            .execute(self.pool())
-            .await
-            .map_err(|e| StorageError::DatabaseError(e.to_string()))?;
-        Ok(())
+        self.set_password_internal(username, password, false).await
+    }
+
+    // === Scrobble Forwarding ===
+    #[cfg(feature = "scrobbler")]
+    async fn get_scrobble_accounts(
+        &self,
+        user: &UserContext,
+    ) -> Result<Vec<reverie_core::SubsonicScrobbleAccount>> {
+        Ok(self
+            .scrobble_accounts(&user.username)
+            .await?
+            .into_iter()
+            .map(|account| reverie_core::SubsonicScrobbleAccount {
+                service: account.service,
+                url: account.url,
+            })
+            .collect())
+    }
+
+    #[cfg(feature = "scrobbler")]
+    async fn update_scrobble_account(
+        &self,
+        user: &UserContext,
+        service: &str,
+        token: &str,
+        url: Option<&str>,
+    ) -> Result<()> {
+        let Some(forwarder) = self.scrobble_forwarder(service) else {
+            return Err(StorageError::NotFound(format!("Scrobble service {}", service)));
+        };
+        // 自定义地址由服务器发起请求，只有管理员可以设置
+        if let Some(url) = url {
+            if !user.is_admin {
+                return Err(StorageError::PermissionDenied(
+                    "Only administrators can set a custom scrobble URL".to_string(),
+                ));
+            }
+            forwarder.check_url(url)?;
+        }
+        let account = crate::scrobbler::ScrobbleAccount {
+            service: service.to_string(),
+            token: token.to_string(),
+            url: url.map(str::to_string),
+        };
+        self.set_scrobble_account(&user.username, &account).await
+    }
+
+    #[cfg(feature = "scrobbler")]
+    async fn delete_scrobble_account(&self, user: &UserContext, service: &str) -> Result<()> {
+        self.remove_scrobble_account(&user.username, service).await
     }
 }
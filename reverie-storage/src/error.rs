@@ -24,6 +24,12 @@ pub enum StorageError {
 
     #[error("Permission denied: {0}")]
     PermissionDenied(String),
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("Credential error: {0}")]
+    CredentialError(String),
 }
 
 pub type Result<T> = std::result::Result<T, StorageError>;
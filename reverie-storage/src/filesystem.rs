@@ -7,11 +7,12 @@ use reverie_core::{Album, Artist, Playlist, PlaylistTrack, Track, User};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use uuid::Uuid;
 
 use crate::error::{Result, StorageError};
 use crate::traits::*;
+use crate::vfs::VfsLocation;
 
 /// 文件系统存储配置
 #[derive(Debug, Clone)]
@@ -525,6 +526,14 @@ impl FileStorage for FileSystemStorage {
         Ok(contents)
     }
 
+    async fn read_file_range(&self, path: &str, offset: u64, length: u64) -> Result<Vec<u8>> {
+        let mut file = tokio::fs::File::open(Path::new(path)).await?;
+        file.seek(std::io::SeekFrom::Start(offset)).await?;
+        let mut contents = Vec::new();
+        file.take(length).read_to_end(&mut contents).await?;
+        Ok(contents)
+    }
+
     async fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
         let path = Path::new(path);
         if let Some(parent) = path.parent() {
@@ -561,4 +570,8 @@ impl FileStorage for FileSystemStorage {
             is_dir: metadata.is_dir(),
         })
     }
+
+    async fn direct_location(&self, path: &str) -> Option<VfsLocation> {
+        std::path::absolute(path).ok().map(VfsLocation::Local)
+    }
 }
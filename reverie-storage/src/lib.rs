@@ -37,6 +37,7 @@
 //! );
 //! ```
 
+pub mod credentials;
 pub mod error;
 pub mod traits;
 pub mod vfs;
@@ -53,15 +54,32 @@ pub mod database;
 #[cfg(feature = "scanner")]
 pub mod scanner;
 
+#[cfg(feature = "outbound")]
+pub mod outbound;
+
+#[cfg(feature = "scrobbler")]
+pub mod scrobbler;
+
 #[cfg(test)]
 mod tests;
 
 pub use error::*;
 pub use traits::*;
-pub use vfs::{create_vfs, OpendalVfs, SharedVfs, Vfs, VfsConfig, VfsEntry, VfsMetadata};
+pub use vfs::{
+    create_vfs, OpendalVfs, SharedVfs, Vfs, VfsConfig, VfsEntry, VfsLocation, VfsMetadata,
+    VfsRead, VfsReader, VfsWrite, VfsWriter,
+};
 
 #[cfg(feature = "database")]
 pub use database::{DatabaseConfig, DatabaseStorage};
 
 #[cfg(feature = "scanner")]
-pub use scanner::{AudioMetadata, MediaScanner, ScanProgress, ScanResult, ScannedAlbum, ScannedArtist, ScannedTrack};
+pub use scanner::{
+    AudioMetadata, FileState, LibraryWatcher, MediaScanner, RescanTarget, ScanProgress, ScanResult,
+    ScannedAlbum, ScannedArtist, ScannedTrack, WatcherConfig,
+};
+
+#[cfg(feature = "scrobbler")]
+pub use scrobbler::{
+    LastFmForwarder, ListenBrainzForwarder, ScrobbleAccount, ScrobbleForwarder, ScrobbleTrack,
+};
@@ -1,11 +1,27 @@
 //! MemoryStorage 基础结构
 
-use reverie_core::{Album, Artist, Playlist, PlaylistTrack, Track, User};
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use reverie_core::{
+    Album, Artist, Playlist, PlaylistTrack, SubsonicBookmark, SubsonicPlayQueue, Track, User,
+};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+/// 单个用户的标注数据（收藏、评分、播放次数、书签、播放队列）
+#[derive(Debug, Clone, Default)]
+pub(crate) struct UserAnnotations {
+    pub starred_songs: HashMap<String, DateTime<Utc>>,
+    pub starred_albums: HashMap<String, DateTime<Utc>>,
+    pub starred_artists: HashMap<String, DateTime<Utc>>,
+    pub ratings: HashMap<String, i32>,
+    pub play_counts: HashMap<String, i64>,
+    pub bookmarks: HashMap<String, SubsonicBookmark>,
+    pub play_queue: Option<SubsonicPlayQueue>,
+}
+
 /// 使用 HashMap 的内存存储实现
 #[derive(Clone)]
 pub struct MemoryStorage {
@@ -16,6 +32,12 @@ pub struct MemoryStorage {
     pub(crate) playlists: Arc<RwLock<HashMap<Uuid, Playlist>>>,
     pub(crate) playlist_tracks: Arc<RwLock<HashMap<Uuid, Vec<PlaylistTrack>>>>,
     pub(crate) files: Arc<RwLock<HashMap<String, Vec<u8>>>>,
+    /// 按用户名索引的标注数据
+    pub(crate) annotations: Arc<RwLock<HashMap<String, UserAnnotations>>>,
+    /// 必须先修改密码的用户名
+    pub(crate) must_change_password: Arc<RwLock<HashSet<String>>>,
+    /// 保存首次启动时生成的管理员密码的文件
+    pub(crate) admin_password_file: Option<PathBuf>,
 }
 
 impl MemoryStorage {
@@ -28,8 +50,17 @@ impl MemoryStorage {
             playlists: Arc::new(RwLock::new(HashMap::new())),
             playlist_tracks: Arc::new(RwLock::new(HashMap::new())),
             files: Arc::new(RwLock::new(HashMap::new())),
+            annotations: Arc::new(RwLock::new(HashMap::new())),
+            must_change_password: Arc::new(RwLock::new(HashSet::new())),
+            admin_password_file: None,
         }
     }
+
+    /// 首次启动时将管理员的初始密码写入指定文件（只有所有者可读），未指定时不保存初始密码
+    pub fn with_admin_password_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.admin_password_file = Some(path.into());
+        self
+    }
 }
 
 impl Default for MemoryStorage {
@@ -2,17 +2,19 @@
 
 use crate::error::Result;
 use crate::traits::*;
+use crate::vfs::VfsConfig;
 
 use async_trait::async_trait;
 use chrono::Utc;
 use reverie_core::{
-    MediaFile, SubsonicAlbum, SubsonicAlbumInfo, SubsonicArtist, SubsonicArtistIndexes,
-    SubsonicArtistInfo, SubsonicBookmark, SubsonicDirectory, SubsonicGenre,
+    MediaFile, MusicFolderAccess, SubsonicAlbum, SubsonicAlbumInfo, SubsonicArtist,
+    SubsonicArtistIndexes, SubsonicArtistInfo, SubsonicBookmark, SubsonicDirectory, SubsonicGenre,
     SubsonicInternetRadioStation, SubsonicLyrics, SubsonicMusicFolder, SubsonicNowPlaying,
     SubsonicPlayQueue, SubsonicPlaylist, SubsonicPlaylistWithSongs, SubsonicScanStatus,
     SubsonicSearchResult2, SubsonicSearchResult3, SubsonicShare, SubsonicStarred,
-    SubsonicStructuredLyrics, SubsonicTopSongs, SubsonicUser,
+    SubsonicStructuredLyrics, SubsonicTopSongs, SubsonicUser, User, UserContext,
 };
+use uuid::Uuid;
 
 use super::core::MemoryStorage;
 
@@ -26,6 +28,16 @@ impl FileStorage for MemoryStorage {
             .ok_or_else(|| crate::error::StorageError::NotFound(path.to_string()))
     }
 
+    async fn read_file_range(&self, path: &str, offset: u64, length: u64) -> Result<Vec<u8>> {
+        let files = self.files.read().await;
+        let data = files
+            .get(path)
+            .ok_or_else(|| crate::error::StorageError::NotFound(path.to_string()))?;
+        let start = (offset as usize).min(data.len());
+        let end = start.saturating_add(length as usize).min(data.len());
+        Ok(data[start..end].to_vec())
+    }
+
     async fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
         let mut files = self.files.write().await;
         files.insert(path.to_string(), data.to_vec());
@@ -69,6 +81,32 @@ impl FileStorage for MemoryStorage {
 #[async_trait]
 impl Storage for MemoryStorage {
     async fn initialize(&self) -> Result<()> {
+        // 与 DatabaseStorage 一致：首次启动时创建使用随机密码的管理员，登录后必须先修改密码。
+        // 密码不写入日志，只保存在配置的文件中。内存存储不持久化，密码直接以明文保存
+        let mut users = self.users.write().await;
+        if users.is_empty() {
+            let password = crate::credentials::generate_password();
+            match &self.admin_password_file {
+                Some(path) => crate::credentials::write_initial_password(path, "admin", &password)?,
+                None => tracing::warn!(
+                    "Created user 'admin' with a random initial password that is not saved anywhere"
+                ),
+            }
+            self.must_change_password
+                .write()
+                .await
+                .insert("admin".to_string());
+            let admin = User {
+                id: Uuid::new_v4(),
+                username: "admin".to_string(),
+                password_hash: password,
+                email: Some("admin@reverie.local".to_string()),
+                is_admin: true,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            };
+            users.insert(admin.id, admin);
+        }
         Ok(())
     }
 
@@ -93,13 +131,14 @@ impl SubsonicStorage for MemoryStorage {
 
     async fn get_indexes(
         &self,
-        _music_folder_id: Option<i32>,
+        _user: &UserContext,
+        _music_folder_ids: Option<&[i32]>,
         _if_modified_since: Option<i64>,
     ) -> Result<SubsonicArtistIndexes> {
         Ok(vec![])
     }
 
-    async fn get_genres(&self) -> Result<Vec<SubsonicGenre>> {
+    async fn get_genres(&self, _music_folder_ids: Option<&[i32]>) -> Result<Vec<SubsonicGenre>> {
         Ok(vec![
             SubsonicGenre {
                 name: "Rock".to_string(),
@@ -114,7 +153,11 @@ impl SubsonicStorage for MemoryStorage {
         ])
     }
 
-    async fn get_music_directory(&self, id: &str) -> Result<Option<SubsonicDirectory>> {
+    async fn get_music_directory(
+        &self,
+        _user: &UserContext,
+        id: &str,
+    ) -> Result<Option<SubsonicDirectory>> {
         Ok(Some(SubsonicDirectory {
             id: id.to_string(),
             parent: None,
@@ -132,11 +175,15 @@ impl SubsonicStorage for MemoryStorage {
         }))
     }
 
-    async fn get_artists(&self, _music_folder_id: Option<i32>) -> Result<SubsonicArtistIndexes> {
+    async fn get_artists(
+        &self,
+        _user: &UserContext,
+        _music_folder_ids: Option<&[i32]>,
+    ) -> Result<SubsonicArtistIndexes> {
         Ok(vec![])
     }
 
-    async fn get_artist(&self, id: &str) -> Result<Option<SubsonicArtist>> {
+    async fn get_artist(&self, _user: &UserContext, id: &str) -> Result<Option<SubsonicArtist>> {
         Ok(Some(SubsonicArtist {
             id: id.to_string(),
             name: "Test Artist".to_string(),
@@ -147,7 +194,7 @@ impl SubsonicStorage for MemoryStorage {
         }))
     }
 
-    async fn get_album(&self, id: &str) -> Result<Option<SubsonicAlbum>> {
+    async fn get_album(&self, _user: &UserContext, id: &str) -> Result<Option<SubsonicAlbum>> {
         Ok(Some(SubsonicAlbum {
             id: id.to_string(),
             name: "Test Album".to_string(),
@@ -167,7 +214,7 @@ impl SubsonicStorage for MemoryStorage {
         }))
     }
 
-    async fn get_song(&self, id: &str) -> Result<Option<MediaFile>> {
+    async fn get_song(&self, _user: &UserContext, id: &str) -> Result<Option<MediaFile>> {
         Ok(Some(MediaFile {
             id: id.to_string(),
             parent: Some("album-1".to_string()),
@@ -243,63 +290,91 @@ impl SubsonicStorage for MemoryStorage {
         self.get_album_info(id).await
     }
 
-    async fn get_similar_songs(&self, _id: &str, _count: Option<i32>) -> Result<Vec<MediaFile>> {
+    async fn get_similar_songs(
+        &self,
+        _user: &UserContext,
+        _id: &str,
+        _count: Option<i32>,
+    ) -> Result<Vec<MediaFile>> {
         Ok(vec![])
     }
 
-    async fn get_similar_songs2(&self, id: &str, count: Option<i32>) -> Result<Vec<MediaFile>> {
-        self.get_similar_songs(id, count).await
+    async fn get_similar_songs2(
+        &self,
+        user: &UserContext,
+        id: &str,
+        count: Option<i32>,
+    ) -> Result<Vec<MediaFile>> {
+        self.get_similar_songs(user, id, count).await
     }
 
-    async fn get_top_songs(&self, _artist: &str, _count: Option<i32>) -> Result<SubsonicTopSongs> {
+    async fn get_top_songs(
+        &self,
+        _user: &UserContext,
+        _artist: &str,
+        _count: Option<i32>,
+    ) -> Result<SubsonicTopSongs> {
         Ok(SubsonicTopSongs { songs: vec![] })
     }
 
     // === Album/Song Lists ===
     async fn get_album_list(
         &self,
+        _user: &UserContext,
         _list_type: &str,
         _size: Option<i32>,
         _offset: Option<i32>,
         _from_year: Option<i32>,
         _to_year: Option<i32>,
         _genre: Option<&str>,
-        _music_folder_id: Option<i32>,
+        _music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<SubsonicAlbum>> {
         Ok(vec![])
     }
 
     async fn get_album_list2(
         &self,
+        user: &UserContext,
         list_type: &str,
         size: Option<i32>,
         offset: Option<i32>,
         from_year: Option<i32>,
         to_year: Option<i32>,
         genre: Option<&str>,
-        music_folder_id: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<SubsonicAlbum>> {
-        self.get_album_list(list_type, size, offset, from_year, to_year, genre, music_folder_id)
-            .await
+        self.get_album_list(
+            user,
+            list_type,
+            size,
+            offset,
+            from_year,
+            to_year,
+            genre,
+            music_folder_ids,
+        )
+        .await
     }
 
     async fn get_random_songs(
         &self,
+        _user: &UserContext,
         _size: Option<i32>,
         _genre: Option<&str>,
         _from_year: Option<i32>,
         _to_year: Option<i32>,
-        _music_folder_id: Option<i32>,
+        _music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<MediaFile>> {
         Ok(vec![])
     }
 
     async fn get_songs_by_genre(
         &self,
+        _user: &UserContext,
         _genre: &str,
         _count: Option<i32>,
         _offset: Option<i32>,
-        _music_folder_id: Option<i32>,
+        _music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<MediaFile>> {
         Ok(vec![])
     }
@@ -307,6 +382,7 @@ impl SubsonicStorage for MemoryStorage {
     // === Searching ===
     async fn search2(
         &self,
+        _user: &UserContext,
         _query: &str,
         _artist_count: Option<i32>,
         _artist_offset: Option<i32>,
@@ -314,6 +390,7 @@ impl SubsonicStorage for MemoryStorage {
         _album_offset: Option<i32>,
         _song_count: Option<i32>,
         _song_offset: Option<i32>,
+        _music_folder_ids: Option<&[i32]>,
     ) -> Result<SubsonicSearchResult2> {
         Ok(SubsonicSearchResult2 {
             artists: vec![],
@@ -324,6 +401,7 @@ impl SubsonicStorage for MemoryStorage {
 
     async fn search3(
         &self,
+        _user: &UserContext,
         _query: &str,
         _artist_count: Option<i32>,
         _artist_offset: Option<i32>,
@@ -331,6 +409,7 @@ impl SubsonicStorage for MemoryStorage {
         _album_offset: Option<i32>,
         _song_count: Option<i32>,
         _song_offset: Option<i32>,
+        _music_folder_ids: Option<&[i32]>,
     ) -> Result<SubsonicSearchResult3> {
         Ok(SubsonicSearchResult3 {
             artists: vec![],
@@ -340,13 +419,29 @@ impl SubsonicStorage for MemoryStorage {
     }
 
     // === User ===
-    async fn get_user(&self, _username: &str) -> Result<Option<SubsonicUser>> {
+    async fn get_user_password(&self, username: &str) -> Result<Option<String>> {
+        let users = self.users.read().await;
+        Ok(users
+            .values()
+            .find(|u| u.username == username)
+            .map(|u| u.password_hash.clone()))
+    }
+
+    async fn password_change_required(&self, username: &str) -> Result<bool> {
+        Ok(self.must_change_password.read().await.contains(username))
+    }
+
+    async fn get_user(&self, username: &str) -> Result<Option<SubsonicUser>> {
+        let users = self.users.read().await;
+        let Some(user) = users.values().find(|u| u.username == username) else {
+            return Ok(None);
+        };
         Ok(Some(SubsonicUser {
-            username: "test".to_string(),
-            email: None,
+            username: user.username.clone(),
+            email: user.email.clone(),
             scrobbling_enabled: true,
             max_bit_rate: None,
-            admin_role: false,
+            admin_role: user.is_admin,
             settings_role: true,
             download_role: true,
             upload_role: true,
@@ -359,7 +454,7 @@ impl SubsonicStorage for MemoryStorage {
             share_role: true,
             video_conversion_role: true,
             avatar_last_changed: None,
-            folders: vec![],
+            folders: MusicFolderAccess::All,
         }))
     }
 
@@ -384,7 +479,7 @@ impl SubsonicStorage for MemoryStorage {
         _podcast_role: bool,
         _share_role: bool,
         _video_conversion_role: bool,
-        _music_folder_ids: &[i32],
+        _music_folders: &MusicFolderAccess,
     ) -> Result<()> {
         Ok(())
     }
@@ -406,7 +501,7 @@ impl SubsonicStorage for MemoryStorage {
         _podcast_role: Option<bool>,
         _share_role: Option<bool>,
         _video_conversion_role: Option<bool>,
-        _music_folder_ids: Option<&[i32]>,
+        _music_folders: Option<&MusicFolderAccess>,
         _max_bit_rate: Option<i32>,
     ) -> Result<()> {
         Ok(())
@@ -416,16 +511,32 @@ impl SubsonicStorage for MemoryStorage {
         Ok(())
     }
 
-    async fn change_password(&self, _username: &str, _password: &str) -> Result<()> {
+    async fn change_password(&self, username: &str, password: &str) -> Result<()> {
+        let mut users = self.users.write().await;
+        let user = users
+            .values_mut()
+            .find(|u| u.username == username)
+            .ok_or_else(|| crate::error::StorageError::NotFound(format!("User {}", username)))?;
+        user.password_hash = password.to_string();
+        user.updated_at = Utc::now();
+        self.must_change_password.write().await.remove(username);
         Ok(())
     }
 
     // === Playlists ===
-    async fn get_playlists(&self, _username: Option<&str>) -> Result<Vec<SubsonicPlaylist>> {
+    async fn get_playlists(
+        &self,
+        _user: &UserContext,
+        _username: Option<&str>,
+    ) -> Result<Vec<SubsonicPlaylist>> {
         Ok(vec![])
     }
 
-    async fn get_playlist(&self, id: &str) -> Result<Option<SubsonicPlaylistWithSongs>> {
+    async fn get_playlist(
+        &self,
+        _user: &UserContext,
+        id: &str,
+    ) -> Result<Option<SubsonicPlaylistWithSongs>> {
         Ok(Some(SubsonicPlaylistWithSongs {
             id: id.to_string(),
             name: "Test Playlist".to_string(),
@@ -443,6 +554,7 @@ impl SubsonicStorage for MemoryStorage {
 
     async fn create_playlist(
         &self,
+        _user: &UserContext,
         _name: Option<&str>,
         _playlist_id: Option<&str>,
         _song_ids: &[&str],
@@ -464,6 +576,7 @@ impl SubsonicStorage for MemoryStorage {
 
     async fn update_playlist(
         &self,
+        _user: &UserContext,
         _playlist_id: &str,
         _name: Option<&str>,
         _comment: Option<&str>,
@@ -474,7 +587,7 @@ impl SubsonicStorage for MemoryStorage {
         Ok(())
     }
 
-    async fn delete_playlist(&self, _id: &str) -> Result<()> {
+    async fn delete_playlist(&self, _user: &UserContext, _id: &str) -> Result<()> {
         Ok(())
     }
 
@@ -483,7 +596,7 @@ impl SubsonicStorage for MemoryStorage {
         Ok(None)
     }
 
-    async fn get_cover_art_path(&self, _id: &str) -> Result<Option<String>> {
+    async fn get_cover_art_path(&self, _user: &UserContext, _id: &str) -> Result<Option<String>> {
         Ok(None)
     }
 
@@ -509,77 +622,182 @@ impl SubsonicStorage for MemoryStorage {
     }
 
     // === Annotation ===
-    async fn star(&self, _ids: &[&str], _album_ids: &[&str], _artist_ids: &[&str]) -> Result<()> {
+    async fn star(
+        &self,
+        user: &UserContext,
+        ids: &[&str],
+        album_ids: &[&str],
+        artist_ids: &[&str],
+    ) -> Result<()> {
+        let now = Utc::now();
+        let mut annotations = self.annotations.write().await;
+        let entry = annotations.entry(user.username.clone()).or_default();
+        entry
+            .starred_songs
+            .extend(ids.iter().map(|id| (id.to_string(), now)));
+        entry
+            .starred_albums
+            .extend(album_ids.iter().map(|id| (id.to_string(), now)));
+        entry
+            .starred_artists
+            .extend(artist_ids.iter().map(|id| (id.to_string(), now)));
         Ok(())
     }
 
-    async fn unstar(&self, _ids: &[&str], _album_ids: &[&str], _artist_ids: &[&str]) -> Result<()> {
+    async fn unstar(
+        &self,
+        user: &UserContext,
+        ids: &[&str],
+        album_ids: &[&str],
+        artist_ids: &[&str],
+    ) -> Result<()> {
+        let mut annotations = self.annotations.write().await;
+        if let Some(entry) = annotations.get_mut(&user.username) {
+            for id in ids {
+                entry.starred_songs.remove(*id);
+            }
+            for id in album_ids {
+                entry.starred_albums.remove(*id);
+            }
+            for id in artist_ids {
+                entry.starred_artists.remove(*id);
+            }
+        }
         Ok(())
     }
 
-    async fn set_rating(&self, _id: &str, _rating: i32) -> Result<()> {
+    async fn set_rating(&self, user: &UserContext, id: &str, rating: i32) -> Result<()> {
+        let mut annotations = self.annotations.write().await;
+        let entry = annotations.entry(user.username.clone()).or_default();
+        if rating > 0 {
+            entry.ratings.insert(id.to_string(), rating.min(5));
+        } else {
+            entry.ratings.remove(id);
+        }
         Ok(())
     }
 
-    async fn scrobble(&self, _id: &str, _time: Option<i64>, _submission: bool) -> Result<()> {
+    async fn scrobble(
+        &self,
+        user: &UserContext,
+        id: &str,
+        _time: Option<i64>,
+        submission: bool,
+        _client: Option<&str>,
+    ) -> Result<()> {
+        if submission {
+            let mut annotations = self.annotations.write().await;
+            let entry = annotations.entry(user.username.clone()).or_default();
+            *entry.play_counts.entry(id.to_string()).or_insert(0) += 1;
+        }
         Ok(())
     }
 
     // === Bookmarks ===
-    async fn get_bookmarks(&self) -> Result<Vec<SubsonicBookmark>> {
-        Ok(vec![])
+    async fn get_bookmarks(&self, user: &UserContext) -> Result<Vec<SubsonicBookmark>> {
+        let annotations = self.annotations.read().await;
+        Ok(annotations
+            .get(&user.username)
+            .map(|entry| entry.bookmarks.values().cloned().collect())
+            .unwrap_or_default())
     }
 
     async fn create_bookmark(
         &self,
-        _id: &str,
-        _position: i64,
-        _comment: Option<&str>,
+        user: &UserContext,
+        id: &str,
+        position: i64,
+        comment: Option<&str>,
     ) -> Result<()> {
+        let Some(entry_file) = self.get_song(user, id).await? else {
+            return Err(crate::error::StorageError::NotFound(id.to_string()));
+        };
+        let now = Utc::now();
+        let mut annotations = self.annotations.write().await;
+        let entry = annotations.entry(user.username.clone()).or_default();
+        let created = entry.bookmarks.get(id).map(|b| b.created).unwrap_or(now);
+        entry.bookmarks.insert(
+            id.to_string(),
+            SubsonicBookmark {
+                position,
+                username: user.username.clone(),
+                comment: comment.map(str::to_string),
+                created,
+                changed: now,
+                entry: entry_file,
+            },
+        );
         Ok(())
     }
 
-    async fn delete_bookmark(&self, _id: &str) -> Result<()> {
+    async fn delete_bookmark(&self, user: &UserContext, id: &str) -> Result<()> {
+        let mut annotations = self.annotations.write().await;
+        if let Some(entry) = annotations.get_mut(&user.username) {
+            entry.bookmarks.remove(id);
+        }
         Ok(())
     }
 
     // === Play Queue ===
-    async fn get_play_queue(&self) -> Result<Option<SubsonicPlayQueue>> {
-        Ok(Some(SubsonicPlayQueue {
-            current: None,
-            position: 0,
-            username: "test".to_string(),
-            changed: Utc::now(),
-            changed_by: "test".to_string(),
-            entries: vec![],
-        }))
+    async fn get_play_queue(&self, user: &UserContext) -> Result<Option<SubsonicPlayQueue>> {
+        let annotations = self.annotations.read().await;
+        Ok(annotations
+            .get(&user.username)
+            .and_then(|entry| entry.play_queue.clone()))
     }
 
     async fn save_play_queue(
         &self,
-        _ids: &[&str],
-        _current: Option<&str>,
-        _position: Option<i64>,
+        user: &UserContext,
+        ids: &[&str],
+        current_index: Option<usize>,
+        position: Option<i64>,
+        changed_by: &str,
     ) -> Result<()> {
+        let mut entries = Vec::with_capacity(ids.len());
+        let mut current = None;
+        for (index, id) in ids.iter().enumerate() {
+            if let Some(song) = self.get_song(user, id).await? {
+                if current_index == Some(index) {
+                    current = Some(entries.len());
+                }
+                entries.push(song);
+            }
+        }
+        let mut annotations = self.annotations.write().await;
+        annotations.entry(user.username.clone()).or_default().play_queue = if ids.is_empty() {
+            None
+        } else {
+            Some(SubsonicPlayQueue {
+                current: current.map(|i: usize| entries[i].id.clone()),
+                current_index: current,
+                entries,
+                position: position.unwrap_or(0),
+                username: user.username.clone(),
+                changed: Utc::now(),
+                changed_by: changed_by.to_string(),
+            })
+        };
         Ok(())
     }
 
     // === Shares ===
-    async fn get_shares(&self) -> Result<Vec<SubsonicShare>> {
+    async fn get_shares(&self, _user: &UserContext) -> Result<Vec<SubsonicShare>> {
         Ok(vec![])
     }
 
     async fn create_share(
         &self,
+        user: &UserContext,
         _ids: &[&str],
-        _description: Option<&str>,
+        description: Option<&str>,
         _expires: Option<i64>,
     ) -> Result<SubsonicShare> {
         Ok(SubsonicShare {
             id: "share-1".to_string(),
-            url: "http://localhost:8080/share/share-1".to_string(),
-            description: None,
-            username: "test".to_string(),
+            url: "/share/share-1".to_string(),
+            description: description.map(str::to_string),
+            username: user.username.clone(),
             created: Utc::now(),
             expires: None,
             last_visited: None,
@@ -590,6 +808,7 @@ impl SubsonicStorage for MemoryStorage {
 
     async fn update_share(
         &self,
+        _user: &UserContext,
         _id: &str,
         _description: Option<&str>,
         _expires: Option<i64>,
@@ -597,7 +816,15 @@ impl SubsonicStorage for MemoryStorage {
         Ok(())
     }
 
-    async fn delete_share(&self, _id: &str) -> Result<()> {
+    async fn delete_share(&self, _user: &UserContext, _id: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn get_public_share(&self, _id: &str) -> Result<Option<SubsonicShare>> {
+        Ok(None)
+    }
+
+    async fn record_share_visit(&self, _id: &str) -> Result<()> {
         Ok(())
     }
 
@@ -606,6 +833,13 @@ impl SubsonicStorage for MemoryStorage {
         Ok(vec![])
     }
 
+    async fn get_internet_radio_station(
+        &self,
+        _id: &str,
+    ) -> Result<Option<SubsonicInternetRadioStation>> {
+        Ok(None)
+    }
+
     async fn create_internet_radio_station(
         &self,
         _stream_url: &str,
@@ -630,26 +864,100 @@ impl SubsonicStorage for MemoryStorage {
     }
 
     // === Now Playing ===
-    async fn get_now_playing(&self) -> Result<Vec<SubsonicNowPlaying>> {
+    async fn get_now_playing(&self, _user: &UserContext) -> Result<Vec<SubsonicNowPlaying>> {
         Ok(vec![])
     }
 
-    async fn get_starred(&self, _music_folder_id: Option<i32>) -> Result<SubsonicStarred> {
-        Ok(SubsonicStarred {
+    async fn get_starred(
+        &self,
+        user: &UserContext,
+        _music_folder_ids: Option<&[i32]>,
+    ) -> Result<SubsonicStarred> {
+        let entry = self
+            .annotations
+            .read()
+            .await
+            .get(&user.username)
+            .cloned()
+            .unwrap_or_default();
+
+        let mut starred = SubsonicStarred {
             artists: vec![],
             albums: vec![],
             songs: vec![],
-        })
+        };
+        for (id, at) in &entry.starred_artists {
+            if let Some(artist) = SubsonicStorage::get_artist(self, user, id).await? {
+                starred.artists.push(SubsonicArtist {
+                    starred: Some(*at),
+                    user_rating: entry.ratings.get(id).copied(),
+                    ..artist
+                });
+            }
+        }
+        for (id, at) in &entry.starred_albums {
+            if let Some(album) = SubsonicStorage::get_album(self, user, id).await? {
+                starred.albums.push(SubsonicAlbum {
+                    starred: Some(*at),
+                    user_rating: entry.ratings.get(id).copied(),
+                    ..album
+                });
+            }
+        }
+        for (id, at) in &entry.starred_songs {
+            if let Some(song) = self.get_song(user, id).await? {
+                starred.songs.push(MediaFile {
+                    starred: Some(*at),
+                    user_rating: entry.ratings.get(id).copied(),
+                    play_count: entry.play_counts.get(id).copied(),
+                    ..song
+                });
+            }
+        }
+        Ok(starred)
+    }
+
+    async fn get_starred2(
+        &self,
+        user: &UserContext,
+        music_folder_ids: Option<&[i32]>,
+    ) -> Result<SubsonicStarred> {
+        self.get_starred(user, music_folder_ids).await
     }
 
-    async fn get_starred2(&self, _music_folder_id: Option<i32>) -> Result<SubsonicStarred> {
-        Ok(SubsonicStarred {
-            artists: vec![],
-            albums: vec![],
-            songs: vec![],
+    // === Music Folders ===
+    async fn get_music_folder_configs(&self) -> Result<Vec<LibraryFolder>> {
+        Ok(vec![LibraryFolder {
+            id: 1,
+            name: "Music".to_string(),
+            path: "/music".to_string(),
+            vfs_config: None,
+        }])
+    }
+
+    async fn create_music_folder(
+        &self,
+        name: &str,
+        path: &str,
+        vfs_config: Option<&VfsConfig>,
+        _scan: bool,
+    ) -> Result<LibraryFolder> {
+        Ok(LibraryFolder {
+            id: 2,
+            name: name.to_string(),
+            path: path.to_string(),
+            vfs_config: vfs_config.cloned(),
         })
     }
 
+    async fn update_music_folder(&self, _folder: &LibraryFolder) -> Result<()> {
+        Ok(())
+    }
+
+    async fn delete_music_folder(&self, _id: i32) -> Result<()> {
+        Ok(())
+    }
+
     // === Scanning ===
     async fn get_scan_status(&self) -> Result<SubsonicScanStatus> {
         Ok(SubsonicScanStatus {
@@ -663,7 +971,7 @@ impl SubsonicStorage for MemoryStorage {
         })
     }
 
-    async fn start_scan(&self) -> Result<SubsonicScanStatus> {
+    async fn start_scan(&self, _full_scan: bool) -> Result<SubsonicScanStatus> {
         Ok(SubsonicScanStatus {
             scanning: true,
             count: 0,
@@ -0,0 +1,172 @@
+//! 连接用户提供的地址的 HTTP 客户端
+//!
+//! 网络电台转发和播放记录转发都会按用户配置的地址发起请求。默认只连接公网地址：地址、
+//! DNS 解析结果和重定向指向本机、链路本地或内网地址时拒绝连接，避免借服务器访问其所在网络中的服务。
+//! 确实需要连接内网服务时由配置显式允许。
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+use crate::error::{Result, StorageError};
+
+/// 重定向的最大次数
+const MAX_REDIRECTS: usize = 10;
+
+/// 地址是否属于公网，本机、链路本地、内网、组播和保留地址都不算
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8、100.64.0.0/10（运营商 NAT）和 240.0.0.0/4
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // fc00::/7 唯一本地地址和 fe80::/10 链路本地地址
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80)
+}
+
+/// 检查地址的协议和 IP 形式的主机名，不符合时返回 `InvalidInput`
+///
+/// 域名在连接时由 [`client_builder`] 创建的客户端检查解析结果
+pub fn check_target(url: &reqwest::Url, allow_private: bool) -> Result<()> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(StorageError::InvalidInput(format!(
+            "Unsupported URL: {}",
+            url
+        )));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| StorageError::InvalidInput(format!("Invalid URL: {}", url)))?;
+    // IPv6 地址带方括号
+    let ip = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>();
+    if allow_private || ip.map_or(true, is_public_ip) {
+        Ok(())
+    } else {
+        Err(StorageError::InvalidInput(format!(
+            "URL {} points to a private address",
+            url
+        )))
+    }
+}
+
+/// 解析并检查地址，见 [`check_target`]
+pub fn parse_target(url: &str, allow_private: bool) -> Result<reqwest::Url> {
+    let url = reqwest::Url::parse(url)
+        .map_err(|e| StorageError::InvalidInput(format!("Invalid URL {}: {}", url, e)))?;
+    check_target(&url, allow_private)?;
+    Ok(url)
+}
+
+/// 只返回公网地址的 DNS 解析器，域名全部解析到内网地址时连接失败
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} does not resolve to a public address", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// 创建 HTTP 客户端构造器，每次重定向都检查目标地址；不允许内网地址时只连接解析到公网的域名
+pub fn client_builder(allow_private: bool) -> reqwest::ClientBuilder {
+    let redirect = reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if let Err(e) = check_target(attempt.url(), allow_private) {
+            attempt.error(e.to_string())
+        } else {
+            attempt.follow()
+        }
+    });
+    let builder = reqwest::Client::builder().redirect(redirect);
+    if allow_private {
+        builder
+    } else {
+        builder.dns_resolver(Arc::new(PublicResolver))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_public_ip() {
+        for ip in [
+            "93.184.216.34",
+            "2606:2800:220:1::1",
+            "::ffff:93.184.216.34",
+        ] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert!(parse_target("https://api.listenbrainz.org", false).is_ok());
+        assert!(parse_target("http://music.example/apis/listenbrainz", false).is_ok());
+        for url in [
+            "file:///etc/passwd",
+            "gopher://example.com",
+            "http://127.0.0.1:8080",
+            "http://[::1]/",
+            "http://169.254.169.254/latest/meta-data/",
+            "not a url",
+        ] {
+            assert!(parse_target(url, false).is_err(), "{}", url);
+        }
+        assert!(parse_target("http://192.168.1.10:42010", true).is_ok());
+        assert!(parse_target("file:///etc/passwd", true).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! 媒体库条目的确定性 ID
+//!
+//! 曲目、专辑和艺术家的 ID 由其稳定属性的哈希生成，重新扫描后保持不变，
+//! 播放列表、收藏和书签等引用因此不会失效。
+
+use uuid::{Builder, Uuid};
+
+/// 由类型和键生成 ID（UUID v3 格式）
+fn stable_id(kind: &str, key: &str) -> String {
+    let digest = md5::compute(format!("{}:{}", kind, key));
+    Builder::from_md5_bytes(digest.0).into_uuid().to_string()
+}
+
+/// 规范化名称：合并空白并转为小写
+pub fn normalize_name(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// 曲目 ID，由所属媒体库和相对于媒体库根目录的文件路径生成
+pub fn track_id(library_id: i32, path: &str) -> String {
+    stable_id(
+        "track",
+        &format!("{}:{}", library_id, path.trim_start_matches('/')),
+    )
+}
+
+/// 艺术家 ID，有 MusicBrainz ID 时优先使用
+pub fn artist_id(name: &str, musicbrainz_id: Option<&str>) -> String {
+    match musicbrainz_id {
+        Some(mbid) => stable_id("artist-mbid", mbid),
+        None => stable_id("artist", &normalize_name(name)),
+    }
+}
+
+/// 专辑 ID，有 MusicBrainz ID 时优先使用，否则由专辑艺术家和专辑名生成
+pub fn album_id(artist: Option<&str>, name: &str, musicbrainz_id: Option<&str>) -> String {
+    match musicbrainz_id {
+        Some(mbid) => stable_id("album-mbid", mbid),
+        None => stable_id(
+            "album",
+            &format!(
+                "{}::{}",
+                artist.map(normalize_name).unwrap_or_default(),
+                normalize_name(name)
+            ),
+        ),
+    }
+}
+
+/// 判断 ID 是否由本模块生成
+///
+/// 旧版本扫描器使用随机 UUID，用于识别需要迁移的旧 ID
+pub fn is_stable_id(id: &str) -> bool {
+    Uuid::parse_str(id).is_ok_and(|uuid| uuid.get_version_num() == 3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_are_deterministic() {
+        assert_eq!(track_id(1, "music/a.mp3"), track_id(1, "music/a.mp3"));
+        assert_eq!(track_id(1, "/music/a.mp3"), track_id(1, "music/a.mp3"));
+        assert_ne!(track_id(1, "music/a.mp3"), track_id(1, "music/b.mp3"));
+        assert_ne!(track_id(1, "music/a.mp3"), track_id(2, "music/a.mp3"));
+
+        assert_eq!(artist_id("The  Band", None), artist_id("the band", None));
+        assert_ne!(
+            artist_id("The Band", None),
+            artist_id("The Band", Some("mbid"))
+        );
+
+        assert_eq!(
+            album_id(Some("Artist"), "Album", None),
+            album_id(Some("ARTIST"), " album ", None)
+        );
+        assert_ne!(
+            album_id(Some("Artist"), "Album", None),
+            album_id(Some("Other"), "Album", None)
+        );
+        assert_eq!(
+            album_id(Some("Artist"), "Album", Some("mbid")),
+            album_id(None, "Renamed", Some("mbid"))
+        );
+    }
+
+    #[test]
+    fn test_is_stable_id() {
+        assert!(is_stable_id(&track_id(1, "music/a.mp3")));
+        assert!(!is_stable_id(&Uuid::new_v4().to_string()));
+        assert!(!is_stable_id("not-a-uuid"));
+    }
+}
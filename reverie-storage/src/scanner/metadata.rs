@@ -2,7 +2,7 @@
 //!
 //! 使用 lofty 库从音频文件中提取 ID3 标签和元数据
 
-use std::io::Cursor;
+use std::io::{BufReader, Cursor, Read, Seek};
 use std::path::Path;
 
 use crate::error::{Result, StorageError};
@@ -44,19 +44,22 @@ pub struct AudioMetadata {
     pub cover_data: Option<Vec<u8>>,
     /// 封面 MIME 类型
     pub cover_mime: Option<String>,
+    /// MusicBrainz 专辑（Release）ID
+    pub musicbrainz_album_id: Option<String>,
+    /// 专辑艺术家的 MusicBrainz ID，未设置专辑艺术家时取艺术家的 ID
+    pub musicbrainz_album_artist_id: Option<String>,
 }
 
 impl AudioMetadata {
     /// 从文件路径提取元数据
     #[cfg(feature = "scanner")]
     pub fn from_path(path: &Path) -> Result<Self> {
-        use lofty::prelude::*;
         use lofty::probe::Probe;
 
         let tagged_file = Probe::open(path)
-            .map_err(|e| StorageError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))?
+            .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))?
             .read()
-            .map_err(|e| StorageError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))?;
+            .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))?;
 
         Self::extract_metadata(&tagged_file)
     }
@@ -64,12 +67,16 @@ impl AudioMetadata {
     /// 从内存数据提取元数据
     #[cfg(feature = "scanner")]
     pub fn from_bytes(data: &[u8], file_type_hint: Option<&str>) -> Result<Self> {
-        use lofty::prelude::*;
+        Self::from_reader(Cursor::new(data), file_type_hint)
+    }
+
+    /// 从可定位的读取器提取元数据，只读取解析标签和音频属性所需的部分
+    #[cfg(feature = "scanner")]
+    pub fn from_reader<R: Read + Seek>(reader: R, file_type_hint: Option<&str>) -> Result<Self> {
         use lofty::probe::Probe;
 
-        let cursor = Cursor::new(data);
-        let mut probe = Probe::new(cursor);
-        
+        let mut probe = Probe::new(BufReader::new(reader));
+
         // 根据文件扩展名提示设置文件类型
         if let Some(hint) = file_type_hint {
             if let Some(ft) = lofty::file::FileType::from_ext(hint) {
@@ -79,7 +86,7 @@ impl AudioMetadata {
 
         let tagged_file = probe
             .read()
-            .map_err(|e| StorageError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e.to_string())))?;
+            .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))?;
 
         Self::extract_metadata(&tagged_file)
     }
@@ -88,6 +95,7 @@ impl AudioMetadata {
     fn extract_metadata(tagged_file: &lofty::file::TaggedFile) -> Result<Self> {
         use lofty::prelude::*;
         use lofty::picture::PictureType;
+        use lofty::tag::ItemKey;
 
         let properties = tagged_file.properties();
         let tag = tagged_file.primary_tag().or_else(|| tagged_file.first_tag());
@@ -113,10 +121,14 @@ impl AudioMetadata {
 
             // 获取专辑艺术家（如果有）
             // 先尝试从 ItemKey 获取，如果没有则使用 artist
-            metadata.album_artist = tag
-                .get_string(&lofty::tag::ItemKey::AlbumArtist)
-                .map(|s| s.to_string())
-                .or_else(|| metadata.artist.clone());
+            let get = |key: ItemKey| tag.get_string(&key).map(|s| s.to_string());
+            let album_artist = get(ItemKey::AlbumArtist);
+            metadata.musicbrainz_album_artist_id = match album_artist {
+                Some(_) => get(ItemKey::MusicBrainzReleaseArtistId),
+                None => get(ItemKey::MusicBrainzArtistId),
+            };
+            metadata.album_artist = album_artist.or_else(|| metadata.artist.clone());
+            metadata.musicbrainz_album_id = get(ItemKey::MusicBrainzReleaseId);
 
             // 提取封面图片
             let cover = tag.pictures().iter().find(|p| {
@@ -139,16 +151,21 @@ impl AudioMetadata {
     /// 不使用 scanner feature 时的空实现
     #[cfg(not(feature = "scanner"))]
     pub fn from_path(_path: &Path) -> Result<Self> {
-        Err(StorageError::IoError(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        Err(StorageError::IoError(std::io::Error::other(
             "Scanner feature not enabled",
         )))
     }
 
     #[cfg(not(feature = "scanner"))]
     pub fn from_bytes(_data: &[u8], _file_type_hint: Option<&str>) -> Result<Self> {
-        Err(StorageError::IoError(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        Err(StorageError::IoError(std::io::Error::other(
+            "Scanner feature not enabled",
+        )))
+    }
+
+    #[cfg(not(feature = "scanner"))]
+    pub fn from_reader<R: Read + Seek>(_reader: R, _file_type_hint: Option<&str>) -> Result<Self> {
+        Err(StorageError::IoError(std::io::Error::other(
             "Scanner feature not enabled",
         )))
     }
@@ -2,8 +2,12 @@
 //!
 //! 提供音乐文件扫描和元数据提取功能
 
+pub mod ids;
 mod metadata;
+#[allow(clippy::module_inception)]
 mod scanner;
+mod watcher;
 
 pub use metadata::*;
 pub use scanner::*;
+pub use watcher::*;
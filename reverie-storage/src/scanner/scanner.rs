@@ -8,11 +8,12 @@ use std::sync::Arc;
 
 use chrono::Utc;
 use tokio::sync::RwLock;
+use tokio_util::io::SyncIoBridge;
 use tracing::{debug, error, info, warn};
-use uuid::Uuid;
 
 use crate::error::{Result, StorageError};
-use crate::vfs::{SharedVfs, VfsEntry};
+use crate::vfs::{SharedVfs, VfsEntry, VfsMetadata};
+use super::ids;
 use super::metadata::{is_audio_file, AudioMetadata, get_extension};
 
 /// 扫描进度状态
@@ -53,6 +54,16 @@ pub struct ScannedTrack {
     pub format: String,
     pub cover_data: Option<Vec<u8>>,
     pub cover_mime: Option<String>,
+    pub musicbrainz_album_id: Option<String>,
+    pub musicbrainz_album_artist_id: Option<String>,
+    /// 所属专辑的 ID
+    pub album_id: Option<String>,
+    /// 专辑艺术家（缺失时为艺术家）的 ID
+    pub artist_id: Option<String>,
+    /// 扫描时文件的修改时间（Unix 秒）
+    pub last_modified: Option<i64>,
+    /// 扫描时文件的 ETag
+    pub etag: Option<String>,
 }
 
 /// 扫描到的专辑信息
@@ -77,14 +88,62 @@ pub struct ScannedArtist {
 /// 扫描结果
 #[derive(Debug, Clone, Default)]
 pub struct ScanResult {
+    /// 新增或发生变化、已重新解析的音轨
     pub tracks: Vec<ScannedTrack>,
+    /// 以专辑 ID 为键
     pub albums: HashMap<String, ScannedAlbum>,
+    /// 以艺术家 ID 为键
     pub artists: HashMap<String, ScannedArtist>,
+    /// 扫描路径下找到的全部音频文件，包括未变化和解析失败的文件
+    pub files: Vec<String>,
+    /// 因未变化而跳过解析的文件数
+    pub unchanged: u64,
+    /// 解析失败的文件数
+    pub errors: u64,
+}
+
+/// 文件状态快照，增量扫描时用于判断文件自上次入库后是否变化
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileState {
+    pub size: i64,
+    /// 修改时间（Unix 秒）
+    pub last_modified: Option<i64>,
+    pub etag: Option<String>,
+}
+
+impl FileState {
+    /// 判断文件是否未发生变化
+    ///
+    /// 大小不同即视为变化；双方都有 ETag 时以 ETag 为准，否则比较修改时间。
+    /// 两者都无法比较时保守地视为已变化。
+    pub fn is_unchanged(&self, current: &FileState) -> bool {
+        if self.size != current.size {
+            return false;
+        }
+        match (&self.etag, &current.etag) {
+            (Some(a), Some(b)) => a == b,
+            _ => matches!(
+                (self.last_modified, current.last_modified),
+                (Some(a), Some(b)) if a == b
+            ),
+        }
+    }
+}
+
+impl From<&VfsMetadata> for FileState {
+    fn from(meta: &VfsMetadata) -> Self {
+        Self {
+            size: meta.size as i64,
+            last_modified: meta.last_modified.map(|t| t.timestamp()),
+            etag: meta.etag.clone(),
+        }
+    }
 }
 
 /// 媒体库扫描器
 pub struct MediaScanner {
     vfs: SharedVfs,
+    library_id: i32,
     scanning: Arc<AtomicBool>,
     count: Arc<AtomicI64>,
     folder_count: Arc<AtomicI64>,
@@ -97,6 +156,7 @@ impl MediaScanner {
     pub fn new(vfs: SharedVfs) -> Self {
         Self {
             vfs,
+            library_id: 1,
             scanning: Arc::new(AtomicBool::new(false)),
             count: Arc::new(AtomicI64::new(0)),
             folder_count: Arc::new(AtomicI64::new(0)),
@@ -105,6 +165,12 @@ impl MediaScanner {
         }
     }
 
+    /// 设置所扫描的媒体库（音乐文件夹）ID，默认为 1
+    pub fn with_library(mut self, library_id: i32) -> Self {
+        self.library_id = library_id;
+        self
+    }
+
     /// 获取当前扫描状态
     pub async fn get_progress(&self) -> ScanProgress {
         ScanProgress {
@@ -117,12 +183,22 @@ impl MediaScanner {
         }
     }
 
-    /// 扫描指定路径
+    /// 扫描指定路径，解析其中的所有音频文件
     pub async fn scan(&self, path: &str) -> Result<ScanResult> {
+        self.scan_incremental(path, &HashMap::new()).await
+    }
+
+    /// 增量扫描指定路径
+    ///
+    /// `known` 为已入库文件的路径到文件状态的映射，状态未变化的文件不会重新解析。
+    pub async fn scan_incremental(
+        &self,
+        path: &str,
+        known: &HashMap<String, FileState>,
+    ) -> Result<ScanResult> {
         // 检查是否已在扫描
         if self.scanning.swap(true, Ordering::SeqCst) {
-            return Err(StorageError::IoError(std::io::Error::new(
-                std::io::ErrorKind::Other,
+            return Err(StorageError::IoError(std::io::Error::other(
                 "Scan already in progress",
             )));
         }
@@ -134,7 +210,7 @@ impl MediaScanner {
 
         info!("Starting media scan at path: {}", path);
 
-        let result = self.scan_directory(path).await;
+        let result = self.scan_directory(path, known).await;
 
         // 扫描完成
         self.scanning.store(false, Ordering::Relaxed);
@@ -143,10 +219,12 @@ impl MediaScanner {
         match &result {
             Ok(scan_result) => {
                 info!(
-                    "Scan completed: {} tracks, {} albums, {} artists",
+                    "Scan completed: {} tracks, {} albums, {} artists, {} unchanged, {} errors",
                     scan_result.tracks.len(),
                     scan_result.albums.len(),
-                    scan_result.artists.len()
+                    scan_result.artists.len(),
+                    scan_result.unchanged,
+                    scan_result.errors
                 );
             }
             Err(e) => {
@@ -159,7 +237,11 @@ impl MediaScanner {
     }
 
     /// 递归扫描目录
-    async fn scan_directory(&self, path: &str) -> Result<ScanResult> {
+    async fn scan_directory(
+        &self,
+        path: &str,
+        known: &HashMap<String, FileState>,
+    ) -> Result<ScanResult> {
         let mut result = ScanResult::default();
 
         // 获取目录列表
@@ -182,49 +264,47 @@ impl MediaScanner {
         for entry in audio_files {
             // 更新当前扫描路径
             *self.current_path.write().await = Some(entry.path.clone());
+            result.files.push(entry.path.clone());
+
+            // 已入库且未变化的文件跳过解析
+            if let Some(state) = known.get(&entry.path) {
+                if state.is_unchanged(&self.file_state(entry).await) {
+                    result.unchanged += 1;
+                    self.count.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            }
 
             match self.scan_file(&entry.path).await {
-                Ok(track) => {
+                Ok(mut track) => {
                     // 处理艺术家
-                    let artist_id = if let Some(artist_name) = &track.album_artist {
-                        let artist_key = artist_name.to_lowercase();
-                        if !result.artists.contains_key(&artist_key) {
-                            let artist = ScannedArtist {
-                                id: Uuid::new_v4().to_string(),
-                                name: artist_name.clone(),
-                            };
-                            result.artists.insert(artist_key.clone(), artist);
-                        }
-                        result.artists.get(&artist_key).map(|a| a.id.clone())
-                    } else if let Some(artist_name) = &track.artist {
-                        let artist_key = artist_name.to_lowercase();
-                        if !result.artists.contains_key(&artist_key) {
-                            let artist = ScannedArtist {
-                                id: Uuid::new_v4().to_string(),
-                                name: artist_name.clone(),
-                            };
-                            result.artists.insert(artist_key.clone(), artist);
-                        }
-                        result.artists.get(&artist_key).map(|a| a.id.clone())
-                    } else {
-                        None
-                    };
+                    let artist_name = track.album_artist.clone().or_else(|| track.artist.clone());
+                    track.artist_id = artist_name.as_ref().map(|name| {
+                        let id = ids::artist_id(name, track.musicbrainz_album_artist_id.as_deref());
+                        result
+                            .artists
+                            .entry(id.clone())
+                            .or_insert_with(|| ScannedArtist {
+                                id: id.clone(),
+                                name: name.clone(),
+                            });
+                        id
+                    });
 
                     // 处理专辑
                     if let Some(album_name) = &track.album {
-                        let artist_name = track.album_artist.as_ref().or(track.artist.as_ref());
-                        let album_key = format!(
-                            "{}::{}",
-                            artist_name.map(|s| s.to_lowercase()).unwrap_or_default(),
-                            album_name.to_lowercase()
+                        let album_id = ids::album_id(
+                            artist_name.as_deref(),
+                            album_name,
+                            track.musicbrainz_album_id.as_deref(),
                         );
 
-                        let album = result.albums.entry(album_key.clone()).or_insert_with(|| {
+                        let album = result.albums.entry(album_id.clone()).or_insert_with(|| {
                             ScannedAlbum {
-                                id: Uuid::new_v4().to_string(),
+                                id: album_id.clone(),
                                 name: album_name.clone(),
-                                artist_id: artist_id.clone(),
-                                artist_name: artist_name.cloned(),
+                                artist_id: track.artist_id.clone(),
+                                artist_name: artist_name.clone(),
                                 year: track.year,
                                 genre: track.genre.clone(),
                                 tracks: Vec::new(),
@@ -237,6 +317,7 @@ impl MediaScanner {
                         if album.year.is_none() && track.year.is_some() {
                             album.year = track.year;
                         }
+                        track.album_id = Some(album_id);
                     }
 
                     result.tracks.push(track);
@@ -244,6 +325,7 @@ impl MediaScanner {
                 }
                 Err(e) => {
                     debug!("Failed to scan file {}: {}", entry.path, e);
+                    result.errors += 1;
                 }
             }
         }
@@ -255,22 +337,37 @@ impl MediaScanner {
         Ok(result)
     }
 
+    /// 获取列表项的文件状态
+    ///
+    /// 部分后端的列表结果不包含修改时间和 ETag，此时改用 stat 获取
+    async fn file_state(&self, entry: &VfsEntry) -> FileState {
+        let meta = &entry.metadata;
+        if meta.last_modified.is_some() || meta.etag.is_some() {
+            return FileState::from(meta);
+        }
+        match self.vfs.stat(&entry.path).await {
+            Ok(meta) => FileState::from(&meta),
+            Err(_) => FileState::from(meta),
+        }
+    }
+
     /// 扫描单个文件
     async fn scan_file(&self, path: &str) -> Result<ScannedTrack> {
         // 读取文件元数据
         let file_meta = self.vfs.stat(path).await?;
         
-        // 读取文件内容
-        let file_data = self.vfs.read(path).await?;
-        
         // 获取文件扩展名
         let extension = get_extension(path).unwrap_or("mp3");
-        
-        // 提取音频元数据
-        let metadata = AudioMetadata::from_bytes(&file_data, Some(extension))?;
 
-        // 生成 track ID
-        let track_id = Uuid::new_v4().to_string();
+        // 通过流式读取器提取音频元数据，lofty 只会读取标签和音频头所需的部分
+        let reader = SyncIoBridge::new(self.vfs.reader(path).await?);
+        let hint = extension.to_string();
+        let metadata = tokio::task::spawn_blocking(move || AudioMetadata::from_reader(reader, Some(&hint)))
+            .await
+            .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))??;
+
+        // 由媒体库和文件路径生成 track ID，重新扫描时保持不变
+        let track_id = ids::track_id(self.library_id, path);
 
         // 使用文件名作为默认标题
         let default_title = std::path::Path::new(path)
@@ -298,6 +395,12 @@ impl MediaScanner {
             format: extension.to_string(),
             cover_data: metadata.cover_data,
             cover_mime: metadata.cover_mime,
+            musicbrainz_album_id: metadata.musicbrainz_album_id,
+            musicbrainz_album_artist_id: metadata.musicbrainz_album_artist_id,
+            album_id: None,
+            artist_id: None,
+            last_modified: file_meta.last_modified.map(|t| t.timestamp()),
+            etag: file_meta.etag,
         })
     }
 
@@ -323,4 +426,44 @@ mod tests {
         assert!(result.albums.is_empty());
         assert!(result.artists.is_empty());
     }
+
+    /// 生成 1 秒、8 kHz、单声道 16 位 PCM 的 WAV 文件
+    fn wav_fixture() -> Vec<u8> {
+        let sample_rate: u32 = 8000;
+        let data_len: u32 = sample_rate * 2;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes()); // 声道数
+        wav.extend_from_slice(&sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        wav.resize(wav.len() + data_len as usize, 0);
+        wav
+    }
+
+    #[tokio::test]
+    async fn test_scan_file_uses_streaming_reader() {
+        use crate::vfs::{OpendalVfs, Vfs, VfsConfig};
+
+        let vfs = Arc::new(OpendalVfs::new(VfsConfig::memory()).unwrap());
+        vfs.write("music/tone.wav", bytes::Bytes::from(wav_fixture()))
+            .await
+            .unwrap();
+
+        let scanner = MediaScanner::new(vfs);
+        let track = scanner.scan_file("music/tone.wav").await.unwrap();
+
+        assert_eq!(track.title, "tone");
+        assert_eq!(track.format, "wav");
+        assert_eq!(track.sample_rate, 8000);
+        assert_eq!(track.channels, 1);
+        assert!((track.duration - 1.0).abs() < 0.01);
+    }
 }
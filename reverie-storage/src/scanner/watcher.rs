@@ -0,0 +1,370 @@
+//! 媒体库变更监视
+//!
+//! 本地文件系统（`fs` 方案）通过 `notify` 监听文件变化，合并一段时间内的事件后
+//! 只对受影响的目录执行增量扫描；其他 VFS 后端无法订阅变更，改为定期轮询扫描。
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use notify::event::{EventKind, ModifyKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use super::metadata::is_audio_file;
+use crate::error::{Result, StorageError};
+use crate::vfs::VfsConfig;
+
+/// 监视器触发扫描的目标
+#[async_trait]
+pub trait RescanTarget: Send + Sync {
+    /// 增量扫描指定目录（相对于 VFS 根目录）
+    async fn rescan(&self, path: &str) -> Result<()>;
+
+    /// 目录（以 `/` 结尾）下是否有已入库的曲目，用于判断已删除的路径是否为媒体目录
+    async fn has_tracks_under(&self, dir: &str) -> Result<bool>;
+}
+
+/// 监视器配置
+#[derive(Debug, Clone)]
+pub struct WatcherConfig {
+    /// 最后一个事件之后等待的静默时间，期间的新事件会合并到同一次扫描
+    pub debounce: Duration,
+    /// 非本地后端的轮询间隔
+    pub poll_interval: Duration,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_secs(2),
+            poll_interval: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+/// 运行中的媒体库监视器，丢弃时停止监视
+pub struct LibraryWatcher {
+    task: JoinHandle<()>,
+    _watcher: Option<RecommendedWatcher>,
+}
+
+impl LibraryWatcher {
+    /// 开始监视 `paths` 下的媒体文件（路径相对于 VFS 根目录）
+    pub fn start(
+        vfs_config: &VfsConfig,
+        paths: Vec<String>,
+        target: Arc<dyn RescanTarget>,
+        config: WatcherConfig,
+    ) -> Result<Self> {
+        match (vfs_config.scheme.as_str(), vfs_config.options.get("root")) {
+            ("fs", Some(root)) => Self::watch_fs(Path::new(root), &paths, target, config),
+            _ => Ok(Self::poll(paths, target, config)),
+        }
+    }
+
+    /// 通过 inotify 等系统接口监视本地目录
+    fn watch_fs(
+        root: &Path,
+        paths: &[String],
+        target: Arc<dyn RescanTarget>,
+        config: WatcherConfig,
+    ) -> Result<Self> {
+        let root = root.canonicalize().map_err(StorageError::IoError)?;
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+                Ok(event) => {
+                    let _ = tx.send(event);
+                }
+                Err(e) => warn!("File watcher error: {}", e),
+            })
+            .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))?;
+
+        for path in paths {
+            let dir = root.join(path.trim_start_matches('/'));
+            watcher
+                .watch(&dir, RecursiveMode::Recursive)
+                .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))?;
+            info!("Watching {} for library changes", dir.display());
+        }
+
+        let task = tokio::spawn(Self::run_fs(root, rx, target, config.debounce));
+        Ok(Self {
+            task,
+            _watcher: Some(watcher),
+        })
+    }
+
+    /// 合并事件并扫描受影响的目录
+    async fn run_fs(
+        root: PathBuf,
+        mut rx: mpsc::UnboundedReceiver<Event>,
+        target: Arc<dyn RescanTarget>,
+        debounce: Duration,
+    ) {
+        while let Some(event) = rx.recv().await {
+            let mut dirs = BTreeSet::new();
+            let mut removed = BTreeSet::new();
+            collect_dirs(&root, &event, &mut dirs, &mut removed);
+
+            // 在静默期内持续收集事件，例如复制整张专辑时产生的大量事件
+            loop {
+                match tokio::time::timeout(debounce, rx.recv()).await {
+                    Ok(Some(event)) => collect_dirs(&root, &event, &mut dirs, &mut removed),
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+
+            // 已删除的路径下有入库曲目时按目录处理，扫描其上级目录
+            for path in removed {
+                let known = target
+                    .has_tracks_under(&to_vfs_dir(&path))
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!("Cannot look up tracks under {}: {}", path.display(), e);
+                        true
+                    });
+                if let (true, Some(parent)) = (known, path.parent()) {
+                    dirs.insert(to_vfs_dir(parent));
+                }
+            }
+
+            for dir in collapse_dirs(dirs) {
+                info!("Library change detected, rescanning {}", dir);
+                if let Err(e) = target.rescan(&dir).await {
+                    warn!("Rescan of {} failed: {}", dir, e);
+                }
+            }
+        }
+    }
+
+    /// 定期扫描全部路径
+    fn poll(paths: Vec<String>, target: Arc<dyn RescanTarget>, config: WatcherConfig) -> Self {
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(config.poll_interval);
+            // 第一次 tick 立即完成，跳过以免启动时重复扫描
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                for path in &paths {
+                    debug!("Polling {} for library changes", path);
+                    if let Err(e) = target.rescan(path).await {
+                        warn!("Rescan of {} failed: {}", path, e);
+                    }
+                }
+            }
+        });
+        Self {
+            task,
+            _watcher: None,
+        }
+    }
+}
+
+impl Drop for LibraryWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// 将事件涉及的目录（相对于 VFS 根目录）加入 `dirs`
+///
+/// 已删除或移走的路径无法判断是文件还是目录（例如名为 `Vol. 2` 的目录），
+/// 除音频文件外都加入 `removed`，稍后按已入库曲目的路径判断
+fn collect_dirs(
+    root: &Path,
+    event: &Event,
+    dirs: &mut BTreeSet<String>,
+    removed: &mut BTreeSet<PathBuf>,
+) {
+    // 读取文件产生的访问事件和属性变化不影响媒体库
+    match event.kind {
+        EventKind::Create(_) | EventKind::Remove(_) => {}
+        EventKind::Modify(ModifyKind::Metadata(_)) => return,
+        EventKind::Modify(_) => {}
+        _ => return,
+    }
+
+    for path in &event.paths {
+        let Ok(relative) = path.strip_prefix(root) else {
+            continue;
+        };
+        // 忽略隐藏目录，例如封面和转码缓存
+        if relative
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+
+        if path.is_dir() {
+            dirs.insert(to_vfs_dir(relative));
+        } else if is_audio_file(&relative.to_string_lossy()) {
+            if let Some(parent) = relative.parent() {
+                dirs.insert(to_vfs_dir(parent));
+            }
+        } else if !path.exists() {
+            removed.insert(relative.to_path_buf());
+        }
+    }
+}
+
+/// 转换为以 `/` 结尾的 VFS 目录路径，根目录为空字符串
+fn to_vfs_dir(path: &Path) -> String {
+    let mut dir = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    if !dir.is_empty() {
+        dir.push('/');
+    }
+    dir
+}
+
+/// 去掉已被其他目录包含的子目录
+fn collapse_dirs(dirs: BTreeSet<String>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    // 有序集合中父目录总是排在其子目录之前
+    for dir in dirs {
+        if !result.iter().any(|parent| dir.starts_with(parent.as_str())) {
+            result.push(dir);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingTarget {
+        scans: Mutex<Vec<String>>,
+        /// 已入库曲目的路径
+        tracks: Vec<String>,
+    }
+
+    #[async_trait]
+    impl RescanTarget for RecordingTarget {
+        async fn rescan(&self, path: &str) -> Result<()> {
+            self.scans.lock().unwrap().push(path.to_string());
+            Ok(())
+        }
+
+        async fn has_tracks_under(&self, dir: &str) -> Result<bool> {
+            Ok(self.tracks.iter().any(|track| track.starts_with(dir)))
+        }
+    }
+
+    /// 等待第一次扫描，再等待一段时间以确认没有多余的扫描
+    async fn wait_for_scans(target: &RecordingTarget) -> Vec<String> {
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            if !target.scans.lock().unwrap().is_empty() {
+                break;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        target.scans.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn test_collapse_dirs() {
+        let dirs: BTreeSet<String> = ["a/b/", "a/", "c/d/", "c/de/"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(collapse_dirs(dirs), vec!["a/", "c/d/", "c/de/"]);
+        assert_eq!(
+            collapse_dirs(["", "a/"].into_iter().map(String::from).collect()),
+            vec![""]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watcher_debounces_album_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let album = dir.path().join("music/Artist/Album");
+        std::fs::create_dir_all(&album).unwrap();
+        std::fs::create_dir_all(dir.path().join("music/Other")).unwrap();
+
+        let target = Arc::new(RecordingTarget::default());
+        let _watcher = LibraryWatcher::start(
+            &VfsConfig::local(dir.path().to_string_lossy()),
+            vec!["music".to_string()],
+            target.clone(),
+            WatcherConfig {
+                debounce: Duration::from_millis(300),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for i in 0..40 {
+            std::fs::write(album.join(format!("{:02}.mp3", i)), b"data").unwrap();
+        }
+        std::fs::write(album.join("cover.jpg"), b"image").unwrap();
+
+        // 等待静默期结束和扫描完成
+        assert_eq!(wait_for_scans(&target).await, vec!["music/Artist/Album/"]);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_rescans_removed_directories_with_tracks() {
+        let dir = tempfile::tempdir().unwrap();
+        let volume = dir.path().join("music/Artist/Vol. 2");
+        std::fs::create_dir_all(&volume).unwrap();
+        std::fs::write(volume.join("01.mp3"), b"data").unwrap();
+        std::fs::write(dir.path().join("music/notes.v2"), b"text").unwrap();
+
+        let target = Arc::new(RecordingTarget {
+            tracks: vec!["music/Artist/Vol. 2/01.mp3".to_string()],
+            ..Default::default()
+        });
+        let _watcher = LibraryWatcher::start(
+            &VfsConfig::local(dir.path().to_string_lossy()),
+            vec!["music".to_string()],
+            target.clone(),
+            WatcherConfig {
+                debounce: Duration::from_millis(300),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // 目录名带扩展名的形式，移出媒体库后按其中的曲目判断为目录；没有曲目的文件被忽略
+        std::fs::rename(&volume, dir.path().join("Vol. 2")).unwrap();
+        std::fs::remove_file(dir.path().join("music/notes.v2")).unwrap();
+
+        assert_eq!(wait_for_scans(&target).await, vec!["music/Artist/"]);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_polls_non_local_backends() {
+        let target = Arc::new(RecordingTarget::default());
+        let _watcher = LibraryWatcher::start(
+            &VfsConfig::memory(),
+            vec!["music/".to_string()],
+            target.clone(),
+            WatcherConfig {
+                poll_interval: Duration::from_millis(50),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(180)).await;
+        let scans = target.scans.lock().unwrap().clone();
+        assert!(scans.len() >= 2);
+        assert!(scans.iter().all(|path| path == "music/"));
+    }
+}
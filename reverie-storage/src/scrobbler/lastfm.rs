@@ -0,0 +1,135 @@
+//! Last.fm（Audioscrobbler 2.0）协议
+//!
+//! 参见 <https://www.last.fm/api/scrobbling>。请求按 Last.fm 的规则用应用密钥签名，
+//! 用户令牌是通过 Last.fm 授权流程获得的会话密钥。
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use super::{http_client, request_error, ScrobbleAccount, ScrobbleForwarder, ScrobbleTrack};
+use crate::error::{Result, StorageError};
+use crate::outbound;
+
+/// 官方 Last.fm API 地址
+pub const LASTFM_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+
+/// 通过 Last.fm 兼容 API 转发播放记录
+pub struct LastFmForwarder {
+    client: reqwest::Client,
+    allow_private: bool,
+    api_key: String,
+    api_secret: String,
+}
+
+impl LastFmForwarder {
+    /// 使用在 Last.fm 注册的应用密钥创建只连接公网地址的转发器
+    pub fn new(api_key: impl Into<String>, api_secret: impl Into<String>) -> Self {
+        Self {
+            client: http_client(false),
+            allow_private: false,
+            api_key: api_key.into(),
+            api_secret: api_secret.into(),
+        }
+    }
+
+    /// 允许转发到本机和内网中的服务
+    pub fn allow_private_targets(mut self) -> Self {
+        self.client = http_client(true);
+        self.allow_private = true;
+        self
+    }
+
+    async fn send(
+        &self,
+        account: &ScrobbleAccount,
+        method: &str,
+        mut params: BTreeMap<&str, String>,
+    ) -> Result<()> {
+        let url = account.url.as_deref().unwrap_or(LASTFM_URL);
+        self.check_url(url)?;
+        params.insert("method", method.to_string());
+        params.insert("api_key", self.api_key.clone());
+        params.insert("sk", account.token.clone());
+        let signature = sign(&params, &self.api_secret);
+        params.insert("api_sig", signature);
+        // format 不参与签名
+        params.insert("format", "json".to_string());
+
+        let response = self
+            .client
+            .post(url)
+            .form(&params)
+            .send()
+            .await
+            .map_err(|e| request_error("Last.fm", e))?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or_default();
+        // 出错时响应体包含错误码和错误信息，部分实现的 HTTP 状态码仍为 200
+        if !status.is_success() || body.get("error").is_some() {
+            return Err(StorageError::Unavailable(format!(
+                "Last.fm returned {}: {}",
+                status,
+                body.get("message").and_then(Value::as_str).unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// 计算请求签名：按参数名排序后拼接参数名和值，末尾加上应用密钥取 MD5
+fn sign(params: &BTreeMap<&str, String>, secret: &str) -> String {
+    let mut payload: String = params
+        .iter()
+        .map(|(key, value)| format!("{}{}", key, value))
+        .collect();
+    payload.push_str(secret);
+    format!("{:x}", md5::compute(payload))
+}
+
+fn track_params(track: &ScrobbleTrack) -> BTreeMap<&'static str, String> {
+    let mut params = BTreeMap::new();
+    params.insert("track", track.title.clone());
+    if let Some(artist) = &track.artist {
+        params.insert("artist", artist.clone());
+    }
+    if let Some(album) = &track.album {
+        params.insert("album", album.clone());
+    }
+    if let Some(duration) = track.duration {
+        params.insert("duration", duration.to_string());
+    }
+    if let Some(track_number) = track.track_number {
+        params.insert("trackNumber", track_number.to_string());
+    }
+    params
+}
+
+#[async_trait]
+impl ScrobbleForwarder for LastFmForwarder {
+    fn service(&self) -> &str {
+        "lastfm"
+    }
+
+    fn check_url(&self, url: &str) -> Result<()> {
+        outbound::parse_target(url, self.allow_private).map(|_| ())
+    }
+
+    async fn now_playing(&self, account: &ScrobbleAccount, track: &ScrobbleTrack) -> Result<()> {
+        self.send(account, "track.updateNowPlaying", track_params(track))
+            .await
+    }
+
+    async fn submit(
+        &self,
+        account: &ScrobbleAccount,
+        track: &ScrobbleTrack,
+        listened_at: DateTime<Utc>,
+    ) -> Result<()> {
+        let mut params = track_params(track);
+        params.insert("timestamp", listened_at.timestamp().to_string());
+        self.send(account, "track.scrobble", params).await
+    }
+}
@@ -0,0 +1,128 @@
+//! ListenBrainz 协议
+//!
+//! 参见 <https://listenbrainz.readthedocs.io/en/latest/users/api/core.html#post--1-submit-listens>
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::header::AUTHORIZATION;
+use serde_json::{json, Value};
+
+use super::{http_client, request_error, ScrobbleAccount, ScrobbleForwarder, ScrobbleTrack};
+use crate::error::{Result, StorageError};
+use crate::outbound;
+
+/// 官方 ListenBrainz 服务地址
+pub const LISTENBRAINZ_URL: &str = "https://api.listenbrainz.org";
+
+/// 通过 ListenBrainz API 转发播放记录
+///
+/// 账号地址为 API 根地址，例如 Maloja 的 `https://maloja.example/apis/listenbrainz`
+pub struct ListenBrainzForwarder {
+    client: reqwest::Client,
+    allow_private: bool,
+}
+
+impl ListenBrainzForwarder {
+    /// 创建只连接公网地址的转发器
+    pub fn new() -> Self {
+        Self {
+            client: http_client(false),
+            allow_private: false,
+        }
+    }
+
+    /// 允许转发到本机和内网中的服务
+    pub fn allow_private_targets(mut self) -> Self {
+        self.client = http_client(true);
+        self.allow_private = true;
+        self
+    }
+
+    async fn send(
+        &self,
+        account: &ScrobbleAccount,
+        listen_type: &str,
+        listen: Value,
+    ) -> Result<()> {
+        let base = account.url.as_deref().unwrap_or(LISTENBRAINZ_URL);
+        self.check_url(base)?;
+        let url = format!("{}/1/submit-listens", base.trim_end_matches('/'));
+        let body = json!({
+            "listen_type": listen_type,
+            "payload": [listen],
+        });
+
+        let response = self
+            .client
+            .post(url)
+            .header(AUTHORIZATION, format!("Token {}", account.token))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| request_error("ListenBrainz", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(StorageError::Unavailable(format!(
+                "ListenBrainz returned {}: {}",
+                status, message
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ListenBrainzForwarder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn track_metadata(track: &ScrobbleTrack) -> Value {
+    let mut additional_info = json!({ "submission_client": "Reverie" });
+    if let Some(duration) = track.duration {
+        additional_info["duration_ms"] = json!(duration * 1000);
+    }
+    if let Some(track_number) = track.track_number {
+        additional_info["tracknumber"] = json!(track_number);
+    }
+
+    let mut metadata = json!({
+        "artist_name": track.artist.as_deref().unwrap_or("Unknown Artist"),
+        "track_name": track.title,
+        "additional_info": additional_info,
+    });
+    if let Some(album) = &track.album {
+        metadata["release_name"] = json!(album);
+    }
+    metadata
+}
+
+#[async_trait]
+impl ScrobbleForwarder for ListenBrainzForwarder {
+    fn service(&self) -> &str {
+        "listenbrainz"
+    }
+
+    fn check_url(&self, url: &str) -> Result<()> {
+        outbound::parse_target(url, self.allow_private).map(|_| ())
+    }
+
+    async fn now_playing(&self, account: &ScrobbleAccount, track: &ScrobbleTrack) -> Result<()> {
+        let listen = json!({ "track_metadata": track_metadata(track) });
+        self.send(account, "playing_now", listen).await
+    }
+
+    async fn submit(
+        &self,
+        account: &ScrobbleAccount,
+        track: &ScrobbleTrack,
+        listened_at: DateTime<Utc>,
+    ) -> Result<()> {
+        let listen = json!({
+            "listened_at": listened_at.timestamp(),
+            "track_metadata": track_metadata(track),
+        });
+        self.send(account, "single", listen).await
+    }
+}
@@ -0,0 +1,86 @@
+//! 向外部服务转发播放记录
+//!
+//! 用户为每个服务单独配置自己的令牌，[`ScrobbleForwarder`] 按服务的协议发送正在播放通知和
+//! 播放记录。内置 ListenBrainz 和 Last.fm 两种协议，账号上可以配置服务地址，
+//! 以便转发到兼容这些协议的自建服务（如 Maloja）。
+//!
+//! 正在播放通知只发送一次；播放记录先写入数据库中的重试队列再发送，
+//! 发送失败时按指数退避的间隔重试。
+//!
+//! 自定义的服务地址与网络电台一样默认只能指向公网，见 [`crate::outbound`]；
+//! 转发到内网中的自建服务时使用转发器的 `allow_private_targets`。
+
+mod lastfm;
+mod listenbrainz;
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, StorageError};
+use crate::outbound;
+
+pub use lastfm::{LastFmForwarder, LASTFM_URL};
+pub use listenbrainz::{ListenBrainzForwarder, LISTENBRAINZ_URL};
+
+/// 请求外部服务的超时时间
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 转发给外部服务的曲目信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScrobbleTrack {
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// 时长（秒）
+    pub duration: Option<i64>,
+    pub track_number: Option<i32>,
+}
+
+/// 用户在外部服务上的账号
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScrobbleAccount {
+    /// 服务名称，对应 [`ScrobbleForwarder::service`]
+    pub service: String,
+    /// 用户令牌，ListenBrainz 为用户令牌，Last.fm 为会话密钥
+    pub token: String,
+    /// 服务地址，为 `None` 时使用官方服务
+    pub url: Option<String>,
+}
+
+/// 播放记录转发器
+#[async_trait]
+pub trait ScrobbleForwarder: Send + Sync {
+    /// 服务名称，例如 `listenbrainz`
+    fn service(&self) -> &str;
+
+    /// 检查账号上配置的服务地址，只接受指向公网的 HTTP(S) 地址
+    fn check_url(&self, url: &str) -> Result<()> {
+        outbound::parse_target(url, false).map(|_| ())
+    }
+
+    /// 通知服务用户正在播放的曲目
+    async fn now_playing(&self, account: &ScrobbleAccount, track: &ScrobbleTrack) -> Result<()>;
+
+    /// 提交一次播放记录
+    async fn submit(
+        &self,
+        account: &ScrobbleAccount,
+        track: &ScrobbleTrack,
+        listened_at: DateTime<Utc>,
+    ) -> Result<()>;
+}
+
+/// 创建请求外部服务的 HTTP 客户端，`allow_private` 为 false 时只连接公网地址
+fn http_client(allow_private: bool) -> reqwest::Client {
+    outbound::client_builder(allow_private)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+fn request_error(service: &str, e: reqwest::Error) -> StorageError {
+    StorageError::Unavailable(format!("{} request failed: {}", service, e))
+}
@@ -119,6 +119,7 @@ mod traits_tests {
 mod subsonic_storage_tests {
     use crate::memory::MemoryStorage;
     use crate::SubsonicStorage;
+    use reverie_core::UserContext;
 
     #[tokio::test]
     async fn test_get_license_default() {
@@ -148,7 +149,16 @@ mod subsonic_storage_tests {
     async fn test_search_default_implementation() {
         let storage = MemoryStorage::new();
         let result = storage
-            .search(None, Some("album"), Some("title"), None, None, None, None)
+            .search(
+                &UserContext::new("test", false),
+                None,
+                Some("album"),
+                Some("title"),
+                None,
+                None,
+                None,
+                None,
+            )
             .await
             .unwrap();
 
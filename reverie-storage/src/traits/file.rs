@@ -3,6 +3,7 @@
 //! 定义了文件存储和元数据相关的接口。
 
 use crate::error::Result;
+use crate::vfs::VfsLocation;
 use async_trait::async_trait;
 
 /// 用于文件存储操作的 trait（音频文件、封面图片等）
@@ -11,6 +12,17 @@ pub trait FileStorage: Send + Sync {
     /// 按路径读取文件
     async fn read_file(&self, path: &str) -> Result<Vec<u8>>;
 
+    /// 从 `offset` 开始读取至多 `length` 字节
+    ///
+    /// 默认实现读取整个文件后截取，支持随机访问的后端应覆盖此方法，
+    /// 以便流式传输大文件时内存占用保持有界。
+    async fn read_file_range(&self, path: &str, offset: u64, length: u64) -> Result<Vec<u8>> {
+        let data = self.read_file(path).await?;
+        let start = (offset as usize).min(data.len());
+        let end = start.saturating_add(length as usize).min(data.len());
+        Ok(data[start..end].to_vec())
+    }
+
     /// 写入文件
     async fn write_file(&self, path: &str, data: &[u8]) -> Result<()>;
 
@@ -25,6 +37,11 @@ pub trait FileStorage: Send + Sync {
 
     /// 获取文件元数据（大小、修改时间等）
     async fn get_file_metadata(&self, path: &str) -> Result<FileMetadata>;
+
+    /// 外部程序（如转码器）可直接随机访问文件的位置，不支持时返回 `None`
+    async fn direct_location(&self, _path: &str) -> Option<VfsLocation> {
+        None
+    }
 }
 
 /// 文件元数据信息
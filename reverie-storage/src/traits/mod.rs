@@ -12,5 +12,5 @@ pub mod subsonic;
 pub use core::{AlbumStorage, ArtistStorage, TrackStorage};
 pub use file::{FileMetadata, FileStorage};
 pub use storage::Storage;
-pub use subsonic::SubsonicStorage;
+pub use subsonic::{LibraryFolder, SubsonicStorage};
 pub use user::{PlaylistStorage, UserStorage};
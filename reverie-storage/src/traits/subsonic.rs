@@ -2,19 +2,37 @@
 //!
 //! 完整的 Subsonic API 存储 trait，实现 navidrome 兼容的 Subsonic API 所需的所有方法。
 
-use crate::error::Result;
+use crate::error::{Result, StorageError};
+use crate::vfs::VfsConfig;
 use async_trait::async_trait;
 use reverie_core::{
-    MediaFile, SubsonicAlbum, SubsonicAlbumInfo, SubsonicArtist, SubsonicArtistIndexes,
-    SubsonicArtistInfo, SubsonicBookmark, SubsonicDirectory, SubsonicGenre,
+    MediaFile, MusicFolderAccess, SubsonicAlbum, SubsonicAlbumInfo, SubsonicArtist,
+    SubsonicArtistIndexes, SubsonicArtistInfo, SubsonicBookmark, SubsonicDirectory, SubsonicGenre,
     SubsonicInternetRadioStation, SubsonicLyrics, SubsonicMusicFolder, SubsonicNowPlaying,
     SubsonicOpenSubsonicExtension, SubsonicPlayQueue, SubsonicPlaylist, SubsonicPlaylistWithSongs,
-    SubsonicScanStatus, SubsonicSearchResult2, SubsonicSearchResult3, SubsonicShare,
-    SubsonicStarred, SubsonicStructuredLyrics, SubsonicTopSongs, SubsonicUser,
+    SubsonicScanStatus, SubsonicScrobbleAccount, SubsonicSearchResult2, SubsonicSearchResult3,
+    SubsonicShare, SubsonicStarred, SubsonicStructuredLyrics, SubsonicTopSongs, SubsonicUser,
+    UserContext,
 };
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+/// 音乐文件夹配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryFolder {
+    pub id: i32,
+    pub name: String,
+    /// 扫描路径，相对于该文件夹 VFS 的根目录
+    pub path: String,
+    /// 独立的 VFS 配置，为 `None` 时使用默认 VFS
+    pub vfs_config: Option<VfsConfig>,
+}
 
 /// 完整的 Subsonic API 存储 trait
 /// 实现 navidrome 兼容的 Subsonic API 所需的所有方法
+///
+/// 列表和搜索方法的 `music_folder_ids` 参数限定结果所在的音乐文件夹，为 `None` 时不限制。
+/// 返回歌曲、专辑和艺术家的方法根据 `user` 填充该用户的收藏时间和评分
 #[allow(clippy::too_many_arguments)]
 #[async_trait]
 pub trait SubsonicStorage: Send + Sync {
@@ -31,27 +49,36 @@ pub trait SubsonicStorage: Send + Sync {
     /// 获取艺术家索引（A-Z 分组的艺术家）
     async fn get_indexes(
         &self,
-        music_folder_id: Option<i32>,
+        user: &UserContext,
+        music_folder_ids: Option<&[i32]>,
         if_modified_since: Option<i64>,
     ) -> Result<SubsonicArtistIndexes>;
 
-    /// 获取所有流派及歌曲/专辑数量
-    async fn get_genres(&self) -> Result<Vec<SubsonicGenre>>;
+    /// 获取流派及歌曲/专辑数量，指定音乐文件夹时只统计其中的曲目
+    async fn get_genres(&self, music_folder_ids: Option<&[i32]>) -> Result<Vec<SubsonicGenre>>;
 
     /// 获取目录内容（用于基于文件夹的浏览）
-    async fn get_music_directory(&self, id: &str) -> Result<Option<SubsonicDirectory>>;
+    async fn get_music_directory(
+        &self,
+        user: &UserContext,
+        id: &str,
+    ) -> Result<Option<SubsonicDirectory>>;
 
     /// 获取艺术家（基于 ID3 标签）
-    async fn get_artists(&self, music_folder_id: Option<i32>) -> Result<SubsonicArtistIndexes>;
+    async fn get_artists(
+        &self,
+        user: &UserContext,
+        music_folder_ids: Option<&[i32]>,
+    ) -> Result<SubsonicArtistIndexes>;
 
     /// 通过 ID 获取单个艺术家
-    async fn get_artist(&self, id: &str) -> Result<Option<SubsonicArtist>>;
+    async fn get_artist(&self, user: &UserContext, id: &str) -> Result<Option<SubsonicArtist>>;
 
     /// 通过 ID 获取单个专辑
-    async fn get_album(&self, id: &str) -> Result<Option<SubsonicAlbum>>;
+    async fn get_album(&self, user: &UserContext, id: &str) -> Result<Option<SubsonicAlbum>>;
 
     /// 通过 ID 获取单个歌曲
-    async fn get_song(&self, id: &str) -> Result<Option<MediaFile>>;
+    async fn get_song(&self, user: &UserContext, id: &str) -> Result<Option<MediaFile>>;
 
     /// 获取视频（未实现，返回空）
     async fn get_videos(&self) -> Result<Vec<MediaFile>> {
@@ -81,71 +108,99 @@ pub trait SubsonicStorage: Send + Sync {
     async fn get_album_info2(&self, id: &str) -> Result<SubsonicAlbumInfo>;
 
     /// 获取相似歌曲
-    async fn get_similar_songs(&self, id: &str, count: Option<i32>) -> Result<Vec<MediaFile>>;
+    async fn get_similar_songs(
+        &self,
+        user: &UserContext,
+        id: &str,
+        count: Option<i32>,
+    ) -> Result<Vec<MediaFile>>;
 
     /// 获取相似歌曲（ID3 版本）
-    async fn get_similar_songs2(&self, id: &str, count: Option<i32>) -> Result<Vec<MediaFile>>;
+    async fn get_similar_songs2(
+        &self,
+        user: &UserContext,
+        id: &str,
+        count: Option<i32>,
+    ) -> Result<Vec<MediaFile>>;
 
     /// 获取艺术家的热门歌曲
-    async fn get_top_songs(&self, artist: &str, count: Option<i32>) -> Result<SubsonicTopSongs>;
+    async fn get_top_songs(
+        &self,
+        user: &UserContext,
+        artist: &str,
+        count: Option<i32>,
+    ) -> Result<SubsonicTopSongs>;
 
     // === 专辑/歌曲列表 ===
     /// 获取专辑列表（多种排序类型）
     async fn get_album_list(
         &self,
+        user: &UserContext,
         list_type: &str,
         size: Option<i32>,
         offset: Option<i32>,
         from_year: Option<i32>,
         to_year: Option<i32>,
         genre: Option<&str>,
-        music_folder_id: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<SubsonicAlbum>>;
 
     /// 获取专辑列表（ID3 版本）
     async fn get_album_list2(
         &self,
+        user: &UserContext,
         list_type: &str,
         size: Option<i32>,
         offset: Option<i32>,
         from_year: Option<i32>,
         to_year: Option<i32>,
         genre: Option<&str>,
-        music_folder_id: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<SubsonicAlbum>>;
 
     /// 获取随机歌曲
     async fn get_random_songs(
         &self,
+        user: &UserContext,
         size: Option<i32>,
         genre: Option<&str>,
         from_year: Option<i32>,
         to_year: Option<i32>,
-        music_folder_id: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<MediaFile>>;
 
     /// 获取按流派的歌曲
     async fn get_songs_by_genre(
         &self,
+        user: &UserContext,
         genre: &str,
         count: Option<i32>,
         offset: Option<i32>,
-        music_folder_id: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<Vec<MediaFile>>;
 
-    /// 获取正在播放的条目
-    async fn get_now_playing(&self) -> Result<Vec<SubsonicNowPlaying>>;
+    /// 获取所有用户正在播放且尚未过期的条目
+    async fn get_now_playing(&self, user: &UserContext) -> Result<Vec<SubsonicNowPlaying>>;
 
-    /// 获取收藏的项目
-    async fn get_starred(&self, music_folder_id: Option<i32>) -> Result<SubsonicStarred>;
+    /// 获取当前用户收藏的项目
+    async fn get_starred(
+        &self,
+        user: &UserContext,
+        music_folder_ids: Option<&[i32]>,
+    ) -> Result<SubsonicStarred>;
 
-    /// 获取收藏的项目（ID3 版本）
-    async fn get_starred2(&self, music_folder_id: Option<i32>) -> Result<SubsonicStarred>;
+    /// 获取当前用户收藏的项目（ID3 版本）
+    async fn get_starred2(
+        &self,
+        user: &UserContext,
+        music_folder_ids: Option<&[i32]>,
+    ) -> Result<SubsonicStarred>;
 
     // === 搜索 ===
     /// 搜索（已废弃，使用 search2/search3）
     async fn search(
         &self,
+        user: &UserContext,
         artist: Option<&str>,
         album: Option<&str>,
         title: Option<&str>,
@@ -156,13 +211,14 @@ pub trait SubsonicStorage: Send + Sync {
     ) -> Result<SubsonicSearchResult2> {
         // 使用 search2 的默认实现
         let query = any.or(title).or(album).or(artist).unwrap_or("");
-        self.search2(query, None, None, None, None, None, None)
+        self.search2(user, query, None, None, None, None, None, None, None)
             .await
     }
 
     /// Search2（基于文件夹）
     async fn search2(
         &self,
+        user: &UserContext,
         query: &str,
         artist_count: Option<i32>,
         artist_offset: Option<i32>,
@@ -170,11 +226,13 @@ pub trait SubsonicStorage: Send + Sync {
         album_offset: Option<i32>,
         song_count: Option<i32>,
         song_offset: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<SubsonicSearchResult2>;
 
     /// Search3（基于 ID3）
     async fn search3(
         &self,
+        user: &UserContext,
         query: &str,
         artist_count: Option<i32>,
         artist_offset: Option<i32>,
@@ -182,26 +240,47 @@ pub trait SubsonicStorage: Send + Sync {
         album_offset: Option<i32>,
         song_count: Option<i32>,
         song_offset: Option<i32>,
+        music_folder_ids: Option<&[i32]>,
     ) -> Result<SubsonicSearchResult3>;
 
     // === 播放列表 ===
-    /// 获取所有播放列表
-    async fn get_playlists(&self, username: Option<&str>) -> Result<Vec<SubsonicPlaylist>>;
+    /// 获取用户可以看到的播放列表，即用户自己的和公开的播放列表
+    ///
+    /// `username` 指定其他用户时返回该用户可以看到的播放列表，只有管理员可以指定，
+    /// 其他用户返回 `PermissionDenied`
+    async fn get_playlists(
+        &self,
+        user: &UserContext,
+        username: Option<&str>,
+    ) -> Result<Vec<SubsonicPlaylist>>;
 
     /// 获取包含歌曲的单个播放列表
-    async fn get_playlist(&self, id: &str) -> Result<Option<SubsonicPlaylistWithSongs>>;
+    ///
+    /// 其他用户的非公开播放列表只有管理员可以读取，其他用户返回 `PermissionDenied`
+    async fn get_playlist(
+        &self,
+        user: &UserContext,
+        id: &str,
+    ) -> Result<Option<SubsonicPlaylistWithSongs>>;
 
     /// 创建播放列表
+    ///
+    /// 指定 `playlist_id` 时改为用 `song_ids` 替换该播放列表的歌曲，权限同
+    /// [`update_playlist`](Self::update_playlist)，播放列表不存在时返回 `NotFound`
     async fn create_playlist(
         &self,
+        user: &UserContext,
         name: Option<&str>,
         playlist_id: Option<&str>,
         song_ids: &[&str],
     ) -> Result<SubsonicPlaylistWithSongs>;
 
     /// 更新播放列表
+    ///
+    /// 只有所有者和管理员可以修改，其他用户返回 `PermissionDenied`
     async fn update_playlist(
         &self,
+        user: &UserContext,
         playlist_id: &str,
         name: Option<&str>,
         comment: Option<&str>,
@@ -210,15 +289,17 @@ pub trait SubsonicStorage: Send + Sync {
         song_indexes_to_remove: &[i32],
     ) -> Result<()>;
 
-    /// 删除播放列表
-    async fn delete_playlist(&self, id: &str) -> Result<()>;
+    /// 删除播放列表，权限同 [`update_playlist`](Self::update_playlist)
+    async fn delete_playlist(&self, user: &UserContext, id: &str) -> Result<()>;
 
     // === 媒体检索（仅路径，实际流媒体由网络层处理） ===
     /// 获取流媒体文件路径
     async fn get_stream_path(&self, id: &str) -> Result<Option<String>>;
 
-    /// 获取封面图片路径
-    async fn get_cover_art_path(&self, id: &str) -> Result<Option<String>>;
+    /// 获取专辑或歌曲的封面图片路径
+    ///
+    /// 位于用户无权访问的音乐文件夹中的专辑和歌曲视为不存在
+    async fn get_cover_art_path(&self, user: &UserContext, id: &str) -> Result<Option<String>>;
 
     /// 获取歌词
     async fn get_lyrics(
@@ -233,67 +314,118 @@ pub trait SubsonicStorage: Send + Sync {
     /// 获取用户头像路径
     async fn get_avatar_path(&self, username: &str) -> Result<Option<String>>;
 
-    // === 媒体标注 ===
+    // === 媒体标注（按用户隔离） ===
     /// 收藏项目（添加到收藏夹）
-    async fn star(&self, ids: &[&str], album_ids: &[&str], artist_ids: &[&str]) -> Result<()>;
+    async fn star(
+        &self,
+        user: &UserContext,
+        ids: &[&str],
+        album_ids: &[&str],
+        artist_ids: &[&str],
+    ) -> Result<()>;
 
     /// 取消收藏（从收藏夹移除）
-    async fn unstar(&self, ids: &[&str], album_ids: &[&str], artist_ids: &[&str]) -> Result<()>;
+    async fn unstar(
+        &self,
+        user: &UserContext,
+        ids: &[&str],
+        album_ids: &[&str],
+        artist_ids: &[&str],
+    ) -> Result<()>;
 
-    /// 设置评分（0-5）
-    async fn set_rating(&self, id: &str, rating: i32) -> Result<()>;
+    /// 设置评分（0-5，0 表示清除评分）
+    async fn set_rating(&self, user: &UserContext, id: &str, rating: i32) -> Result<()>;
 
     /// 记录播放（Scrobble）
-    async fn scrobble(&self, id: &str, time: Option<i64>, submission: bool) -> Result<()>;
+    ///
+    /// `submission` 为 `false` 时仅将曲目登记为该用户在客户端 `client` 上正在播放，
+    /// 为 `true` 时记录一次播放并增加播放次数
+    async fn scrobble(
+        &self,
+        user: &UserContext,
+        id: &str,
+        time: Option<i64>,
+        submission: bool,
+        client: Option<&str>,
+    ) -> Result<()>;
 
     // === 书签 ===
     /// 获取用户的所有书签
-    async fn get_bookmarks(&self) -> Result<Vec<SubsonicBookmark>>;
+    async fn get_bookmarks(&self, user: &UserContext) -> Result<Vec<SubsonicBookmark>>;
 
     /// 创建/更新书签
-    async fn create_bookmark(&self, id: &str, position: i64, comment: Option<&str>) -> Result<()>;
+    async fn create_bookmark(
+        &self,
+        user: &UserContext,
+        id: &str,
+        position: i64,
+        comment: Option<&str>,
+    ) -> Result<()>;
 
     /// 删除书签
-    async fn delete_bookmark(&self, id: &str) -> Result<()>;
+    async fn delete_bookmark(&self, user: &UserContext, id: &str) -> Result<()>;
 
-    /// 获取播放队列
-    async fn get_play_queue(&self) -> Result<Option<SubsonicPlayQueue>>;
+    /// 获取用户的播放队列
+    async fn get_play_queue(&self, user: &UserContext) -> Result<Option<SubsonicPlayQueue>>;
 
-    /// 保存播放队列
+    /// 保存用户的播放队列，`ids` 为空时清除队列
+    ///
+    /// `current_index` 为当前曲目在 `ids` 中的位置，`changed_by` 为保存队列的客户端
     async fn save_play_queue(
         &self,
+        user: &UserContext,
         ids: &[&str],
-        current: Option<&str>,
+        current_index: Option<usize>,
         position: Option<i64>,
+        changed_by: &str,
     ) -> Result<()>;
 
     // === 分享 ===
-    /// 获取所有分享
-    async fn get_shares(&self) -> Result<Vec<SubsonicShare>>;
+    /// 获取用户创建的分享
+    async fn get_shares(&self, user: &UserContext) -> Result<Vec<SubsonicShare>>;
 
     /// 创建分享
+    ///
+    /// `ids` 可以是歌曲、专辑或播放列表的 ID，`expires` 为过期时间的毫秒时间戳。
+    /// 返回的分享 ID 即公开链接中的令牌，`url` 为相对于服务器根路径的链接。
     async fn create_share(
         &self,
+        user: &UserContext,
         ids: &[&str],
         description: Option<&str>,
         expires: Option<i64>,
     ) -> Result<SubsonicShare>;
 
-    /// 更新分享
+    /// 更新分享的描述和过期时间，参数为 `None` 时保持不变
+    ///
+    /// 只有创建者和管理员可以修改，其他用户返回 `PermissionDenied`
     async fn update_share(
         &self,
+        user: &UserContext,
         id: &str,
         description: Option<&str>,
         expires: Option<i64>,
     ) -> Result<()>;
 
-    /// 删除分享
-    async fn delete_share(&self, id: &str) -> Result<()>;
+    /// 删除分享，权限同 [`update_share`](Self::update_share)
+    async fn delete_share(&self, user: &UserContext, id: &str) -> Result<()>;
+
+    /// 通过公开链接获取分享，不存在或已过期时返回 `None`
+    async fn get_public_share(&self, id: &str) -> Result<Option<SubsonicShare>>;
+
+    /// 记录一次公开链接的访问
+    async fn record_share_visit(&self, id: &str) -> Result<()>;
 
     // === 网络电台 ===
     /// 获取所有网络电台
     async fn get_internet_radio_stations(&self) -> Result<Vec<SubsonicInternetRadioStation>>;
 
+    /// 通过 ID 获取网络电台
+    async fn get_internet_radio_station(
+        &self,
+        id: &str,
+    ) -> Result<Option<SubsonicInternetRadioStation>>;
+
     /// 创建网络电台
     async fn create_internet_radio_station(
         &self,
@@ -302,7 +434,7 @@ pub trait SubsonicStorage: Send + Sync {
         homepage_url: Option<&str>,
     ) -> Result<()>;
 
-    /// 更新网络电台
+    /// 更新网络电台，电台不存在时返回 `NotFound`
     async fn update_internet_radio_station(
         &self,
         id: &str,
@@ -311,9 +443,42 @@ pub trait SubsonicStorage: Send + Sync {
         homepage_url: Option<&str>,
     ) -> Result<()>;
 
-    /// 删除网络电台
+    /// 删除网络电台，电台不存在时返回 `NotFound`
     async fn delete_internet_radio_station(&self, id: &str) -> Result<()>;
 
+    // === 身份验证 ===
+    /// 获取用户的 Subsonic 密码（明文）
+    ///
+    /// 令牌验证（`t = md5(password + salt)`）要求服务器能取回原始密码。
+    /// 用户不存在或密码不可取回时返回 `None`。
+    async fn get_user_password(&self, username: &str) -> Result<Option<String>>;
+
+    /// 验证用户的明文密码（`p` 参数）
+    ///
+    /// 默认与 [`get_user_password`](Self::get_user_password) 取回的密码做常量时间比较；
+    /// 保存密码哈希的实现应覆盖此方法，使密码无法取回时也能登录
+    async fn verify_user_password(&self, username: &str, password: &str) -> Result<bool> {
+        Ok(self
+            .get_user_password(username)
+            .await?
+            .is_some_and(|stored| bool::from(stored.as_bytes().ct_eq(password.as_bytes()))))
+    }
+
+    /// 用户是否必须先修改密码才能使用其他端点，例如首次启动时创建的管理员
+    async fn password_change_required(&self, _username: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// 是否支持 OpenSubsonic `apiKey` 身份验证
+    fn supports_api_key_auth(&self) -> bool {
+        false
+    }
+
+    /// 通过 API 密钥查找用户名（OpenSubsonic `apiKeyAuthentication` 扩展）
+    async fn get_username_by_api_key(&self, _api_key: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+
     // === 用户管理 ===
     /// 通过用户名获取用户
     async fn get_user(&self, username: &str) -> Result<Option<SubsonicUser>>;
@@ -321,7 +486,7 @@ pub trait SubsonicStorage: Send + Sync {
     /// 获取所有用户
     async fn get_users(&self) -> Result<Vec<SubsonicUser>>;
 
-    /// 创建用户
+    /// 创建用户，用户名已存在时返回错误
     async fn create_user(
         &self,
         username: &str,
@@ -339,10 +504,12 @@ pub trait SubsonicStorage: Send + Sync {
         podcast_role: bool,
         share_role: bool,
         video_conversion_role: bool,
-        music_folder_ids: &[i32],
+        music_folders: &MusicFolderAccess,
     ) -> Result<()>;
 
-    /// 更新用户
+    /// 更新用户，为 `None` 的字段保持不变，`max_bit_rate` 为 0 表示不限制码率
+    ///
+    /// 用户不存在时返回 `NotFound`
     async fn update_user(
         &self,
         username: &str,
@@ -360,27 +527,79 @@ pub trait SubsonicStorage: Send + Sync {
         podcast_role: Option<bool>,
         share_role: Option<bool>,
         video_conversion_role: Option<bool>,
-        music_folder_ids: Option<&[i32]>,
+        music_folders: Option<&MusicFolderAccess>,
         max_bit_rate: Option<i32>,
     ) -> Result<()>;
 
-    /// 删除用户
+    /// 删除用户及其播放列表、收藏、分享等数据
+    ///
+    /// 用户不存在时返回 `NotFound`
     async fn delete_user(&self, username: &str) -> Result<()>;
 
-    /// 更改密码
+    /// 更改密码，同时解除 [`password_change_required`](Self::password_change_required)
+    ///
+    /// 用户不存在时返回 `NotFound`
     async fn change_password(&self, username: &str, password: &str) -> Result<()>;
 
+    // === 播放记录转发 ===
+    /// 获取用户配置的播放记录转发账号，不返回令牌
+    async fn get_scrobble_accounts(
+        &self,
+        _user: &UserContext,
+    ) -> Result<Vec<SubsonicScrobbleAccount>> {
+        Ok(Vec::new())
+    }
+
+    /// 设置用户在某个服务上的令牌，`url` 为 `None` 时使用官方服务
+    ///
+    /// 不支持的服务返回 `NotFound`
+    async fn update_scrobble_account(
+        &self,
+        _user: &UserContext,
+        service: &str,
+        _token: &str,
+        _url: Option<&str>,
+    ) -> Result<()> {
+        Err(StorageError::NotFound(format!("Scrobble service {}", service)))
+    }
+
+    /// 删除用户在某个服务上的令牌及尚未发送的播放记录
+    async fn delete_scrobble_account(&self, _user: &UserContext, _service: &str) -> Result<()> {
+        Ok(())
+    }
+
+    // === 音乐文件夹管理 ===
+    /// 获取所有音乐文件夹的完整配置，包括扫描路径和 VFS 配置
+    async fn get_music_folder_configs(&self) -> Result<Vec<LibraryFolder>>;
+
+    /// 添加音乐文件夹，`scan` 为 true 时在后台扫描该文件夹
+    async fn create_music_folder(
+        &self,
+        name: &str,
+        path: &str,
+        vfs_config: Option<&VfsConfig>,
+        scan: bool,
+    ) -> Result<LibraryFolder>;
+
+    /// 更新音乐文件夹的名称、扫描路径和 VFS 配置
+    async fn update_music_folder(&self, folder: &LibraryFolder) -> Result<()>;
+
+    /// 删除音乐文件夹及其中的曲目，不再包含曲目的专辑和艺术家一并删除
+    async fn delete_music_folder(&self, id: i32) -> Result<()>;
+
     // === 库扫描 ===
     /// 获取扫描状态
     async fn get_scan_status(&self) -> Result<SubsonicScanStatus>;
 
     /// 开始扫描库
-    async fn start_scan(&self) -> Result<SubsonicScanStatus>;
+    ///
+    /// 默认只扫描新增和变化的文件，`full_scan` 为 true 时重新解析所有文件
+    async fn start_scan(&self, full_scan: bool) -> Result<SubsonicScanStatus>;
 
     // === OpenSubsonic 扩展 ===
     /// 获取支持的 OpenSubsonic 扩展
     async fn get_open_subsonic_extensions(&self) -> Result<Vec<SubsonicOpenSubsonicExtension>> {
-        Ok(vec![
+        let mut extensions = vec![
             SubsonicOpenSubsonicExtension {
                 name: "transcodeOffset".to_string(),
                 versions: vec![1],
@@ -393,6 +612,17 @@ pub trait SubsonicStorage: Send + Sync {
                 name: "songLyrics".to_string(),
                 versions: vec![1],
             },
-        ])
+            SubsonicOpenSubsonicExtension {
+                name: "indexBasedQueue".to_string(),
+                versions: vec![1],
+            },
+        ];
+        if self.supports_api_key_auth() {
+            extensions.push(SubsonicOpenSubsonicExtension {
+                name: "apiKeyAuthentication".to_string(),
+                versions: vec![1],
+            });
+        }
+        Ok(extensions)
     }
 }
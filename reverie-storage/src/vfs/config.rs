@@ -4,8 +4,22 @@
 
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
+
+/// API 响应中代替凭据的占位符
+pub const REDACTED: &str = "***";
+
+/// 选项是否保存凭据，例如 S3 的 `secret_access_key`、Azure 的 `account_key`
+fn is_secret_option(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name == "key"
+        || ["secret", "password", "token", "credential", "account_key", "sas"]
+            .iter()
+            .any(|s| name.contains(s))
+}
+
 /// 虚拟文件系统配置
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct VfsConfig {
     /// 存储后端的方案（例如："fs"、"s3"、"azblob"、"gcs"）
     pub scheme: String,
@@ -14,6 +28,42 @@ pub struct VfsConfig {
 }
 
 impl VfsConfig {
+    /// 将凭据替换为 [`REDACTED`] 的副本，用于返回给客户端
+    pub fn redacted(&self) -> Self {
+        let options = self
+            .options
+            .iter()
+            .map(|(name, value)| {
+                let value = if is_secret_option(name) {
+                    REDACTED.to_string()
+                } else {
+                    value.clone()
+                };
+                (name.clone(), value)
+            })
+            .collect();
+        Self {
+            scheme: self.scheme.clone(),
+            options,
+        }
+    }
+
+    /// 将值为 [`REDACTED`] 的选项恢复为 `previous` 中保存的值
+    ///
+    /// 客户端把读取到的配置原样提交时，不会用占位符覆盖已保存的凭据
+    pub fn restore_redacted(&mut self, previous: Option<&VfsConfig>) {
+        let Some(previous) = previous.filter(|p| p.scheme == self.scheme) else {
+            return;
+        };
+        for (name, value) in self.options.iter_mut() {
+            if value == REDACTED {
+                if let Some(saved) = previous.options.get(name) {
+                    value.clone_from(saved);
+                }
+            }
+        }
+    }
+
     /// 创建本地文件系统 VFS 配置
     pub fn local(root: impl Into<String>) -> Self {
         let mut options = HashMap::new();
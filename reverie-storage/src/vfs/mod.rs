@@ -11,8 +11,8 @@ pub mod types;
 // 重新导出主要类型
 pub use config::VfsConfig;
 pub use opendal::OpendalVfs;
-pub use vfs_trait::Vfs;
-pub use types::{VfsEntry, VfsMetadata};
+pub use vfs_trait::{Vfs, VfsRead, VfsReader, VfsWrite, VfsWriter};
+pub use types::{VfsEntry, VfsLocation, VfsMetadata};
 
 use std::sync::Arc;
 
@@ -5,11 +5,18 @@
 use async_trait::async_trait;
 use bytes::Bytes;
 use opendal::Operator;
+use tokio_util::compat::FuturesAsyncReadCompatExt;
 
 use crate::error::{Result, StorageError};
 use super::config::VfsConfig;
-use super::vfs_trait::Vfs;
-use super::types::{VfsEntry, VfsMetadata};
+use super::vfs_trait::{Vfs, VfsReader, VfsWrite, VfsWriter};
+use super::types::{VfsEntry, VfsLocation, VfsMetadata};
+
+/// 流式读取器每次向后端请求的块大小
+const READER_CHUNK_SIZE: usize = 64 * 1024;
+
+/// 预签名 URL 的有效期，需覆盖一次完整的转码
+const PRESIGN_EXPIRY: std::time::Duration = std::time::Duration::from_secs(6 * 60 * 60);
 
 /// 基于 OpenDAL 的 VFS 实现
 #[derive(Clone)]
@@ -159,6 +166,19 @@ impl Vfs for OpendalVfs {
             .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))
     }
 
+    async fn reader(&self, path: &str) -> Result<VfsReader> {
+        let reader = self
+            .operator
+            .reader_with(path)
+            .chunk(READER_CHUNK_SIZE)
+            .await
+            .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))?
+            .into_futures_async_read(..)
+            .await
+            .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))?;
+        Ok(Box::new(reader.compat()))
+    }
+
     async fn write(&self, path: &str, data: Bytes) -> Result<()> {
         let _ = self
             .operator
@@ -168,6 +188,15 @@ impl Vfs for OpendalVfs {
         Ok(())
     }
 
+    async fn writer(&self, path: &str) -> Result<VfsWriter> {
+        let writer = self
+            .operator
+            .writer(path)
+            .await
+            .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))?;
+        Ok(Box::new(OpendalWriter(writer)))
+    }
+
     async fn append(&self, path: &str, data: Bytes) -> Result<()> {
         // OpenDAL doesn't have native append, so we read + write
         let existing = match self.read(path).await {
@@ -248,6 +277,52 @@ impl Vfs for OpendalVfs {
             .await
             .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))
     }
+
+    async fn direct_location(&self, path: &str) -> Option<VfsLocation> {
+        if self.config.scheme == "fs" {
+            let root = self.config.options.get("root")?;
+            let full = std::path::Path::new(root).join(path.trim_start_matches('/'));
+            return std::path::absolute(full).ok().map(VfsLocation::Local);
+        }
+        if !self.operator.info().full_capability().presign_read {
+            return None;
+        }
+        match self.operator.presign_read(path, PRESIGN_EXPIRY).await {
+            Ok(request) => Some(VfsLocation::Url(request.uri().to_string())),
+            Err(e) => {
+                tracing::warn!("Failed to presign {}: {}", path, e);
+                None
+            }
+        }
+    }
+}
+
+/// OpenDAL 写入器的 `VfsWrite` 适配
+struct OpendalWriter(opendal::Writer);
+
+#[async_trait]
+impl VfsWrite for OpendalWriter {
+    async fn write(&mut self, data: Bytes) -> Result<()> {
+        self.0
+            .write(data)
+            .await
+            .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        self.0
+            .close()
+            .await
+            .map(|_| ())
+            .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))
+    }
+
+    async fn abort(&mut self) -> Result<()> {
+        self.0
+            .abort()
+            .await
+            .map_err(|e| StorageError::IoError(std::io::Error::other(e.to_string())))
+    }
 }
 
 #[cfg(test)]
@@ -275,4 +350,63 @@ mod tests {
         vfs.delete("test.txt").await.unwrap();
         assert!(!vfs.exists("test.txt").await.unwrap());
     }
+
+    #[tokio::test]
+    async fn test_memory_vfs_reader() {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        let vfs = OpendalVfs::new(VfsConfig::memory()).unwrap();
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        vfs.write("large.bin", Bytes::from(data.clone())).await.unwrap();
+
+        let mut reader = vfs.reader("large.bin").await.unwrap();
+
+        let mut head = [0u8; 16];
+        reader.read_exact(&mut head).await.unwrap();
+        assert_eq!(&head[..], &data[..16]);
+
+        // 跨越读取块边界定位
+        reader.seek(std::io::SeekFrom::Start(150_000)).await.unwrap();
+        let mut chunk = [0u8; 32];
+        reader.read_exact(&mut chunk).await.unwrap();
+        assert_eq!(&chunk[..], &data[150_000..150_032]);
+
+        let end = reader.seek(std::io::SeekFrom::End(-8)).await.unwrap();
+        assert_eq!(end, 200_000 - 8);
+        let mut tail = Vec::new();
+        reader.read_to_end(&mut tail).await.unwrap();
+        assert_eq!(&tail[..], &data[200_000 - 8..]);
+
+        assert!(vfs.reader("nonexistent.bin").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_memory_vfs_writer() {
+        let vfs = OpendalVfs::new(VfsConfig::memory()).unwrap();
+
+        let mut writer = vfs.writer("parts.bin").await.unwrap();
+        writer.write(Bytes::from("hello ")).await.unwrap();
+        writer.write(Bytes::from("world")).await.unwrap();
+        writer.close().await.unwrap();
+        assert_eq!(&vfs.read("parts.bin").await.unwrap()[..], b"hello world");
+
+        let mut writer = vfs.writer("aborted.bin").await.unwrap();
+        writer.write(Bytes::from("partial")).await.unwrap();
+        writer.abort().await.unwrap();
+        assert!(!vfs.exists("aborted.bin").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_direct_location() {
+        let root = tempfile::tempdir().unwrap();
+        let vfs = OpendalVfs::new(VfsConfig::local(root.path().to_string_lossy())).unwrap();
+        assert_eq!(
+            vfs.direct_location("/Artist/track.m4a").await,
+            Some(VfsLocation::Local(root.path().join("Artist/track.m4a")))
+        );
+
+        // 内存后端既没有本地路径也不支持预签名
+        let vfs = OpendalVfs::new(VfsConfig::memory()).unwrap();
+        assert_eq!(vfs.direct_location("track.m4a").await, None);
+    }
 }
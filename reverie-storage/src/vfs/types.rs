@@ -4,6 +4,7 @@
 
 use chrono::{DateTime, Utc};
 use opendal::{Entry, Metadata};
+use std::path::PathBuf;
 
 /// 来自 VFS 的文件元数据
 #[derive(Debug, Clone)]
@@ -50,3 +51,12 @@ impl From<Entry> for VfsEntry {
         }
     }
 }
+
+/// 外部程序可直接随机访问文件的位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VfsLocation {
+    /// 本地文件的绝对路径
+    Local(PathBuf),
+    /// 可直接读取的 URL（如预签名 URL）
+    Url(String),
+}
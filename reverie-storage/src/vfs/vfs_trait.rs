@@ -4,9 +4,34 @@
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncSeek};
 
 use crate::error::Result;
-use super::types::{VfsEntry, VfsMetadata};
+use super::types::{VfsEntry, VfsLocation, VfsMetadata};
+
+/// 可异步读取和定位的文件读取器
+pub trait VfsRead: AsyncRead + AsyncSeek + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncSeek + Send + Unpin> VfsRead for T {}
+
+/// `Vfs::reader` 返回的读取器
+pub type VfsReader = Box<dyn VfsRead>;
+
+/// 分块写入文件的写入器，`close` 之前写入的内容不保证可见
+#[async_trait]
+pub trait VfsWrite: Send {
+    /// 追加一块数据
+    async fn write(&mut self, data: Bytes) -> Result<()>;
+
+    /// 完成写入
+    async fn close(&mut self) -> Result<()>;
+
+    /// 放弃写入，已写入的数据被丢弃
+    async fn abort(&mut self) -> Result<()>;
+}
+
+/// `Vfs::writer` 返回的写入器
+pub type VfsWriter = Box<dyn VfsWrite>;
 
 /// 虚拟文件系统 trait - 不同存储后端的抽象
 #[async_trait]
@@ -17,9 +42,20 @@ pub trait Vfs: Send + Sync {
     /// 从文件中读取指定范围的字节
     async fn read_range(&self, path: &str, offset: u64, size: u64) -> Result<Bytes>;
 
+    /// 打开文件的流式读取器，按需从后端拉取数据
+    ///
+    /// 默认实现会先将整个文件读入内存，后端应尽量提供真正的流式实现。
+    async fn reader(&self, path: &str) -> Result<VfsReader> {
+        let data = self.read(path).await?;
+        Ok(Box::new(std::io::Cursor::new(data)))
+    }
+
     /// 向文件写入数据（创建或覆盖）
     async fn write(&self, path: &str, data: Bytes) -> Result<()>;
 
+    /// 打开文件的分块写入器（创建或覆盖）
+    async fn writer(&self, path: &str) -> Result<VfsWriter>;
+
     /// 向文件追加数据
     async fn append(&self, path: &str, data: Bytes) -> Result<()>;
 
@@ -46,4 +82,9 @@ pub trait Vfs: Send + Sync {
 
     /// 重命名/移动文件
     async fn rename(&self, from: &str, to: &str) -> Result<()>;
+
+    /// 外部程序（如转码器）可直接随机访问文件的位置，后端不支持时返回 `None`
+    async fn direct_location(&self, _path: &str) -> Option<VfsLocation> {
+        None
+    }
 }
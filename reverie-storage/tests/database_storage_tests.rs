@@ -0,0 +1,1950 @@
+//! Integration tests for the SQLite database storage implementation
+
+use axum::extract::State;
+use axum::http::{header::AUTHORIZATION, HeaderMap, StatusCode, Uri};
+use chrono::Utc;
+use reverie_core::{Album, Artist, MusicFolderAccess, Track, User, UserContext};
+use reverie_storage::database::SCHEMA_VERSION;
+use reverie_storage::{
+    DatabaseConfig, DatabaseStorage, FileStorage, LastFmForwarder, LibraryFolder,
+    ListenBrainzForwarder, ScrobbleAccount, Storage, SubsonicStorage, TrackStorage, UserStorage,
+    VfsConfig, WatcherConfig,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+async fn create_storage() -> DatabaseStorage {
+    let storage = DatabaseStorage::new(DatabaseConfig::memory())
+        .await
+        .expect("Failed to create database storage");
+    storage
+        .initialize()
+        .await
+        .expect("Failed to initialize storage");
+    storage
+}
+
+/// 不关心收藏和评分时查询使用的用户
+fn anonymous() -> UserContext {
+    UserContext::new("anonymous", false)
+}
+
+async fn create_user(storage: &DatabaseStorage, username: &str) -> UserContext {
+    let user = User {
+        id: Uuid::new_v4(),
+        username: username.to_string(),
+        password_hash: "secret".to_string(),
+        email: None,
+        is_admin: false,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    };
+    storage.save_user(&user).await.expect("Failed to save user");
+    UserContext::new(username, false)
+}
+
+async fn create_track(storage: &DatabaseStorage, title: &str) -> String {
+    let track = Track {
+        id: Uuid::new_v4(),
+        title: title.to_string(),
+        album_id: None,
+        artist_id: None,
+        duration: 180,
+        file_path: format!("/music/{}.mp3", title),
+        file_size: 5_000_000,
+        bitrate: 320,
+        format: "mp3".to_string(),
+        track_number: Some(1),
+        disc_number: Some(1),
+        year: Some(2024),
+        genre: None,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    };
+    storage.save_track(&track).await.expect("Failed to save track");
+    track.id.to_string()
+}
+
+#[tokio::test]
+async fn test_database_storage_annotations_are_per_user() {
+    let storage = create_storage().await;
+    let alice = create_user(&storage, "alice").await;
+    let bob = create_user(&storage, "bob").await;
+    let song = create_track(&storage, "song").await;
+
+    storage
+        .star(&alice, &[&song], &[], &[])
+        .await
+        .expect("Failed to star");
+    storage
+        .set_rating(&alice, &song, 5)
+        .await
+        .expect("Failed to set rating");
+
+    let starred = storage.get_starred2(&alice, None).await.unwrap();
+    assert_eq!(starred.songs.len(), 1);
+    assert_eq!(starred.songs[0].id, song);
+    assert!(starred.songs[0].starred.is_some());
+    assert_eq!(starred.songs[0].user_rating, Some(5));
+
+    let starred = storage.get_starred2(&bob, None).await.unwrap();
+    assert!(starred.songs.is_empty());
+
+    storage
+        .unstar(&alice, &[&song], &[], &[])
+        .await
+        .expect("Failed to unstar");
+    let starred = storage.get_starred2(&alice, None).await.unwrap();
+    assert!(starred.songs.is_empty());
+}
+
+#[tokio::test]
+async fn test_database_storage_reads_back_annotations() {
+    let storage = create_storage().await;
+    let alice = create_user(&storage, "alice").await;
+    let bob = create_user(&storage, "bob").await;
+    let now = Utc::now();
+    let artist = Artist {
+        id: Uuid::new_v4(),
+        name: "Artist".to_string(),
+        bio: None,
+        created_at: now,
+        updated_at: now,
+    };
+    let album = Album {
+        id: Uuid::new_v4(),
+        name: "Album".to_string(),
+        artist_id: Some(artist.id),
+        year: None,
+        genre: None,
+        cover_art_path: None,
+        created_at: now,
+        updated_at: now,
+    };
+    reverie_storage::ArtistStorage::save_artist(&storage, &artist)
+        .await
+        .unwrap();
+    reverie_storage::AlbumStorage::save_album(&storage, &album)
+        .await
+        .unwrap();
+    let song = create_track(&storage, "song").await;
+    sqlx::query("UPDATE tracks SET album_id = ?, artist_id = ? WHERE id = ?")
+        .bind(album.id.to_string())
+        .bind(artist.id.to_string())
+        .bind(&song)
+        .execute(storage.pool())
+        .await
+        .unwrap();
+    let (artist_id, album_id) = (artist.id.to_string(), album.id.to_string());
+
+    storage
+        .star(&alice, &[&song], &[&album_id], &[&artist_id])
+        .await
+        .unwrap();
+    storage.set_rating(&alice, &song, 4).await.unwrap();
+    storage.set_rating(&alice, &album_id, 5).await.unwrap();
+    storage.set_rating(&bob, &song, 1).await.unwrap();
+
+    let entry = storage.get_song(&alice, &song).await.unwrap().unwrap();
+    assert!(entry.starred.is_some());
+    assert_eq!(entry.user_rating, Some(4));
+    let entry = storage.get_song(&bob, &song).await.unwrap().unwrap();
+    assert!(entry.starred.is_none());
+    assert_eq!(entry.user_rating, Some(1));
+
+    let found = storage.get_album(&alice, &album_id).await.unwrap().unwrap();
+    assert!(found.starred.is_some());
+    assert_eq!(found.user_rating, Some(5));
+    let found = storage.get_artist(&alice, &artist_id).await.unwrap().unwrap();
+    assert!(found.starred.is_some());
+    assert!(storage
+        .get_artist(&bob, &artist_id)
+        .await
+        .unwrap()
+        .unwrap()
+        .starred
+        .is_none());
+
+    let result = storage
+        .search3(&alice, "", None, None, None, None, None, None, None)
+        .await
+        .unwrap();
+    assert!(result.artists[0].starred.is_some());
+    assert!(result.albums[0].starred.is_some());
+    assert_eq!(result.songs[0].user_rating, Some(4));
+    let directory = storage
+        .get_music_directory(&alice, &artist_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(directory.starred.is_some());
+    assert_eq!(directory.children[0].user_rating, Some(5));
+    let songs = storage
+        .get_random_songs(&alice, Some(10), None, None, None, None)
+        .await
+        .unwrap();
+    assert!(songs[0].starred.is_some());
+
+    // 收藏和评分最高的专辑列表按用户区分
+    for list_type in ["starred", "highest"] {
+        let albums = storage
+            .get_album_list2(&alice, list_type, None, None, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(albums.len(), 1, "{}", list_type);
+        assert_eq!(albums[0].user_rating, Some(5));
+        let albums = storage
+            .get_album_list2(&bob, list_type, None, None, None, None, None, None)
+            .await
+            .unwrap();
+        assert!(albums.is_empty(), "{}", list_type);
+    }
+
+    let playlist = storage
+        .create_playlist(&bob, Some("Mix"), None, &[&song])
+        .await
+        .unwrap();
+    assert_eq!(playlist.owner, "bob");
+    assert_eq!(playlist.entries[0].user_rating, Some(1));
+
+    // 非公开的播放列表只有所有者和管理员可以看到
+    use reverie_storage::error::StorageError;
+    let err = storage.get_playlist(&alice, &playlist.id).await.unwrap_err();
+    assert!(matches!(err, StorageError::PermissionDenied(_)));
+    assert!(storage.get_playlists(&alice, None).await.unwrap().is_empty());
+    let err = storage.get_playlists(&alice, Some("bob")).await.unwrap_err();
+    assert!(matches!(err, StorageError::PermissionDenied(_)));
+    let admin = UserContext::new("admin", true);
+    assert_eq!(storage.get_playlists(&admin, Some("bob")).await.unwrap().len(), 1);
+    assert!(storage.get_playlists(&admin, None).await.unwrap().is_empty());
+    storage
+        .update_playlist(&bob, &playlist.id, None, None, Some(true), &[], &[])
+        .await
+        .unwrap();
+    assert_eq!(storage.get_playlists(&alice, None).await.unwrap().len(), 1);
+    let playlist = storage
+        .get_playlist(&alice, &playlist.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(playlist.entries[0].user_rating, Some(4));
+
+    // 只有所有者和管理员可以修改或删除播放列表
+    let err = storage
+        .update_playlist(&alice, &playlist.id, Some("Mine"), None, None, &[], &[])
+        .await
+        .unwrap_err();
+    assert!(matches!(err, StorageError::PermissionDenied(_)));
+    let err = storage.delete_playlist(&alice, &playlist.id).await.unwrap_err();
+    assert!(matches!(err, StorageError::PermissionDenied(_)));
+    storage
+        .update_playlist(&bob, &playlist.id, Some("Renamed"), None, None, &[], &[])
+        .await
+        .unwrap();
+
+    // 指定 playlistId 时替换已有播放列表的歌曲，不会创建新的播放列表
+    let err = storage
+        .create_playlist(&alice, None, Some(&playlist.id), &[])
+        .await
+        .unwrap_err();
+    assert!(matches!(err, StorageError::PermissionDenied(_)));
+    let err = storage
+        .create_playlist(&bob, Some("Mix"), Some("unknown"), &[&song])
+        .await
+        .unwrap_err();
+    assert!(matches!(err, StorageError::NotFound(_)));
+    let replaced = storage
+        .create_playlist(&bob, None, Some(&playlist.id), &[])
+        .await
+        .unwrap();
+    assert_eq!(replaced.id, playlist.id);
+    assert_eq!(replaced.name, "Renamed");
+    assert!(replaced.entries.is_empty());
+    assert_eq!(storage.get_playlists(&bob, None).await.unwrap().len(), 1);
+    storage.delete_playlist(&admin, &playlist.id).await.unwrap();
+    let err = storage.delete_playlist(&bob, &playlist.id).await.unwrap_err();
+    assert!(matches!(err, StorageError::NotFound(_)));
+}
+
+#[tokio::test]
+async fn test_database_storage_scrobbles_and_now_playing() {
+    let storage = create_storage().await;
+    let alice = create_user(&storage, "alice").await;
+    let bob = create_user(&storage, "bob").await;
+    let now = Utc::now();
+    let mut songs = Vec::new();
+    let mut album_ids = Vec::new();
+    for name in ["First", "Second"] {
+        let album = Album {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            artist_id: None,
+            year: None,
+            genre: None,
+            cover_art_path: None,
+            created_at: now,
+            updated_at: now,
+        };
+        reverie_storage::AlbumStorage::save_album(&storage, &album)
+            .await
+            .unwrap();
+        let song = create_track(&storage, name).await;
+        sqlx::query("UPDATE tracks SET album_id = ? WHERE id = ?")
+            .bind(album.id.to_string())
+            .bind(&song)
+            .execute(storage.pool())
+            .await
+            .unwrap();
+        songs.push(song);
+        album_ids.push(album.id.to_string());
+    }
+
+    // 未提交的 scrobble 只登记正在播放，不计入播放次数
+    let started = now - chrono::Duration::minutes(2);
+    storage
+        .scrobble(&alice, &songs[0], Some(started.timestamp_millis()), false, Some("web"))
+        .await
+        .unwrap();
+    storage
+        .scrobble(&bob, &songs[1], None, false, None)
+        .await
+        .unwrap();
+    let playing = storage.get_now_playing(&bob).await.unwrap();
+    assert_eq!(playing.len(), 2);
+    let entry = playing.iter().find(|p| p.username == "alice").unwrap();
+    assert_eq!(entry.entry.id, songs[0]);
+    assert_eq!(entry.minutes_ago, 2);
+    assert_eq!(entry.player_name.as_deref(), Some("web"));
+    assert!(entry.player_id.is_some());
+    assert_eq!(entry.entry.play_count, Some(0));
+
+    // 同一客户端播放下一首时替换原记录，playerId 保持不变
+    storage
+        .scrobble(&alice, &songs[1], None, false, Some("web"))
+        .await
+        .unwrap();
+    let playing = storage.get_now_playing(&alice).await.unwrap();
+    let replaced = playing.iter().find(|p| p.username == "alice").unwrap();
+    assert_eq!(playing.len(), 2);
+    assert_eq!(replaced.entry.id, songs[1]);
+    assert_eq!(replaced.player_id, entry.player_id);
+    assert_eq!(replaced.minutes_ago, 0);
+
+    // 播放时长加宽限期之后过期
+    let an_hour_ago = (now - chrono::Duration::hours(1)).timestamp_millis();
+    storage
+        .scrobble(&bob, &songs[1], Some(an_hour_ago), false, None)
+        .await
+        .unwrap();
+    let playing = storage.get_now_playing(&bob).await.unwrap();
+    assert_eq!(playing.len(), 1);
+    assert_eq!(playing[0].username, "alice");
+
+    let albums = storage
+        .get_album_list2(&alice, "frequent", None, None, None, None, None, None)
+        .await
+        .unwrap();
+    assert!(albums.is_empty());
+
+    for (song, time) in [(&songs[0], 1), (&songs[0], 2), (&songs[1], 3)] {
+        let time = (now - chrono::Duration::minutes(10 - time)).timestamp_millis();
+        storage
+            .scrobble(&alice, song, Some(time), true, Some("web"))
+            .await
+            .unwrap();
+    }
+    storage
+        .scrobble(&bob, &songs[1], None, true, None)
+        .await
+        .unwrap();
+
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM scrobbles")
+        .fetch_one(storage.pool())
+        .await
+        .unwrap();
+    assert_eq!(count, 4);
+
+    let albums = storage
+        .get_album_list2(&alice, "frequent", None, None, None, None, None, None)
+        .await
+        .unwrap();
+    let ids: Vec<&str> = albums.iter().map(|a| a.id.as_str()).collect();
+    assert_eq!(ids, [album_ids[0].as_str(), album_ids[1].as_str()]);
+    assert_eq!(albums[0].play_count, Some(2));
+    let albums = storage
+        .get_album_list2(&alice, "recent", None, None, None, None, None, None)
+        .await
+        .unwrap();
+    let ids: Vec<&str> = albums.iter().map(|a| a.id.as_str()).collect();
+    assert_eq!(ids, [album_ids[1].as_str(), album_ids[0].as_str()]);
+    let albums = storage
+        .get_album_list2(&bob, "recent", None, None, None, None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(albums.len(), 1);
+    assert_eq!(albums[0].id, album_ids[1]);
+
+    // 曲目的播放次数同样按用户区分
+    let song = storage.get_song(&alice, &songs[0]).await.unwrap().unwrap();
+    assert_eq!(song.play_count, Some(2));
+    let song = storage.get_song(&bob, &songs[0]).await.unwrap().unwrap();
+    assert_eq!(song.play_count, Some(0));
+
+    assert!(storage
+        .scrobble(&alice, "missing", None, false, None)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_database_storage_bookmarks_and_play_queue_are_per_user() {
+    let storage = create_storage().await;
+    let alice = create_user(&storage, "alice").await;
+    let bob = create_user(&storage, "bob").await;
+    let first = create_track(&storage, "first").await;
+    let second = create_track(&storage, "second").await;
+
+    storage
+        .create_bookmark(&alice, &first, 42_000, Some("chapter 2"))
+        .await
+        .expect("Failed to create bookmark");
+    storage
+        .create_bookmark(&alice, &first, 43_000, None)
+        .await
+        .expect("Failed to update bookmark");
+    storage
+        .save_play_queue(&alice, &[&first, &second, &first], Some(2), Some(1_500), "Feishin")
+        .await
+        .expect("Failed to save play queue");
+
+    let bookmarks = storage.get_bookmarks(&alice).await.unwrap();
+    assert_eq!(bookmarks.len(), 1);
+    assert_eq!(bookmarks[0].position, 43_000);
+    assert_eq!(bookmarks[0].entry.id, first);
+    assert!(storage.get_bookmarks(&bob).await.unwrap().is_empty());
+
+    let queue = storage.get_play_queue(&alice).await.unwrap().unwrap();
+    assert_eq!(queue.entries.len(), 3);
+    assert_eq!(queue.current.as_deref(), Some(first.as_str()));
+    assert_eq!(queue.current_index, Some(2));
+    assert_eq!(queue.position, 1_500);
+    assert_eq!(queue.username, "alice");
+    assert_eq!(queue.changed_by, "Feishin");
+    assert!(storage.get_play_queue(&bob).await.unwrap().is_none());
+
+    // 书签只能指向歌曲
+    let err = storage.create_bookmark(&alice, "missing", 0, None).await.unwrap_err();
+    assert!(matches!(err, reverie_storage::error::StorageError::NotFound(_)));
+
+    storage
+        .delete_bookmark(&alice, &first)
+        .await
+        .expect("Failed to delete bookmark");
+    assert!(storage.get_bookmarks(&alice).await.unwrap().is_empty());
+
+    storage
+        .save_play_queue(&alice, &[], None, None, "Feishin")
+        .await
+        .expect("Failed to clear play queue");
+    assert!(storage.get_play_queue(&alice).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_database_storage_shares() {
+    use reverie_storage::error::StorageError;
+
+    let storage = create_storage().await;
+    let alice = create_user(&storage, "alice").await;
+    let bob = create_user(&storage, "bob").await;
+    let first = create_track(&storage, "first").await;
+    let second = create_track(&storage, "second").await;
+    let third = create_track(&storage, "third").await;
+    let playlist = storage
+        .create_playlist(&alice, Some("Mix"), None, &[&second, &third])
+        .await
+        .unwrap();
+
+    let share = storage
+        .create_share(&alice, &[&first, &playlist.id], Some("For Bob"), None)
+        .await
+        .expect("Failed to create share");
+    assert_eq!(share.id.len(), 32);
+    assert_eq!(share.url, format!("/share/{}", share.id));
+    assert_eq!(share.username, "alice");
+    assert_eq!(share.description.as_deref(), Some("For Bob"));
+    let entries: Vec<&str> = share.entries.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(entries, [first.as_str(), second.as_str(), third.as_str()]);
+
+    let err = storage.create_share(&alice, &["missing"], None, None).await.unwrap_err();
+    assert!(matches!(err, StorageError::NotFound(_)));
+
+    assert_eq!(storage.get_shares(&alice).await.unwrap().len(), 1);
+    assert!(storage.get_shares(&bob).await.unwrap().is_empty());
+
+    // 访问公开链接
+    storage.record_share_visit(&share.id).await.unwrap();
+    let public = storage.get_public_share(&share.id).await.unwrap().unwrap();
+    assert_eq!(public.visit_count, 1);
+    assert!(public.last_visited.is_some());
+
+    // 只有创建者可以修改
+    let err = storage
+        .update_share(&bob, &share.id, Some("Mine"), None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, StorageError::PermissionDenied(_)));
+    let expired = Utc::now().timestamp_millis() - 1_000;
+    storage
+        .update_share(&alice, &share.id, None, Some(expired))
+        .await
+        .unwrap();
+    assert!(storage.get_public_share(&share.id).await.unwrap().is_none());
+    let shares = storage.get_shares(&alice).await.unwrap();
+    assert_eq!(shares[0].description.as_deref(), Some("For Bob"));
+    assert!(shares[0].expires.is_some());
+
+    storage.delete_share(&alice, &share.id).await.unwrap();
+    assert!(storage.get_shares(&alice).await.unwrap().is_empty());
+    let err = storage.delete_share(&alice, &share.id).await.unwrap_err();
+    assert!(matches!(err, StorageError::NotFound(_)));
+
+    // 不能分享他人的私有播放列表，公开后可以分享
+    let err = storage
+        .create_share(&bob, &[&playlist.id], None, None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, StorageError::PermissionDenied(_)));
+    storage
+        .update_playlist(&alice, &playlist.id, None, None, Some(true), &[], &[])
+        .await
+        .unwrap();
+    storage.create_share(&bob, &[&playlist.id], None, None).await.unwrap();
+
+    // 不能分享无权访问的音乐文件夹中的歌曲
+    let other = storage
+        .add_music_folder("Other", "/other", None)
+        .await
+        .unwrap();
+    let carol = create_user(&storage, "carol").await.with_music_folders(MusicFolderAccess::Only(vec![other]));
+    let err = storage
+        .create_share(&carol, &[&first], None, None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, StorageError::NotFound(_)));
+
+    // 创建者失去访问权限后，分享不再公开这些歌曲
+    let share = storage.create_share(&alice, &[&first], None, None).await.unwrap();
+    assert_eq!(share.entries.len(), 1);
+    storage
+        .update_user(
+            "alice", None, None, None, None, None, None, None, None, None, None, None, None, None,
+            None, Some(&MusicFolderAccess::Only(vec![other])), None,
+        )
+        .await
+        .unwrap();
+    let public = storage.get_public_share(&share.id).await.unwrap().unwrap();
+    assert!(public.entries.is_empty());
+
+    // 删除用户时一并删除其分享
+    storage.create_share(&bob, &[&first], None, None).await.unwrap();
+    SubsonicStorage::delete_user(&storage, "bob").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_database_storage_internet_radio_stations() {
+    use reverie_storage::error::StorageError;
+
+    let storage = create_storage().await;
+    storage
+        .create_internet_radio_station("http://b.example/live", "B Radio", None)
+        .await
+        .expect("Failed to create station");
+    storage
+        .create_internet_radio_station("http://a.example/a.pls", "A", Some("http://a.example"))
+        .await
+        .unwrap();
+
+    let stations = storage.get_internet_radio_stations().await.unwrap();
+    let names: Vec<&str> = stations.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["A", "B Radio"]);
+    assert_eq!(stations[0].homepage_url.as_deref(), Some("http://a.example"));
+
+    let id = stations[1].id.clone();
+    storage
+        .update_internet_radio_station(&id, "http://b.example/new", "B", None)
+        .await
+        .unwrap();
+    let station = storage.get_internet_radio_station(&id).await.unwrap().unwrap();
+    assert_eq!(station.stream_url, "http://b.example/new");
+    assert_eq!(station.name, "B");
+
+    storage.delete_internet_radio_station(&id).await.unwrap();
+    assert!(storage.get_internet_radio_station(&id).await.unwrap().is_none());
+    assert_eq!(storage.get_internet_radio_stations().await.unwrap().len(), 1);
+
+    let err = storage.delete_internet_radio_station(&id).await.unwrap_err();
+    assert!(matches!(err, StorageError::NotFound(_)));
+    let err = storage
+        .update_internet_radio_station("999", "http://x", "X", None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, StorageError::NotFound(_)));
+    assert!(storage.get_internet_radio_station("not-a-number").await.unwrap().is_none());
+}
+
+/// 生成指定时长、8 kHz、单声道 16 位 PCM 的 WAV 文件
+fn wav_fixture(seconds: u32) -> Vec<u8> {
+    let sample_rate: u32 = 8000;
+    let data_len: u32 = sample_rate * 2 * seconds;
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.resize(wav.len() + data_len as usize, 0);
+    wav
+}
+
+async fn track_id_for(storage: &DatabaseStorage, path: &str) -> String {
+    sqlx::query_scalar("SELECT id FROM tracks WHERE file_path = ?")
+        .bind(path)
+        .fetch_one(storage.pool())
+        .await
+        .expect("Track not found")
+}
+
+#[tokio::test]
+async fn test_database_storage_incremental_scan() {
+    let dir = tempfile::tempdir().unwrap();
+    let music = dir.path().join("music");
+    std::fs::create_dir_all(&music).unwrap();
+    std::fs::write(music.join("a.wav"), wav_fixture(1)).unwrap();
+    std::fs::write(music.join("b.wav"), wav_fixture(1)).unwrap();
+
+    let storage = DatabaseStorage::new(DatabaseConfig {
+        database_url: ":memory:".to_string(),
+        max_connections: 1,
+        vfs_config: VfsConfig::local(dir.path().to_string_lossy()),
+        password_encryption_key: None,
+        cover_vfs_config: None,
+    })
+    .await
+    .expect("Failed to create database storage");
+    storage.initialize().await.unwrap();
+
+    let stats = storage.perform_scan(1, "music/", false).await.unwrap();
+    assert_eq!(stats.scanned_files, 2);
+    assert_eq!(stats.new_tracks, 2);
+    assert_eq!(stats.total_tracks, 2);
+    let a_id = track_id_for(&storage, "music/a.wav").await;
+    let b_id = track_id_for(&storage, "music/b.wav").await;
+
+    // 文件未变化时不重新解析
+    let stats = storage.perform_scan(1, "music/", false).await.unwrap();
+    assert_eq!(stats.scanned_files, 2);
+    assert_eq!(stats.new_tracks, 0);
+    assert_eq!(stats.updated_tracks, 0);
+    assert_eq!(stats.deleted_tracks, 0);
+
+    // 修改一个文件并删除另一个
+    std::fs::write(music.join("b.wav"), wav_fixture(2)).unwrap();
+    std::fs::remove_file(music.join("a.wav")).unwrap();
+    let stats = storage.perform_scan(1, "music/", false).await.unwrap();
+    assert_eq!(stats.updated_tracks, 1);
+    assert_eq!(stats.deleted_tracks, 1);
+    assert_eq!(stats.total_tracks, 1);
+
+    let b = storage.get_song(&anonymous(), &b_id).await.unwrap().unwrap();
+    assert_eq!(track_id_for(&storage, "music/b.wav").await, b_id);
+    assert_eq!(b.duration, 2.0);
+    assert!(!b.missing);
+    assert!(storage.get_song(&anonymous(), &a_id).await.unwrap().unwrap().missing);
+
+    // 完整扫描重新解析所有文件
+    let stats = storage.perform_scan(1, "music/", true).await.unwrap();
+    assert_eq!(stats.updated_tracks, 1);
+    assert_eq!(stats.new_tracks, 0);
+    assert_eq!(storage.get_scan_status().await.unwrap().scan_type.as_deref(), Some("full"));
+
+    // 文件重新出现后取消缺失标记
+    std::fs::write(music.join("a.wav"), wav_fixture(1)).unwrap();
+    let stats = storage.perform_scan(1, "music/", false).await.unwrap();
+    assert_eq!(stats.total_tracks, 2);
+    assert_eq!(stats.deleted_tracks, 0);
+    assert!(!storage.get_song(&anonymous(), &a_id).await.unwrap().unwrap().missing);
+}
+
+#[tokio::test]
+async fn test_database_storage_hides_missing_tracks() {
+    let dir = tempfile::tempdir().unwrap();
+    let music = dir.path().join("music");
+    std::fs::create_dir_all(&music).unwrap();
+    std::fs::write(music.join("a.wav"), wav_fixture(1)).unwrap();
+    std::fs::write(music.join("b.wav"), wav_fixture(1)).unwrap();
+
+    let storage = DatabaseStorage::new(DatabaseConfig {
+        database_url: ":memory:".to_string(),
+        max_connections: 1,
+        vfs_config: VfsConfig::local(dir.path().to_string_lossy()),
+        password_encryption_key: None,
+        cover_vfs_config: None,
+    })
+    .await
+    .expect("Failed to create database storage");
+    storage.initialize().await.unwrap();
+    storage.perform_scan(1, "music/", false).await.unwrap();
+    let b_id = track_id_for(&storage, "music/b.wav").await;
+
+    // 测试文件没有标签，手动归入同一专辑
+    let now = Utc::now();
+    let album = Album {
+        id: Uuid::new_v4(),
+        name: "Album".to_string(),
+        artist_id: None,
+        year: None,
+        genre: None,
+        cover_art_path: None,
+        created_at: now,
+        updated_at: now,
+    };
+    reverie_storage::AlbumStorage::save_album(&storage, &album)
+        .await
+        .unwrap();
+    let album_id = album.id.to_string();
+    sqlx::query("UPDATE tracks SET album_id = ?")
+        .bind(&album_id)
+        .execute(storage.pool())
+        .await
+        .unwrap();
+
+    std::fs::remove_file(music.join("a.wav")).unwrap();
+    let stats = storage.perform_scan(1, "music/", false).await.unwrap();
+    assert_eq!(stats.deleted_tracks, 1);
+
+    let directory = storage
+        .get_music_directory(&anonymous(), &album_id)
+        .await
+        .unwrap()
+        .unwrap();
+    let songs: Vec<&str> = directory.children.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(songs, [b_id.as_str()]);
+    let album = storage.get_album(&anonymous(), &album_id).await.unwrap().unwrap();
+    assert_eq!(album.song_count, 1);
+
+    let found = storage
+        .search3(&anonymous(), "", None, None, None, None, None, None, None)
+        .await
+        .unwrap();
+    let songs: Vec<&str> = found.songs.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(songs, [b_id.as_str()]);
+    let random = storage
+        .get_random_songs(&anonymous(), Some(10), None, None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(random.len(), 1);
+}
+
+#[tokio::test]
+async fn test_database_storage_rescan_migrates_legacy_ids() {
+    use lofty::config::WriteOptions;
+    use lofty::prelude::*;
+    use lofty::tag::{Tag, TagType};
+    use reverie_storage::scanner::ids;
+
+    let dir = tempfile::tempdir().unwrap();
+    let music = dir.path().join("music");
+    std::fs::create_dir_all(&music).unwrap();
+    let file = music.join("a.wav");
+    std::fs::write(&file, wav_fixture(1)).unwrap();
+    let mut tag = Tag::new(TagType::RiffInfo);
+    tag.set_artist("Legacy Artist".to_string());
+    tag.set_album("Legacy Album".to_string());
+    tag.save_to_path(&file, WriteOptions::default()).unwrap();
+
+    let storage = DatabaseStorage::new(DatabaseConfig {
+        database_url: ":memory:".to_string(),
+        max_connections: 1,
+        vfs_config: VfsConfig::local(dir.path().to_string_lossy()),
+        password_encryption_key: None,
+        cover_vfs_config: None,
+    })
+    .await
+    .expect("Failed to create database storage");
+    storage.initialize().await.unwrap();
+    let alice = create_user(&storage, "alice").await;
+
+    // 模拟旧版本扫描器写入的随机 ID
+    let old_artist = Uuid::new_v4().to_string();
+    let old_album = Uuid::new_v4().to_string();
+    let old_track = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+    sqlx::query("INSERT INTO artists (id, name, created_at, updated_at) VALUES (?, ?, ?, ?)")
+        .bind(&old_artist)
+        .bind("Legacy Artist")
+        .bind(&now)
+        .bind(&now)
+        .execute(storage.pool())
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO albums (id, name, artist_id, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(&old_album)
+    .bind("Legacy Album")
+    .bind(&old_artist)
+    .bind(&now)
+    .bind(&now)
+    .execute(storage.pool())
+    .await
+    .unwrap();
+    sqlx::query(
+        r#"INSERT INTO tracks (id, title, album_id, artist_id, duration, file_path, file_size,
+               bitrate, format, created_at, updated_at)
+           VALUES (?, 'a', ?, ?, 1, 'music/a.wav', 0, 128, 'wav', ?, ?)"#,
+    )
+    .bind(&old_track)
+    .bind(&old_album)
+    .bind(&old_artist)
+    .bind(&now)
+    .bind(&now)
+    .execute(storage.pool())
+    .await
+    .unwrap();
+
+    storage
+        .star(&alice, &[&old_track], &[&old_album], &[&old_artist])
+        .await
+        .unwrap();
+    let playlist_id = Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"INSERT INTO playlists (id, name, user_id, created_at, updated_at)
+           SELECT ?, 'Mix', id, ?, ? FROM users WHERE username = 'alice'"#,
+    )
+    .bind(&playlist_id)
+    .bind(&now)
+    .bind(&now)
+    .execute(storage.pool())
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO playlist_tracks (playlist_id, track_id, position, added_at) VALUES (?, ?, 0, ?)",
+    )
+    .bind(&playlist_id)
+    .bind(&old_track)
+    .bind(&now)
+    .execute(storage.pool())
+    .await
+    .unwrap();
+    storage
+        .save_play_queue(&alice, &[&old_track], Some(0), None, "test")
+        .await
+        .unwrap();
+
+    let stats = storage.perform_scan(1, "music/", false).await.unwrap();
+    assert_eq!(stats.new_tracks, 0);
+    assert_eq!(stats.updated_tracks, 1);
+    assert_eq!(stats.total_artists, 1);
+    assert_eq!(stats.total_albums, 1);
+
+    let track_id = ids::track_id(1, "music/a.wav");
+    let album_id = ids::album_id(Some("Legacy Artist"), "Legacy Album", None);
+    let artist_id = ids::artist_id("Legacy Artist", None);
+    assert!(storage.get_song(&anonymous(), &old_track).await.unwrap().is_none());
+    let song = storage.get_song(&anonymous(), &track_id).await.unwrap().unwrap();
+    assert_eq!(song.parent.as_deref(), Some(album_id.as_str()));
+    let album = storage.get_album(&anonymous(), &album_id).await.unwrap().unwrap();
+    assert_eq!(album.artist_id.as_deref(), Some(artist_id.as_str()));
+    assert!(storage.get_album(&anonymous(), &old_album).await.unwrap().is_none());
+
+    let starred = storage.get_starred(&alice, None).await.unwrap();
+    assert_eq!(starred.songs[0].id, track_id);
+    assert_eq!(starred.albums[0].id, album_id);
+    assert_eq!(starred.artists[0].id, artist_id);
+
+    let playlist = storage.get_playlist(&alice, &playlist_id).await.unwrap().unwrap();
+    assert_eq!(playlist.entries[0].id, track_id);
+
+    let queue = storage.get_play_queue(&alice).await.unwrap().unwrap();
+    assert_eq!(queue.entries[0].id, track_id);
+    assert_eq!(queue.current.as_deref(), Some(track_id.as_str()));
+
+    // 再次扫描时 ID 保持不变
+    storage.perform_scan(1, "music/", true).await.unwrap();
+    assert!(storage.get_song(&anonymous(), &track_id).await.unwrap().is_some());
+    assert_eq!(storage.get_starred(&alice, None).await.unwrap().songs.len(), 1);
+}
+
+#[tokio::test]
+async fn test_database_storage_watcher_scans_new_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let album = dir.path().join("music/Artist/Album");
+    std::fs::create_dir_all(&album).unwrap();
+
+    let storage = DatabaseStorage::new(DatabaseConfig {
+        database_url: ":memory:".to_string(),
+        max_connections: 1,
+        vfs_config: VfsConfig::local(dir.path().to_string_lossy()),
+        password_encryption_key: None,
+        cover_vfs_config: None,
+    })
+    .await
+    .expect("Failed to create database storage");
+    storage.initialize().await.unwrap();
+
+    storage
+        .watch_library(WatcherConfig {
+            debounce: Duration::from_millis(200),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    std::fs::write(album.join("01.wav"), wav_fixture(1)).unwrap();
+    std::fs::write(album.join("02.wav"), wav_fixture(1)).unwrap();
+
+    let wait_for_count = |expected: i64| {
+        let storage = storage.clone();
+        async move {
+            let mut count = 0;
+            for _ in 0..50 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                count = storage.get_scan_status().await.unwrap().count;
+                if count == expected {
+                    break;
+                }
+            }
+            count
+        }
+    };
+    assert_eq!(wait_for_count(2).await, 2);
+    assert!(storage
+        .get_song(
+            &anonymous(),
+            &reverie_storage::scanner::ids::track_id(1, "music/Artist/Album/01.wav")
+        )
+        .await
+        .unwrap()
+        .is_some());
+
+    // 之后添加的文件夹同样被监视
+    let other_root = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(other_root.path().join("audio")).unwrap();
+    let vfs = VfsConfig::local(other_root.path().to_string_lossy());
+    storage
+        .create_music_folder("Other", "audio", Some(&vfs), false)
+        .await
+        .unwrap();
+    std::fs::write(other_root.path().join("audio/03.wav"), wav_fixture(2)).unwrap();
+    assert_eq!(wait_for_count(3).await, 3);
+
+    storage.unwatch_library();
+}
+
+#[tokio::test]
+async fn test_database_storage_scans_multiple_libraries() {
+    let default_root = tempfile::tempdir().unwrap();
+    let other_root = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(default_root.path().join("music")).unwrap();
+    std::fs::create_dir_all(other_root.path().join("audio")).unwrap();
+    std::fs::write(default_root.path().join("music/song.wav"), wav_fixture(1)).unwrap();
+    let other_file = wav_fixture(2);
+    std::fs::write(other_root.path().join("audio/song.wav"), &other_file).unwrap();
+
+    let storage = DatabaseStorage::new(DatabaseConfig {
+        database_url: ":memory:".to_string(),
+        max_connections: 1,
+        vfs_config: VfsConfig::local(default_root.path().to_string_lossy()),
+        password_encryption_key: None,
+        cover_vfs_config: None,
+    })
+    .await
+    .expect("Failed to create database storage");
+    storage.initialize().await.unwrap();
+
+    // 第二个音乐文件夹使用独立的 VFS
+    let other_config = VfsConfig::local(other_root.path().to_string_lossy());
+    let other = storage
+        .add_music_folder("Other", "audio/", Some(&other_config))
+        .await
+        .unwrap();
+    let folders = storage.library_folders().await.unwrap();
+    assert_eq!(folders.len(), 2);
+    assert_eq!(folders[1].vfs_config.as_ref(), Some(&other_config));
+
+    storage.perform_scan(1, "music/", false).await.unwrap();
+    let stats = storage.perform_scan(other, "audio/", false).await.unwrap();
+    assert_eq!(stats.total_tracks, 2);
+
+    // 两个文件夹中的同名文件是不同的曲目
+    let all = storage
+        .get_random_songs(&anonymous(), Some(10), None, None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(all.len(), 2);
+    let only_other = storage
+        .get_random_songs(&anonymous(), Some(10), None, None, None, Some(&[other]))
+        .await
+        .unwrap();
+    assert_eq!(only_other.len(), 1);
+    assert_eq!(only_other[0].library_id, other);
+    assert_eq!(only_other[0].duration, 2.0);
+
+    let found = storage
+        .search3(&anonymous(), "song", None, None, None, None, None, None, Some(&[1]))
+        .await
+        .unwrap();
+    assert_eq!(found.songs.len(), 1);
+    assert_eq!(found.songs[0].library_id, 1);
+    let found = storage
+        .search3(&anonymous(), "song", None, None, None, None, None, None, Some(&[]))
+        .await
+        .unwrap();
+    assert!(found.songs.is_empty());
+
+    // 流媒体路径解析到曲目所在文件夹的 VFS
+    let path = storage
+        .get_stream_path(&only_other[0].id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(storage.read_file(&path).await.unwrap(), other_file);
+
+    // 重新扫描一个文件夹不影响另一个文件夹的曲目
+    std::fs::remove_file(other_root.path().join("audio/song.wav")).unwrap();
+    let stats = storage.perform_scan(other, "audio/", false).await.unwrap();
+    assert_eq!(stats.deleted_tracks, 1);
+    assert_eq!(stats.total_tracks, 1);
+}
+
+#[tokio::test]
+async fn test_database_storage_keeps_covers_out_of_music_folders() {
+    let music_root = tempfile::tempdir().unwrap();
+    let cover_root = tempfile::tempdir().unwrap();
+    let music = VfsConfig::local(music_root.path().to_string_lossy());
+    let config = DatabaseConfig::new(":memory:", music)
+        .with_cover_vfs_config(VfsConfig::local(cover_root.path().to_string_lossy()));
+    let storage = DatabaseStorage::new(config).await.unwrap();
+
+    storage.write_file("covers://album.jpg", b"jpeg").await.unwrap();
+    assert_eq!(storage.read_file("covers://album.jpg").await.unwrap(), b"jpeg");
+    assert!(cover_root.path().join("album.jpg").exists());
+    assert!(!music_root.path().join("album.jpg").exists());
+}
+
+#[tokio::test]
+async fn test_database_storage_persists_user_music_folders() {
+    let storage = create_storage().await;
+    let other = storage
+        .add_music_folder("Other", "/other", None)
+        .await
+        .unwrap();
+
+    storage
+        .create_user(
+            "bob", "secret", None, false, false, true, false, false, false, false, false, false,
+            false, false, false, &MusicFolderAccess::Only(vec![other]),
+        )
+        .await
+        .unwrap();
+    let bob = SubsonicStorage::get_user(&storage, "bob").await.unwrap().unwrap();
+    assert_eq!(bob.folders, MusicFolderAccess::Only(vec![other]));
+
+    storage
+        .update_user(
+            "bob", None, None, None, None, None, None, None, None, None, None, None, None, None,
+            None, Some(&MusicFolderAccess::Only(vec![1, other])), None,
+        )
+        .await
+        .unwrap();
+    let users = storage.get_users().await.unwrap();
+    let bob = users.iter().find(|u| u.username == "bob").unwrap();
+    assert_eq!(bob.folders, MusicFolderAccess::Only(vec![1, other]));
+
+    SubsonicStorage::delete_user(&storage, "bob").await.unwrap();
+    assert!(SubsonicStorage::get_user(&storage, "bob")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_database_storage_persists_user_roles() {
+    use reverie_storage::error::StorageError;
+
+    let storage = create_storage().await;
+    // 参数依次为 admin、settings、stream、jukebox、download、upload、playlist、coverArt、
+    // comment、podcast、share、videoConversion
+    storage
+        .create_user(
+            "bob", "secret", Some("bob@example.com"), false, true, true, false, false, false,
+            true, false, false, false, true, false, &MusicFolderAccess::All,
+        )
+        .await
+        .unwrap();
+    let bob = SubsonicStorage::get_user(&storage, "bob").await.unwrap().unwrap();
+    assert!(!bob.admin_role);
+    assert!(bob.stream_role);
+    assert!(!bob.download_role);
+    assert!(bob.playlist_role);
+    assert!(bob.share_role);
+    assert!(!bob.jukebox_role);
+    assert_eq!(bob.max_bit_rate, None);
+
+    // 只修改传入的字段，其他字段保持不变
+    storage
+        .update_user(
+            "bob", Some("sesame"), None, None, None, None, None, Some(true), None,
+            Some(false), None, None, None, None, None, None, Some(128),
+        )
+        .await
+        .unwrap();
+    let bob = SubsonicStorage::get_user(&storage, "bob").await.unwrap().unwrap();
+    assert_eq!(bob.email.as_deref(), Some("bob@example.com"));
+    assert!(bob.download_role);
+    assert!(!bob.playlist_role);
+    assert!(bob.share_role);
+    assert_eq!(bob.max_bit_rate, Some(128));
+    assert!(storage.verify_user_password("bob", "sesame").await.unwrap());
+
+    // 码率为 0 表示取消限制
+    storage
+        .update_user(
+            "bob", None, None, Some(true), None, None, None, None, None, None, None, None, None,
+            None, None, None, Some(0),
+        )
+        .await
+        .unwrap();
+    let users = storage.get_users().await.unwrap();
+    let bob = users.iter().find(|u| u.username == "bob").unwrap();
+    assert!(bob.admin_role);
+    assert!(bob.download_role);
+    assert_eq!(bob.max_bit_rate, None);
+
+    let err = storage
+        .update_user(
+            "nobody", None, Some("x@example.com"), None, None, None, None, None, None, None,
+            None, None, None, None, None, None, None,
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, StorageError::NotFound(_)));
+
+    // 删除用户时一并删除其播放列表、书签和评分
+    let bob = UserContext::new("bob", false);
+    let song = create_track(&storage, "song").await;
+    storage
+        .create_playlist(&bob, Some("Mine"), None, &[&song])
+        .await
+        .unwrap();
+    storage.create_bookmark(&bob, &song, 1000, None).await.unwrap();
+    storage.set_rating(&bob, &song, 4).await.unwrap();
+    SubsonicStorage::delete_user(&storage, "bob").await.unwrap();
+    assert!(SubsonicStorage::get_user(&storage, "bob").await.unwrap().is_none());
+    let err = SubsonicStorage::delete_user(&storage, "bob").await.unwrap_err();
+    assert!(matches!(err, StorageError::NotFound(_)));
+}
+
+#[tokio::test]
+async fn test_database_storage_manages_music_folders() {
+    use lofty::config::WriteOptions;
+    use lofty::prelude::*;
+    use lofty::tag::{Tag, TagType};
+    use reverie_storage::scanner::ids;
+    use std::collections::HashMap;
+
+    let write_tagged = |path: std::path::PathBuf, artist: &str, album: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, wav_fixture(1)).unwrap();
+        let mut tag = Tag::new(TagType::RiffInfo);
+        tag.set_artist(artist.to_string());
+        tag.set_album(album.to_string());
+        tag.save_to_path(&path, WriteOptions::default()).unwrap();
+    };
+    let default_root = tempfile::tempdir().unwrap();
+    let other_root = tempfile::tempdir().unwrap();
+    write_tagged(default_root.path().join("music/shared.wav"), "Shared", "Both");
+    write_tagged(other_root.path().join("audio/shared.wav"), "Shared", "Both");
+    write_tagged(other_root.path().join("audio/solo.wav"), "Solo", "Only");
+
+    let storage = DatabaseStorage::new(DatabaseConfig {
+        database_url: ":memory:".to_string(),
+        max_connections: 1,
+        vfs_config: VfsConfig::local(default_root.path().to_string_lossy()),
+        password_encryption_key: None,
+        cover_vfs_config: None,
+    })
+    .await
+    .expect("Failed to create database storage");
+    storage.initialize().await.unwrap();
+
+    // 无效的 VFS 配置被拒绝
+    let invalid = VfsConfig {
+        scheme: "nonexistent".to_string(),
+        options: HashMap::new(),
+    };
+    assert!(storage
+        .create_music_folder("Broken", "broken/", Some(&invalid), false)
+        .await
+        .is_err());
+
+    // 添加文件夹时只在后台扫描该文件夹
+    let other_config = VfsConfig::local(other_root.path().to_string_lossy());
+    let other = storage
+        .create_music_folder("Other", "audio/", Some(&other_config), true)
+        .await
+        .unwrap();
+    assert_eq!(other.vfs_config.as_ref(), Some(&other_config));
+    let mut scanned = Vec::new();
+    for _ in 0..50 {
+        scanned = storage
+            .get_random_songs(&anonymous(), Some(10), None, None, None, None)
+            .await
+            .unwrap();
+        if scanned.len() == 2 && !storage.get_scan_status().await.unwrap().scanning {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(scanned.len(), 2);
+    assert!(scanned.iter().all(|song| song.library_id == other.id));
+    storage.perform_scan(1, "music/", false).await.unwrap();
+
+    storage
+        .create_user(
+            "bob", "secret", None, false, false, true, false, false, false, false, false, false,
+            false, false, false, &MusicFolderAccess::Only(vec![1, other.id]),
+        )
+        .await
+        .unwrap();
+    storage
+        .create_user(
+            "carol", "secret", None, false, false, true, false, false, false, false, false, false,
+            false, false, false, &MusicFolderAccess::Only(vec![other.id]),
+        )
+        .await
+        .unwrap();
+    let bob = UserContext::new("bob", false);
+    let solo_track = track_id_for(&storage, "audio/solo.wav").await;
+    let solo_album = ids::album_id(Some("Solo"), "Only", None);
+    let solo_artist = ids::artist_id("Solo", None);
+    let shared_album = ids::album_id(Some("Shared"), "Both", None);
+    storage
+        .star(&bob, &[&solo_track], &[&solo_album], &[&solo_artist])
+        .await
+        .unwrap();
+    let shared_track = track_id_for(&storage, "music/shared.wav").await;
+    storage
+        .save_play_queue(&bob, &[&solo_track, &shared_track], Some(1), None, "Feishin")
+        .await
+        .unwrap();
+
+    // 播放列表中不会加入或返回用户无权访问的音乐文件夹中的歌曲
+    let carol = UserContext::new("carol", false).with_music_folders(MusicFolderAccess::Only(vec![other.id]));
+    let playlist = storage
+        .create_playlist(&carol, Some("Solo"), None, &[&solo_track, &shared_track])
+        .await
+        .unwrap();
+    assert_eq!(playlist.entries.len(), 1);
+    assert_eq!(playlist.entries[0].id, solo_track);
+    let bob_ctx = UserContext::new("bob", false).with_music_folders(MusicFolderAccess::Only(vec![1, other.id]));
+    let playlist = storage
+        .create_playlist(&bob_ctx, Some("Both"), None, &[&shared_track, &solo_track])
+        .await
+        .unwrap();
+    assert_eq!(playlist.entries.len(), 2);
+    storage
+        .update_playlist(&bob_ctx, &playlist.id, None, None, Some(true), &[], &[])
+        .await
+        .unwrap();
+    let visible = storage.get_playlist(&carol, &playlist.id).await.unwrap().unwrap();
+    assert_eq!(visible.entries.len(), 1);
+    assert_eq!(visible.entries[0].id, solo_track);
+    let listed = storage.get_playlists(&carol, None).await.unwrap();
+    let listed = listed.iter().find(|p| p.id == playlist.id).unwrap();
+    assert_eq!(listed.song_count, 1);
+
+    // 流派只统计用户可以访问的音乐文件夹中的曲目
+    sqlx::query("UPDATE tracks SET genre = 'Rock'")
+        .execute(storage.pool())
+        .await
+        .unwrap();
+    let genres = storage.get_genres(None).await.unwrap();
+    assert_eq!((genres[0].song_count, genres[0].album_count), (3, 2));
+    let genres = storage.get_genres(Some(&[1])).await.unwrap();
+    assert_eq!(genres.len(), 1);
+    assert_eq!((genres[0].song_count, genres[0].album_count), (1, 1));
+    let genres = storage.get_genres(Some(&[other.id])).await.unwrap();
+    assert_eq!((genres[0].song_count, genres[0].album_count), (2, 2));
+
+    // 无权访问的音乐文件夹中的专辑和歌曲没有封面
+    sqlx::query("UPDATE albums SET cover_art_path = 'covers://album.jpg'")
+        .execute(storage.pool())
+        .await
+        .unwrap();
+    sqlx::query("UPDATE tracks SET cover_art_path = 'covers://track.jpg'")
+        .execute(storage.pool())
+        .await
+        .unwrap();
+    assert!(storage.get_cover_art_path(&carol, &solo_album).await.unwrap().is_some());
+    assert!(storage.get_cover_art_path(&carol, &shared_track).await.unwrap().is_none());
+    assert!(storage.get_cover_art_path(&bob_ctx, &shared_track).await.unwrap().is_some());
+
+    // 删除文件夹时级联删除其曲目和不再包含曲目的专辑、艺术家
+    storage.delete_music_folder(other.id).await.unwrap();
+    let folders = storage.get_music_folder_configs().await.unwrap();
+    assert_eq!(folders.len(), 1);
+    let songs = storage
+        .get_random_songs(&anonymous(), Some(10), None, None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(songs.len(), 1);
+    assert_eq!(songs[0].library_id, 1);
+    assert!(storage.get_album(&anonymous(), &solo_album).await.unwrap().is_none());
+    assert!(storage.get_artist(&anonymous(), &solo_artist).await.unwrap().is_none());
+    assert!(storage.get_album(&anonymous(), &shared_album).await.unwrap().is_some());
+    let annotations: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM annotations")
+        .fetch_one(storage.pool())
+        .await
+        .unwrap();
+    assert_eq!(annotations, 0);
+    // 播放队列中不再保存已删除曲目的 ID
+    let (track_ids, current_index): (String, Option<i64>) =
+        sqlx::query_as("SELECT track_ids, current_index FROM play_queue")
+            .fetch_one(storage.pool())
+            .await
+            .unwrap();
+    assert_eq!(track_ids, serde_json::to_string(&[&shared_track]).unwrap());
+    assert_eq!(current_index, Some(0));
+    let bob_user = SubsonicStorage::get_user(&storage, "bob").await.unwrap().unwrap();
+    assert_eq!(bob_user.folders, MusicFolderAccess::Only(vec![1]));
+    // 只能访问被删除文件夹的用户仍受限制，不会变为可以访问所有文件夹
+    let carol_user = SubsonicStorage::get_user(&storage, "carol").await.unwrap().unwrap();
+    assert_eq!(carol_user.folders, MusicFolderAccess::Only(vec![]));
+    let carol = UserContext::new("carol", false);
+    let carol_folders = carol_user.folders.folder_ids();
+    assert!(storage
+        .get_random_songs(&carol, Some(10), None, None, None, carol_folders)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(storage.delete_music_folder(other.id).await.is_err());
+
+    // 重命名并改用另一个 VFS 后，文件从新的位置读取
+    let path = storage.get_stream_path(&songs[0].id).await.unwrap().unwrap();
+    assert!(storage.read_file(&path).await.is_ok());
+    let mut folder = folders[0].clone();
+    folder.name = "Moved".to_string();
+    folder.vfs_config = Some(other_config);
+    storage.update_music_folder(&folder).await.unwrap();
+    assert_eq!(storage.library_folder(1).await.unwrap(), Some(folder));
+    assert!(storage.read_file(&path).await.is_err());
+
+    let missing = LibraryFolder {
+        id: 99,
+        name: "Missing".to_string(),
+        path: "missing/".to_string(),
+        vfs_config: None,
+    };
+    assert!(storage.update_music_folder(&missing).await.is_err());
+}
+
+/// 执行 SQL 脚本创建数据库文件
+async fn create_fixture_db(path: &std::path::Path, sql: &str) {
+    let pool = sqlx::SqlitePool::connect(&format!("sqlite://{}?mode=rwc", path.display()))
+        .await
+        .unwrap();
+    sqlx::query(sql).execute(&pool).await.unwrap();
+    pool.close().await;
+}
+
+async fn open_fixture_db(path: &std::path::Path) -> reverie_storage::Result<DatabaseStorage> {
+    DatabaseStorage::new(DatabaseConfig::new(
+        path.to_string_lossy(),
+        VfsConfig::memory(),
+    ))
+    .await
+}
+
+#[tokio::test]
+async fn test_database_storage_upgrades_v1_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("reverie.db");
+    create_fixture_db(&path, include_str!("fixtures/schema_v1.sql")).await;
+
+    let storage = open_fixture_db(&path).await.unwrap();
+    assert_eq!(storage.schema_version().await.unwrap(), SCHEMA_VERSION);
+    for column in ["file_mtime", "missing", "library_id", "mbid", "bit_depth", "lyrics"] {
+        let (count,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM pragma_table_info('tracks') WHERE name = ?")
+                .bind(column)
+                .fetch_one(storage.pool())
+                .await
+                .unwrap();
+        assert_eq!(count, 1, "{}", column);
+    }
+
+    // 已有数据保留，新增的列使用默认值
+    let song = storage.get_song(&anonymous(), "track-1").await.unwrap().unwrap();
+    assert_eq!(song.title, "Old Song");
+    assert_eq!(song.library_id, 1);
+    assert!(!song.missing);
+    assert_eq!(song.bit_depth, None);
+
+    // 已有条目加入全文索引
+    let found = storage
+        .search3(&anonymous(), "old", None, None, None, None, None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(found.artists.len(), 1);
+    assert_eq!(found.albums.len(), 1);
+    assert_eq!(found.songs.len(), 1);
+    storage.close().await.unwrap();
+
+    // 再次打开时不重复执行迁移
+    let storage = open_fixture_db(&path).await.unwrap();
+    let (applied,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM schema_version")
+        .fetch_one(storage.pool())
+        .await
+        .unwrap();
+    assert_eq!(applied, SCHEMA_VERSION);
+}
+
+#[tokio::test]
+async fn test_database_storage_first_run_admin_must_change_password() {
+    use reverie_storage::error::StorageError;
+
+    let storage = create_storage().await;
+    // 首次启动不再使用 admin/admin
+    assert!(!storage.verify_user_password("admin", "admin").await.unwrap());
+    assert!(storage.password_change_required("admin").await.unwrap());
+    let initial = storage.get_user_password("admin").await.unwrap().unwrap();
+    assert_eq!(initial.len(), 16);
+    assert!(storage.verify_user_password("admin", &initial).await.unwrap());
+
+    storage.change_password("admin", "sesame").await.unwrap();
+    assert!(!storage.password_change_required("admin").await.unwrap());
+    assert!(storage.verify_user_password("admin", "sesame").await.unwrap());
+    assert!(!storage.verify_user_password("admin", &initial).await.unwrap());
+    assert_eq!(storage.get_user_password("admin").await.unwrap().as_deref(), Some("sesame"));
+
+    // 数据库中只有哈希和密文
+    let (hash, encrypted): (String, String) = sqlx::query_as(
+        "SELECT password_hash, subsonic_password FROM users WHERE username = 'admin'",
+    )
+    .fetch_one(storage.pool())
+    .await
+    .unwrap();
+    assert!(hash.starts_with("$argon2id$"));
+    assert!(!encrypted.contains("sesame"));
+
+    // 可以解密 Subsonic 密码时直接比较，不计算 Argon2 哈希
+    sqlx::query("UPDATE users SET password_hash = 'unused' WHERE username = 'admin'")
+        .execute(storage.pool())
+        .await
+        .unwrap();
+    assert!(storage.verify_user_password("admin", "sesame").await.unwrap());
+    assert!(!storage.verify_user_password("admin", "sesame!").await.unwrap());
+
+    let err = storage.change_password("nobody", "x").await.unwrap_err();
+    assert!(matches!(err, StorageError::NotFound(_)));
+    assert!(!storage.verify_user_password("nobody", "x").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_database_storage_writes_initial_admin_password_to_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("reverie.db");
+    let storage = open_fixture_db(&path).await.unwrap();
+    storage.initialize().await.unwrap();
+
+    // 初始密码只保存在数据库旁的文件中
+    let password_file = dir.path().join("reverie.db.admin-password");
+    let password = std::fs::read_to_string(&password_file).unwrap();
+    assert!(storage
+        .verify_user_password("admin", password.trim())
+        .await
+        .unwrap());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&password_file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[tokio::test]
+async fn test_database_storage_upgrades_plaintext_passwords() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("reverie.db");
+    create_fixture_db(&path, include_str!("fixtures/schema_v1.sql")).await;
+
+    let storage = open_fixture_db(&path).await.unwrap();
+    storage.initialize().await.unwrap();
+    assert!(storage.verify_user_password("admin", "admin").await.unwrap());
+    assert_eq!(storage.get_user_password("admin").await.unwrap().as_deref(), Some("admin"));
+    // 沿用默认密码的管理员需要修改密码
+    assert!(storage.password_change_required("admin").await.unwrap());
+    let (hash,): (String,) = sqlx::query_as("SELECT password_hash FROM users")
+        .fetch_one(storage.pool())
+        .await
+        .unwrap();
+    assert!(hash.starts_with("$argon2id$"));
+    storage.close().await.unwrap();
+
+    // 更换密钥后无法取回密码，令牌验证不可用，但明文密码仍可登录
+    let config = DatabaseConfig::new(path.to_string_lossy(), VfsConfig::memory())
+        .with_password_encryption_key("another key");
+    let storage = DatabaseStorage::new(config).await.unwrap();
+    storage.initialize().await.unwrap();
+    assert!(storage.get_user_password("admin").await.unwrap().is_none());
+    assert!(storage.verify_user_password("admin", "admin").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_database_storage_adopts_unversioned_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("reverie.db");
+    let sql = format!(
+        "{}\nDROP TABLE schema_version;",
+        include_str!("fixtures/schema_v1.sql")
+    );
+    create_fixture_db(&path, &sql).await;
+
+    let storage = open_fixture_db(&path).await.unwrap();
+    assert_eq!(storage.schema_version().await.unwrap(), SCHEMA_VERSION);
+    assert!(storage.get_song(&anonymous(), "track-1").await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_database_storage_refuses_newer_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("reverie.db");
+    let sql = format!(
+        "{}\nINSERT INTO schema_version VALUES ({}, 'future', '2100-01-01T00:00:00+00:00');",
+        include_str!("fixtures/schema_v1.sql"),
+        SCHEMA_VERSION + 1
+    );
+    create_fixture_db(&path, &sql).await;
+
+    let Err(e) = open_fixture_db(&path).await else {
+        panic!("Opened a database with a newer schema");
+    };
+    assert!(e.to_string().contains("newer"));
+}
+
+#[tokio::test]
+async fn test_database_storage_full_text_search() {
+    let storage = create_storage().await;
+    let now = Utc::now();
+    let artist = |name: &str| Artist {
+        id: Uuid::new_v4(),
+        name: name.to_string(),
+        bio: None,
+        created_at: now,
+        updated_at: now,
+    };
+    let beatles = artist("The Beatles");
+    let bjork = artist("Björk");
+    let album = |name: &str, artist: &Artist| Album {
+        id: Uuid::new_v4(),
+        name: name.to_string(),
+        artist_id: Some(artist.id),
+        year: None,
+        genre: None,
+        cover_art_path: None,
+        created_at: now,
+        updated_at: now,
+    };
+    let abbey_road = album("Abbey Road", &beatles);
+    let homogenic = album("Homogenic", &bjork);
+    for artist in [&beatles, &bjork] {
+        reverie_storage::ArtistStorage::save_artist(&storage, artist).await.unwrap();
+    }
+    for album in [&abbey_road, &homogenic] {
+        reverie_storage::AlbumStorage::save_album(&storage, album).await.unwrap();
+    }
+    let mut tracks = Vec::new();
+    for (title, album, genre) in [
+        ("Come Together", &abbey_road, "Rock"),
+        ("Something", &abbey_road, "Rock"),
+        ("Jóga", &homogenic, "Electronic"),
+    ] {
+        let track = Track {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            album_id: Some(album.id),
+            artist_id: album.artist_id,
+            duration: 180,
+            file_path: format!("/music/{}.mp3", title),
+            file_size: 5_000_000,
+            bitrate: 320,
+            format: "mp3".to_string(),
+            track_number: Some(1),
+            disc_number: Some(1),
+            year: None,
+            genre: Some(genre.to_string()),
+            created_at: now,
+            updated_at: now,
+        };
+        storage.save_track(&track).await.unwrap();
+        tracks.push(track);
+    }
+
+    let search = |query: &'static str| {
+        let storage = &storage;
+        async move {
+            storage
+                .search3(&anonymous(), query, None, None, None, None, None, None, None)
+                .await
+                .unwrap()
+        }
+    };
+    let titles = |songs: &[reverie_core::MediaFile]| {
+        songs.iter().map(|s| s.title.clone()).collect::<Vec<_>>()
+    };
+
+    // 多个词分别匹配艺术家和专辑
+    let found = search("beatles abbey").await;
+    assert!(found.artists.is_empty());
+    assert_eq!(found.albums.len(), 1);
+    assert_eq!(found.albums[0].name, "Abbey Road");
+    let mut songs = titles(&found.songs);
+    songs.sort();
+    assert_eq!(songs, vec!["Come Together", "Something"]);
+
+    // 忽略变音符号，按前缀匹配
+    let found = search("bjork").await;
+    assert_eq!(found.artists.len(), 1);
+    assert_eq!(found.artists[0].name, "Björk");
+    assert_eq!(found.albums.len(), 1);
+    assert_eq!(titles(&found.songs), vec!["Jóga"]);
+    assert_eq!(titles(&search("JOGA").await.songs), vec!["Jóga"]);
+    assert_eq!(titles(&search("come tog").await.songs), vec!["Come Together"]);
+    assert_eq!(search("electronic").await.songs.len(), 1);
+    assert!(search("beatles jóga").await.songs.is_empty());
+
+    // 标题匹配的曲目排在仅专辑名匹配的曲目之前
+    let mut something = album("Something Else", &bjork);
+    reverie_storage::AlbumStorage::save_album(&storage, &something).await.unwrap();
+    let mut track = tracks[2].clone();
+    track.album_id = Some(something.id);
+    storage.save_track(&track).await.unwrap();
+    assert_eq!(
+        titles(&search("something").await.songs),
+        vec!["Something", "Jóga"]
+    );
+
+    // 空查询返回整个媒体库
+    for query in ["", "\"\""] {
+        let found = search(query).await;
+        assert_eq!(found.artists.len(), 2);
+        assert_eq!(found.albums.len(), 3);
+        assert_eq!(found.songs.len(), 3);
+    }
+
+    // 没有可搜索内容的其他查询不返回整个媒体库
+    for query in ["*", "!!", "-"] {
+        let found = search(query).await;
+        assert!(found.artists.is_empty() && found.albums.is_empty() && found.songs.is_empty());
+    }
+
+    // 改名和删除后索引同步更新
+    let mut renamed = bjork.clone();
+    renamed.name = "Björk Gudmundsdóttir".to_string();
+    reverie_storage::ArtistStorage::save_artist(&storage, &renamed).await.unwrap();
+    something.name = "Vespertine".to_string();
+    reverie_storage::AlbumStorage::save_album(&storage, &something).await.unwrap();
+    let found = search("gudmundsdottir vespertine").await;
+    assert_eq!(found.albums.len(), 1);
+    assert_eq!(titles(&found.songs), vec!["Jóga"]);
+    storage.delete_track(tracks[0].id).await.unwrap();
+    assert!(search("together").await.songs.is_empty());
+}
+
+/// 模拟服务收到的请求：路径、Authorization 头和请求体
+type RecordedRequest = (String, Option<String>, String);
+
+/// 模拟外部 scrobble 服务，记录收到的请求
+#[derive(Clone, Default)]
+struct MockScrobbleServer {
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    failing: Arc<AtomicBool>,
+}
+
+impl MockScrobbleServer {
+    /// 在随机端口上启动，返回服务地址
+    async fn start(&self) -> String {
+        async fn record(
+            State(server): State<MockScrobbleServer>,
+            uri: Uri,
+            headers: HeaderMap,
+            body: String,
+        ) -> (StatusCode, &'static str) {
+            let authorization = headers
+                .get(AUTHORIZATION)
+                .map(|v| v.to_str().unwrap().to_string());
+            server
+                .requests
+                .lock()
+                .unwrap()
+                .push((uri.path().to_string(), authorization, body));
+            if server.failing.load(Ordering::SeqCst) {
+                (StatusCode::SERVICE_UNAVAILABLE, "{}")
+            } else {
+                (StatusCode::OK, "{}")
+            }
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().fallback(record).with_state(self.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    /// 等待收到 `count` 个请求，返回全部请求
+    async fn wait_for(&self, count: usize) -> Vec<RecordedRequest> {
+        for _ in 0..50 {
+            if self.requests.lock().unwrap().len() >= count {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let requests = self.requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), count);
+        requests
+    }
+}
+
+/// 等待后台发送结束，此时重试队列中各条目的发送失败次数应为 `expected`
+async fn wait_for_queue(storage: &DatabaseStorage, expected: &[i64]) {
+    let mut attempts: Vec<i64> = Vec::new();
+    for _ in 0..50 {
+        attempts = sqlx::query_scalar("SELECT attempts FROM scrobble_queue ORDER BY id")
+            .fetch_all(storage.pool())
+            .await
+            .unwrap();
+        if attempts == expected {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(attempts, expected);
+}
+
+#[tokio::test]
+async fn test_database_storage_forwards_scrobbles_to_listenbrainz() {
+    let storage = create_storage().await;
+    let alice = create_user(&storage, "alice").await;
+    let bob = create_user(&storage, "bob").await;
+    let song = create_track(&storage, "song").await;
+    let server = MockScrobbleServer::default();
+    let url = server.start().await;
+    // 测试服务器监听本机地址
+    storage.register_scrobble_forwarder(Arc::new(
+        ListenBrainzForwarder::new().allow_private_targets(),
+    ));
+    let account = ScrobbleAccount {
+        service: "listenbrainz".to_string(),
+        token: "alice-token".to_string(),
+        url: Some(url),
+    };
+    storage.set_scrobble_account("alice", &account).await.unwrap();
+    assert_eq!(storage.scrobble_accounts("alice").await.unwrap(), [account]);
+    assert!(storage.scrobble_accounts("bob").await.unwrap().is_empty());
+
+    // 令牌加密保存
+    let stored: String = sqlx::query_scalar("SELECT token FROM scrobble_accounts")
+        .fetch_one(storage.pool())
+        .await
+        .unwrap();
+    assert!(!stored.contains("alice-token"));
+    let accounts = storage.get_scrobble_accounts(&alice).await.unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].service, "listenbrainz");
+    // 没有注册转发器的服务
+    assert!(matches!(
+        storage.update_scrobble_account(&alice, "lastfm", "key", None).await,
+        Err(reverie_storage::error::StorageError::NotFound(_))
+    ));
+
+    // 正在播放通知只发送一次，没有配置账号的用户不转发
+    storage
+        .scrobble(&alice, &song, None, false, None)
+        .await
+        .unwrap();
+    storage.scrobble(&bob, &song, None, true, None).await.unwrap();
+    let requests = server.wait_for(1).await;
+    let (path, authorization, body) = &requests[0];
+    assert_eq!(path, "/1/submit-listens");
+    assert_eq!(authorization.as_deref(), Some("Token alice-token"));
+    let body: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(body["listen_type"], "playing_now");
+    assert_eq!(body["payload"][0]["track_metadata"]["track_name"], "song");
+    assert_eq!(
+        body["payload"][0]["track_metadata"]["additional_info"]["duration_ms"],
+        180_000
+    );
+
+    // 发送失败的播放记录留在队列中，到期前不重试
+    server.failing.store(true, Ordering::SeqCst);
+    let listened_at = Utc::now() - chrono::Duration::minutes(3);
+    storage
+        .scrobble(&alice, &song, Some(listened_at.timestamp_millis()), true, None)
+        .await
+        .unwrap();
+    server.wait_for(2).await;
+    wait_for_queue(&storage, &[1]).await;
+    assert_eq!(storage.process_scrobble_queue().await.unwrap(), 0);
+    server.wait_for(2).await;
+
+    server.failing.store(false, Ordering::SeqCst);
+    sqlx::query("UPDATE scrobble_queue SET next_attempt_at = ?")
+        .bind(Utc::now().to_rfc3339())
+        .execute(storage.pool())
+        .await
+        .unwrap();
+    assert_eq!(storage.process_scrobble_queue().await.unwrap(), 1);
+    wait_for_queue(&storage, &[]).await;
+    let requests = server.wait_for(3).await;
+    let body: serde_json::Value = serde_json::from_str(&requests[2].2).unwrap();
+    assert_eq!(body["listen_type"], "single");
+    assert_eq!(body["payload"][0]["listened_at"], listened_at.timestamp());
+
+    // 删除账号后不再转发
+    storage
+        .remove_scrobble_account("alice", "listenbrainz")
+        .await
+        .unwrap();
+    storage
+        .scrobble(&alice, &song, None, true, None)
+        .await
+        .unwrap();
+    wait_for_queue(&storage, &[]).await;
+    server.wait_for(3).await;
+}
+
+#[tokio::test]
+async fn test_database_storage_rejects_private_scrobble_urls() {
+    use reverie_storage::error::StorageError;
+
+    let storage = create_storage().await;
+    let alice = create_user(&storage, "alice").await;
+    let admin = UserContext::new("admin", true);
+    let song = create_track(&storage, "song").await;
+    let server = MockScrobbleServer::default();
+    let url = server.start().await;
+
+    // 只有管理员可以设置自定义地址，且只能指向公网的 HTTP(S) 服务
+    assert!(matches!(
+        storage
+            .update_scrobble_account(
+                &alice,
+                "listenbrainz",
+                "token",
+                Some("https://maloja.example"),
+            )
+            .await,
+        Err(StorageError::PermissionDenied(_))
+    ));
+    for private in [
+        url.as_str(),
+        "http://192.168.1.10/apis/listenbrainz",
+        "http://[::1]/",
+        "file:///etc/passwd",
+    ] {
+        let result = storage
+            .update_scrobble_account(&admin, "listenbrainz", "token", Some(private))
+            .await;
+        assert!(matches!(result, Err(StorageError::InvalidInput(_))), "{}", private);
+    }
+    storage
+        .update_scrobble_account(&admin, "listenbrainz", "token", Some("https://maloja.example"))
+        .await
+        .unwrap();
+    storage
+        .update_scrobble_account(&alice, "listenbrainz", "token", None)
+        .await
+        .unwrap();
+
+    // 域名在发送时检查解析结果，解析到内网地址的服务不会收到请求
+    let account = ScrobbleAccount {
+        service: "listenbrainz".to_string(),
+        token: "alice-token".to_string(),
+        url: Some(url.replace("127.0.0.1", "localhost")),
+    };
+    storage.set_scrobble_account("alice", &account).await.unwrap();
+    storage.scrobble(&alice, &song, None, true, None).await.unwrap();
+    wait_for_queue(&storage, &[1]).await;
+    assert!(server.requests.lock().unwrap().is_empty());
+
+    // 配置允许后可以转发到内网中的服务
+    storage.register_scrobble_forwarder(Arc::new(
+        ListenBrainzForwarder::new().allow_private_targets(),
+    ));
+    storage
+        .update_scrobble_account(&admin, "listenbrainz", "token", Some(&url))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_database_storage_forwards_scrobbles_to_lastfm() {
+    let storage = create_storage().await;
+    let alice = create_user(&storage, "alice").await;
+    let song = create_track(&storage, "song").await;
+    let server = MockScrobbleServer::default();
+    let url = server.start().await;
+    storage.register_scrobble_forwarder(Arc::new(
+        LastFmForwarder::new("api-key", "api-secret").allow_private_targets(),
+    ));
+    storage
+        .set_scrobble_account(
+            "alice",
+            &ScrobbleAccount {
+                service: "lastfm".to_string(),
+                token: "session-key".to_string(),
+                url: Some(format!("{}/2.0/", url)),
+            },
+        )
+        .await
+        .unwrap();
+
+    let listened_at = Utc::now();
+    storage
+        .scrobble(&alice, &song, Some(listened_at.timestamp_millis()), true, None)
+        .await
+        .unwrap();
+    let requests = server.wait_for(1).await;
+    wait_for_queue(&storage, &[]).await;
+
+    let (path, _, body) = &requests[0];
+    assert_eq!(path, "/2.0/");
+    let params: std::collections::BTreeMap<&str, &str> = body
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .collect();
+    assert_eq!(params["method"], "track.scrobble");
+    assert_eq!(params["track"], "song");
+    assert_eq!(params["sk"], "session-key");
+    assert_eq!(params["timestamp"], listened_at.timestamp().to_string());
+
+    // 签名覆盖 api_sig 和 format 以外的全部参数
+    let mut payload: String = params
+        .iter()
+        .filter(|(key, _)| !matches!(**key, "api_sig" | "format"))
+        .map(|(key, value)| format!("{}{}", key, value))
+        .collect();
+    payload.push_str("api-secret");
+    assert_eq!(params["api_sig"], format!("{:x}", md5::compute(payload)));
+}
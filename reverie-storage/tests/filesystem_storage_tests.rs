@@ -1,5 +1,7 @@
 //! Filesystem storage tests
 
+#![allow(clippy::len_zero)]
+
 use chrono::Utc;
 use reverie_core::{Album, Artist, Playlist, PlaylistTrack, Track, User};
 use reverie_storage::filesystem::{FileSystemConfig, FileSystemStorage};
@@ -0,0 +1,205 @@
+-- 表结构版本 1 的数据库，用于测试升级迁移
+--
+-- 此文件是 v1 表结构的固定副本，不应随后续迁移修改
+
+CREATE TABLE IF NOT EXISTS artists (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    bio TEXT,
+    image_url TEXT,
+    starred_at TEXT,
+    play_count INTEGER DEFAULT 0,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS albums (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    artist_id TEXT,
+    year INTEGER,
+    genre TEXT,
+    cover_art_path TEXT,
+    song_count INTEGER DEFAULT 0,
+    duration REAL DEFAULT 0,
+    play_count INTEGER DEFAULT 0,
+    starred_at TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (artist_id) REFERENCES artists(id)
+);
+
+CREATE TABLE IF NOT EXISTS tracks (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    album_id TEXT,
+    artist_id TEXT,
+    duration INTEGER NOT NULL,
+    file_path TEXT NOT NULL,
+    file_size INTEGER NOT NULL,
+    bitrate INTEGER NOT NULL,
+    sample_rate INTEGER DEFAULT 44100,
+    channels INTEGER DEFAULT 2,
+    format TEXT NOT NULL,
+    track_number INTEGER,
+    disc_number INTEGER,
+    year INTEGER,
+    genre TEXT,
+    cover_art_path TEXT,
+    play_count INTEGER DEFAULT 0,
+    starred_at TEXT,
+    rating INTEGER,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (album_id) REFERENCES albums(id),
+    FOREIGN KEY (artist_id) REFERENCES artists(id)
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY,
+    username TEXT UNIQUE NOT NULL,
+    password_hash TEXT NOT NULL,
+    email TEXT,
+    is_admin INTEGER NOT NULL DEFAULT 0,
+    last_login_at TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS playlists (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT,
+    user_id TEXT NOT NULL,
+    is_public INTEGER NOT NULL DEFAULT 0,
+    cover_art_path TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS playlist_tracks (
+    playlist_id TEXT NOT NULL,
+    track_id TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    added_at TEXT NOT NULL,
+    PRIMARY KEY (playlist_id, track_id),
+    FOREIGN KEY (playlist_id) REFERENCES playlists(id),
+    FOREIGN KEY (track_id) REFERENCES tracks(id)
+);
+
+CREATE TABLE IF NOT EXISTS music_folders (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    path TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS genres (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS scrobbles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    track_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    played_at TEXT NOT NULL,
+    FOREIGN KEY (track_id) REFERENCES tracks(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS play_queue (
+    user_id TEXT PRIMARY KEY,
+    track_ids TEXT NOT NULL,
+    current_track_id TEXT,
+    position INTEGER DEFAULT 0,
+    changed_at TEXT NOT NULL,
+    changed_by TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS bookmarks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL,
+    track_id TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    comment TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id),
+    FOREIGN KEY (track_id) REFERENCES tracks(id),
+    UNIQUE(user_id, track_id)
+);
+
+CREATE TABLE IF NOT EXISTS internet_radio_stations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    stream_url TEXT NOT NULL,
+    homepage_url TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS shares (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    description TEXT,
+    expires_at TEXT,
+    visit_count INTEGER DEFAULT 0,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS share_items (
+    share_id TEXT NOT NULL,
+    item_type TEXT NOT NULL,
+    item_id TEXT NOT NULL,
+    PRIMARY KEY (share_id, item_type, item_id),
+    FOREIGN KEY (share_id) REFERENCES shares(id)
+);
+
+CREATE TABLE IF NOT EXISTS scan_status (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    scanning INTEGER NOT NULL DEFAULT 0,
+    count INTEGER DEFAULT 0,
+    folder_count INTEGER DEFAULT 0,
+    last_scan TEXT,
+    error TEXT,
+    scan_type TEXT,
+    elapsed_time INTEGER
+);
+
+-- Indexes for common queries
+CREATE INDEX IF NOT EXISTS idx_tracks_album ON tracks(album_id);
+CREATE INDEX IF NOT EXISTS idx_tracks_artist ON tracks(artist_id);
+CREATE INDEX IF NOT EXISTS idx_tracks_title ON tracks(title);
+CREATE INDEX IF NOT EXISTS idx_albums_artist ON albums(artist_id);
+CREATE INDEX IF NOT EXISTS idx_albums_name ON albums(name);
+CREATE INDEX IF NOT EXISTS idx_artists_name ON artists(name);
+CREATE INDEX IF NOT EXISTS idx_playlist_tracks ON playlist_tracks(playlist_id, position);
+
+-- Insert default scan status row
+INSERT OR IGNORE INTO scan_status (id, scanning, count, folder_count) VALUES (1, 0, 0, 0);
+
+CREATE TABLE schema_version (
+    version INTEGER PRIMARY KEY,
+    description TEXT NOT NULL,
+    applied_at TEXT NOT NULL
+);
+INSERT INTO schema_version (version, description, applied_at)
+VALUES (1, 'initial schema', '2024-01-01T00:00:00+00:00');
+
+INSERT INTO users (id, username, password_hash, email, is_admin, created_at, updated_at)
+VALUES ('00000000-0000-0000-0000-000000000001', 'admin', 'admin', NULL, 1,
+        '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00');
+INSERT INTO music_folders (name, path) VALUES ('Music', '/music');
+INSERT INTO artists (id, name, created_at, updated_at)
+VALUES ('artist-1', 'Old Artist', '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00');
+INSERT INTO albums (id, name, artist_id, song_count, created_at, updated_at)
+VALUES ('album-1', 'Old Album', 'artist-1', 1,
+        '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00');
+INSERT INTO tracks (id, title, album_id, artist_id, duration, file_path, file_size, bitrate,
+                    format, created_at, updated_at)
+VALUES ('track-1', 'Old Song', 'album-1', 'artist-1', 180, 'music/old.mp3', 1024, 320, 'mp3',
+        '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00');
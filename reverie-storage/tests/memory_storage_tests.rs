@@ -418,3 +418,75 @@ async fn test_memory_storage_relationships() {
         .expect("Failed to get artist tracks");
     assert_eq!(artist_tracks.len(), 3);
 }
+
+#[tokio::test]
+async fn test_memory_storage_annotations_are_per_user() {
+    use reverie_core::UserContext;
+    use reverie_storage::SubsonicStorage;
+
+    let storage = MemoryStorage::new();
+    let alice = UserContext::new("alice", false);
+    let bob = UserContext::new("bob", false);
+
+    storage
+        .star(&alice, &["song-1"], &["album-1"], &[])
+        .await
+        .expect("Failed to star");
+    storage
+        .set_rating(&alice, "song-1", 4)
+        .await
+        .expect("Failed to set rating");
+    storage
+        .save_play_queue(&alice, &["song-1", "song-2"], Some(1), Some(1000), "test")
+        .await
+        .expect("Failed to save play queue");
+
+    let starred = storage.get_starred2(&alice, None).await.unwrap();
+    assert_eq!(starred.songs.len(), 1);
+    assert_eq!(starred.songs[0].user_rating, Some(4));
+    assert_eq!(starred.albums.len(), 1);
+
+    let starred = storage.get_starred2(&bob, None).await.unwrap();
+    assert!(starred.songs.is_empty());
+    assert!(starred.albums.is_empty());
+    assert!(storage.get_play_queue(&bob).await.unwrap().is_none());
+
+    let queue = storage.get_play_queue(&alice).await.unwrap().unwrap();
+    assert_eq!(queue.entries.len(), 2);
+    assert_eq!(queue.current.as_deref(), Some("song-2"));
+    assert_eq!(queue.current_index, Some(1));
+    assert_eq!(queue.changed_by, "test");
+
+    storage
+        .unstar(&alice, &["song-1"], &[], &[])
+        .await
+        .expect("Failed to unstar");
+    let starred = storage.get_starred2(&alice, None).await.unwrap();
+    assert!(starred.songs.is_empty());
+}
+
+#[tokio::test]
+async fn test_memory_storage_writes_initial_admin_password_to_file() {
+    use reverie_storage::SubsonicStorage;
+
+    let dir = tempfile::tempdir().unwrap();
+    let password_file = dir.path().join("reverie.admin-password");
+    let storage = MemoryStorage::new().with_admin_password_file(&password_file);
+    storage
+        .initialize()
+        .await
+        .expect("Failed to initialize storage");
+
+    // 初始密码只保存在配置的文件中
+    let password = std::fs::read_to_string(&password_file).unwrap();
+    assert!(storage
+        .verify_user_password("admin", password.trim())
+        .await
+        .unwrap());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&password_file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
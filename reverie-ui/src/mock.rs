@@ -131,17 +131,17 @@ pub fn album_detail(id: &str) -> (Album, Vec<Song>) {
             album_id: Some(album.id.clone()),
             artist: album.artist.clone(),
             artist_id: album.artist_id.clone(),
-            track: Some(i as i32),
+            track: Some(i),
             year: album.year,
             genre: album.genre.clone(),
             cover_art: None,
-            duration: Some(180 + (i as i32 % 60)),
+            duration: Some(180 + (i % 60)),
             bit_rate: Some(320),
             suffix: Some("mp3".to_string()),
             content_type: Some("audio/mpeg".to_string()),
             path: Some(format!("/music/{}/{}.mp3", album.name, i)),
             starred: None,
-            play_count: (i as i32) * 2,
+            play_count: i * 2,
         })
         .collect();
 
@@ -234,7 +234,7 @@ pub fn playlist_detail(id: &str) -> Playlist {
         song_count: entries.len() as i32,
         duration: total_duration,
         owner: Some("demo".to_string()),
-        public: Some(n % 2 == 0),
+        public: Some(n.is_multiple_of(2)),
         created: None,
         changed: None,
         cover_art: None,
@@ -34,11 +34,14 @@ async fn main() -> Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let storage = Arc::new(MemoryStorage::new());
+    // 首次启动时生成的管理员密码写入工作目录下只有所有者可读的文件，不写入日志
+    let storage = Arc::new(MemoryStorage::new().with_admin_password_file("reverie.admin-password"));
 
-    let mut config = ServerRunConfig::default();
-    // Serve the web UI (if present)
-    config.ui_dir = default_ui_dir();
+    let config = ServerRunConfig {
+        // Serve the web UI (if present)
+        ui_dir: default_ui_dir(),
+        ..Default::default()
+    };
 
     run_with_storage(storage, config).await
 }